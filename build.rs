@@ -0,0 +1,26 @@
+//! Build script, generating the eBPF program skeleton if enabled
+
+fn main() {
+    #[cfg(feature = "ebpf")]
+    ebpf::generate_skeleton();
+}
+
+#[cfg(feature = "ebpf")]
+mod ebpf {
+    use std::{env, path::PathBuf};
+
+    const SOURCE_PATH: &str = "src/ebpf/bpf/shh.bpf.c";
+
+    #[expect(clippy::panic)]
+    pub(super) fn generate_skeleton() {
+        #[expect(clippy::unwrap_used)]
+        let out_path = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("shh.skel.rs");
+        if let Err(err) = libbpf_cargo::SkeletonBuilder::new()
+            .source(SOURCE_PATH)
+            .build_and_generate(&out_path)
+        {
+            panic!("Failed to build eBPF program from {SOURCE_PATH}: {err:#}");
+        }
+        println!("cargo:rerun-if-changed={SOURCE_PATH}");
+    }
+}
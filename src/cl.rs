@@ -3,6 +3,13 @@
 use std::path::PathBuf;
 
 use clap::Parser;
+use shh::{
+    parse_option_name, FragmentNaming, HardeningMode, HardeningOptions, IpAllowCoarsening, Service,
+    TracingBackend, DEFAULT_PROFILE_STORE_DIR,
+};
+
+/// Strace binary, looked up in `PATH`
+pub(crate) const DEFAULT_STRACE_PATH: &str = "strace";
 
 /// Command line arguments
 #[derive(Parser, Debug)]
@@ -12,52 +19,149 @@ pub(crate) struct Args {
     pub action: Action,
 }
 
-/// How hard we should harden
-#[derive(Debug, Clone, Default, clap::ValueEnum, strum::Display)]
-#[strum(serialize_all = "snake_case")]
-pub(crate) enum HardeningMode {
-    /// Only generate hardening options if they have a very low risk of breaking things
-    #[default]
-    Safe,
-    /// Will harden further and prevent circumventing restrictions of some options, but may increase the risk of
-    /// breaking services
-    Aggressive,
+/// Hardening options, optionally set from a level preset
+#[derive(Debug, clap::Args)]
+pub(crate) struct HardeningArgs {
+    #[command(flatten)]
+    opts: HardeningOptions,
+    /// Hardening level, sets all other hardening choices at once
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = ["mode", "network_firewalling", "ip_allow_coarsening", "path_grouping_threshold", "syscall_deny_list"]
+    )]
+    level: Option<HardeningLevel>,
 }
 
-#[derive(Debug, clap::Parser)]
-pub(crate) struct HardeningOptions {
-    /// How hard we should harden
-    #[arg(short, long, default_value_t, value_enum)]
-    pub mode: HardeningMode,
-    /// Enable advanced network firewalling
-    #[arg(short = 'f', long, default_value_t)]
-    pub network_firewalling: bool,
+impl HardeningArgs {
+    /// Get hardening options, with level choices applied
+    pub(crate) fn into_options(self) -> HardeningOptions {
+        let mut opts = self.opts;
+        if let Some(level) = self.level {
+            level.apply(&mut opts);
+        }
+        opts
+    }
 }
 
-impl HardeningOptions {
-    /// Build the most safe options
-    #[cfg_attr(not(test), expect(dead_code))]
-    pub(crate) fn safe() -> Self {
-        Self {
-            mode: HardeningMode::Safe,
-            network_firewalling: false,
+/// How far config changes are activated once written, each step implying the previous ones
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum Activation {
+    /// Only write config fragments
+    Write,
+    /// Also reload systemd config
+    Reload,
+    /// Also (re)start units
+    Restart,
+}
+
+impl Activation {
+    /// Whether systemd config is reloaded
+    pub(crate) fn reload(self) -> bool {
+        self >= Self::Reload
+    }
+
+    /// Whether units are (re)started
+    pub(crate) fn restart(self) -> bool {
+        self >= Self::Restart
+    }
+
+    /// Run the activation steps for config written for units, warning about the steps left to do later
+    pub(crate) fn run<U, L, R>(
+        self,
+        config_desc: &str,
+        units: &[U],
+        reload: L,
+        mut restart: R,
+    ) -> anyhow::Result<()>
+    where
+        L: FnOnce() -> anyhow::Result<()>,
+        R: FnMut(&U) -> anyhow::Result<()>,
+    {
+        if self.reload() {
+            reload()?;
+        } else {
+            log::warn!("{config_desc} will only be applied when systemd config is reloaded, and service restarted");
         }
+        if self.restart() {
+            for unit in units {
+                restart(unit)?;
+            }
+        } else if self.reload() {
+            log::warn!("{config_desc} will only be applied when service is restarted");
+        }
+        Ok(())
     }
+}
 
-    /// Build the most strict options
-    pub(crate) fn strict() -> Self {
-        Self {
-            mode: HardeningMode::Aggressive,
-            network_firewalling: true,
+/// Config activation choices, to write config fragments first, and reload or restart later, ie. during a maintenance
+/// window
+#[derive(Debug, clap::Args)]
+pub(crate) struct ActivationArgs {
+    /// Disable immediate service restart
+    #[arg(short, long, default_value_t = false)]
+    no_restart: bool,
+    /// Only write config fragments, without reloading systemd config nor restarting the service (implies
+    /// `--no-restart`)
+    #[arg(long, default_value_t = false)]
+    no_reload: bool,
+}
+
+impl ActivationArgs {
+    /// Get activation steps to run
+    pub(crate) fn activation(&self) -> Activation {
+        if self.no_reload {
+            Activation::Write
+        } else if self.no_restart {
+            Activation::Reload
+        } else {
+            Activation::Restart
         }
     }
+}
 
-    pub(crate) fn to_cmdline(&self) -> String {
-        format!(
-            "-m {}{}",
-            self.mode,
-            if self.network_firewalling { " -n" } else { "" }
-        )
+/// Hardening level, from least to most strict
+#[derive(Debug, Clone, clap::ValueEnum, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum HardeningLevel {
+    /// Only options with a very low risk of breaking things
+    Minimal,
+    /// Also options preventing circumventing restrictions, only denying the syscall classes that were not used, and
+    /// allowing writes to whole directories generously
+    Balanced,
+    /// Same options as balanced, only allowing the syscalls that were used, and allowing writes to whole directories
+    /// less generously
+    Aggressive,
+    /// Also IP address filtering, and only allow writing to the exact paths written to during profiling
+    Paranoid,
+}
+
+impl HardeningLevel {
+    /// Set hardening options according to the level
+    fn apply(&self, opts: &mut HardeningOptions) {
+        // This is the single place where levels are mapped to hardening choices:
+        // (mode, syscall deny list, IP address filtering coarsening if enabled, path grouping threshold if changed)
+        let (mode, syscall_deny_list, ip_allow_coarsening, path_grouping_threshold) = match self {
+            Self::Minimal => (HardeningMode::Safe, false, None, None),
+            Self::Balanced => (HardeningMode::Aggressive, true, None, Some(2)),
+            Self::Aggressive => (HardeningMode::Aggressive, false, None, None),
+            Self::Paranoid => (
+                HardeningMode::Aggressive,
+                false,
+                Some(IpAllowCoarsening::Host),
+                Some(usize::MAX),
+            ),
+        };
+        opts.mode = mode;
+        opts.syscall_deny_list = syscall_deny_list;
+        opts.network_firewalling = ip_allow_coarsening.is_some();
+        if let Some(ip_allow_coarsening) = ip_allow_coarsening {
+            opts.ip_allow_coarsening = ip_allow_coarsening;
+        }
+        if let Some(path_grouping_threshold) = path_grouping_threshold {
+            opts.path_grouping_threshold = path_grouping_threshold;
+        }
+        opts.level = Some(self.to_string());
     }
 }
 
@@ -69,20 +173,115 @@ pub(crate) enum Action {
         #[arg(num_args = 1.., required = true)]
         command: Vec<String>,
         #[command(flatten)]
-        hardening_opts: HardeningOptions,
+        hardening_opts: HardeningArgs,
         /// Generate profile data file to be merged with others instead of generating systemd options directly
         #[arg(short, long, default_value = None)]
         profile_data_path: Option<PathBuf>,
+        /// Summarize actions incrementally, and update the profile data file with the actions observed so far every
+        /// this number of seconds, instead of only when the command exits
+        #[arg(long, value_name = "SECONDS", requires = "profile_data_path")]
+        checkpoint_interval: Option<u64>,
+        /// Terminate the command if it is still running after this number of seconds, and summarize the actions
+        /// observed until then
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<u64>,
+        /// How to trace the command, eBPF has a much lower overhead, but falls back to strace if not available
+        #[arg(long, value_enum, default_value_t)]
+        tracer: TracingBackend,
         /// Log strace output to this file.
         /// Only use for debugging: this will slow down processing, and may generate a huge file.
         #[arg(short = 'l', long, default_value = None)]
         strace_log_path: Option<PathBuf>,
+        /// Report options as JSON, with the rationale for each of them, instead of config lines
+        #[arg(short, long, default_value_t = false)]
+        json: bool,
+        /// List all observed actions (paths, syscalls, network activity...) on stderr, before reporting options
+        #[arg(long, default_value_t = false)]
+        explain: bool,
+        /// Path of the strace binary to run, if not the one in `PATH`
+        #[arg(long, value_name = "PATH", default_value = DEFAULT_STRACE_PATH)]
+        strace_path: PathBuf,
+        /// Additional strace argument, ie. `-s 4096` to capture longer strings. Arguments changing the output format
+        /// or the traced processes or calls (like `-f`, `-y`, `-o`, `-e abbrev=`, `-e trace=`...) are reserved
+        #[arg(
+            long = "strace-extra-arg",
+            value_name = "ARG",
+            allow_hyphen_values = true
+        )]
+        strace_extra_args: Vec<String>,
+    },
+    /// Profile a command run by systemd in a transient service unit, without installing any unit or config fragment
+    ProfileTransient {
+        /// The command line to run
+        #[arg(num_args = 1.., required = true)]
+        command: Vec<String>,
+        #[command(flatten)]
+        hardening_opts: HardeningArgs,
+        /// Run command with the calling user's service manager
+        #[arg(long, default_value_t = false)]
+        user: bool,
+        /// Maximum number of retries of systemctl/journalctl commands failing transiently
+        #[arg(long, default_value_t = Service::DEFAULT_COMMAND_RETRIES)]
+        command_retries: u32,
     },
     /// Merge profile data from previous runs to generate systemd options
     MergeProfileData {
         #[command(flatten)]
-        hardening_opts: HardeningOptions,
-        /// Profile data paths
+        hardening_opts: HardeningArgs,
+        /// Profile data paths, or directories to merge all profile data files of
+        #[arg(num_args = 1.., required = true)]
+        paths: Vec<PathBuf>,
+        /// Report options as JSON, with the rationale for each of them, instead of config lines
+        #[arg(short, long, default_value_t = false)]
+        json: bool,
+        /// Write options as JSON, with the rationale for each of them, to this path (`-` for stdout), instead of
+        /// reporting them as config lines
+        #[arg(long, value_name = "PATH", conflicts_with = "json")]
+        json_output: Option<PathBuf>,
+        /// Do not remove profile data files passed explicitly after merging them (files of directories are never
+        /// removed)
+        #[arg(long, default_value_t = false)]
+        keep_files: bool,
+        /// Fold profile data into the one accumulated for this unit in the profile store, instead of reporting
+        /// options, to generate them later with `harden --from-store`. Profile data files are kept
+        #[arg(long, value_name = "UNIT", conflicts_with_all = ["json", "json_output"])]
+        store: Option<String>,
+        /// Profile store directory
+        #[arg(long, value_name = "DIR", default_value = DEFAULT_PROFILE_STORE_DIR)]
+        store_dir: PathBuf,
+    },
+    /// Generate systemd options from the profile data accumulated for a unit in the profile store
+    Harden {
+        #[command(flatten)]
+        hardening_opts: HardeningArgs,
+        /// Unit whose accumulated profile data is used
+        #[arg(long, value_name = "UNIT")]
+        from_store: String,
+        /// Profile store directory
+        #[arg(long, value_name = "DIR", default_value = DEFAULT_PROFILE_STORE_DIR)]
+        store_dir: PathBuf,
+        /// Report options as JSON, with the rationale for each of them, instead of config lines
+        #[arg(short, long, default_value_t = false)]
+        json: bool,
+    },
+    /// Generate systemd options from a strace log file captured beforehand with `strace -f`, preferably with
+    /// `-y` to decode file descriptor paths
+    AnalyzeStraceLog {
+        #[command(flatten)]
+        hardening_opts: HardeningArgs,
+        /// Report options as JSON, with the rationale for each of them, instead of config lines
+        #[arg(short, long, default_value_t = false)]
+        json: bool,
+        /// Current directory of the traced program when the log was captured, to resolve relative paths, which are
+        /// ignored otherwise
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<PathBuf>,
+        /// Strace log path
+        path: PathBuf,
+    },
+    /// Generate a Docker/OCI seccomp profile allowing only the syscalls observed in profile data
+    ExportSeccompProfile {
+        /// Profile data paths, or directories to merge all files of
         #[arg(num_args = 1.., required = true)]
         paths: Vec<PathBuf>,
     },
@@ -100,10 +299,50 @@ pub(crate) enum ServiceAction {
         /// Service unit name
         service: String,
         #[command(flatten)]
-        hardening_opts: HardeningOptions,
-        /// Disable immediate service restart
+        hardening_opts: HardeningArgs,
+        #[command(flatten)]
+        activation: ActivationArgs,
+        /// Also profile `ExecReload=` and `ExecStop=` commands
+        #[arg(short = 's', long, default_value_t = false)]
+        profile_stop_reload: bool,
+        /// Other instance of the same template unit to profile at the same time, the hardening config will allow the
+        /// behavior of all of them
+        #[arg(long = "instance", value_name = "INSTANCE")]
+        instances: Vec<String>,
+        /// Stop profiling commands still running after this number of seconds, keeping their partial profile, and
+        /// set a matching `TimeoutStartSec=` instead of disabling the start timeout
+        #[arg(long, value_name = "SECONDS")]
+        profile_timeout: Option<u64>,
+        /// How to trace the service commands, eBPF has a much lower overhead, but falls back to strace if not
+        /// available
+        #[arg(long, value_enum, default_value_t)]
+        tracer: TracingBackend,
+        /// Set `MemoryMax=` and `MemoryHigh=` of the unit to this value while profiling (ie. `2G`, `80%` or `infinity`),
+        /// so that the strace overhead does not get it killed for lack of memory
+        #[arg(long, value_name = "SIZE")]
+        profile_memory_max: Option<String>,
+        /// Keep the existing hardening config aside during profiling, to merge it with the new one with
+        /// `finish-profile --merge`, instead of refusing to profile
+        #[arg(long, default_value_t = false)]
+        merge: bool,
+        /// Print profiling config fragment instead of writing it, and do not restart service
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Write profile data and raw strace logs to this persistent directory, and keep them after profiling for
+        /// debugging, instead of using a temporary runtime directory
+        #[arg(long, value_name = "DIR")]
+        keep_profile_data: Option<PathBuf>,
+        /// Do not ask for confirmation before modifying and restarting the service, required if stdin is not a terminal
         #[arg(short, long, default_value_t = false)]
-        no_restart: bool,
+        yes: bool,
+        /// Act on a service of the calling user's service manager
+        #[arg(long, default_value_t = false)]
+        user: bool,
+        /// Maximum number of retries of systemctl/journalctl commands failing transiently
+        #[arg(long, default_value_t = Service::DEFAULT_COMMAND_RETRIES)]
+        command_retries: u32,
+        #[command(flatten)]
+        fragment_naming: FragmentNaming,
     },
     /// Get profiling result and remove fragment config from service
     FinishProfile {
@@ -112,13 +351,308 @@ pub(crate) enum ServiceAction {
         /// Automatically apply hardening config
         #[arg(short, long, default_value_t = false)]
         apply: bool,
-        /// Disable immediate service restart
+        /// Print hardening config fragment instead of writing it, and do not stop units nor remove profiling config.
+        /// Profiling results can then only be collected from already stopped units
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Write hardening config fragment to this path (`-` for stdout) instead of the unit config directory, to
+        /// deploy it by other means
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["apply", "dry_run"])]
+        output_file: Option<PathBuf>,
+        /// Other instance of the same template unit profiled at the same time, whose profiling result is merged
+        #[arg(long = "instance", value_name = "INSTANCE")]
+        instances: Vec<String>,
+        /// Merge hardening config with the one that existed before profiling, instead of replacing it
+        #[arg(long, default_value_t = false)]
+        merge: bool,
+        /// Skip options the unit config already sets to an equal or more restrictive value, instead of possibly
+        /// weakening them
+        #[arg(long, default_value_t = false)]
+        merge_with_existing_unit: bool,
+        /// Exclude option from the generated hardening config, even if it is compatible with the service
+        #[arg(long = "exclude-option", value_name = "NAME", value_parser = parse_option_name)]
+        exclude_options: Vec<String>,
+        /// Only include this option in the generated hardening config, if it is compatible with the service
+        #[arg(long = "only-option", value_name = "NAME", value_parser = parse_option_name)]
+        only_options: Vec<String>,
+        #[command(flatten)]
+        activation: ActivationArgs,
+        /// Do not check the hardening config with `systemd-analyze verify` before reloading the service
+        #[arg(long, default_value_t = false)]
+        no_verify: bool,
+        /// Give up if the profiled units are not stopped and their profiling result collected after this number of
+        /// seconds, or if interrupted, stopping units without waiting and removing the profiling config
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<u64>,
+        /// Do not ask for confirmation before modifying and restarting the service, required if stdin is not a terminal
         #[arg(short, long, default_value_t = false)]
-        no_restart: bool,
+        yes: bool,
+        /// Act on a service of the calling user's service manager
+        #[arg(long, default_value_t = false)]
+        user: bool,
+        /// Maximum number of retries of systemctl/journalctl commands failing transiently
+        #[arg(long, default_value_t = Service::DEFAULT_COMMAND_RETRIES)]
+        command_retries: u32,
+        #[command(flatten)]
+        fragment_naming: FragmentNaming,
+    },
+    /// Compare `systemd-analyze security` exposure of service without and with its hardening config fragment
+    SecurityDelta {
+        /// Service unit name
+        service: String,
+        /// Act on a service of the calling user's service manager
+        #[arg(long, default_value_t = false)]
+        user: bool,
+        /// Maximum number of retries of systemctl/journalctl commands failing transiently
+        #[arg(long, default_value_t = Service::DEFAULT_COMMAND_RETRIES)]
+        command_retries: u32,
+        #[command(flatten)]
+        fragment_naming: FragmentNaming,
     },
     /// Remove profiling and/or hardening config fragments, and restart service to restore its initial state
     Reset {
         /// Service unit name
         service: String,
+        /// Do not ask for confirmation before modifying and restarting the service, required if stdin is not a terminal
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
+        /// Act on a service of the calling user's service manager
+        #[arg(long, default_value_t = false)]
+        user: bool,
+        /// Maximum number of retries of systemctl/journalctl commands failing transiently
+        #[arg(long, default_value_t = Service::DEFAULT_COMMAND_RETRIES)]
+        command_retries: u32,
+        #[command(flatten)]
+        fragment_naming: FragmentNaming,
+    },
+    /// List service units having profiling and/or hardening config fragments
+    List {
+        /// Act on services of the calling user's service manager
+        #[arg(long, default_value_t = false)]
+        user: bool,
+        /// Output as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        #[command(flatten)]
+        fragment_naming: FragmentNaming,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        collections::BTreeSet,
+    };
+
+    use clap::ValueEnum as _;
+    use shh::{KernelVersion, ProfileData, SupportedOptions, SystemdVersion};
+
+    use super::*;
+
+    fn level_option_names(level: &HardeningLevel) -> BTreeSet<String> {
+        let mut opts = HardeningOptions::safe();
+        level.apply(&mut opts);
+        SupportedOptions::new(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &opts,
+        )
+        .to_string()
+        .split(", ")
+        .map(ToOwned::to_owned)
+        .collect()
+    }
+
+    #[test]
+    fn test_levels_ordered() {
+        let levels = HardeningLevel::value_variants();
+        for (less_strict, more_strict) in levels.iter().zip(levels.iter().skip(1)) {
+            assert!(
+                level_option_names(more_strict).is_superset(&level_option_names(less_strict)),
+                "{more_strict} {less_strict}"
+            );
+        }
+
+        let aggressive_opts = level_option_names(&HardeningLevel::Aggressive);
+        let paranoid_opts = level_option_names(&HardeningLevel::Paranoid);
+        assert!(paranoid_opts.is_superset(&aggressive_opts));
+        assert!(paranoid_opts.len() > aggressive_opts.len());
+        assert!(paranoid_opts.contains("IPAddressAllow"));
+    }
+
+    #[test]
+    fn test_levels_differ() {
+        let profile_data = ProfileData::from_strace_log(
+            concat!(
+                "1 0.000001 getpid() = 1234\n",
+                "1 0.000001 openat(AT_FDCWD, \"/var/lib/foo/a\", O_WRONLY|O_CREAT, 0644) = 3\n",
+                "1 0.000001 openat(AT_FDCWD, \"/var/lib/foo/b\", O_WRONLY|O_CREAT, 0644) = 3\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let resolve = |level: &HardeningLevel| -> Vec<String> {
+            let mut opts = HardeningOptions::safe();
+            level.apply(&mut opts);
+            SupportedOptions::new(
+                &SystemdVersion::new(254, 0),
+                &KernelVersion::new(6, 4, 0),
+                &opts,
+            )
+            .resolve(&profile_data)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+        };
+
+        let levels = HardeningLevel::value_variants();
+        for (less_strict, more_strict) in levels.iter().zip(levels.iter().skip(1)) {
+            assert_ne!(
+                resolve(less_strict),
+                resolve(more_strict),
+                "{more_strict} {less_strict}"
+            );
+        }
+
+        let balanced_opts = resolve(&HardeningLevel::Balanced);
+        assert!(balanced_opts.contains(&"ReadWritePaths=-/var/lib/foo".to_owned()));
+        assert!(balanced_opts
+            .iter()
+            .any(|o| o.starts_with("SystemCallFilter=~")));
+        let aggressive_opts = resolve(&HardeningLevel::Aggressive);
+        assert!(
+            aggressive_opts.contains(&"ReadWritePaths=-/var/lib/foo/a -/var/lib/foo/b".to_owned())
+        );
+        assert!(aggressive_opts
+            .iter()
+            .any(|o| o.starts_with("SystemCallFilter=") && !o.starts_with("SystemCallFilter=~")));
+    }
+
+    #[test]
+    fn test_activation() {
+        for (flags, activation) in [
+            (&[][..], Activation::Restart),
+            (&["--no-restart"], Activation::Reload),
+            (&["-n"], Activation::Reload),
+            (&["--no-reload"], Activation::Write),
+            (&["--no-reload", "--no-restart"], Activation::Write),
+        ] {
+            for action in ["start-profile", "finish-profile"] {
+                let args =
+                    Args::try_parse_from(["shh", "service", action, "foo"].iter().chain(flags))
+                        .unwrap();
+                let (Action::Service(
+                    ServiceAction::StartProfile {
+                        activation: activation_args,
+                        ..
+                    }
+                    | ServiceAction::FinishProfile {
+                        activation: activation_args,
+                        ..
+                    },
+                )) = &args.action
+                else {
+                    panic!("Unexpected action {:?}", args.action);
+                };
+                assert_eq!(
+                    activation_args.activation(),
+                    activation,
+                    "{action} {flags:?}"
+                );
+            }
+        }
+        assert!(!Activation::Write.reload());
+        assert!(!Activation::Write.restart());
+        assert!(Activation::Reload.reload());
+        assert!(!Activation::Reload.restart());
+        assert!(Activation::Restart.reload());
+        assert!(Activation::Restart.restart());
+    }
+
+    #[test]
+    fn test_activation_run() {
+        for (activation, expected) in [
+            (Activation::Write, &[][..]),
+            (Activation::Reload, &["reload"]),
+            (
+                Activation::Restart,
+                &["reload", "restart foo", "restart bar"],
+            ),
+        ] {
+            let calls = RefCell::new(Vec::new());
+            activation
+                .run(
+                    "Config",
+                    &["foo", "bar"],
+                    || {
+                        calls.borrow_mut().push("reload".to_owned());
+                        Ok(())
+                    },
+                    |unit| {
+                        calls.borrow_mut().push(format!("restart {unit}"));
+                        Ok(())
+                    },
+                )
+                .unwrap();
+            assert_eq!(calls.into_inner(), expected, "{activation:?}");
+        }
+
+        // Units are not restarted if reloading failed
+        let restarted = Cell::new(false);
+        assert!(Activation::Restart
+            .run(
+                "Config",
+                &["foo"],
+                || anyhow::bail!("reload failed"),
+                |_| {
+                    restarted.set(true);
+                    Ok(())
+                },
+            )
+            .is_err());
+        assert!(!restarted.get());
+    }
+
+    #[test]
+    fn test_option_names() {
+        let parse = |flag: &str, name: &str| {
+            Args::try_parse_from(["shh", "service", "finish-profile", flag, name, "foo"])
+        };
+        assert!(parse("--exclude-option", "ProtectSystem").is_ok());
+        assert!(parse("--only-option", "ReadWritePaths").is_ok());
+        assert!(parse("--exclude-option", "ProtectSytem").is_err());
+        assert!(parse("--only-option", "DoesNotExist").is_err());
+    }
+
+    #[test]
+    fn test_allowed_syscalls() {
+        let parse =
+            |name: &str| Args::try_parse_from(["shh", "run", "--allow-syscall", name, "true"]);
+        assert!(parse("mlock").is_ok());
+        assert!(parse("_llseek").is_ok());
+        assert!(parse("mlck").is_err());
+        assert!(parse("@memlock").is_err());
+    }
+
+    #[test]
+    fn test_reset_confirmation() {
+        for (flags, expected_yes) in [(&[][..], false), (&["-y"], true), (&["--yes"], true)] {
+            let args = Args::try_parse_from(["shh", "service", "reset", "foo"].iter().chain(flags))
+                .unwrap();
+            let Action::Service(ServiceAction::Reset { yes, .. }) = args.action else {
+                panic!("Unexpected action {:?}", args.action);
+            };
+            assert_eq!(yes, expected_yes, "{flags:?}");
+        }
+    }
+
+    #[test]
+    fn test_level_conflicts() {
+        assert!(Args::try_parse_from(["shh", "run", "--level", "paranoid", "true"]).is_ok());
+        assert!(
+            Args::try_parse_from(["shh", "run", "--level", "paranoid", "-m", "safe", "true"])
+                .is_err()
+        );
+    }
+}
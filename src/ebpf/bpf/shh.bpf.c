@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: GPL-3.0-only
+/*
+ * Syscall tracing of a profiled program and its descendants, streaming syscall events to userspace through a ring
+ * buffer. Event and map layouts must match the ones in src/ebpf/decode.rs
+ */
+
+#include <linux/bpf.h>
+#include <linux/types.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+/* Maximum number of string arguments (ie. paths) captured per syscall */
+#define MAX_STR_ARGS 2
+/* Maximum length of captured strings, including the terminating NUL */
+#define MAX_STR_LEN 4096
+/* Maximum length of captured buffers (ie. socket addresses, or small structs) */
+#define MAX_BUF_LEN 128
+/* Maximum number of traced threads */
+#define MAX_TRACED 65536
+/* Syscall number of the event sent once all traced threads have exited */
+#define DONE_NR -1
+
+/* Thread states in the traced map */
+#define STATE_TRACED 1
+/* The first traced process runs our code until it executes the program, which is not reported */
+#define STATE_WAITING_EXEC 2
+
+/* Syscall description flags */
+#define DESC_EXEC 1
+
+/* Only the fields we need, relocated against the kernel BTF */
+struct task_struct {
+	int pid;
+	int tgid;
+} __attribute__((preserve_access_index));
+
+/* Tracepoint contexts, see /sys/kernel/tracing/events/raw_syscalls/sys_{enter,exit}/format */
+struct sys_enter_ctx {
+	__u64 common;
+	long id;
+	unsigned long args[6];
+};
+
+struct sys_exit_ctx {
+	__u64 common;
+	long id;
+	long ret;
+};
+
+struct config {
+	/* Process whose next child is traced, once it executes a program */
+	__u32 parent_tgid;
+	__u32 follow_next_fork;
+};
+
+/* What to capture for a syscall, set from userspace */
+struct syscall_desc {
+	/* Index + 1 of string arguments, 0 if unused */
+	__u8 str_args[MAX_STR_ARGS];
+	/* Index + 1 of the buffer argument, 0 if unused */
+	__u8 buf_arg;
+	/* Index + 1 of the argument holding the buffer length, 0 to always capture buf_size bytes */
+	__u8 buf_len_arg;
+	__u16 buf_size;
+	__u8 flags;
+	__u8 pad;
+};
+
+struct pending_syscall {
+	__s64 nr;
+	__u64 args[6];
+};
+
+struct event {
+	__u32 pid;
+	__s32 buf_len;
+	__s64 nr;
+	__u64 ts_ns;
+	__s64 ret;
+	__u64 args[6];
+	__s32 str_lens[MAX_STR_ARGS];
+	__u8 buf[MAX_BUF_LEN];
+	/* Only sent for syscalls with string arguments */
+	char strs[MAX_STR_ARGS][MAX_STR_LEN];
+};
+
+#define EVENT_HEADER_SIZE __builtin_offsetof(struct event, strs)
+
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, MAX_TRACED);
+	__type(key, __u32);
+	__type(value, __u8);
+} traced SEC(".maps");
+
+struct {
+	__uint(type, BPF_MAP_TYPE_ARRAY);
+	__uint(max_entries, 1);
+	__type(key, __u32);
+	__type(value, __s64);
+} traced_count SEC(".maps");
+
+struct {
+	__uint(type, BPF_MAP_TYPE_ARRAY);
+	__uint(max_entries, 1);
+	__type(key, __u32);
+	__type(value, struct config);
+} config SEC(".maps");
+
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, 1024);
+	__type(key, __u32);
+	__type(value, struct syscall_desc);
+} syscall_descs SEC(".maps");
+
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, MAX_TRACED);
+	__type(key, __u32);
+	__type(value, struct pending_syscall);
+} pending SEC(".maps");
+
+/* Executed paths, captured when the syscall starts, because the memory holding them is gone once it succeeds */
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, 1024);
+	__type(key, __u32);
+	__type(value, char[MAX_STR_LEN]);
+} exec_paths SEC(".maps");
+
+/* Events are too large for the stack */
+struct {
+	__uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+	__uint(max_entries, 1);
+	__type(key, __u32);
+	__type(value, struct event);
+} scratch SEC(".maps");
+
+struct {
+	__uint(type, BPF_MAP_TYPE_RINGBUF);
+	__uint(max_entries, 16 * 1024 * 1024);
+} events SEC(".maps");
+
+static __always_inline struct event *new_event(__u32 tid, __s64 nr, __s64 ret)
+{
+	__u32 zero = 0;
+	struct event *e = bpf_map_lookup_elem(&scratch, &zero);
+	if (!e)
+		return NULL;
+	e->pid = tid;
+	e->buf_len = 0;
+	e->nr = nr;
+	e->ts_ns = bpf_ktime_get_ns();
+	e->ret = ret;
+	for (int i = 0; i < MAX_STR_ARGS; i++)
+		e->str_lens[i] = 0;
+	return e;
+}
+
+SEC("tp_btf/sched_process_fork")
+int BPF_PROG(handle_fork, struct task_struct *parent, struct task_struct *child)
+{
+	__u32 parent_tid = parent->pid;
+	__u32 child_tid = child->pid;
+	__u8 state;
+	__u8 *parent_state = bpf_map_lookup_elem(&traced, &parent_tid);
+	if (parent_state) {
+		/* Threads and child processes are traced like their parent */
+		state = *parent_state;
+	} else {
+		__u32 zero = 0;
+		struct config *cfg = bpf_map_lookup_elem(&config, &zero);
+		if (!cfg || !cfg->follow_next_fork || (__u32)parent->tgid != cfg->parent_tgid)
+			return 0;
+		cfg->follow_next_fork = 0;
+		state = STATE_WAITING_EXEC;
+	}
+	if (bpf_map_update_elem(&traced, &child_tid, &state, BPF_NOEXIST) == 0) {
+		__u32 zero = 0;
+		__s64 *count = bpf_map_lookup_elem(&traced_count, &zero);
+		if (count)
+			__sync_fetch_and_add(count, 1);
+	}
+	return 0;
+}
+
+SEC("tp_btf/sched_process_exit")
+int BPF_PROG(handle_exit, struct task_struct *task)
+{
+	__u32 tid = task->pid;
+	__u32 zero = 0;
+	if (bpf_map_delete_elem(&traced, &tid) != 0)
+		return 0;
+	/* Syscalls ending the thread (ie. exit_group) never return, report them anyway */
+	struct pending_syscall *p = bpf_map_lookup_elem(&pending, &tid);
+	if (p) {
+		struct event *e = new_event(tid, p->nr, 0);
+		if (e) {
+			for (int i = 0; i < 6; i++)
+				e->args[i] = p->args[i];
+			bpf_ringbuf_output(&events, e, EVENT_HEADER_SIZE, 0);
+		}
+		bpf_map_delete_elem(&pending, &tid);
+	}
+	bpf_map_delete_elem(&exec_paths, &tid);
+	__s64 *count = bpf_map_lookup_elem(&traced_count, &zero);
+	if (!count)
+		return 0;
+	__sync_fetch_and_add(count, -1);
+	/* Concurrent exits may both send it, userspace stops at the first one */
+	if (*count == 0) {
+		struct event *e = new_event(tid, DONE_NR, 0);
+		if (e)
+			bpf_ringbuf_output(&events, e, EVENT_HEADER_SIZE, 0);
+	}
+	return 0;
+}
+
+SEC("tracepoint/raw_syscalls/sys_enter")
+int handle_sys_enter(struct sys_enter_ctx *ctx)
+{
+	__u32 tid = (__u32)bpf_get_current_pid_tgid();
+	__u8 *state = bpf_map_lookup_elem(&traced, &tid);
+	if (!state)
+		return 0;
+	__u32 nr = (__u32)ctx->id;
+	struct syscall_desc *desc = bpf_map_lookup_elem(&syscall_descs, &nr);
+	int exec = desc && (desc->flags & DESC_EXEC);
+	if (*state == STATE_WAITING_EXEC) {
+		if (!exec)
+			return 0;
+		*state = STATE_TRACED;
+	}
+
+	struct pending_syscall p = { .nr = ctx->id };
+	for (int i = 0; i < 6; i++)
+		p.args[i] = ctx->args[i];
+	bpf_map_update_elem(&pending, &tid, &p, BPF_ANY);
+
+	if (exec) {
+		__u8 idx = desc->str_args[0];
+		if (idx == 0 || idx > 6)
+			return 0;
+		struct event *e = new_event(tid, ctx->id, 0);
+		if (!e)
+			return 0;
+		if (bpf_probe_read_user_str(e->strs[0], MAX_STR_LEN, (const void *)ctx->args[idx - 1]) > 0)
+			bpf_map_update_elem(&exec_paths, &tid, e->strs[0], BPF_ANY);
+	}
+	return 0;
+}
+
+SEC("tracepoint/raw_syscalls/sys_exit")
+int handle_sys_exit(struct sys_exit_ctx *ctx)
+{
+	__u32 tid = (__u32)bpf_get_current_pid_tgid();
+	struct pending_syscall *p = bpf_map_lookup_elem(&pending, &tid);
+	if (!p)
+		return 0;
+	struct event *e = new_event(tid, p->nr, ctx->ret);
+	if (!e)
+		goto out;
+	for (int i = 0; i < 6; i++)
+		e->args[i] = p->args[i];
+
+	__u32 size = EVENT_HEADER_SIZE;
+	__u32 nr = (__u32)p->nr;
+	struct syscall_desc *desc = bpf_map_lookup_elem(&syscall_descs, &nr);
+	if (desc) {
+		for (int i = 0; i < MAX_STR_ARGS; i++) {
+			__u8 idx = desc->str_args[i];
+			if (idx == 0 || idx > 6)
+				continue;
+			if ((desc->flags & DESC_EXEC) && i == 0) {
+				char *path = bpf_map_lookup_elem(&exec_paths, &tid);
+				if (path)
+					e->str_lens[i] = bpf_probe_read_kernel_str(e->strs[i], MAX_STR_LEN, path);
+			} else {
+				e->str_lens[i] = bpf_probe_read_user_str(e->strs[i], MAX_STR_LEN,
+									 (const void *)e->args[idx - 1]);
+			}
+			size = sizeof(struct event);
+		}
+		__u8 idx = desc->buf_arg;
+		if (idx > 0 && idx <= 6 && e->args[idx - 1] != 0) {
+			__u32 len = desc->buf_size;
+			__u8 len_idx = desc->buf_len_arg;
+			if (len_idx > 0 && len_idx <= 6 && e->args[len_idx - 1] < len)
+				len = e->args[len_idx - 1];
+			if (len > MAX_BUF_LEN)
+				len = MAX_BUF_LEN;
+			if (len > 0 && bpf_probe_read_user(e->buf, len, (const void *)e->args[idx - 1]) == 0)
+				e->buf_len = len;
+		}
+	}
+	bpf_ringbuf_output(&events, e, size, 0);
+
+out:
+	bpf_map_delete_elem(&pending, &tid);
+	bpf_map_delete_elem(&exec_paths, &tid);
+	return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
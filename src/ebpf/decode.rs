@@ -0,0 +1,1071 @@
+//! Decoding of raw eBPF syscall events, into syscalls with the same arguments as the ones parsed from strace output
+
+use std::{
+    collections::HashMap,
+    fs,
+    net::{Ipv4Addr, Ipv6Addr},
+    os::unix::ffi::OsStrExt as _,
+};
+
+use crate::strace::{
+    BufferExpression, BufferType, Expression, IntegerExpression, IntegerExpressionValue, Syscall,
+    CAPABILITY_GATED_SYSCALLS,
+};
+
+use super::names::SYSCALL_NAMES;
+
+/// Maximum number of captured string arguments per syscall, must match `MAX_STR_ARGS` in `bpf/shh.bpf.c`
+const MAX_STR_ARGS: usize = 2;
+/// Maximum length of captured strings, must match `MAX_STR_LEN` in `bpf/shh.bpf.c`
+const MAX_STR_LEN: usize = 4096;
+/// Maximum length of captured buffers, must match `MAX_BUF_LEN` in `bpf/shh.bpf.c`
+const MAX_BUF_LEN: usize = 128;
+/// Size of events without captured strings, ie. `offsetof(struct event, strs)`
+const EVENT_HEADER_SIZE: usize = 88 + MAX_BUF_LEN;
+/// Syscall number of the event sent once all traced threads have exited
+pub(super) const DONE_NR: i64 = -1;
+/// Syscall description flag for syscalls executing a program
+const DESC_EXEC: u8 = 1;
+/// Errno of syscalls failing because of missing privileges
+const EPERM: i64 = 1;
+/// Highest errno, syscall return values between `-MAX_ERRNO` and -1 are failures
+const MAX_ERRNO: i64 = 4095;
+/// Directory file descriptor value for the current directory
+const AT_FDCWD: i32 = -100;
+
+/// Syscall event, as sent by the eBPF program
+#[derive(Debug, Default)]
+pub(super) struct RawEvent {
+    /// Thread id
+    pub pid: u32,
+    pub nr: i64,
+    /// Monotonic timestamp of the end of the syscall
+    pub ts_ns: u64,
+    pub ret: i64,
+    pub args: [u64; 6],
+    /// Captured string arguments, without their terminating NUL, in the order of the syscall description
+    pub strs: [Option<Vec<u8>>; MAX_STR_ARGS],
+    /// Captured buffer argument
+    pub buf: Option<Vec<u8>>,
+}
+
+impl RawEvent {
+    /// Parse event from the ring buffer, laid out like `struct event` in `bpf/shh.bpf.c`
+    pub(super) fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let mut event = Self {
+            pid: u32::from_ne_bytes(event_bytes(data, 0)?),
+            nr: i64::from_ne_bytes(event_bytes(data, 8)?),
+            ts_ns: u64::from_ne_bytes(event_bytes(data, 16)?),
+            ret: i64::from_ne_bytes(event_bytes(data, 24)?),
+            ..Self::default()
+        };
+        for (i, arg) in event.args.iter_mut().enumerate() {
+            *arg = u64::from_ne_bytes(event_bytes(data, 32 + 8 * i)?);
+        }
+        let buf_len = i32::from_ne_bytes(event_bytes(data, 4)?);
+        if let Ok(buf_len @ 1..) = usize::try_from(buf_len) {
+            event.buf = data
+                .get(88..88 + buf_len.min(MAX_BUF_LEN))
+                .map(<[u8]>::to_vec);
+        }
+        // Strings are only sent if the syscall has string arguments
+        if data.len() > EVENT_HEADER_SIZE {
+            for (i, str_) in event.strs.iter_mut().enumerate() {
+                let len = i32::from_ne_bytes(event_bytes(data, 80 + 4 * i)?);
+                // Length includes the terminating NUL, and is negative if the string could not be read
+                if let Ok(len @ 1..) = usize::try_from(len) {
+                    let start = EVENT_HEADER_SIZE + MAX_STR_LEN * i;
+                    *str_ = data
+                        .get(start..start + len.min(MAX_STR_LEN) - 1)
+                        .map(<[u8]>::to_vec);
+                }
+            }
+        }
+        Ok(event)
+    }
+
+    /// Whether this is the event sent once all traced threads have exited
+    pub(super) fn is_done(&self) -> bool {
+        self.nr == DONE_NR
+    }
+}
+
+/// Get `N` bytes of an event at `offset`
+fn event_bytes<const N: usize>(data: &[u8], offset: usize) -> anyhow::Result<[u8; N]> {
+    data.get(offset..offset + N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Truncated eBPF event of {} bytes", data.len()))
+}
+
+/// How a syscall argument is decoded, to match what the strace output parser produces
+#[derive(Clone, Copy)]
+enum Arg {
+    /// Integer, as a literal
+    Int,
+    /// 32 bit integer (ie. an user or group id), as a literal
+    U32,
+    /// File descriptor, with its path as metadata (like `strace -y`), or `AT_FDCWD`
+    Fd,
+    /// NUL terminated string (ie. a path), captured in the given string slot
+    Str(usize),
+    /// Named constants for the set bits, and a literal for the remaining ones
+    Flags(&'static [(&'static str, u64)]),
+    /// Named constant if the value is known, or a literal
+    Const(&'static [(&'static str, u64)]),
+    /// File open flags, whose access mode is a value and not a flag
+    OpenFlags,
+    /// File mode, with its type as a named constant
+    Mode,
+    /// Socket type, with its flags
+    SocketType,
+    /// Struct captured in the buffer, of which only some members are decoded
+    Struct(&'static [Member]),
+    /// Socket address captured in the buffer, whose length is the argument at the given index
+    Sockaddr { len_idx: usize },
+    /// Array of 32 bit ids captured in the buffer, whose count is the argument at the given index
+    Ids { count_idx: usize },
+    /// Named argument, reported by strace in a single struct with the other named arguments of the syscall
+    Named(&'static str, &'static Arg),
+}
+
+/// Native endian struct member
+#[derive(Clone, Copy)]
+struct Member {
+    name: &'static str,
+    offset: usize,
+    size: usize,
+    arg: Arg,
+}
+
+impl Member {
+    const fn new(name: &'static str, offset: usize, size: usize, arg: Arg) -> Self {
+        Self {
+            name,
+            offset,
+            size,
+            arg,
+        }
+    }
+}
+
+const OPEN_FLAGS: &[(&str, u64)] = &[
+    ("O_TMPFILE", 0o20_200_000),
+    ("O_SYNC", 0o4_010_000),
+    ("O_CREAT", 0o100),
+    ("O_EXCL", 0o200),
+    ("O_NOCTTY", 0o400),
+    ("O_TRUNC", 0o1000),
+    ("O_APPEND", 0o2000),
+    ("O_NONBLOCK", 0o4000),
+    ("O_DSYNC", 0o10_000),
+    ("O_ASYNC", 0o20_000),
+    ("O_DIRECT", 0o40_000),
+    ("O_LARGEFILE", 0o100_000),
+    ("O_DIRECTORY", 0o200_000),
+    ("O_NOFOLLOW", 0o400_000),
+    ("O_NOATIME", 0o1_000_000),
+    ("O_CLOEXEC", 0o2_000_000),
+    ("O_PATH", 0o10_000_000),
+];
+const OPEN_ACCESS_MODES: &[(&str, u64)] = &[("O_RDONLY", 0), ("O_WRONLY", 1), ("O_RDWR", 2)];
+const RESOLVE_FLAGS: &[(&str, u64)] = &[
+    ("RESOLVE_NO_XDEV", 0x01),
+    ("RESOLVE_NO_MAGICLINKS", 0x02),
+    ("RESOLVE_NO_SYMLINKS", 0x04),
+    ("RESOLVE_BENEATH", 0x08),
+    ("RESOLVE_IN_ROOT", 0x10),
+    ("RESOLVE_CACHED", 0x20),
+];
+const FILE_TYPES: &[(&str, u64)] = &[
+    ("S_IFIFO", 0o010_000),
+    ("S_IFCHR", 0o020_000),
+    ("S_IFDIR", 0o040_000),
+    ("S_IFBLK", 0o060_000),
+    ("S_IFREG", 0o100_000),
+    ("S_IFLNK", 0o120_000),
+    ("S_IFSOCK", 0o140_000),
+];
+const FILE_TYPE_MASK: u64 = 0o170_000;
+const PROT_FLAGS: &[(&str, u64)] = &[("PROT_READ", 1), ("PROT_WRITE", 2), ("PROT_EXEC", 4)];
+const RENAME_FLAGS: &[(&str, u64)] = &[
+    ("RENAME_NOREPLACE", 1),
+    ("RENAME_EXCHANGE", 2),
+    ("RENAME_WHITEOUT", 4),
+];
+const CLONE_FLAGS: &[(&str, u64)] = &[
+    ("CLONE_NEWTIME", 0x80),
+    ("CLONE_VM", 0x100),
+    ("CLONE_FS", 0x200),
+    ("CLONE_FILES", 0x400),
+    ("CLONE_SIGHAND", 0x800),
+    ("CLONE_PIDFD", 0x1000),
+    ("CLONE_PTRACE", 0x2000),
+    ("CLONE_VFORK", 0x4000),
+    ("CLONE_PARENT", 0x8000),
+    ("CLONE_THREAD", 0x1_0000),
+    ("CLONE_NEWNS", 0x2_0000),
+    ("CLONE_SYSVSEM", 0x4_0000),
+    ("CLONE_SETTLS", 0x8_0000),
+    ("CLONE_PARENT_SETTID", 0x10_0000),
+    ("CLONE_CHILD_CLEARTID", 0x20_0000),
+    ("CLONE_DETACHED", 0x40_0000),
+    ("CLONE_UNTRACED", 0x80_0000),
+    ("CLONE_CHILD_SETTID", 0x100_0000),
+    ("CLONE_NEWCGROUP", 0x200_0000),
+    ("CLONE_NEWUTS", 0x400_0000),
+    ("CLONE_NEWIPC", 0x800_0000),
+    ("CLONE_NEWUSER", 0x1000_0000),
+    ("CLONE_NEWPID", 0x2000_0000),
+    ("CLONE_NEWNET", 0x4000_0000),
+    ("CLONE_IO", 0x8000_0000),
+];
+const ADDRESS_FAMILIES: &[(&str, u64)] = &[
+    ("AF_UNSPEC", 0),
+    ("AF_UNIX", 1),
+    ("AF_INET", 2),
+    ("AF_AX25", 3),
+    ("AF_IPX", 4),
+    ("AF_APPLETALK", 5),
+    ("AF_NETROM", 6),
+    ("AF_BRIDGE", 7),
+    ("AF_ATMPVC", 8),
+    ("AF_X25", 9),
+    ("AF_INET6", 10),
+    ("AF_ROSE", 11),
+    ("AF_DECnet", 12),
+    ("AF_NETBEUI", 13),
+    ("AF_SECURITY", 14),
+    ("AF_KEY", 15),
+    ("AF_NETLINK", 16),
+    ("AF_PACKET", 17),
+    ("AF_ASH", 18),
+    ("AF_ECONET", 19),
+    ("AF_ATMSVC", 20),
+    ("AF_RDS", 21),
+    ("AF_SNA", 22),
+    ("AF_IRDA", 23),
+    ("AF_PPPOX", 24),
+    ("AF_WANPIPE", 25),
+    ("AF_LLC", 26),
+    ("AF_IB", 27),
+    ("AF_MPLS", 28),
+    ("AF_CAN", 29),
+    ("AF_TIPC", 30),
+    ("AF_BLUETOOTH", 31),
+    ("AF_IUCV", 32),
+    ("AF_RXRPC", 33),
+    ("AF_ISDN", 34),
+    ("AF_PHONET", 35),
+    ("AF_IEEE802154", 36),
+    ("AF_CAIF", 37),
+    ("AF_ALG", 38),
+    ("AF_NFC", 39),
+    ("AF_VSOCK", 40),
+    ("AF_KCM", 41),
+    ("AF_QIPCRTR", 42),
+    ("AF_SMC", 43),
+    ("AF_XDP", 44),
+    ("AF_MCTP", 45),
+];
+const AF_UNIX: u64 = 1;
+const AF_INET: u64 = 2;
+const AF_INET6: u64 = 10;
+const SOCKET_TYPES: &[(&str, u64)] = &[
+    ("SOCK_STREAM", 1),
+    ("SOCK_DGRAM", 2),
+    ("SOCK_RAW", 3),
+    ("SOCK_RDM", 4),
+    ("SOCK_SEQPACKET", 5),
+    ("SOCK_DCCP", 6),
+    ("SOCK_PACKET", 10),
+];
+const SOCKET_TYPE_MASK: u64 = 0xf;
+const SOCKET_FLAGS: &[(&str, u64)] = &[("SOCK_NONBLOCK", 0o4000), ("SOCK_CLOEXEC", 0o2_000_000)];
+const SCHED_POLICIES: &[(&str, u64)] = &[
+    ("SCHED_OTHER", 0),
+    ("SCHED_FIFO", 1),
+    ("SCHED_RR", 2),
+    ("SCHED_BATCH", 3),
+    ("SCHED_IDLE", 5),
+    ("SCHED_DEADLINE", 6),
+];
+const SHM_FLAGS: &[(&str, u64)] = &[
+    ("SHM_RDONLY", 0o10_000),
+    ("SHM_RND", 0o20_000),
+    ("SHM_REMAP", 0o40_000),
+    ("SHM_EXEC", 0o100_000),
+];
+const IPC_FLAGS: &[(&str, u64)] = &[("IPC_CREAT", 0o1000), ("IPC_EXCL", 0o2000)];
+const EPOLL_CTL_OPS: &[(&str, u64)] = &[
+    ("EPOLL_CTL_ADD", 1),
+    ("EPOLL_CTL_DEL", 2),
+    ("EPOLL_CTL_MOD", 3),
+];
+const EPOLL_EVENTS: &[(&str, u64)] = &[
+    ("EPOLLIN", 0x1),
+    ("EPOLLPRI", 0x2),
+    ("EPOLLOUT", 0x4),
+    ("EPOLLERR", 0x8),
+    ("EPOLLHUP", 0x10),
+    ("EPOLLRDHUP", 0x2000),
+    ("EPOLLEXCLUSIVE", 1 << 28),
+    ("EPOLLWAKEUP", 1 << 29),
+    ("EPOLLONESHOT", 1 << 30),
+    ("EPOLLET", 1 << 31),
+];
+const CLOCK_IDS: &[(&str, u64)] = &[
+    ("CLOCK_REALTIME", 0),
+    ("CLOCK_MONOTONIC", 1),
+    ("CLOCK_PROCESS_CPUTIME_ID", 2),
+    ("CLOCK_THREAD_CPUTIME_ID", 3),
+    ("CLOCK_MONOTONIC_RAW", 4),
+    ("CLOCK_REALTIME_COARSE", 5),
+    ("CLOCK_MONOTONIC_COARSE", 6),
+    ("CLOCK_BOOTTIME", 7),
+    ("CLOCK_REALTIME_ALARM", 8),
+    ("CLOCK_BOOTTIME_ALARM", 9),
+    ("CLOCK_TAI", 11),
+];
+const EXEC_AT_FLAGS: &[(&str, u64)] = &[("AT_SYMLINK_NOFOLLOW", 0x100), ("AT_EMPTY_PATH", 0x1000)];
+
+const OPEN_HOW: &[Member] = &[
+    Member::new("flags", 0, 8, Arg::OpenFlags),
+    Member::new("mode", 8, 8, Arg::Int),
+    Member::new("resolve", 16, 8, Arg::Flags(RESOLVE_FLAGS)),
+];
+const CLONE_ARGS: &[Member] = &[Member::new("flags", 0, 8, Arg::Flags(CLONE_FLAGS))];
+const SCHED_ATTR: &[Member] = &[
+    Member::new("size", 0, 4, Arg::Int),
+    Member::new("sched_policy", 4, 4, Arg::Const(SCHED_POLICIES)),
+];
+const EPOLL_EVENT: &[Member] = &[Member::new("events", 0, 4, Arg::Flags(EPOLL_EVENTS))];
+const TIMEX: &[Member] = &[Member::new("modes", 0, 4, Arg::Int)];
+
+/// Arguments of syscalls whose arguments are looked at when summarizing, other syscalls have all their arguments
+/// decoded as literals
+fn syscall_args(name: &str) -> &'static [Arg] {
+    match name {
+        "read" | "pread64" | "readv" | "preadv" | "preadv2" | "write" | "pwrite64" | "writev"
+        | "pwritev" | "pwritev2" | "ftruncate" | "fallocate" | "fstat" | "getdents" | "fchdir"
+        | "sendmsg" | "recvmsg" | "recvfrom" => &[Arg::Fd],
+        "open" | "mq_open" => &[Arg::Str(0), Arg::OpenFlags, Arg::Mode],
+        "openat" => &[Arg::Fd, Arg::Str(0), Arg::OpenFlags, Arg::Mode],
+        "openat2" => &[Arg::Fd, Arg::Str(0), Arg::Struct(OPEN_HOW), Arg::Int],
+        "creat" | "chmod" | "mkdir" => &[Arg::Str(0), Arg::Mode],
+        "fchmod" => &[Arg::Fd, Arg::Mode],
+        "fchmodat" | "fchmodat2" | "mkdirat" => &[Arg::Fd, Arg::Str(0), Arg::Mode],
+        "mknod" => &[Arg::Str(0), Arg::Mode, Arg::Int],
+        "mknodat" => &[Arg::Fd, Arg::Str(0), Arg::Mode, Arg::Int],
+        "rename" => &[Arg::Str(0), Arg::Str(1)],
+        "renameat" => &[Arg::Fd, Arg::Str(0), Arg::Fd, Arg::Str(1)],
+        "renameat2" => &[
+            Arg::Fd,
+            Arg::Str(0),
+            Arg::Fd,
+            Arg::Str(1),
+            Arg::Flags(RENAME_FLAGS),
+        ],
+        "stat" | "lstat" | "chdir" => &[Arg::Str(0)],
+        "newfstatat" | "statx" => &[Arg::Fd, Arg::Str(0)],
+        "chown" | "lchown" => &[Arg::Str(0), Arg::U32, Arg::U32],
+        "fchown" => &[Arg::Fd, Arg::U32, Arg::U32],
+        "fchownat" => &[Arg::Fd, Arg::Str(0), Arg::U32, Arg::U32],
+        "setuid" | "setgid" | "setfsuid" | "setfsgid" | "personality" => &[Arg::U32],
+        "setreuid" | "setregid" => &[Arg::U32, Arg::U32],
+        "setresuid" | "setresgid" => &[Arg::U32, Arg::U32, Arg::U32],
+        "setgroups" => &[Arg::Int, Arg::Ids { count_idx: 0 }],
+        "mmap" | "mprotect" | "pkey_mprotect" => &[Arg::Int, Arg::Int, Arg::Flags(PROT_FLAGS)],
+        "connect" | "bind" => &[Arg::Fd, Arg::Sockaddr { len_idx: 2 }, Arg::Int],
+        "sendto" => &[
+            Arg::Fd,
+            Arg::Int,
+            Arg::Int,
+            Arg::Int,
+            Arg::Sockaddr { len_idx: 5 },
+            Arg::Int,
+        ],
+        "socket" => &[Arg::Const(ADDRESS_FAMILIES), Arg::SocketType, Arg::Int],
+        "sched_setscheduler" => &[Arg::Int, Arg::Const(SCHED_POLICIES)],
+        "sched_setattr" => &[Arg::Int, Arg::Struct(SCHED_ATTR), Arg::Int],
+        "clone" => &[Arg::Named("flags", &Arg::Flags(CLONE_FLAGS))],
+        "clone3" => &[Arg::Struct(CLONE_ARGS), Arg::Int],
+        "unshare" => &[Arg::Flags(CLONE_FLAGS)],
+        "setns" => &[Arg::Fd, Arg::Flags(CLONE_FLAGS)],
+        "shmat" => &[Arg::Int, Arg::Int, Arg::Flags(SHM_FLAGS)],
+        "shmget" | "semget" => &[Arg::Int, Arg::Int, Arg::Flags(IPC_FLAGS)],
+        "msgget" => &[Arg::Int, Arg::Flags(IPC_FLAGS)],
+        "execve" => &[Arg::Str(0), Arg::Int, Arg::Int],
+        "execveat" => &[
+            Arg::Fd,
+            Arg::Str(0),
+            Arg::Int,
+            Arg::Int,
+            Arg::Flags(EXEC_AT_FLAGS),
+        ],
+        "epoll_ctl" => &[
+            Arg::Fd,
+            Arg::Const(EPOLL_CTL_OPS),
+            Arg::Fd,
+            Arg::Struct(EPOLL_EVENT),
+        ],
+        "mount" => &[Arg::Int, Arg::Str(0)],
+        "move_mount" => &[Arg::Fd, Arg::Str(0), Arg::Fd, Arg::Str(1), Arg::Int],
+        "adjtimex" => &[Arg::Struct(TIMEX)],
+        "clock_adjtime" => &[Arg::Const(CLOCK_IDS), Arg::Struct(TIMEX)],
+        "timer_create" => &[Arg::Const(CLOCK_IDS), Arg::Int, Arg::Int],
+        _ => &[],
+    }
+}
+
+/// What the eBPF program captures for a syscall, laid out like `struct syscall_desc` in `bpf/shh.bpf.c`
+fn syscall_desc(name: &str) -> Option<[u8; 8]> {
+    let args = syscall_args(name);
+    let mut desc = [0; 8];
+    let mut captured = false;
+    for (idx, arg) in args.iter().enumerate() {
+        // Indexes are offset by 1, 0 meaning unused
+        #[expect(clippy::cast_possible_truncation)]
+        let arg_idx = idx as u8 + 1;
+        let (buf_len_arg, buf_size) = match arg {
+            Arg::Str(slot) => {
+                desc[*slot] = arg_idx;
+                captured = true;
+                continue;
+            }
+            Arg::Struct(members) => (
+                0,
+                members
+                    .iter()
+                    .map(|m| m.offset + m.size)
+                    .max()
+                    .unwrap_or_default(),
+            ),
+            #[expect(clippy::cast_possible_truncation)]
+            Arg::Sockaddr { len_idx } => (*len_idx as u8 + 1, MAX_BUF_LEN),
+            Arg::Ids { .. } => (0, MAX_BUF_LEN),
+            _ => continue,
+        };
+        desc[2] = arg_idx;
+        desc[3] = buf_len_arg;
+        #[expect(clippy::cast_possible_truncation)]
+        desc[4..6].copy_from_slice(&(buf_size as u16).to_ne_bytes());
+        captured = true;
+    }
+    if matches!(name, "execve" | "execveat") {
+        desc[6] = DESC_EXEC;
+    }
+    captured.then_some(desc)
+}
+
+/// Descriptions of the syscalls whose string or buffer arguments the eBPF program must capture, by number
+pub(super) fn syscall_descs() -> Vec<(u32, [u8; 8])> {
+    SYSCALL_NAMES
+        .iter()
+        .filter_map(|(nr, name)| Some((u32::try_from(*nr).ok()?, syscall_desc(name)?)))
+        .collect()
+}
+
+/// Whether syscall names are known for the native ABI
+pub(super) fn is_supported_arch() -> bool {
+    !SYSCALL_NAMES.is_empty()
+}
+
+/// Decodes raw events into syscalls
+pub(super) struct Decoder {
+    names: HashMap<i64, &'static str>,
+    /// Timestamp of the previous event, to compute relative timestamps like `strace -r`
+    prev_ts_ns: Option<u64>,
+}
+
+impl Decoder {
+    pub(super) fn new() -> Self {
+        Self {
+            names: SYSCALL_NAMES.iter().copied().collect(),
+            prev_ts_ns: None,
+        }
+    }
+
+    /// Decode raw event, or return `None` if it is a failed syscall, which are ignored like when profiling with
+    /// strace, except the ones denied for lack of privileges
+    pub(super) fn decode(&mut self, event: &RawEvent) -> Option<Syscall> {
+        let name = self.names.get(&event.nr).map_or_else(
+            // Same as strace for unknown syscalls
+            || format!("syscall_{:#x}", event.nr),
+            |n| (*n).to_owned(),
+        );
+        if (-MAX_ERRNO..0).contains(&event.ret)
+            && (event.ret != -EPERM || !CAPABILITY_GATED_SYSCALLS.contains(&name.as_str()))
+        {
+            return None;
+        }
+
+        let arg_descs = syscall_args(&name);
+        let mut args = Vec::with_capacity(event.args.len());
+        let mut named_args = HashMap::new();
+        for (idx, val) in event.args.iter().enumerate() {
+            match arg_descs.get(idx) {
+                Some(Arg::Named(arg_name, arg)) => {
+                    named_args.insert((*arg_name).to_owned(), decode_arg(arg, *val, event));
+                }
+                Some(arg) => args.push(decode_arg(arg, *val, event)),
+                None => args.push(literal(*val)),
+            }
+        }
+        if !named_args.is_empty() {
+            // Named arguments are stuffed in a single struct by the strace output parser
+            args = vec![Expression::Struct(named_args)];
+        }
+
+        #[expect(clippy::cast_precision_loss)]
+        let rel_ts = self
+            .prev_ts_ns
+            .map_or(0.0, |prev| event.ts_ns.saturating_sub(prev) as f64 / 1e9);
+        self.prev_ts_ns = Some(event.ts_ns);
+
+        Some(Syscall {
+            pid: event.pid,
+            rel_ts,
+            name,
+            args,
+            ret_val: event.ret.into(),
+        })
+    }
+}
+
+fn literal(val: u64) -> Expression {
+    Expression::Integer(IntegerExpression {
+        value: IntegerExpressionValue::Literal(val.into()),
+        metadata: None,
+    })
+}
+
+fn integer(value: IntegerExpressionValue) -> Expression {
+    Expression::Integer(IntegerExpression {
+        value,
+        metadata: None,
+    })
+}
+
+fn buffer(value: Vec<u8>) -> Expression {
+    Expression::Buffer(BufferExpression {
+        value,
+        type_: BufferType::Unknown,
+    })
+}
+
+fn decode_arg(arg: &Arg, val: u64, event: &RawEvent) -> Expression {
+    match arg {
+        Arg::Int => literal(val),
+        Arg::U32 => literal(val & u64::from(u32::MAX)),
+        Arg::Fd => decode_fd(val, event.pid),
+        Arg::Str(slot) => event
+            .strs
+            .get(*slot)
+            .and_then(Clone::clone)
+            // Could not be read, but the summarizer expects a string
+            .map_or_else(|| buffer(vec![]), buffer),
+        Arg::Flags(flags) => integer(flags_value(val, flags)),
+        Arg::Const(consts) => integer(const_value(val, consts)),
+        Arg::OpenFlags => integer(open_flags_value(val)),
+        Arg::Mode => integer(mode_value(val)),
+        Arg::SocketType => {
+            let type_ = const_value(val & SOCKET_TYPE_MASK, SOCKET_TYPES);
+            integer(match flags_value(val & !SOCKET_TYPE_MASK, SOCKET_FLAGS) {
+                IntegerExpressionValue::Literal(0) => type_,
+                IntegerExpressionValue::BinaryOr(mut flags) => {
+                    flags.insert(0, type_);
+                    IntegerExpressionValue::BinaryOr(flags)
+                }
+                flag => IntegerExpressionValue::BinaryOr(vec![type_, flag]),
+            })
+        }
+        Arg::Struct(members) => event
+            .buf
+            .as_deref()
+            .and_then(|buf| decode_struct(buf, members, event))
+            .unwrap_or_else(|| literal(val)),
+        Arg::Sockaddr { .. } => event
+            .buf
+            .as_deref()
+            .and_then(decode_sockaddr)
+            .unwrap_or_else(|| literal(val)),
+        Arg::Ids { count_idx } => decode_ids(
+            event.buf.as_deref().unwrap_or_default(),
+            event.args.get(*count_idx).copied().unwrap_or_default(),
+        ),
+        Arg::Named(_, arg) => decode_arg(arg, val, event),
+    }
+}
+
+/// Decode file descriptor, with the path it refers to, if any
+fn decode_fd(val: u64, pid: u32) -> Expression {
+    #[expect(clippy::cast_possible_truncation)]
+    let fd = val as i32;
+    if fd == AT_FDCWD {
+        return integer(IntegerExpressionValue::NamedConst("AT_FDCWD".to_owned()));
+    }
+    // TODO APPROXIMATION
+    // Unlike with strace, the fd is resolved after the syscall, so it may have been closed (ie. if it was the
+    // syscall that closed it) or reused meanwhile, or the process may have exited
+    let metadata = (fd >= 0)
+        .then(|| fs::read_link(format!("/proc/{pid}/fd/{fd}")).ok())
+        .flatten()
+        .map(|p| p.as_os_str().as_bytes().to_vec());
+    Expression::Integer(IntegerExpression {
+        value: IntegerExpressionValue::Literal(fd.into()),
+        metadata,
+    })
+}
+
+/// Named constants for all flags in `val`, and a literal for the remaining bits
+fn flags_value(val: u64, flags: &[(&str, u64)]) -> IntegerExpressionValue {
+    let mut rest = val;
+    let mut values = Vec::new();
+    for (name, flag) in flags {
+        // Some flags are several bits, including the ones of other flags
+        if (*flag != 0) && (rest & flag == *flag) {
+            values.push(IntegerExpressionValue::NamedConst((*name).to_owned()));
+            rest &= !flag;
+        }
+    }
+    if (rest != 0) || values.is_empty() {
+        values.push(IntegerExpressionValue::Literal(rest.into()));
+    }
+    if values.len() == 1 {
+        values.remove(0)
+    } else {
+        IntegerExpressionValue::BinaryOr(values)
+    }
+}
+
+/// Named constant for `val`, or a literal if it is unknown
+fn const_value(val: u64, consts: &[(&str, u64)]) -> IntegerExpressionValue {
+    consts.iter().find(|(_, c)| *c == val).map_or_else(
+        || IntegerExpressionValue::Literal(val.into()),
+        |(name, _)| IntegerExpressionValue::NamedConst((*name).to_owned()),
+    )
+}
+
+/// Open flags, with the access mode first, like strace
+fn open_flags_value(val: u64) -> IntegerExpressionValue {
+    let access_mode = const_value(val & 0b11, OPEN_ACCESS_MODES);
+    match flags_value(val & !0b11, OPEN_FLAGS) {
+        IntegerExpressionValue::Literal(0) => access_mode,
+        IntegerExpressionValue::BinaryOr(mut flags) => {
+            flags.insert(0, access_mode);
+            IntegerExpressionValue::BinaryOr(flags)
+        }
+        flag => IntegerExpressionValue::BinaryOr(vec![access_mode, flag]),
+    }
+}
+
+/// File mode, with the file type as a named constant, and the permissions as a literal
+fn mode_value(val: u64) -> IntegerExpressionValue {
+    let perms = IntegerExpressionValue::Literal((val & !FILE_TYPE_MASK).into());
+    match FILE_TYPES.iter().find(|(_, t)| *t == val & FILE_TYPE_MASK) {
+        Some((name, _)) => IntegerExpressionValue::BinaryOr(vec![
+            IntegerExpressionValue::NamedConst((*name).to_owned()),
+            perms,
+        ]),
+        None => IntegerExpressionValue::Literal(val.into()),
+    }
+}
+
+/// Read native endian integer of `size` bytes at `offset`
+fn buf_integer(buf: &[u8], offset: usize, size: usize) -> Option<u64> {
+    let bytes = buf.get(offset..offset + size)?;
+    Some(match size {
+        2 => u16::from_ne_bytes(bytes.try_into().ok()?).into(),
+        4 => u32::from_ne_bytes(bytes.try_into().ok()?).into(),
+        8 => u64::from_ne_bytes(bytes.try_into().ok()?),
+        _ => return None,
+    })
+}
+
+fn decode_struct(buf: &[u8], members: &[Member], event: &RawEvent) -> Option<Expression> {
+    let mut decoded = HashMap::with_capacity(members.len());
+    for member in members {
+        let val = buf_integer(buf, member.offset, member.size)?;
+        decoded.insert(member.name.to_owned(), decode_arg(&member.arg, val, event));
+    }
+    Some(Expression::Struct(decoded))
+}
+
+/// Decode socket address, with the same members as strace for the address families we look at
+fn decode_sockaddr(buf: &[u8]) -> Option<Expression> {
+    let family = buf_integer(buf, 0, 2)?;
+    let mut members = HashMap::from([(
+        "sa_family".to_owned(),
+        integer(const_value(family, ADDRESS_FAMILIES)),
+    )]);
+    let port = || {
+        let port = u16::from_be_bytes(buf.get(2..4)?.try_into().ok()?);
+        Some(Expression::Macro {
+            name: "htons".to_owned(),
+            args: vec![literal(port.into())],
+        })
+    };
+    match family {
+        AF_UNIX => {
+            let path = buf.get(2..).unwrap_or_default();
+            let (path, type_) = match path.split_first() {
+                // Abstract socket, not in the filesystem
+                Some((0, name)) => (name, BufferType::AbstractPath),
+                _ => (path, BufferType::Unknown),
+            };
+            let path = path.split(|b| *b == 0).next().unwrap_or_default();
+            members.insert(
+                "sun_path".to_owned(),
+                Expression::Buffer(BufferExpression {
+                    value: path.to_vec(),
+                    type_,
+                }),
+            );
+        }
+        AF_INET => {
+            let addr: [u8; 4] = buf.get(4..8)?.try_into().ok()?;
+            members.insert("sin_port".to_owned(), port()?);
+            members.insert(
+                "sin_addr".to_owned(),
+                Expression::Macro {
+                    name: "inet_addr".to_owned(),
+                    args: vec![buffer(Ipv4Addr::from(addr).to_string().into_bytes())],
+                },
+            );
+        }
+        AF_INET6 => {
+            let addr: [u8; 16] = buf.get(8..24)?.try_into().ok()?;
+            members.insert("sin6_port".to_owned(), port()?);
+            members.insert(
+                "sin6_addr".to_owned(),
+                Expression::Macro {
+                    name: "inet_pton".to_owned(),
+                    args: vec![
+                        integer(IntegerExpressionValue::NamedConst("AF_INET6".to_owned())),
+                        buffer(Ipv6Addr::from(addr).to_string().into_bytes()),
+                        Expression::DestinationAddress("sin6_addr".to_owned()),
+                    ],
+                },
+            );
+        }
+        _ => (),
+    }
+    Some(Expression::Struct(members))
+}
+
+/// Decode array of 32 bit ids, with an unknown id if not all of them were captured
+fn decode_ids(buf: &[u8], count: u64) -> Expression {
+    let count = usize::try_from(count).unwrap_or(usize::MAX);
+    let mut values: Vec<_> = buf
+        .chunks_exact(4)
+        .take(count)
+        .filter_map(|id| Some(literal(u32::from_ne_bytes(id.try_into().ok()?).into())))
+        .collect();
+    if values.len() < count {
+        // Unknown, assumed to be the worst
+        values.push(Expression::Macro {
+            name: "truncated".to_owned(),
+            args: vec![],
+        });
+    }
+    Expression::Collection {
+        complement: false,
+        values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, os::fd::AsRawFd as _, process};
+
+    use super::*;
+
+    #[expect(clippy::cast_sign_loss)]
+    const AT_FDCWD_ARG: u64 = AT_FDCWD as u64;
+
+    fn named(name: &str) -> IntegerExpressionValue {
+        IntegerExpressionValue::NamedConst(name.to_owned())
+    }
+
+    fn event(name: &str, args: &[u64], ret: i64) -> RawEvent {
+        let mut event = RawEvent {
+            pid: process::id(),
+            nr: SYSCALL_NAMES
+                .iter()
+                .find_map(|(nr, n)| (*n == name).then_some(*nr))
+                .unwrap(),
+            ret,
+            ..RawEvent::default()
+        };
+        event.args[..args.len()].copy_from_slice(args);
+        event
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut data = vec![0; EVENT_HEADER_SIZE + MAX_STR_ARGS * MAX_STR_LEN];
+        data[0..4].copy_from_slice(&1234_u32.to_ne_bytes());
+        data[4..8].copy_from_slice(&2_i32.to_ne_bytes());
+        data[8..16].copy_from_slice(&257_i64.to_ne_bytes());
+        data[16..24].copy_from_slice(&1_000_u64.to_ne_bytes());
+        data[24..32].copy_from_slice(&(-2_i64).to_ne_bytes());
+        data[40..48].copy_from_slice(&42_u64.to_ne_bytes());
+        data[80..84].copy_from_slice(&5_i32.to_ne_bytes());
+        data[84..88].copy_from_slice(&(-14_i32).to_ne_bytes());
+        data[88..90].copy_from_slice(&[1, 2]);
+        data[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + 5].copy_from_slice(b"/etc\0");
+
+        let event = RawEvent::parse(&data).unwrap();
+        assert_eq!(event.pid, 1234);
+        assert_eq!(event.nr, 257);
+        assert_eq!(event.ts_ns, 1_000);
+        assert_eq!(event.ret, -2);
+        assert_eq!(event.args, [0, 42, 0, 0, 0, 0]);
+        assert_eq!(event.strs, [Some(b"/etc".to_vec()), None]);
+        assert_eq!(event.buf, Some(vec![1, 2]));
+        assert!(!event.is_done());
+
+        // Without strings
+        let event = RawEvent::parse(&data[..EVENT_HEADER_SIZE]).unwrap();
+        assert_eq!(event.strs, [None, None]);
+
+        data[8..16].copy_from_slice(&DONE_NR.to_ne_bytes());
+        assert!(RawEvent::parse(&data).unwrap().is_done());
+
+        assert!(RawEvent::parse(&data[..64]).is_err());
+    }
+
+    #[test]
+    fn test_syscall_descs() {
+        if !is_supported_arch() {
+            return;
+        }
+        let descs: HashMap<_, _> = syscall_descs().into_iter().collect();
+        // openat: path in 2nd argument
+        assert_eq!(descs.get(&257), Some(&[2, 0, 0, 0, 0, 0, 0, 0]));
+        // renameat2: paths in 2nd and 4th arguments
+        assert_eq!(descs.get(&316), Some(&[2, 4, 0, 0, 0, 0, 0, 0]));
+        // connect: socket address in 2nd argument, with its length in the 3rd one
+        let mut desc = [0, 0, 2, 3, 0, 0, 0, 0];
+        desc[4..6].copy_from_slice(&128_u16.to_ne_bytes());
+        assert_eq!(descs.get(&42), Some(&desc));
+        // execve: executed path in 1st argument
+        assert_eq!(descs.get(&59), Some(&[1, 0, 0, 0, 0, 0, DESC_EXEC, 0]));
+        // read: nothing to capture
+        assert!(!descs.contains_key(&0));
+    }
+
+    #[test]
+    fn test_decode_open() {
+        if !is_supported_arch() {
+            return;
+        }
+        let mut decoder = Decoder::new();
+
+        let mut ev = event("openat", &[AT_FDCWD_ARG, 0, 0o1101, 0o644], 3);
+        ev.strs[0] = Some(b"/var/log/foo".to_vec());
+        let syscall = decoder.decode(&ev).unwrap();
+        assert_eq!(syscall.name, "openat");
+        assert_eq!(syscall.ret_val, 3);
+        assert_eq!(syscall.args[0], integer(named("AT_FDCWD")));
+        assert_eq!(syscall.args[1], buffer(b"/var/log/foo".to_vec()));
+        assert_eq!(
+            syscall.args[2],
+            integer(IntegerExpressionValue::BinaryOr(vec![
+                named("O_WRONLY"),
+                named("O_CREAT"),
+                named("O_TRUNC")
+            ]))
+        );
+        assert_eq!(syscall.args[3], literal(0o644));
+
+        let mut ev = event("open", &[0, 0], 3);
+        ev.strs[0] = Some(b"/etc/passwd".to_vec());
+        let syscall = decoder.decode(&ev).unwrap();
+        assert_eq!(syscall.args[1], integer(named("O_RDONLY")));
+
+        // Failed, ignored
+        let ev = event("openat", &[AT_FDCWD_ARG, 0, 0], -2);
+        assert!(decoder.decode(&ev).is_none());
+    }
+
+    #[test]
+    fn test_decode_failures() {
+        if !is_supported_arch() {
+            return;
+        }
+        let mut decoder = Decoder::new();
+        // Denied for lack of privileges, kept
+        assert!(decoder.decode(&event("setuid", &[1000], -EPERM)).is_some());
+        // Denied for another reason
+        assert!(decoder.decode(&event("unlink", &[0], -EPERM)).is_none());
+        assert!(decoder.decode(&event("setuid", &[1000], -22)).is_none());
+        // Not a failure
+        assert!(decoder
+            .decode(&event(
+                "mmap",
+                &[0, 4096, 3, 0x22, u64::MAX, 0],
+                0x7f00_0000_0000
+            ))
+            .is_some());
+    }
+
+    #[test]
+    fn test_decode_fd() {
+        if !is_supported_arch() {
+            return;
+        }
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let fd = File::open(file.path()).unwrap();
+        let mut decoder = Decoder::new();
+        let syscall = decoder
+            .decode(&event(
+                "read",
+                &[fd.as_raw_fd().try_into().unwrap(), 0, 1],
+                1,
+            ))
+            .unwrap();
+        assert_eq!(
+            syscall.args[0].metadata(),
+            Some(file.path().canonicalize().unwrap().as_os_str().as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_decode_socket() {
+        if !is_supported_arch() {
+            return;
+        }
+        let mut decoder = Decoder::new();
+
+        let syscall = decoder
+            .decode(&event("socket", &[AF_INET, 1 | 0o2_000_000, 6], 3))
+            .unwrap();
+        assert_eq!(syscall.args[0], integer(named("AF_INET")));
+        assert_eq!(
+            syscall.args[1],
+            integer(IntegerExpressionValue::BinaryOr(vec![
+                named("SOCK_STREAM"),
+                named("SOCK_CLOEXEC")
+            ]))
+        );
+
+        let mut ev = event("connect", &[3, 0x1000, 16], 0);
+        let mut sockaddr = vec![0; 16];
+        sockaddr[0..2].copy_from_slice(&2_u16.to_ne_bytes());
+        sockaddr[2..4].copy_from_slice(&443_u16.to_be_bytes());
+        sockaddr[4..8].copy_from_slice(&[93, 184, 215, 14]);
+        ev.buf = Some(sockaddr);
+        let syscall = decoder.decode(&ev).unwrap();
+        assert_eq!(
+            syscall.args[1],
+            Expression::Struct(HashMap::from([
+                ("sa_family".to_owned(), integer(named("AF_INET"))),
+                (
+                    "sin_port".to_owned(),
+                    Expression::Macro {
+                        name: "htons".to_owned(),
+                        args: vec![literal(443)],
+                    }
+                ),
+                (
+                    "sin_addr".to_owned(),
+                    Expression::Macro {
+                        name: "inet_addr".to_owned(),
+                        args: vec![buffer(b"93.184.215.14".to_vec())],
+                    }
+                ),
+            ]))
+        );
+
+        let mut ev = event("bind", &[3, 0x1000, 14], 0);
+        let mut sockaddr = 1_u16.to_ne_bytes().to_vec();
+        sockaddr.extend(b"/run/foo\0");
+        ev.buf = Some(sockaddr);
+        let syscall = decoder.decode(&ev).unwrap();
+        let Expression::Struct(members) = &syscall.args[1] else {
+            panic!();
+        };
+        assert_eq!(members.get("sun_path"), Some(&buffer(b"/run/foo".to_vec())));
+
+        // Socket address could not be read
+        let syscall = decoder
+            .decode(&event("connect", &[3, 0x1000, 16], 0))
+            .unwrap();
+        assert_eq!(syscall.args[1], literal(0x1000));
+    }
+
+    #[test]
+    fn test_decode_named_and_struct_args() {
+        if !is_supported_arch() {
+            return;
+        }
+        let mut decoder = Decoder::new();
+
+        let syscall = decoder
+            .decode(&event("clone", &[0x4000_0000 | 17, 0, 0, 0, 0], 2))
+            .unwrap();
+        assert_eq!(
+            syscall.args,
+            vec![Expression::Struct(HashMap::from([(
+                "flags".to_owned(),
+                integer(IntegerExpressionValue::BinaryOr(vec![
+                    named("CLONE_NEWNET"),
+                    IntegerExpressionValue::Literal(17)
+                ]))
+            )]))]
+        );
+
+        let mut ev = event("clone3", &[0x1000, 88], 2);
+        ev.buf = Some(0x2_0000_u64.to_ne_bytes().to_vec());
+        let syscall = decoder.decode(&ev).unwrap();
+        assert_eq!(
+            syscall.args[0],
+            Expression::Struct(HashMap::from([(
+                "flags".to_owned(),
+                integer(named("CLONE_NEWNS"))
+            )]))
+        );
+
+        // Privileges dropped
+        let syscall = decoder.decode(&event("setgroups", &[0, 0], 0)).unwrap();
+        assert_eq!(
+            syscall.args[1],
+            Expression::Collection {
+                complement: false,
+                values: vec![]
+            }
+        );
+
+        let mut ev = event("setgroups", &[2, 0x1000], 0);
+        ev.buf = Some([0_u32.to_ne_bytes(), 42_u32.to_ne_bytes()].concat());
+        let syscall = decoder.decode(&ev).unwrap();
+        assert_eq!(
+            syscall.args[1],
+            Expression::Collection {
+                complement: false,
+                values: vec![literal(0), literal(42)]
+            }
+        );
+    }
+
+    #[test]
+    fn test_flags_value() {
+        assert_eq!(
+            flags_value(0, PROT_FLAGS),
+            IntegerExpressionValue::Literal(0)
+        );
+        assert_eq!(flags_value(4, PROT_FLAGS), named("PROT_EXEC"));
+        assert_eq!(
+            flags_value(0o20_200_000 | 0o100, OPEN_FLAGS),
+            IntegerExpressionValue::BinaryOr(vec![named("O_TMPFILE"), named("O_CREAT")])
+        );
+        assert_eq!(
+            mode_value(0o020_666),
+            IntegerExpressionValue::BinaryOr(vec![
+                named("S_IFCHR"),
+                IntegerExpressionValue::Literal(0o666)
+            ])
+        );
+    }
+}
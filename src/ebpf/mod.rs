@@ -0,0 +1,259 @@
+//! eBPF tracing code, an alternative to strace with a much lower overhead on the profiled program
+
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    mem::MaybeUninit,
+    os::unix::process::CommandExt as _,
+    process::{Child, Command, ExitStatus, Stdio},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use libbpf_rs::{
+    skel::{OpenSkel as _, Skel as _, SkelBuilder as _},
+    MapCore as _, MapFlags, RingBufferBuilder,
+};
+
+use crate::strace::{Syscall, TraceeGroup};
+
+mod decode;
+mod names;
+
+use decode::{Decoder, RawEvent};
+
+/// Generated by the build script from `bpf/shh.bpf.c`
+mod skel {
+    // Which lints the generated code triggers depends on the libbpf-cargo version, so they can not be expected
+    #![allow(
+        clippy::pedantic,
+        clippy::restriction,
+        single_use_lifetimes,
+        unreachable_pub,
+        unused_qualifications
+    )]
+    include!(concat!(env!("OUT_DIR"), "/shh.skel.rs"));
+}
+
+/// Interval at which the event polling thread checks if it must stop
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A command traced by the eBPF program
+pub(crate) struct Ebpf {
+    /// Traced command
+    process: Child,
+    /// Traced command was reaped, so its pid may have been reused
+    reaped: Arc<Mutex<bool>>,
+    /// Raw events, until the polling thread stops
+    events: Option<mpsc::Receiver<Vec<u8>>>,
+    /// All traced processes have exited
+    done: Arc<AtomicBool>,
+    /// Dropped after the command is killed
+    _poll_thread: PollThread,
+}
+
+/// Thread polling events of the eBPF program, which is unloaded when it stops
+struct PollThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for PollThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Ebpf {
+    /// Load the eBPF program, and run the command traced by it
+    pub(crate) fn run(command: &[&str]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            decode::is_supported_arch(),
+            "eBPF tracing is not supported on this architecture"
+        );
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("Empty command"))?;
+
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (events_tx, events_rx) = mpsc::channel();
+        let done = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        // The eBPF program is loaded from this thread, so that the thread exists before we start following our next
+        // child, and is not traced itself
+        let handle = {
+            let done = Arc::clone(&done);
+            let stop = Arc::clone(&stop);
+            thread::Builder::new()
+                .name("ebpf-events".to_owned())
+                .spawn(move || {
+                    if let Err(err) = Self::poll_events(&ready_tx, events_tx, &done, &stop) {
+                        // Receiver is gone if we were already tracing
+                        if let Err(mpsc::SendError(Err(err))) = ready_tx.send(Err(err)) {
+                            log::error!("eBPF event polling failed: {err:#}");
+                        }
+                    }
+                })?
+        };
+        let poll_thread = PollThread {
+            stop,
+            handle: Some(handle),
+        };
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("eBPF event polling thread exited"))??;
+
+        let process = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            // Traced command leads its own process group, like with strace
+            .process_group(0)
+            .spawn()
+            .with_context(|| format!("Failed to start {program:?}"))?;
+        Ok(Self {
+            process,
+            reaped: Arc::new(Mutex::new(false)),
+            events: Some(events_rx),
+            done,
+            _poll_thread: poll_thread,
+        })
+    }
+
+    /// Load and attach the eBPF program, then forward its events until all traced processes have exited, or we are
+    /// asked to stop
+    fn poll_events(
+        ready_tx: &mpsc::Sender<anyhow::Result<()>>,
+        events_tx: mpsc::Sender<Vec<u8>>,
+        done: &AtomicBool,
+        stop: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        let mut open_object = MaybeUninit::uninit();
+        let open_skel = skel::ShhSkelBuilder::default()
+            .open(&mut open_object)
+            .context("Failed to open eBPF program")?;
+        let mut skel = open_skel
+            .load()
+            .context("Failed to load eBPF program, this needs CAP_BPF and CAP_PERFMON, and a kernel with BTF")?;
+        for (nr, desc) in decode::syscall_descs() {
+            skel.maps
+                .syscall_descs
+                .update(&nr.to_ne_bytes(), &desc, MapFlags::ANY)?;
+        }
+        skel.attach().context("Failed to attach eBPF program")?;
+
+        let mut ring_buffer = RingBufferBuilder::new();
+        ring_buffer.add(&skel.maps.events, |data| {
+            if RawEvent::parse(data).is_ok_and(|e| e.is_done()) {
+                done.store(true, Ordering::Release);
+            }
+            // Receiver is gone if profiling was aborted
+            if events_tx.send(data.to_vec()).is_err() {
+                stop.store(true, Ordering::Release);
+            }
+            0
+        })?;
+        let ring_buffer = ring_buffer.build()?;
+
+        // Follow the next process we create, laid out like `struct config` in `bpf/shh.bpf.c`
+        let config = [std::process::id().to_ne_bytes(), 1_u32.to_ne_bytes()].concat();
+        skel.maps
+            .config
+            .update(&0_u32.to_ne_bytes(), &config, MapFlags::ANY)?;
+        ready_tx
+            .send(Ok(()))
+            .map_err(|_| anyhow::anyhow!("eBPF tracing was aborted"))?;
+
+        while !done.load(Ordering::Acquire) && !stop.load(Ordering::Acquire) {
+            ring_buffer.poll(POLL_INTERVAL)?;
+        }
+        Ok(())
+    }
+
+    /// Exit status of the traced command, if it has exited
+    pub(crate) fn tracee_status(&mut self) -> anyhow::Result<Option<ExitStatus>> {
+        #[expect(clippy::unwrap_used)]
+        let mut reaped = self.reaped.lock().unwrap();
+        let status = if self.done.load(Ordering::Acquire) {
+            // The command may not be reapable yet when the eBPF program sees it exiting
+            Some(self.process.wait()?)
+        } else {
+            self.process.try_wait()?
+        };
+        *reaped = status.is_some();
+        Ok(status)
+    }
+
+    /// Handle to signal the process group of the traced command
+    #[expect(clippy::cast_possible_wrap)]
+    pub(crate) fn tracee_group(&self) -> TraceeGroup {
+        TraceeGroup::new(
+            nix::unistd::Pid::from_raw(self.process.id() as i32),
+            Arc::clone(&self.reaped),
+        )
+    }
+
+    /// Syscalls of the traced command and its descendants, until they all exit
+    pub(crate) fn events(&mut self) -> anyhow::Result<Events> {
+        Ok(Events {
+            events: self
+                .events
+                .take()
+                .ok_or_else(|| anyhow::anyhow!("eBPF events are already consumed"))?,
+            decoder: Decoder::new(),
+            personality_modes: Rc::default(),
+        })
+    }
+}
+
+impl Drop for Ebpf {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Syscalls decoded from eBPF events
+pub(crate) struct Events {
+    events: mpsc::Receiver<Vec<u8>>,
+    decoder: Decoder,
+    /// TODO APPROXIMATION
+    /// Unlike strace, we do not detect programs using another ABI (ie. 32 bit programs), which are not supported
+    personality_modes: Rc<RefCell<BTreeSet<String>>>,
+}
+
+impl Events {
+    /// Non native ABIs used by the traced programs, always empty
+    pub(crate) fn shared_personality_modes(&self) -> Rc<RefCell<BTreeSet<String>>> {
+        Rc::clone(&self.personality_modes)
+    }
+}
+
+impl Iterator for Events {
+    type Item = anyhow::Result<Syscall>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // Polling thread stops, dropping the sender, once all traced processes have exited
+            let data = self.events.recv().ok()?;
+            let event = match RawEvent::parse(&data) {
+                Ok(event) => event,
+                Err(err) => return Some(Err(err)),
+            };
+            if event.is_done() {
+                return None;
+            }
+            if let Some(syscall) = self.decoder.decode(&event) {
+                return Some(Ok(syscall));
+            }
+        }
+    }
+}
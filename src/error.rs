@@ -0,0 +1,113 @@
+//! Error types for failures callers may want to handle programmatically
+
+use std::{error::Error, fmt, process::ExitStatus};
+
+/// Explanation for a partial profiling result snippet in the journal
+const TRUNCATED_JOURNAL_HINT: &str = "The service journal was likely rotated or vacuumed while reading it, \
+    consider increasing journald SystemMaxUse= or RuntimeMaxUse= (see journald.conf(5)) and profiling again.";
+
+/// Marker delimiting the profiling result snippet in the unit logs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SnippetMarker {
+    /// Marker logged before the options
+    Start,
+    /// Marker logged after the options
+    End,
+}
+
+/// Main failure classes of shh operations
+///
+/// Errors returned as `anyhow::Error` can be matched with `downcast_ref::<ShhError>()`.
+#[derive(Debug)]
+pub enum ShhError {
+    /// Unit is not loaded by the service manager, so its config files can not be located
+    UnitNotFound {
+        /// Unit load state, as reported by systemd
+        state: String,
+        /// Reason given by systemd
+        details: String,
+    },
+    /// Profiling result snippet is absent from the unit logs, or truncated
+    ProfilingSnippetMissing {
+        /// Marker missing from a truncated snippet, `None` if no snippet was found at all
+        missing_marker: Option<SnippetMarker>,
+    },
+    /// Config line could not be parsed
+    ConfigParse {
+        /// Offending line
+        line: String,
+        /// Parser error
+        reason: String,
+    },
+    /// Syscall arguments do not have the expected layout
+    SyscallArgs {
+        /// Syscall name
+        name: String,
+        /// Debug representation of the arguments
+        args: String,
+    },
+    /// systemctl command exited with an error
+    SystemctlFailed {
+        /// Exit status of the command
+        status: ExitStatus,
+    },
+    /// Unit logs could not be read
+    JournalRead(anyhow::Error),
+    /// Profiled program was killed by `SIGKILL`, so its profile is partial
+    ProgramKilled {
+        /// An out of memory kill happened in our cgroup while profiling
+        oom_killed: bool,
+    },
+}
+
+impl fmt::Display for ShhError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnitNotFound { state, details } => {
+                write!(
+                    f,
+                    "Unit is {state}, unable to locate its config files {details}"
+                )
+            }
+            Self::ProfilingSnippetMissing {
+                missing_marker: None,
+            } => write!(f, "Unable to get profiling result snippet"),
+            Self::ProfilingSnippetMissing {
+                missing_marker: Some(marker),
+            } => {
+                let marker = match marker {
+                    SnippetMarker::Start => "start",
+                    SnippetMarker::End => "end",
+                };
+                write!(
+                    f,
+                    "Profiling result snippet is truncated, its {marker} marker is missing. {TRUNCATED_JOURNAL_HINT}"
+                )
+            }
+            Self::ConfigParse { line, reason } => {
+                write!(f, "Unable to parse config line {line:?}: {reason}")
+            }
+            Self::SyscallArgs { name, args } => write!(f, "Unexpected args for {name}: {args}"),
+            Self::SystemctlFailed { status } => write!(f, "systemctl failed: {status}"),
+            Self::JournalRead(_) => write!(f, "Unable to read unit logs"),
+            Self::ProgramKilled { oom_killed: true } => write!(
+                f,
+                "Profiled program was killed by the out of memory killer, its profile is partial. \
+                 Consider raising the memory limit while profiling, ie. with --profile-memory-max"
+            ),
+            Self::ProgramKilled { oom_killed: false } => write!(
+                f,
+                "Profiled program was killed by SIGKILL, its profile is partial"
+            ),
+        }
+    }
+}
+
+impl Error for ShhError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::JournalRead(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
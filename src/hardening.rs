@@ -0,0 +1,337 @@
+//! Hardening options
+
+use std::{collections::HashSet, fmt, iter, num::NonZeroU16, path::PathBuf, str::FromStr};
+
+use crate::{
+    summarize::{
+        CountableSetSpecifier, NetworkActivity, NetworkActivityKind, ProgramAction, SetSpecifier,
+    },
+    systemd::{SocketFamily, SocketProtocol, SystemdVersion, KNOWN_SYSCALLS, SOCKET_FAMILIES},
+};
+
+/// Default minimum number of written paths in a directory to allow writing to the whole directory
+const DEFAULT_PATH_GROUPING_THRESHOLD: usize = 3;
+
+/// How hard we should harden
+#[derive(Debug, Clone, Default, clap::ValueEnum, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum HardeningMode {
+    /// Only generate hardening options if they have a very low risk of breaking things
+    #[default]
+    Safe,
+    /// Will harden further and prevent circumventing restrictions of some options, but may increase the risk of
+    /// breaking services
+    Aggressive,
+}
+
+/// How much we should coarsen allowed IP addresses into networks
+#[derive(Debug, Clone, Default, clap::ValueEnum, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum IpAllowCoarsening {
+    /// Only allow the exact addresses that were used
+    #[default]
+    Host,
+    /// Allow /24 IPv4 networks, and /64 IPv6 networks
+    Subnet,
+    /// Allow /16 IPv4 networks, and /48 IPv6 networks
+    Wide,
+}
+
+/// Options controlling how hardening options are generated
+#[derive(Debug, Clone, clap::Parser)]
+pub struct HardeningOptions {
+    /// How hard we should harden
+    #[arg(short, long, default_value_t, value_enum)]
+    pub mode: HardeningMode,
+    /// Enable advanced network firewalling
+    #[arg(short = 'f', long, default_value_t)]
+    pub network_firewalling: bool,
+    /// With network firewalling, how much we should coarsen allowed IP addresses into networks
+    #[arg(long, default_value_t, value_enum)]
+    pub ip_allow_coarsening: IpAllowCoarsening,
+    /// In aggressive mode, minimum number of written paths in a directory to allow writing to the whole directory
+    #[arg(long, default_value_t = DEFAULT_PATH_GROUPING_THRESHOLD)]
+    pub path_grouping_threshold: usize,
+    /// In aggressive mode, generate `SystemCallFilter=` denying the syscall classes that were not used, instead of
+    /// only allowing the syscalls that were used
+    #[arg(long, default_value_t)]
+    pub syscall_deny_list: bool,
+    /// Paths the program is allowed to write to even with `ProtectSystem=strict`, ie. its `StateDirectory=`
+    #[arg(long = "writable-path")]
+    pub writable_paths: Vec<PathBuf>,
+    /// Paths the program may read even if not seen while profiling, ie. referenced by its environment variables
+    #[arg(long = "readable-path")]
+    pub readable_paths: Vec<PathBuf>,
+    /// Binds the program is allowed to do even if not seen while profiling, ie. declared by its socket unit,
+    /// as `FAMILY:PROTOCOL:PORT` (ie. `ipv4:tcp:80`)
+    #[arg(long = "known-bind")]
+    pub known_binds: Vec<KnownBind>,
+    /// Path the program is allowed to read, write and create even if not seen while profiling, ie. only accessed by
+    /// rarely hit code paths
+    #[arg(long = "allow-path")]
+    pub allow_paths: Vec<PathBuf>,
+    /// Syscall the program is allowed to make even if not seen while profiling
+    #[arg(long = "allow-syscall", value_parser = parse_syscall)]
+    pub allow_syscalls: Vec<String>,
+    /// Socket address family the program is allowed to use even if not seen while profiling, as named by strace
+    /// (ie. `AF_UNIX`)
+    #[arg(long = "allow-address-family", value_parser = parse_address_family)]
+    pub allow_address_families: Vec<String>,
+    /// Capability the program is allowed to use even if not seen while profiling (ie. `CAP_NET_ADMIN`)
+    #[arg(long = "allow-capability", value_parser = parse_capability)]
+    pub allow_capabilities: Vec<String>,
+    /// The program runs in its own root directory or image (ie. with `RootDirectory=` or `RootImage=`), so do not
+    /// canonicalize the paths it accesses against the host filesystem
+    #[arg(long, default_value_t = false)]
+    pub root_confined: bool,
+    /// Target systemd version (ie. 254 or 254.1), options it does not support will not be generated.
+    /// Detected from the local system if not set
+    #[arg(long)]
+    pub systemd_version: Option<SystemdVersion>,
+    /// Name of the hardening level preset these options were set from, if any
+    #[arg(skip)]
+    pub level: Option<String>,
+}
+
+impl HardeningOptions {
+    /// Build the most safe options
+    pub fn safe() -> Self {
+        Self {
+            mode: HardeningMode::Safe,
+            network_firewalling: false,
+            ip_allow_coarsening: IpAllowCoarsening::Host,
+            path_grouping_threshold: DEFAULT_PATH_GROUPING_THRESHOLD,
+            syscall_deny_list: false,
+            writable_paths: vec![],
+            readable_paths: vec![],
+            known_binds: vec![],
+            allow_paths: vec![],
+            allow_syscalls: vec![],
+            allow_address_families: vec![],
+            allow_capabilities: vec![],
+            root_confined: false,
+            systemd_version: None,
+            level: None,
+        }
+    }
+
+    /// Build the most strict options
+    pub fn strict() -> Self {
+        Self {
+            mode: HardeningMode::Aggressive,
+            network_firewalling: true,
+            ip_allow_coarsening: IpAllowCoarsening::Host,
+            path_grouping_threshold: DEFAULT_PATH_GROUPING_THRESHOLD,
+            syscall_deny_list: false,
+            writable_paths: vec![],
+            readable_paths: vec![],
+            known_binds: vec![],
+            allow_paths: vec![],
+            allow_syscalls: vec![],
+            allow_address_families: vec![],
+            allow_capabilities: vec![],
+            root_confined: false,
+            systemd_version: None,
+            level: None,
+        }
+    }
+
+    /// Build command line arguments to pass these options to another invocation
+    pub(crate) fn to_args(&self) -> Vec<String> {
+        let mut args = vec!["-m".to_owned(), self.mode.to_string()];
+        if self.network_firewalling {
+            args.extend([
+                "-f".to_owned(),
+                "--ip-allow-coarsening".to_owned(),
+                self.ip_allow_coarsening.to_string(),
+            ]);
+        }
+        if self.path_grouping_threshold != DEFAULT_PATH_GROUPING_THRESHOLD {
+            args.extend([
+                "--path-grouping-threshold".to_owned(),
+                self.path_grouping_threshold.to_string(),
+            ]);
+        }
+        if self.syscall_deny_list {
+            args.push("--syscall-deny-list".to_owned());
+        }
+        for path in &self.writable_paths {
+            args.extend(["--writable-path".to_owned(), path.display().to_string()]);
+        }
+        for path in &self.readable_paths {
+            args.extend(["--readable-path".to_owned(), path.display().to_string()]);
+        }
+        for bind in &self.known_binds {
+            args.extend(["--known-bind".to_owned(), bind.to_string()]);
+        }
+        if self.root_confined {
+            args.push("--root-confined".to_owned());
+        }
+        for (arg, val) in self.allowances() {
+            args.extend([format!("--allow-{arg}"), val]);
+        }
+        if let Some(systemd_version) = &self.systemd_version {
+            args.extend(["--systemd-version".to_owned(), systemd_version.to_string()]);
+        }
+        args
+    }
+
+    /// Parse options back from command line arguments built by `to_args`
+    pub(crate) fn from_args(args: &[String]) -> anyhow::Result<Self> {
+        Ok(<Self as clap::Parser>::try_parse_from(
+            iter::once(env!("CARGO_PKG_NAME")).chain(args.iter().map(String::as_str)),
+        )?)
+    }
+
+    /// Actions the program is allowed to do even if not seen while profiling
+    pub(crate) fn allowed_actions(&self) -> Vec<ProgramAction> {
+        let mut actions: Vec<_> = self
+            .allow_paths
+            .iter()
+            .flat_map(|p| {
+                [
+                    ProgramAction::Read(p.to_owned()),
+                    ProgramAction::Write(p.to_owned()),
+                    ProgramAction::Create(p.to_owned()),
+                ]
+            })
+            .chain(
+                self.readable_paths
+                    .iter()
+                    .map(|p| ProgramAction::Read(p.to_owned())),
+            )
+            .collect();
+        if !self.allow_syscalls.is_empty() {
+            actions.push(ProgramAction::Syscalls(
+                self.allow_syscalls.iter().cloned().collect::<HashSet<_>>(),
+            ));
+        }
+        actions.extend(self.allow_address_families.iter().map(|af| {
+            #[expect(clippy::unwrap_used)]
+            let af: SocketFamily = af.parse().unwrap();
+            ProgramAction::NetworkActivity(NetworkActivity {
+                af: SetSpecifier::One(af),
+                proto: SetSpecifier::All,
+                kind: SetSpecifier::All,
+                local_port: CountableSetSpecifier::All,
+                address: SetSpecifier::All,
+            })
+        }));
+        actions.extend(
+            self.allow_capabilities
+                .iter()
+                .map(|c| ProgramAction::UseCapability(c.to_owned())),
+        );
+        actions
+    }
+
+    /// Kinds and values of actions allowed even if not seen while profiling, as named in `--allow-xxx` arguments
+    pub(crate) fn allowances(&self) -> Vec<(&'static str, String)> {
+        self.allow_paths
+            .iter()
+            .map(|p| ("path", p.display().to_string()))
+            .chain(
+                self.allow_syscalls
+                    .iter()
+                    .map(|s| ("syscall", s.to_owned())),
+            )
+            .chain(
+                self.allow_address_families
+                    .iter()
+                    .map(|af| ("address-family", af.to_owned())),
+            )
+            .chain(
+                self.allow_capabilities
+                    .iter()
+                    .map(|c| ("capability", c.to_owned())),
+            )
+            .collect()
+    }
+}
+
+/// Parse syscall name
+fn parse_syscall(s: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(KNOWN_SYSCALLS.contains(&s), "Unknown syscall {s:?}");
+    Ok(s.to_owned())
+}
+
+/// Parse socket address family name, as named by strace
+fn parse_address_family(s: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        SOCKET_FAMILIES.contains(&s),
+        "Unknown socket address family {s:?}"
+    );
+    Ok(s.to_owned())
+}
+
+/// Parse capability name, as named by systemd
+fn parse_capability(s: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        s.strip_prefix("CAP_").is_some_and(|n| !n.is_empty()
+            && n.chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')),
+        "Invalid capability name {s:?}"
+    );
+    Ok(s.to_owned())
+}
+
+/// Socket bind on a TCP or UDP port
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct KnownBind {
+    pub(crate) af: SocketFamily,
+    pub(crate) proto: SocketProtocol,
+    /// Port, `None` if the kernel picks an ephemeral one
+    pub(crate) port: Option<NonZeroU16>,
+}
+
+impl KnownBind {
+    /// Get the action equivalent to this bind
+    pub(crate) fn action(&self) -> ProgramAction {
+        ProgramAction::NetworkActivity(NetworkActivity {
+            af: SetSpecifier::One(self.af.clone()),
+            proto: SetSpecifier::One(self.proto.clone()),
+            kind: SetSpecifier::One(NetworkActivityKind::Bind),
+            local_port: self.port.map_or(CountableSetSpecifier::All, |p| {
+                CountableSetSpecifier::One(p.into())
+            }),
+            address: SetSpecifier::All,
+        })
+    }
+}
+
+impl FromStr for KnownBind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split(':');
+        let (Some(af), Some(proto), Some(port), None) =
+            (tokens.next(), tokens.next(), tokens.next(), tokens.next())
+        else {
+            anyhow::bail!("Invalid bind {s:?}, expected FAMILY:PROTOCOL:PORT");
+        };
+        let af = match af {
+            "ipv4" => SocketFamily::Ipv4,
+            "ipv6" => SocketFamily::Ipv6,
+            _ => anyhow::bail!("Invalid bind address family {af:?}"),
+        };
+        let proto = match proto {
+            "tcp" => SocketProtocol::Tcp,
+            "udp" => SocketProtocol::Udp,
+            _ => anyhow::bail!("Invalid bind protocol {proto:?}"),
+        };
+        let port = NonZeroU16::new(port.parse()?);
+        Ok(Self { af, proto, port })
+    }
+}
+
+impl fmt::Display for KnownBind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.af,
+            self.proto,
+            self.port.map_or(0, NonZeroU16::get)
+        )
+    }
+}
@@ -0,0 +1,1007 @@
+//! Systemd Hardening Helper
+//!
+//! Profile programs with strace, and find systemd hardening options compatible with their behavior.
+
+#![cfg_attr(all(feature = "nightly", test), feature(test))]
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    env, fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufWriter, Write},
+    os::unix::process::ExitStatusExt as _,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    rc::Rc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+
+#[cfg(feature = "ebpf")]
+mod ebpf;
+mod error;
+mod hardening;
+mod seccomp;
+mod strace;
+mod summarize;
+mod systemd;
+
+pub use error::{ShhError, SnippetMarker};
+pub use hardening::{HardeningMode, HardeningOptions, IpAllowCoarsening, KnownBind};
+pub use strace::{StraceVersion, TraceeGroup};
+pub use systemd::{
+    filter_options, parse_option_name, report_options, report_options_json, FragmentNaming,
+    KernelVersion, ListMode, OptionValue, OptionWithValue, ResolvedOption, SecurityDelta, Service,
+    SystemdVersion,
+};
+
+/// Cgroup v2 hierarchy mount point
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Default directory of the profile store, where profile data of units is accumulated over several profiling sessions
+pub const DEFAULT_PROFILE_STORE_DIR: &str = "/var/lib/shh/profiles";
+
+/// Version of the profile store format, to increase on incompatible changes of stored actions
+const PROFILE_STORE_VERSION: u32 = 1;
+
+/// Program actions observed while profiling
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ProfileData(Vec<summarize::ProgramAction>);
+
+/// Profile data accumulated in a profile store, which is kept across upgrades, so unlike profile data files it is
+/// serialized as JSON, with actions tagged by name instead of by variant index
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredProfileData {
+    version: u32,
+    actions: Vec<summarize::ProgramAction>,
+}
+
+impl ProfileData {
+    /// Summarize actions from a strace log, as generated by `strace -f -y`
+    pub fn from_strace_log(log: &[u8]) -> anyhow::Result<Self> {
+        Self::from_strace_reader(io::Cursor::new(log.to_vec()), None)
+    }
+
+    /// Summarize actions from a strace log reader. Failed syscalls are ignored, except the ones denied for lack of
+    /// privileges. If `initial_cwd` is set, paths relative to the current directory of processes are resolved from it.
+    pub fn from_strace_reader<R: BufRead + 'static>(
+        reader: R,
+        initial_cwd: Option<&Path>,
+    ) -> anyhow::Result<Self> {
+        let mut logs = strace::LogParser::new(Box::new(reader), None)?.only_denied_failures();
+        let mut actions =
+            summarize::summarize_with_checkpoints(&mut logs, true, initial_cwd, None, |_| Ok(()))?;
+        summarize::add_file_system_action(&mut actions);
+        summarize::add_syscall_arch_action(&mut actions, &logs.personality_modes());
+        Ok(Self(actions))
+    }
+
+    /// Add actions from other profile data
+    pub fn append(&mut self, other: &mut Self) {
+        self.0.append(&mut other.0);
+    }
+
+    /// Add actions the program is allowed to do even if they were not observed
+    pub fn add_allowed_actions(&mut self, hardening_opts: &HardeningOptions) {
+        self.0.extend(hardening_opts.allowed_actions());
+    }
+
+    /// Load and merge profile data files, ignoring missing ones
+    /// Files are loaded in parallel, but merged in the order of `paths`
+    pub fn from_files(paths: &[PathBuf]) -> anyhow::Result<Self> {
+        // There is one file per profiled command, so a thread for each is fine
+        let files_data = thread::scope(|scope| {
+            let handles: Vec<_> = paths
+                .iter()
+                .map(|path| scope.spawn(|| Self::from_file(path)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .map_err(|_| anyhow::anyhow!("Profile data loading thread panicked"))?
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+        Ok(files_data.into_iter().flatten().fold(
+            Self::default(),
+            |mut profile_data, mut file_profile_data| {
+                profile_data.append(&mut file_profile_data);
+                profile_data
+            },
+        ))
+    }
+
+    /// Write a human readable listing of all observed actions, grouped by kind
+    pub fn explain<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        let mut groups: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for action in &self.0 {
+            let (group, descs) = action.explain();
+            groups.entry(group).or_default().extend(descs);
+        }
+        for (group, descs) in groups.iter().filter(|(_, d)| !d.is_empty()) {
+            writeln!(writer, "{group}:")?;
+            for desc in descs {
+                writeln!(writer, "  {desc}")?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Fold profile data into the one accumulated for a unit in a profile store, creating it if needed
+    pub fn add_to_store(self, store_dir: &Path, unit: &str) -> anyhow::Result<()> {
+        let path = Self::store_path(store_dir, unit)?;
+        let mut stored = if path.is_file() {
+            Self::from_store_file(&path)?
+        } else {
+            Self::default()
+        };
+        let prev_count = stored.0.len();
+        summarize::merge_actions(&mut stored.0, self.0);
+        log::info!(
+            "Profile store for {unit} now has {} action(s), {} new",
+            stored.0.len(),
+            stored.0.len().saturating_sub(prev_count)
+        );
+        fs::create_dir_all(store_dir)?;
+        let stored = StoredProfileData {
+            version: PROFILE_STORE_VERSION,
+            actions: stored.0,
+        };
+        Self::write_atomically(&path, |file| Ok(serde_json::to_writer(file, &stored)?))
+    }
+
+    /// Load profile data accumulated for a unit in a profile store
+    pub fn from_store(store_dir: &Path, unit: &str) -> anyhow::Result<Self> {
+        let path = Self::store_path(store_dir, unit)?;
+        anyhow::ensure!(
+            path.is_file(),
+            "No profile data stored for {unit} in {store_dir:?}"
+        );
+        Self::from_store_file(&path)
+    }
+
+    /// Load profile store file, checking its format version
+    fn from_store_file(path: &Path) -> anyhow::Result<Self> {
+        let file = io::BufReader::new(File::open(path)?);
+        let stored: StoredProfileData = serde_json::from_reader(file)
+            .with_context(|| format!("Unable to parse profile store file {path:?}"))?;
+        anyhow::ensure!(
+            stored.version == PROFILE_STORE_VERSION,
+            "Profile store file {path:?} has format version {}, expected {PROFILE_STORE_VERSION}",
+            stored.version
+        );
+        Ok(Self(stored.actions))
+    }
+
+    /// Path of the profile data accumulated for a unit in a profile store
+    fn store_path(store_dir: &Path, unit: &str) -> anyhow::Result<PathBuf> {
+        anyhow::ensure!(
+            !unit.is_empty() && !unit.contains('/') && !unit.starts_with('.'),
+            "Invalid unit name {unit:?}"
+        );
+        Ok(store_dir.join(format!("{unit}.profile")))
+    }
+
+    /// Expand directories among profile data paths to the profile data files they contain, sorted by name
+    pub fn expand_paths(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut expanded = Vec::with_capacity(paths.len());
+        for path in paths {
+            if path.is_dir() {
+                let mut dir_paths = Vec::new();
+                for entry in fs::read_dir(path)? {
+                    let entry_path = entry?.path();
+                    if entry_path.is_file()
+                        && entry_path
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(Self::is_file_name)
+                    {
+                        dir_paths.push(entry_path);
+                    }
+                }
+                dir_paths.sort_unstable();
+                expanded.extend(dir_paths);
+            } else {
+                expanded.push(path.to_owned());
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Whether a file name is the one of a profile data file written while profiling a service, ie. `001` or
+    /// `001_foo` for an instance, and not a strace log (`001.strace`) or a partially written file (`001.tmp`)
+    fn is_file_name(name: &str) -> bool {
+        let (idx, valid_instance) = match name.split_once('_') {
+            Some((idx, instance)) => (
+                idx,
+                !instance.is_empty()
+                    && !instance.ends_with(".strace")
+                    && !instance.ends_with(".tmp"),
+            ),
+            None => (name, true),
+        };
+        idx.len() == 3 && idx.bytes().all(|c| c.is_ascii_digit()) && valid_instance
+    }
+
+    /// Build Docker/OCI seccomp profile allowing only the observed syscalls, as JSON
+    pub fn seccomp_profile_json(&self) -> anyhow::Result<String> {
+        let profile = seccomp::SeccompProfile::from_actions(&self.0)?;
+        Ok(serde_json::to_string_pretty(&profile)?)
+    }
+
+    /// Write profile data file, replacing any previous one atomically
+    pub fn to_file(&self, path: &Path) -> anyhow::Result<()> {
+        Self::write_atomically(path, |file| Ok(bincode::serialize_into(file, self)?))
+    }
+
+    /// Write a file through a temporary one, renamed over the previous one once fully written
+    fn write_atomically<F>(path: &Path, write: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut BufWriter<File>) -> anyhow::Result<()>,
+    {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let mut file = BufWriter::new(File::create(&tmp_path)?);
+        write(&mut file)?;
+        file.flush()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Load profile data file, or return `None` if it does not exist
+    fn from_file(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.is_file() {
+            // ExecReload= commands may never have run
+            log::warn!("Profile data file {path:?} does not exist, ignoring");
+            return Ok(None);
+        }
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            // Commands may have been interrupted before writing anything
+            log::warn!("Profile data file {path:?} is empty, ignoring");
+            return Ok(None);
+        }
+        Ok(Some(bincode::deserialize_from(file)?))
+    }
+}
+
+/// How profiled programs are traced
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, clap::ValueEnum, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum TracingBackend {
+    /// Trace with strace, which works everywhere, but slows down the program a lot
+    #[default]
+    Strace,
+    /// Trace with an eBPF program, with a much lower overhead, if built with the `ebpf` feature, and the kernel and
+    /// our privileges allow it, otherwise fall back to strace
+    Ebpf,
+}
+
+/// Tracer of a profiled program
+enum Tracer {
+    Strace(strace::Strace),
+    #[cfg(feature = "ebpf")]
+    Ebpf(ebpf::Ebpf),
+}
+
+impl Tracer {
+    fn tracee_group(&self) -> TraceeGroup {
+        match self {
+            Self::Strace(strace) => strace.tracee_group(),
+            #[cfg(feature = "ebpf")]
+            Self::Ebpf(ebpf) => ebpf.tracee_group(),
+        }
+    }
+
+    fn tracee_status(&mut self) -> anyhow::Result<Option<ExitStatus>> {
+        match self {
+            Self::Strace(strace) => strace.tracee_status(),
+            #[cfg(feature = "ebpf")]
+            Self::Ebpf(ebpf) => ebpf.tracee_status(),
+        }
+    }
+
+    fn syscalls(&mut self) -> anyhow::Result<Syscalls> {
+        Ok(match self {
+            Self::Strace(strace) => Syscalls::Strace(strace.log_lines()?),
+            #[cfg(feature = "ebpf")]
+            Self::Ebpf(ebpf) => Syscalls::Ebpf(ebpf.events()?),
+        })
+    }
+}
+
+/// Syscalls of a profiled program, from any tracing backend
+enum Syscalls {
+    Strace(strace::LogParser),
+    #[cfg(feature = "ebpf")]
+    Ebpf(ebpf::Events),
+}
+
+impl Syscalls {
+    /// Non native ABIs used by the program, which can be read while syscalls are iterated
+    fn shared_personality_modes(&self) -> Rc<RefCell<BTreeSet<String>>> {
+        match self {
+            Self::Strace(parser) => parser.shared_personality_modes(),
+            #[cfg(feature = "ebpf")]
+            Self::Ebpf(events) => events.shared_personality_modes(),
+        }
+    }
+}
+
+impl Iterator for Syscalls {
+    type Item = anyhow::Result<strace::Syscall>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Strace(parser) => parser.next(),
+            #[cfg(feature = "ebpf")]
+            Self::Ebpf(events) => events.next(),
+        }
+    }
+}
+
+/// A program running under profiling
+pub struct Profiler {
+    tracer: Tracer,
+    /// The program runs in its own root, so its paths are not canonicalized against the host filesystem
+    root_confined: bool,
+    /// Initial current directory of the program, inherited from ours (ie. the service `WorkingDirectory=`)
+    cwd: Option<PathBuf>,
+    /// Out of memory kill count of our cgroup when profiling started, if available
+    oom_kills: Option<u64>,
+}
+
+impl Profiler {
+    /// Start profiling a command with the given tracing backend, falling back to strace if it is not available.
+    /// The strace binary, additional arguments, and raw output mirror file are only used when tracing with strace
+    pub fn start(
+        command: &[&str],
+        backend: TracingBackend,
+        strace_log_path: Option<PathBuf>,
+        root_confined: bool,
+        strace_path: &Path,
+        strace_extra_args: &[String],
+    ) -> anyhow::Result<Self> {
+        let cwd = env::current_dir()
+            .inspect_err(|err| log::warn!("Unable to get current directory: {err}"))
+            .ok();
+        let tracer = match backend {
+            TracingBackend::Strace => None,
+            #[cfg(feature = "ebpf")]
+            TracingBackend::Ebpf => match ebpf::Ebpf::run(command) {
+                Ok(ebpf) => {
+                    if strace_log_path.is_some() || !strace_extra_args.is_empty() {
+                        log::warn!(
+                            "Strace log path and arguments are ignored when tracing with eBPF"
+                        );
+                    }
+                    Some(Tracer::Ebpf(ebpf))
+                }
+                Err(err) => {
+                    log::warn!("Unable to trace with eBPF, falling back to strace: {err:#}");
+                    None
+                }
+            },
+            #[cfg(not(feature = "ebpf"))]
+            TracingBackend::Ebpf => {
+                log::warn!("eBPF tracing support is not built in, falling back to strace");
+                None
+            }
+        };
+        let tracer = match tracer {
+            Some(tracer) => tracer,
+            None => Tracer::Strace(strace::Strace::run(
+                command,
+                strace_log_path,
+                strace_path,
+                strace_extra_args,
+            )?),
+        };
+        Ok(Self {
+            tracer,
+            root_confined,
+            cwd,
+            oom_kills: cgroup_oom_kills(),
+        })
+    }
+
+    /// Terminate the profiled program if it is still running after `timeout`, so that the actions observed until
+    /// then can be collected
+    pub fn terminate_after(&self, timeout: Duration) {
+        let group = self.tracee_group();
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            log::warn!("Profiled program may still be running after {timeout:?}, terminating it");
+            group.signal(nix::sys::signal::Signal::SIGTERM);
+        });
+    }
+
+    /// Handle to signal the profiled program and its descendants, which run in their own process group
+    pub fn tracee_group(&self) -> TraceeGroup {
+        self.tracer.tracee_group()
+    }
+
+    /// Wait for the profiled program to exit, and summarize its actions
+    pub fn collect(mut self) -> anyhow::Result<ProfileData> {
+        let mut syscalls = self.tracer.syscalls()?;
+        let mut actions = summarize::summarize_with_checkpoints(
+            &mut syscalls,
+            !self.root_confined,
+            self.cwd.as_deref(),
+            None,
+            |_| Ok(()),
+        )?;
+        summarize::add_file_system_action(&mut actions);
+        summarize::add_syscall_arch_action(
+            &mut actions,
+            &syscalls.shared_personality_modes().borrow(),
+        );
+        self.check_exit()?;
+        Ok(ProfileData(actions))
+    }
+
+    /// Same as `collect`, but summarize actions incrementally, and write the profile data observed so far to
+    /// `profile_data_path` every `interval`, so it is available even if we are killed before the program exits
+    pub fn collect_with_checkpoints(
+        mut self,
+        interval: Duration,
+        profile_data_path: &Path,
+    ) -> anyhow::Result<ProfileData> {
+        let mut syscalls = self.tracer.syscalls()?;
+        let personality_modes = syscalls.shared_personality_modes();
+        let mut actions = summarize::summarize_with_checkpoints(
+            &mut syscalls,
+            !self.root_confined,
+            self.cwd.as_deref(),
+            Some(interval),
+            |mut actions| {
+                // Same as the final profile data, so that options are not resolved differently from a checkpoint
+                summarize::add_file_system_action(&mut actions);
+                summarize::add_syscall_arch_action(&mut actions, &personality_modes.borrow());
+                log::debug!("Writing profile data checkpoint into {profile_data_path:?}");
+                ProfileData(actions).to_file(profile_data_path)
+            },
+        )?;
+        summarize::add_file_system_action(&mut actions);
+        summarize::add_syscall_arch_action(&mut actions, &personality_modes.borrow());
+        self.check_exit()?;
+        Ok(ProfileData(actions))
+    }
+
+    /// Fail if the profiled program was killed by `SIGKILL`, because its actions were then only partially observed
+    fn check_exit(&mut self) -> anyhow::Result<()> {
+        // If strace was killed instead, the program keeps running untraced
+        let Some(status) = self.tracer.tracee_status()? else {
+            return Ok(());
+        };
+        let oom_killed = self
+            .oom_kills
+            .is_some_and(|before| cgroup_oom_kills().is_some_and(|after| after > before));
+        match killed_error(status, oom_killed) {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error for a profiled program that exited with `status`
+fn killed_error(status: ExitStatus, oom_killed: bool) -> Option<ShhError> {
+    (status.signal() == Some(nix::sys::signal::Signal::SIGKILL as i32))
+        .then_some(ShhError::ProgramKilled { oom_killed })
+}
+
+/// Number of processes of our cgroup (and its descendants) killed for lack of memory, if available
+fn cgroup_oom_kills() -> Option<u64> {
+    let cgroups = fs::read_to_string("/proc/self/cgroup").ok()?;
+    // Only the unified cgroup v2 hierarchy has a single entry with id 0
+    let cgroup = cgroups.lines().find_map(|l| l.strip_prefix("0::"))?;
+    let events = fs::read_to_string(
+        Path::new(CGROUP_ROOT)
+            .join(cgroup.trim_start_matches('/'))
+            .join("memory.events"),
+    )
+    .ok()?;
+    parse_oom_kills(&events)
+}
+
+/// Parse out of memory kill count from a cgroup `memory.events` file content
+fn parse_oom_kills(events: &str) -> Option<u64> {
+    events
+        .lines()
+        .find_map(|l| l.strip_prefix("oom_kill "))?
+        .parse()
+        .ok()
+}
+
+/// Systemd options supported on a system, and how they are modeled
+pub struct SupportedOptions {
+    opts: Vec<systemd::OptionDescription>,
+    /// Only emit the low priority options as commented suggestions
+    suggest: bool,
+}
+
+impl SupportedOptions {
+    /// Build options supported by the given systemd & kernel versions
+    pub fn new(
+        sd_version: &SystemdVersion,
+        kernel_version: &KernelVersion,
+        hardening_opts: &HardeningOptions,
+    ) -> Self {
+        Self {
+            opts: systemd::build_options(sd_version, kernel_version, hardening_opts),
+            suggest: !matches!(hardening_opts.mode, HardeningMode::Aggressive),
+        }
+    }
+
+    /// Flag option if it should only be suggested
+    fn mark_suggestion(&self, opt: &mut OptionWithValue) {
+        opt.suggestion = self.suggest && systemd::SUGGESTED_OPTIONS.contains(&opt.name.as_str());
+    }
+
+    /// Find the most restrictive option values compatible with profiled actions
+    pub fn resolve(&self, profile_data: &ProfileData) -> Vec<OptionWithValue> {
+        let mut opts = systemd::resolve(&self.opts, &profile_data.0);
+        opts.iter_mut().for_each(|o| self.mark_suggestion(o));
+        opts
+    }
+
+    /// Same as `resolve`, but also explain which observed actions led to each option value
+    pub fn resolve_with_rationale(&self, profile_data: &ProfileData) -> Vec<ResolvedOption> {
+        let mut resolved = systemd::resolve_with_rationale(&self.opts, &profile_data.0);
+        resolved
+            .iter_mut()
+            .for_each(|r| self.mark_suggestion(&mut r.option));
+        resolved
+    }
+
+    /// Merge options from a previous resolution with new ones, keeping for each option the most permissive value
+    pub fn merge(
+        &self,
+        prev_opts: &[OptionWithValue],
+        new_opts: &[OptionWithValue],
+    ) -> Vec<OptionWithValue> {
+        let mut opts = systemd::merge(&self.opts, prev_opts, new_opts);
+        opts.iter_mut().for_each(|o| self.mark_suggestion(o));
+        opts
+    }
+
+    /// Drop new options already set by a unit to an equal or more restrictive value, to never weaken them
+    pub fn reconcile_existing(
+        &self,
+        existing_opts: &[OptionWithValue],
+        new_opts: Vec<OptionWithValue>,
+    ) -> Vec<OptionWithValue> {
+        systemd::reconcile_existing(&self.opts, existing_opts, new_opts)
+    }
+
+    /// Print markdown formatted list of options and their possible values
+    pub fn print_markdown(&self) {
+        println!("# Supported systemd options");
+        let mut sd_opts: Vec<_> = self.opts.iter().collect();
+        sd_opts.sort_unstable_by_key(|o| o.name);
+        for sd_opt in sd_opts {
+            println!("- [`{sd_opt}`](https://www.freedesktop.org/software/systemd/man/latest/systemd.exec.html#{sd_opt}=)");
+            for opt_val in &sd_opt.possible_values {
+                match &opt_val.value {
+                    OptionValue::Boolean(v) => {
+                        println!("    - `{}`", if *v { "true" } else { "false" });
+                    }
+                    OptionValue::String(v) => println!("    - `{v}`"),
+                    OptionValue::List { values, .. } => {
+                        for val in values {
+                            println!("    - `{val}`");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for SupportedOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.opts
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::shadow_unrelated)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_strace_log() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let profile_data =
+            ProfileData::from_strace_log(b"1 0.000001 unshare(CLONE_NEWNET) = 0\n").unwrap();
+        let sd_opts = SupportedOptions::new(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::safe(),
+        );
+        let opts: Vec<_> = sd_opts
+            .resolve(&profile_data)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(opts.contains(&"RestrictNamespaces=net".to_owned()));
+        assert!(opts.contains(&"ProtectSystem=strict".to_owned()));
+    }
+
+    #[test]
+    fn test_explain() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let profile_data = ProfileData::from_strace_log(
+            concat!(
+                "1 0.000001 openat(AT_FDCWD, \"/etc/passwd\", O_RDONLY) = 3\n",
+                "1 0.000001 unshare(CLONE_NEWNET) = 0\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let mut listing = Vec::new();
+        profile_data.explain(&mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert!(!listing.is_empty());
+        assert!(listing.contains("Paths read:\n  /etc/passwd\n"));
+        assert!(listing.contains("Namespaces:\n  net\n"));
+        assert!(listing.contains("Syscalls:\n  openat\n  unshare\n"));
+
+        let mut listing = Vec::new();
+        ProfileData::default().explain(&mut listing).unwrap();
+        assert!(listing.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_umask_suggestion() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let profile_data = ProfileData::from_strace_log(concat!(
+            "1 0.000001 openat(AT_FDCWD, \"/var/lib/foo/db\", O_WRONLY|O_CREAT|O_TRUNC, 0600) = 3\n",
+            "1 0.000001 mkdir(\"/var/lib/foo/cache\", 0700) = 0\n",
+            "1 0.000001 chmod(\"/var/lib/foo/key\", 0600) = 0\n",
+        ).as_bytes())
+        .unwrap();
+        let sd_opts = SupportedOptions::new(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::safe(),
+        );
+        let opts: Vec<_> = sd_opts
+            .resolve(&profile_data)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(opts.contains(&"# Suggested: UMask=0077".to_owned()));
+        assert!(!opts.contains(&"UMask=0077".to_owned()));
+
+        let sd_opts = SupportedOptions::new(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::strict(),
+        );
+        let opts: Vec<_> = sd_opts
+            .resolve(&profile_data)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(opts.contains(&"UMask=0077".to_owned()));
+
+        let profile_data = ProfileData::from_strace_log(
+            b"1 0.000001 openat(AT_FDCWD, \"/var/lib/foo/db\", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 3\n",
+        )
+        .unwrap();
+        assert!(!sd_opts
+            .resolve(&profile_data)
+            .iter()
+            .any(|o| o.name == "UMask"));
+    }
+
+    #[test]
+    fn test_resolve_allowed_actions() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let hardening_opts = HardeningOptions {
+            mode: HardeningMode::Aggressive,
+            allow_paths: vec!["/var/lib/foo/cache".into()],
+            allow_syscalls: vec!["mlock".to_owned()],
+            allow_address_families: vec!["AF_NETLINK".to_owned()],
+            ..HardeningOptions::safe()
+        };
+        let sd_opts = SupportedOptions::new(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &hardening_opts,
+        );
+        let mut profile_data =
+            ProfileData::from_strace_log(b"1 0.000001 getpid() = 1234\n").unwrap();
+        let opts: Vec<_> = sd_opts
+            .resolve(&profile_data)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(!opts.iter().any(|o| o.starts_with("ReadWritePaths=")));
+        assert!(opts
+            .iter()
+            .any(|o| o.starts_with("SystemCallFilter=") && !o.contains("mlock")));
+        assert!(opts.contains(&"RestrictAddressFamilies=none".to_owned()));
+
+        profile_data.add_allowed_actions(&hardening_opts);
+        let opts: Vec<_> = sd_opts
+            .resolve(&profile_data)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(opts.contains(&"ProtectSystem=strict".to_owned()));
+        assert!(opts.contains(&"ReadWritePaths=-/var/lib/foo/cache".to_owned()));
+        assert!(opts
+            .iter()
+            .any(|o| o.starts_with("SystemCallFilter=") && o.contains("mlock")));
+        assert!(opts.contains(&"RestrictAddressFamilies=AF_NETLINK".to_owned()));
+    }
+
+    #[test]
+    fn test_unsupported_systemd_version() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let sd_opts = SupportedOptions::new(
+            &SystemdVersion::new(240, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::safe(),
+        );
+        let opts: Vec<_> = sd_opts
+            .resolve(&ProfileData::default())
+            .iter()
+            .map(|o| o.name.clone())
+            .collect();
+        assert!(!opts.contains(&"ProtectHostname".to_owned()));
+        assert!(!opts.contains(&"ProtectClock".to_owned()));
+        assert!(opts.contains(&"ProtectSystem".to_owned()));
+
+        let sd_opts = SupportedOptions::new(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::safe(),
+        );
+        let opts: Vec<_> = sd_opts
+            .resolve(&ProfileData::default())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(opts.contains(&"ProtectHostname=true".to_owned()));
+        assert!(opts.contains(&"ProtectSystem=strict".to_owned()));
+        assert!(opts.contains(&"ProtectHome=tmpfs".to_owned()));
+
+        // Values not supported by the target version are skipped, but not less restrictive ones
+        let sd_opts = SupportedOptions::new(
+            &SystemdVersion::new(231, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::safe(),
+        );
+        let opts: Vec<_> = sd_opts
+            .resolve(&ProfileData::default())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(opts.contains(&"ProtectSystem=full".to_owned()));
+        assert!(opts.contains(&"ProtectHome=true".to_owned()));
+    }
+
+    #[test]
+    fn test_profile_data_to_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("001");
+        for i in 0..2 {
+            let profile_data = ProfileData(vec![summarize::ProgramAction::Read(
+                format!("/var/lib/foo/{i}").into(),
+            )]);
+            profile_data.to_file(&path).unwrap();
+            assert_eq!(
+                ProfileData::from_file(&path).unwrap().unwrap().0,
+                profile_data.0
+            );
+        }
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_profile_data_from_files() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = (0..16)
+            .map(|i| temp_dir.path().join(format!("{i:03}")))
+            .collect();
+        for (i, path) in paths.iter().enumerate() {
+            // Last file is missing, ie. for a command that never ran
+            if i == paths.len() - 1 {
+                continue;
+            }
+            let actions = (0..1000)
+                .map(|j| {
+                    if j % 2 == 0 {
+                        summarize::ProgramAction::Read(format!("/var/lib/foo/{i}/{j}").into())
+                    } else {
+                        summarize::ProgramAction::Write(format!("/var/lib/foo/{i}/{j}").into())
+                    }
+                })
+                .collect();
+            let file = File::create(path).unwrap();
+            bincode::serialize_into(file, &ProfileData(actions)).unwrap();
+        }
+
+        let mut serial_profile_data = ProfileData::default();
+        for path in &paths {
+            if let Some(mut file_profile_data) = ProfileData::from_file(path).unwrap() {
+                serial_profile_data.append(&mut file_profile_data);
+            }
+        }
+        assert_eq!(serial_profile_data.0.len(), 15 * 1000);
+
+        for _ in 0..4 {
+            let profile_data = ProfileData::from_files(&paths).unwrap();
+            assert_eq!(profile_data.0, serial_profile_data.0);
+        }
+    }
+
+    #[test]
+    fn test_killed_error() {
+        assert!(killed_error(ExitStatus::from_raw(0), false).is_none());
+        assert!(killed_error(ExitStatus::from_raw(1 << 8), true).is_none());
+        // SIGTERM, ie. from --timeout
+        assert!(killed_error(ExitStatus::from_raw(15), false).is_none());
+
+        let err = killed_error(ExitStatus::from_raw(9), true).unwrap();
+        assert!(matches!(err, ShhError::ProgramKilled { oom_killed: true }));
+        assert!(err.to_string().contains("out of memory"), "{err}");
+        let err = killed_error(ExitStatus::from_raw(9), false).unwrap();
+        assert!(matches!(err, ShhError::ProgramKilled { oom_killed: false }));
+        assert!(err.to_string().contains("SIGKILL"), "{err}");
+    }
+
+    #[test]
+    fn test_parse_oom_kills() {
+        assert_eq!(
+            parse_oom_kills("low 0\nhigh 12\nmax 3\noom 2\noom_kill 2\noom_group_kill 0\n"),
+            Some(2)
+        );
+        assert_eq!(parse_oom_kills("low 0\nhigh 0\n"), None);
+    }
+
+    #[test]
+    fn test_profile_store() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store_dir = temp_dir.path().join("store");
+        assert!(ProfileData::from_store(&store_dir, "foo.service").is_err());
+        assert!(ProfileData::default()
+            .add_to_store(&store_dir, "../foo")
+            .is_err());
+
+        for session in [
+            "1 0.000001 openat(AT_FDCWD, \"/var/lib/foo/a\", O_RDONLY) = 3\n",
+            "1 0.000001 openat(AT_FDCWD, \"/var/lib/foo/b\", O_RDONLY) = 3\n1 0.000002 getpid() = 1\n",
+        ] {
+            ProfileData::from_strace_log(session.as_bytes())
+                .unwrap()
+                .add_to_store(&store_dir, "foo.service")
+                .unwrap();
+        }
+        let stored = ProfileData::from_store(&store_dir, "foo.service").unwrap();
+        assert!(stored
+            .0
+            .contains(&summarize::ProgramAction::Read("/var/lib/foo/a".into())));
+        assert!(stored
+            .0
+            .contains(&summarize::ProgramAction::Read("/var/lib/foo/b".into())));
+        let syscalls: Vec<_> = stored
+            .0
+            .iter()
+            .filter_map(|a| match a {
+                summarize::ProgramAction::Syscalls(s) => Some(s),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            syscalls,
+            vec![&["openat".to_owned(), "getpid".to_owned()].into()]
+        );
+        assert!(ProfileData::from_store(&store_dir, "bar.service").is_err());
+
+        // Actions are stored by name, along with the format version
+        let store_path = store_dir.join("foo.service.profile");
+        let content = fs::read_to_string(&store_path).unwrap();
+        assert!(content.starts_with(&format!("{{\"version\":{PROFILE_STORE_VERSION},")));
+        assert!(content.contains("{\"Read\":\"/var/lib/foo/a\"}"));
+        fs::write(
+            &store_path,
+            content.replacen(
+                &format!("\"version\":{PROFILE_STORE_VERSION}"),
+                "\"version\":0",
+                1,
+            ),
+        )
+        .unwrap();
+        assert!(ProfileData::from_store(&store_dir, "foo.service").is_err());
+        assert!(ProfileData::default()
+            .add_to_store(&store_dir, "foo.service")
+            .is_err());
+    }
+
+    #[test]
+    fn test_profile_data_file_name() {
+        for name in ["001", "012_foo", "001_foo.bar"] {
+            assert!(ProfileData::is_file_name(name), "{name}");
+        }
+        for name in [
+            "001.strace",
+            "001_foo.strace",
+            "001.tmp",
+            "001_foo.tmp",
+            "001_",
+            "01",
+            "0001",
+            "abc",
+            "notes.txt",
+        ] {
+            assert!(!ProfileData::is_file_name(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_profile_data_from_dir() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        for i in 0..4 {
+            let path = temp_dir.path().join(format!("{i:03}"));
+            if i % 2 == 0 {
+                ProfileData(vec![summarize::ProgramAction::Read(
+                    format!("/var/lib/foo/{i}").into(),
+                )])
+                .to_file(&path)
+                .unwrap();
+            } else {
+                // Empty capture, ie. for a command interrupted before writing anything
+                File::create(&path).unwrap();
+            }
+        }
+        fs::create_dir(temp_dir.path().join("subdir")).unwrap();
+        // Kept strace logs and unrelated files are not profile data
+        fs::write(temp_dir.path().join("000.strace"), "").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "").unwrap();
+
+        let paths = ProfileData::expand_paths(&[temp_dir.path().to_owned()]).unwrap();
+        assert_eq!(
+            paths,
+            (0..4)
+                .map(|i| temp_dir.path().join(format!("{i:03}")))
+                .collect::<Vec<_>>()
+        );
+        let profile_data = ProfileData::from_files(&paths).unwrap();
+        assert_eq!(
+            profile_data.0,
+            vec![
+                summarize::ProgramAction::Read("/var/lib/foo/0".into()),
+                summarize::ProgramAction::Read("/var/lib/foo/2".into()),
+            ]
+        );
+    }
+}
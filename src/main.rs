@@ -1,35 +1,149 @@
 //! Systemd Hardening Helper
 
-#![cfg_attr(all(feature = "nightly", test), feature(test))]
-
 use std::{
+    env,
+    fmt::Write as _,
     fs::{self, File},
+    io::{self, BufRead as _, BufReader, BufWriter, IsTerminal as _, Write as _},
+    iter,
+    path::{Path, PathBuf},
+    sync::mpsc,
     thread,
+    time::Duration,
 };
 
 use anyhow::Context;
 use clap::Parser;
+use shh::{
+    filter_options, HardeningOptions, KernelVersion, OptionWithValue, ProfileData, Profiler,
+    Service, StraceVersion, SupportedOptions, SystemdVersion,
+};
 
 mod cl;
-mod strace;
-mod summarize;
-mod systemd;
 
 fn sd_options(
-    sd_version: &systemd::SystemdVersion,
-    kernel_version: &systemd::KernelVersion,
-    hardening_opts: &cl::HardeningOptions,
-) -> Vec<systemd::OptionDescription> {
-    let sd_opts = systemd::build_options(sd_version, kernel_version, hardening_opts);
-    log::info!(
-        "Enabled support for systemd options: {}",
-        sd_opts
-            .iter()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>()
-            .join(", ")
+    kernel_version: &KernelVersion,
+    hardening_opts: &HardeningOptions,
+) -> anyhow::Result<SupportedOptions> {
+    let sd_version = if let Some(sd_version) = &hardening_opts.systemd_version {
+        sd_version.clone()
+    } else {
+        SystemdVersion::local_system()?
+    };
+    log::info!("Target systemd version: {sd_version}");
+    let sd_opts = SupportedOptions::new(&sd_version, kernel_version, hardening_opts);
+    log::info!("Enabled support for systemd options: {sd_opts}");
+    Ok(sd_opts)
+}
+
+/// Resolve options compatible with actions, and report them
+fn report(
+    sd_opts: &SupportedOptions,
+    profile_data: &ProfileData,
+    json: bool,
+) -> anyhow::Result<()> {
+    if json {
+        let resolved_opts = sd_opts.resolve_with_rationale(profile_data);
+        shh::report_options_json(&resolved_opts, io::stdout().lock())?;
+    } else {
+        let resolved_opts = sd_opts.resolve(profile_data);
+        // Markers are only needed to parse back options from the logs of a unit, systemd sets this for its units
+        let markers = env::var_os("INVOCATION_ID").is_some();
+        shh::report_options(resolved_opts, markers);
+    }
+    Ok(())
+}
+
+/// Ask for confirmation before modifying system state, unless it was already given with `--yes`
+fn confirm(summary: &str, yes: bool) -> anyhow::Result<()> {
+    if yes {
+        return Ok(());
+    }
+    let stdin = io::stdin();
+    anyhow::ensure!(
+        stdin.is_terminal(),
+        "Standard input is not a terminal, pass --yes to confirm: {summary}"
+    );
+    let mut stderr = io::stderr().lock();
+    write!(stderr, "{summary}. Continue? [y/N] ")?;
+    stderr.flush()?;
+    let mut answer = String::new();
+    stdin.lock().read_line(&mut answer)?;
+    anyhow::ensure!(
+        matches!(answer.trim(), "y" | "Y" | "yes"),
+        "Aborted by user"
     );
-    sd_opts
+    Ok(())
+}
+
+/// Event ending the collection of profiling results
+enum CollectEvent {
+    /// Units are stopped, and their profiling results collected
+    Done(anyhow::Result<Vec<Vec<OptionWithValue>>>),
+    /// Interrupted by signal
+    Signal(i32),
+}
+
+/// Stop profiled units and collect their profiling results. If this does not complete within `timeout`, or if
+/// interrupted by a signal, stop units without waiting and remove the profiling config instead.
+/// In dry run mode, units are left untouched, so results can only be collected from units already stopped.
+fn collect_profiling_results(
+    service: &Service,
+    other_instances: &[Service],
+    timeout: Option<Duration>,
+    dry_run: bool,
+) -> anyhow::Result<Vec<Vec<OptionWithValue>>> {
+    let (event_tx, event_rx) = mpsc::channel();
+
+    let mut signals = signal_hook::iterator::Signals::new([
+        signal_hook::consts::signal::SIGINT,
+        signal_hook::consts::signal::SIGQUIT,
+        signal_hook::consts::signal::SIGTERM,
+    ])?;
+    let signal_tx = event_tx.clone();
+    thread::spawn(move || {
+        for sig in signals.forever() {
+            if signal_tx.send(CollectEvent::Signal(sig)).is_err() {
+                // Collection is over, behave as if we did not handle signals
+                let _ = signal_hook::low_level::emulate_default_handler(sig);
+            }
+        }
+    });
+
+    let services: Vec<_> = iter::once(service)
+        .chain(other_instances)
+        .cloned()
+        .collect();
+    thread::spawn(move || {
+        let res = services
+            .iter()
+            .filter(|_| !dry_run)
+            .try_for_each(|s| s.action("stop", true))
+            .and_then(|()| services.iter().map(Service::profiling_result).collect());
+        let _ = event_tx.send(CollectEvent::Done(res));
+    });
+
+    let event = if let Some(timeout) = timeout {
+        event_rx.recv_timeout(timeout).ok()
+    } else {
+        event_rx.recv().ok()
+    };
+    let reason = match event {
+        Some(CollectEvent::Done(res)) => return res,
+        Some(CollectEvent::Signal(sig)) => format!("interrupted by signal {sig}"),
+        None => format!("timed out after {}s", timeout.unwrap_or_default().as_secs()),
+    };
+    if dry_run {
+        anyhow::bail!("Profiling result collection {reason}");
+    }
+    log::warn!("Profiling result collection {reason}, removing profiling config");
+    for service in iter::once(service).chain(other_instances) {
+        service.action("stop", false)?;
+    }
+    service.remove_profile_fragment()?;
+    service.restore_prev_hardening_fragment()?;
+    service.reload_unit_config()?;
+    anyhow::bail!("Profiling did not complete ({reason}), profiling config was removed")
 }
 
 fn main() -> anyhow::Result<()> {
@@ -44,32 +158,54 @@ fn main() -> anyhow::Result<()> {
         .init()
         .context("Failed to setup logger")?;
 
+    // Parse cl args
+    let args = cl::Args::parse();
+
     // Get versions
-    let sd_version = systemd::SystemdVersion::local_system()?;
-    let kernel_version = systemd::KernelVersion::local_system()?;
-    let strace_version = strace::StraceVersion::local_system()?;
-    log::info!("Detected versions: Systemd {sd_version}, Linux kernel {kernel_version}, strace {strace_version}");
-    if strace_version < strace::StraceVersion::new(6, 4) {
+    let kernel_version = KernelVersion::local_system()?;
+    let strace_path = match &args.action {
+        cl::Action::Run { strace_path, .. } => strace_path.clone(),
+        _ => PathBuf::from(cl::DEFAULT_STRACE_PATH),
+    };
+    let strace_version = StraceVersion::local_system(&strace_path)?;
+    log::info!("Detected versions: Linux kernel {kernel_version}, strace {strace_version}");
+    if strace_version < StraceVersion::new(6, 4) {
         log::warn!("Strace version >=6.4 is strongly recommended, if you experience strace output parsing errors, please consider upgrading");
     }
 
-    // Parse cl args
-    let args = cl::Args::parse();
-
     // Handle CL args
     match args.action {
         cl::Action::Run {
             command,
             hardening_opts,
             profile_data_path,
+            checkpoint_interval,
+            timeout,
+            tracer,
             strace_log_path,
+            json,
+            explain,
+            strace_path,
+            strace_extra_args,
         } => {
+            let hardening_opts = hardening_opts.into_options();
+
             // Build supported systemd options
-            let sd_opts = sd_options(&sd_version, &kernel_version, &hardening_opts);
+            let sd_opts = sd_options(&kernel_version, &hardening_opts)?;
 
-            // Run strace
+            // Run command traced
             let cmd = command.iter().map(|a| &**a).collect::<Vec<&str>>();
-            let st = strace::Strace::run(&cmd, strace_log_path)?;
+            let profiler = Profiler::start(
+                &cmd,
+                tracer,
+                strace_log_path,
+                hardening_opts.root_confined,
+                &strace_path,
+                &strace_extra_args,
+            )?;
+            if let Some(timeout) = timeout {
+                profiler.terminate_after(Duration::from_secs(timeout));
+            }
 
             // Start signal handling thread
             let mut signals = signal_hook::iterator::Signals::new([
@@ -77,82 +213,287 @@ fn main() -> anyhow::Result<()> {
                 signal_hook::consts::signal::SIGQUIT,
                 signal_hook::consts::signal::SIGTERM,
             ])?;
+            let tracee_group = profiler.tracee_group();
             thread::spawn(move || {
                 for sig in signals.forever() {
-                    // The strace, and its watched child processes already get the signal, so the iterator will stop naturally
-                    log::info!("Got signal {sig:?}, ignoring");
+                    // The profiled program runs in its own process group, so it does not get terminal signals,
+                    // forward them and the iterator will stop naturally when it exits
+                    log::info!("Got signal {sig:?}, forwarding it to profiled program");
+                    if let Ok(sig) = nix::sys::signal::Signal::try_from(sig) {
+                        tracee_group.signal(sig);
+                    }
                 }
             });
 
             // Summarize actions
-            let logs = st.log_lines()?;
-            let actions = summarize::summarize(logs)?;
-            log::debug!("{actions:?}");
+            let mut profile_data = match (checkpoint_interval, &profile_data_path) {
+                (Some(interval), Some(profile_data_path)) => profiler
+                    .collect_with_checkpoints(Duration::from_secs(interval), profile_data_path)?,
+                _ => profiler.collect()?,
+            };
+            log::debug!("{profile_data:?}");
+            if explain {
+                profile_data.explain(io::stderr().lock())?;
+            }
 
             if let Some(profile_data_path) = profile_data_path {
                 // Dump profile data
                 log::info!("Writing profile data into {profile_data_path:?}...");
-                let file = File::create(profile_data_path)?;
-                bincode::serialize_into(file, &actions)?;
+                profile_data.to_file(&profile_data_path)?;
             } else {
-                // Resolve
-                let resolved_opts = systemd::resolve(&sd_opts, &actions);
-
-                // Report
-                systemd::report_options(resolved_opts);
+                profile_data.add_allowed_actions(&hardening_opts);
+                report(&sd_opts, &profile_data, json)?;
             }
         }
+        cl::Action::ProfileTransient {
+            command,
+            hardening_opts,
+            user,
+            command_retries,
+        } => {
+            let hardening_opts = hardening_opts.into_options();
+            let service = Service::new_transient(user, command_retries)?;
+            service.run_transient(&command, &hardening_opts)?;
+            let resolved_opts = service.profiling_result()?;
+            shh::report_options(resolved_opts, false);
+        }
         cl::Action::MergeProfileData {
             hardening_opts,
             paths,
+            json,
+            json_output,
+            keep_files,
+            store,
+            store_dir,
         } => {
+            let hardening_opts = hardening_opts.into_options();
+
             // Build supported systemd options
-            let sd_opts = sd_options(&sd_version, &kernel_version, &hardening_opts);
+            let sd_opts = sd_options(&kernel_version, &hardening_opts)?;
 
             // Load and merge profile data
-            let mut actions: Vec<summarize::ProgramAction> = Vec::new();
-            for path in &paths {
-                let file = File::open(path)?;
-                let mut profile_actions: Vec<summarize::ProgramAction> =
-                    bincode::deserialize_from(file)?;
-                actions.append(&mut profile_actions);
+            let mut profile_data = ProfileData::from_files(&ProfileData::expand_paths(&paths)?)?;
+            log::debug!("{profile_data:?}");
+
+            if let Some(unit) = &store {
+                // Allowed actions are added when generating options, so they are not accumulated
+                profile_data.add_to_store(&store_dir, unit)?;
+            } else {
+                profile_data.add_allowed_actions(&hardening_opts);
+                if let Some(json_output) = json_output {
+                    let resolved_opts = sd_opts.resolve_with_rationale(&profile_data);
+                    if json_output == Path::new("-") {
+                        shh::report_options_json(&resolved_opts, io::stdout().lock())?;
+                    } else {
+                        let file = File::create(&json_output)
+                            .with_context(|| format!("Failed to create {json_output:?}"))?;
+                        shh::report_options_json(&resolved_opts, BufWriter::new(file))?;
+                    }
+                } else {
+                    report(&sd_opts, &profile_data, json)?;
+                }
+            }
+
+            // Remove profile data files, only if passed explicitly, so that we never empty a directory, and keep the
+            // ones folded into the store, since the store can not be split back into sessions
+            if !keep_files && store.is_none() {
+                for path in paths.iter().filter(|p| p.is_file()) {
+                    fs::remove_file(path)?;
+                }
             }
-            log::debug!("{actions:?}");
+        }
+        cl::Action::Harden {
+            hardening_opts,
+            from_store,
+            store_dir,
+            json,
+        } => {
+            let hardening_opts = hardening_opts.into_options();
 
-            // Resolve
-            let resolved_opts = systemd::resolve(&sd_opts, &actions);
+            // Build supported systemd options
+            let sd_opts = sd_options(&kernel_version, &hardening_opts)?;
 
-            // Report
-            systemd::report_options(resolved_opts);
+            let mut profile_data = ProfileData::from_store(&store_dir, &from_store)?;
+            log::debug!("{profile_data:?}");
 
-            // Remove profile data files
-            for path in paths {
-                fs::remove_file(path)?;
+            profile_data.add_allowed_actions(&hardening_opts);
+            report(&sd_opts, &profile_data, json)?;
+        }
+        cl::Action::AnalyzeStraceLog {
+            hardening_opts,
+            json,
+            cwd,
+            path,
+        } => {
+            let hardening_opts = hardening_opts.into_options();
+            if let Some(cwd) = &cwd {
+                anyhow::ensure!(cwd.is_absolute(), "{cwd:?} is not an absolute path");
             }
+
+            // Build supported systemd options
+            let sd_opts = sd_options(&kernel_version, &hardening_opts)?;
+
+            // Summarize actions
+            let file = BufReader::new(
+                File::open(&path).with_context(|| format!("Failed to open {path:?}"))?,
+            );
+            let mut profile_data = ProfileData::from_strace_reader(file, cwd.as_deref())?;
+            log::debug!("{profile_data:?}");
+
+            profile_data.add_allowed_actions(&hardening_opts);
+            report(&sd_opts, &profile_data, json)?;
+        }
+        cl::Action::ExportSeccompProfile { paths } => {
+            let profile_data = ProfileData::from_files(&ProfileData::expand_paths(&paths)?)?;
+            log::debug!("{profile_data:?}");
+
+            println!("{}", profile_data.seccomp_profile_json()?);
         }
         cl::Action::Service(cl::ServiceAction::StartProfile {
             service,
             hardening_opts,
-            no_restart,
+            activation,
+            profile_stop_reload,
+            instances,
+            profile_timeout,
+            tracer,
+            profile_memory_max,
+            merge,
+            dry_run,
+            keep_profile_data,
+            yes,
+            user,
+            command_retries,
+            fragment_naming,
         }) => {
-            let service = systemd::Service::new(&service);
-            service.add_profile_fragment(&hardening_opts)?;
-            if no_restart {
-                log::warn!("Profiling config will only be applied when systemd config is reloaded, and service restarted");
-            } else {
-                service.reload_unit_config()?;
-                service.action("restart", false)?;
+            let hardening_opts = hardening_opts.into_options();
+            let activation = activation.activation();
+            let service =
+                Service::new(&service, user, command_retries, fragment_naming).canonical()?;
+            let other_instances = instances
+                .iter()
+                .map(|i| service.instance(i))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if !dry_run {
+                service.ensure_config_writable()?;
+                confirm(
+                    &format!(
+                        "Add profiling config to {}{}",
+                        service.unit_name(),
+                        if activation.restart() {
+                            format!(", and restart {} unit(s)", other_instances.len() + 1)
+                        } else {
+                            String::new()
+                        }
+                    ),
+                    yes,
+                )?;
+            }
+            // The profiling fragment applies to all instances
+            service.add_profile_fragment(
+                &hardening_opts,
+                profile_stop_reload,
+                profile_timeout.map(Duration::from_secs),
+                tracer,
+                profile_memory_max.as_deref(),
+                merge,
+                dry_run,
+                keep_profile_data.as_deref(),
+            )?;
+            if !dry_run {
+                activation.run(
+                    "Profiling config",
+                    &iter::once(&service)
+                        .chain(&other_instances)
+                        .collect::<Vec<_>>(),
+                    || service.reload_unit_config(),
+                    |service| service.action("restart", false),
+                )?;
             }
         }
         cl::Action::Service(cl::ServiceAction::FinishProfile {
             service,
             apply,
-            no_restart,
+            dry_run,
+            output_file,
+            instances,
+            merge,
+            merge_with_existing_unit,
+            exclude_options,
+            only_options,
+            activation,
+            no_verify,
+            timeout,
+            yes,
+            user,
+            command_retries,
+            fragment_naming,
         }) => {
-            let service = systemd::Service::new(&service);
-            service.action("stop", true)?;
-            service.remove_profile_fragment()?;
-            let resolved_opts = service.profiling_result()?;
+            let activation = activation.activation();
+            let service =
+                Service::new(&service, user, command_retries, fragment_naming).canonical()?;
+            let other_instances = instances
+                .iter()
+                .map(|i| service.instance(i))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            // The profiling config is always removed, except in dry run mode
+            if !dry_run {
+                service.ensure_config_writable()?;
+                // Single confirmation for all changes, since units are stopped before the hardening config is known
+                let mut summary = format!(
+                    "Stop {} unit(s) to collect their profiling results, remove profiling config from {}",
+                    other_instances.len() + 1,
+                    service.unit_name()
+                );
+                if (apply || merge) && output_file.is_none() {
+                    write!(summary, ", add the resulting hardening config")?;
+                }
+                if activation.restart() {
+                    write!(
+                        summary,
+                        ", and restart {} unit(s)",
+                        other_instances.len() + 1
+                    )?;
+                }
+                confirm(&summary, yes)?;
+            }
+            let level = service.profiling_hardening_level()?;
+            let allowances = service.profiling_allowances()?;
+            // Options are merged as described while profiling, for the same target systemd version
+            let hardening_opts = service.profiling_hardening_options()?.unwrap_or_else(|| {
+                log::warn!("No hardening options recorded while profiling, merging with the most strict options");
+                HardeningOptions::strict()
+            });
+            let sd_opts = sd_options(&kernel_version, &hardening_opts)?;
+            let mut results = collect_profiling_results(
+                &service,
+                &other_instances,
+                timeout.map(Duration::from_secs),
+                dry_run,
+            )?
+            .into_iter();
+            let mut resolved_opts = results.next().unwrap_or_default();
+            for other_result in results {
+                // Keep options compatible with all instances
+                resolved_opts = sd_opts.merge(&resolved_opts, &other_result);
+            }
+            if merge {
+                if let Some(prev_opts) = service.prev_hardening_options()? {
+                    resolved_opts = sd_opts.merge(&prev_opts, &resolved_opts);
+                } else {
+                    log::warn!("No previous hardening config to merge with");
+                }
+            }
+            resolved_opts = filter_options(resolved_opts, &exclude_options, &only_options);
+            let names: Vec<_> = resolved_opts.iter().map(|o| o.name.as_str()).collect();
+            let mut existing_opts = if merge_with_existing_unit {
+                service.unit_options(&names)?
+            } else {
+                vec![]
+            };
+            // Options implied by DynamicUser=yes can not be weakened by the unit, so they come last
+            existing_opts.extend(service.dynamic_user_options(&names)?);
+            resolved_opts = sd_opts.reconcile_existing(&existing_opts, resolved_opts);
             log::info!(
                 "Resolved systemd options: {}",
                 resolved_opts
@@ -161,46 +502,105 @@ fn main() -> anyhow::Result<()> {
                     .collect::<Vec<_>>()
                     .join(", ")
             );
-            if apply && !resolved_opts.is_empty() {
-                service.add_hardening_fragment(resolved_opts)?;
+            let write_fragment =
+                (apply || merge || dry_run || output_file.is_some()) && !resolved_opts.is_empty();
+            if write_fragment && !dry_run && output_file.is_none() {
+                eprintln!("Hardening config directives to be applied:");
+                for opt in &resolved_opts {
+                    eprintln!("  {opt}");
+                }
             }
-            service.reload_unit_config()?;
-            if !no_restart {
-                service.action("start", false)?;
+            if write_fragment {
+                // Verified before being written, so if it is rejected, the unit config is left untouched
+                service
+                    .add_hardening_fragment(
+                        resolved_opts,
+                        level.as_deref(),
+                        &allowances,
+                        dry_run,
+                        output_file.as_deref(),
+                        !no_verify,
+                    )
+                    .context("Profiling config was left in place")?;
+            }
+            if !dry_run {
+                service.remove_profile_fragment()?;
+                if (apply || merge) && output_file.is_none() {
+                    service.remove_prev_hardening_fragment()?;
+                } else {
+                    service.restore_prev_hardening_fragment()?;
+                }
+            }
+            if !dry_run {
+                // Reloading is still needed to remove the profiling config
+                activation.run(
+                    "Profiling config removal and hardening config",
+                    &iter::once(&service)
+                        .chain(&other_instances)
+                        .collect::<Vec<_>>(),
+                    || service.reload_unit_config(),
+                    |service| service.action("start", false),
+                )?;
             }
         }
-        cl::Action::Service(cl::ServiceAction::Reset { service }) => {
-            let service = systemd::Service::new(&service);
-            let _ = service.remove_profile_fragment();
-            let _ = service.remove_hardening_fragment();
+        cl::Action::Service(cl::ServiceAction::SecurityDelta {
+            service,
+            user,
+            command_retries,
+            fragment_naming,
+        }) => {
+            let service =
+                Service::new(&service, user, command_retries, fragment_naming).canonical()?;
+            let delta = service.security_delta()?;
+            println!("{delta}");
+        }
+        cl::Action::Service(cl::ServiceAction::Reset {
+            service,
+            yes,
+            user,
+            command_retries,
+            fragment_naming,
+        }) => {
+            let service =
+                Service::new(&service, user, command_retries, fragment_naming).canonical()?;
+            service.ensure_config_writable()?;
+            confirm(
+                &format!(
+                    "Remove all shh config from {}, and restart it if it is running",
+                    service.unit_name()
+                ),
+                yes,
+            )?;
+            let removed = service.remove_fragments()?;
+            if removed.is_empty() {
+                println!("No config fragment to remove");
+            }
+            for path in removed {
+                println!("Removed {}", path.display());
+            }
             service.reload_unit_config()?;
             service.action("try-restart", false)?;
         }
-        cl::Action::ListSystemdOptions => {
-            println!("# Supported systemd options");
-            let mut sd_opts = sd_options(
-                &sd_version,
-                &kernel_version,
-                &cl::HardeningOptions::strict(),
-            );
-            sd_opts.sort_unstable_by_key(|o| o.name);
-            for sd_opt in sd_opts {
-                println!("- [`{sd_opt}`](https://www.freedesktop.org/software/systemd/man/latest/systemd.exec.html#{sd_opt}=)");
-                for opt_val in sd_opt.possible_values {
-                    match opt_val.value {
-                        systemd::OptionValue::Boolean(v) => {
-                            println!("    - `{}`", if v { "true" } else { "false" });
-                        }
-                        systemd::OptionValue::String(v) => println!("    - `{v}`"),
-                        systemd::OptionValue::List { values, .. } => {
-                            for val in values {
-                                println!("    - `{val}`");
-                            }
-                        }
-                    }
+        cl::Action::Service(cl::ServiceAction::List {
+            user,
+            json,
+            fragment_naming,
+        }) => {
+            let units = Service::list_fragments(user, &fragment_naming)?;
+            if json {
+                let mut stdout = io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, &units)?;
+                writeln!(stdout)?;
+            } else {
+                for unit in units {
+                    println!("{}: {}", unit.unit, unit.fragments.join(", "));
                 }
             }
         }
+        cl::Action::ListSystemdOptions => {
+            let sd_opts = sd_options(&kernel_version, &HardeningOptions::strict())?;
+            sd_opts.print_markdown();
+        }
     }
 
     Ok(())
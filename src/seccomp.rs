@@ -0,0 +1,156 @@
+//! Docker/OCI seccomp profile generation
+
+use std::{collections::BTreeSet, env, iter};
+
+use itertools::Itertools as _;
+
+use crate::summarize::{ProgramAction, SetSpecifier};
+
+/// Errno returned for denied syscalls, same as for the `SystemCallFilter=` we generate
+const EPERM: u32 = 1;
+
+/// Seccomp profile in the format used by Docker and OCI runtimes
+/// See <https://github.com/opencontainers/runtime-spec/blob/main/config-linux.md#seccomp>
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SeccompProfile {
+    default_action: &'static str,
+    default_errno_ret: u32,
+    architectures: Vec<&'static str>,
+    syscalls: Vec<SeccompSyscallRule>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SeccompSyscallRule {
+    names: Vec<String>,
+    action: &'static str,
+}
+
+impl SeccompProfile {
+    /// Build profile denying all syscalls, except those observed in actions
+    pub(crate) fn from_actions(actions: &[ProgramAction]) -> anyhow::Result<Self> {
+        let syscalls: BTreeSet<&String> = actions
+            .iter()
+            .filter_map(|a| match a {
+                ProgramAction::Syscalls(syscalls) => Some(syscalls),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        Ok(Self {
+            default_action: "SCMP_ACT_ERRNO",
+            default_errno_ret: EPERM,
+            // Only allow the native architecture, and the other ones syscalls were made with,
+            // like SystemCallArchitectures=
+            architectures: iter::once(native_architecture())
+                .chain(actions.iter().filter_map(|a| match a {
+                    ProgramAction::NonNativeSyscalls(archs) => Some(archs_seccomp_names(archs)),
+                    _ => None,
+                }))
+                .flatten_ok()
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .into_iter()
+                .unique()
+                .collect(),
+            syscalls: syscalls
+                .into_iter()
+                .map(|s| SeccompSyscallRule {
+                    names: vec![s.to_owned()],
+                    action: "SCMP_ACT_ALLOW",
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Get libseccomp names of non native architectures, as named by systemd
+fn archs_seccomp_names(archs: &SetSpecifier<String>) -> anyhow::Result<Vec<&'static str>> {
+    let Some(archs) = archs.elements() else {
+        anyhow::bail!("Unable to build seccomp profile for unknown syscall architectures");
+    };
+    archs
+        .iter()
+        .map(|arch| {
+            Ok(match arch.as_str() {
+                "x86" => "SCMP_ARCH_X86",
+                "x32" => "SCMP_ARCH_X32",
+                "arm" => "SCMP_ARCH_ARM",
+                "ppc" => "SCMP_ARCH_PPC",
+                "s390" => "SCMP_ARCH_S390",
+                arch => anyhow::bail!("Unsupported architecture {arch:?} for seccomp profile"),
+            })
+        })
+        .collect()
+}
+
+/// Get libseccomp name of the architecture we run on
+fn native_architecture() -> anyhow::Result<Vec<&'static str>> {
+    let arch = match env::consts::ARCH {
+        "x86_64" => "SCMP_ARCH_X86_64",
+        "x86" => "SCMP_ARCH_X86",
+        "aarch64" => "SCMP_ARCH_AARCH64",
+        "arm" => "SCMP_ARCH_ARM",
+        "riscv64" => "SCMP_ARCH_RISCV64",
+        "powerpc64" if cfg!(target_endian = "little") => "SCMP_ARCH_PPC64LE",
+        "powerpc64" => "SCMP_ARCH_PPC64",
+        "s390x" => "SCMP_ARCH_S390X",
+        "loongarch64" => "SCMP_ARCH_LOONGARCH64",
+        arch => anyhow::bail!("Unsupported architecture {arch:?} for seccomp profile"),
+    };
+    Ok(vec![arch])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seccomp_profile() {
+        let actions = vec![
+            ProgramAction::Syscalls(
+                ["read", "write"]
+                    .into_iter()
+                    .map(ToOwned::to_owned)
+                    .collect(),
+            ),
+            ProgramAction::Read("/etc/passwd".into()),
+            ProgramAction::Syscalls(
+                ["openat", "read"]
+                    .into_iter()
+                    .map(ToOwned::to_owned)
+                    .collect(),
+            ),
+        ];
+        let profile = SeccompProfile::from_actions(&actions).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&profile).unwrap()).unwrap();
+
+        assert_eq!(json["defaultAction"], "SCMP_ACT_ERRNO");
+        assert_eq!(json["architectures"].as_array().unwrap().len(), 1);
+        let syscalls = json["syscalls"].as_array().unwrap();
+        assert!(syscalls.iter().all(|s| s["action"] == "SCMP_ACT_ALLOW"));
+        let names: Vec<_> = syscalls
+            .iter()
+            .flat_map(|s| s["names"].as_array().unwrap())
+            .map(|n| n.as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["openat", "read", "write"]);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_seccomp_profile_non_native_archs() {
+        let actions = vec![
+            ProgramAction::NonNativeSyscalls(SetSpecifier::Some(vec!["x86".to_owned()])),
+            ProgramAction::NonNativeSyscalls(SetSpecifier::One("x86".to_owned())),
+        ];
+        let profile = SeccompProfile::from_actions(&actions).unwrap();
+        assert_eq!(
+            profile.architectures,
+            vec!["SCMP_ARCH_X86_64", "SCMP_ARCH_X86"]
+        );
+
+        let actions = vec![ProgramAction::NonNativeSyscalls(SetSpecifier::All)];
+        assert!(SeccompProfile::from_actions(&actions).is_err());
+    }
+}
@@ -1,12 +1,16 @@
 //! Strace related code
 
-use std::{collections::HashMap, fmt, io::BufRead, process::Command, str};
+use std::{collections::HashMap, fmt, io::BufRead, path::Path, process::Command, str};
 
 mod parser;
 mod run;
 
+pub(crate) use parser::{LogParser, CAPABILITY_GATED_SYSCALLS};
 pub(crate) use run::Strace;
+pub use run::TraceeGroup;
 
+/// Syscall invocation, parsed from strace output or decoded from eBPF events, so that summarizing does not depend on
+/// the tracing backend
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct Syscall {
     pub pid: u32,
@@ -96,19 +100,24 @@ impl IntegerExpressionValue {
 
 pub(crate) type SyscallRetVal = i128; // allows holding both signed and unsigned 64 bit integers
 
+/// Strace version
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
-pub(crate) struct StraceVersion {
+pub struct StraceVersion {
+    /// Major version
     pub major: u16,
+    /// Minor version
     pub minor: u16,
 }
 
 impl StraceVersion {
-    pub(crate) fn new(major: u16, minor: u16) -> Self {
+    /// Build version from its components
+    pub fn new(major: u16, minor: u16) -> Self {
         Self { major, minor }
     }
 
-    pub(crate) fn local_system() -> anyhow::Result<Self> {
-        let output = Command::new("strace").arg("--version").output()?;
+    /// Get version of a local strace binary
+    pub fn local_system(strace_path: &Path) -> anyhow::Result<Self> {
+        let output = Command::new(strace_path).arg("--version").output()?;
         if !output.status.success() {
             anyhow::bail!("strace invocation failed with code {:?}", output.status);
         }
@@ -81,15 +81,17 @@ fn parse_syscall_line(i: &str) -> IResult<&str, ParseResult> {
                 delimited(
                     tag("<... "),
                     parse_name,
-                    tuple((tag(" resumed> )"), space1)),
+                    pair(tag(" resumed>"), opt(alt((tag(", "), tag(" "))))),
                 ),
+                parse_args_resumed,
                 parse_ret_val,
             )),
-            |(pid, rel_ts, name, ret_val)| {
+            |(pid, rel_ts, name, args, ret_val)| {
                 ParseResult::SyscallEnd(SyscallEnd {
                     pid,
                     rel_ts,
                     name: name.to_owned(),
+                    args,
                     ret_val,
                 })
             },
@@ -124,7 +126,17 @@ fn parse_args_complete(i: &str) -> IResult<&str, Vec<Expression>> {
 #[function_name::named]
 fn parse_args_incomplete(i: &str) -> IResult<&str, Vec<Expression>> {
     dbg_parser!(i);
-    delimited(char('('), parse_args_inner, tag(" <unfinished ...>"))(i)
+    delimited(
+        char('('),
+        parse_args_inner,
+        pair(opt(tag(", ")), tag(" <unfinished ...>")),
+    )(i)
+}
+
+#[function_name::named]
+fn parse_args_resumed(i: &str) -> IResult<&str, Vec<Expression>> {
+    dbg_parser!(i);
+    terminated(parse_args_inner, terminated(char(')'), space1))(i)
 }
 
 #[function_name::named]
@@ -1,9 +1,13 @@
 //! Strace output parser
 
 use std::{
+    borrow::Cow,
+    cell::{Ref, RefCell},
+    collections::BTreeSet,
     fs::File,
     io::{self, BufRead, BufWriter, Write},
     path::Path,
+    rc::Rc,
 };
 
 use crate::strace::Syscall;
@@ -25,8 +29,88 @@ pub(crate) struct LogParser {
     log: Option<BufWriter<File>>,
     buf: String,
     unfinished_syscalls: Vec<SyscallStart>,
+    /// Personality modes processes switched to, as reported by strace
+    personality_modes: Rc<RefCell<BTreeSet<String>>>,
+    /// Ignore failed syscalls, except the ones denied for lack of privileges
+    only_denied_failures: bool,
 }
 
+/// Errno of syscalls failing because of missing privileges, ie. a capability
+const DENIED_ERRNO: &str = "EPERM";
+
+/// Syscalls that may fail with `EPERM` because of a missing capability we model, other failures with this errno
+/// (ie. from LSMs, or a seccomp filter) do not mean the program needs more privileges
+pub(crate) const CAPABILITY_GATED_SYSCALLS: &[&str] = &[
+    "acct",
+    "adjtimex",
+    "bind",
+    "bpf",
+    "chown",
+    "chown32",
+    "chroot",
+    "clock_adjtime",
+    "clock_adjtime64",
+    "clock_settime",
+    "clock_settime64",
+    "delete_module",
+    "fchown",
+    "fchown32",
+    "fchownat",
+    "finit_module",
+    "get_robust_list",
+    "init_module",
+    "ioprio_set",
+    "kcmp",
+    "kexec_file_load",
+    "kexec_load",
+    "lchown",
+    "lchown32",
+    "mbind",
+    "migrate_pages",
+    "mknod",
+    "mknodat",
+    "move_pages",
+    "perf_event_open",
+    "prlimit64",
+    "process_vm_readv",
+    "process_vm_writev",
+    "ptrace",
+    "reboot",
+    "sched_setaffinity",
+    "sched_setattr",
+    "sched_setparam",
+    "sched_setscheduler",
+    "set_mempolicy",
+    "setfsgid",
+    "setfsgid32",
+    "setfsuid",
+    "setfsuid32",
+    "setgid",
+    "setgid32",
+    "setgroups",
+    "setgroups32",
+    "setns",
+    "setpriority",
+    "setregid",
+    "setregid32",
+    "setresgid",
+    "setresgid32",
+    "setresuid",
+    "setresuid32",
+    "setreuid",
+    "setreuid32",
+    "setrlimit",
+    "settimeofday",
+    "setuid",
+    "setuid32",
+    "socket",
+    "stime",
+    "syslog",
+    "timer_create",
+    "timerfd_create",
+    "vhangup",
+];
+
 impl LogParser {
     pub(crate) fn new(reader: Box<dyn BufRead>, log_path: Option<&Path>) -> anyhow::Result<Self> {
         let log = log_path
@@ -40,8 +124,137 @@ impl LogParser {
             log,
             buf: String::new(),
             unfinished_syscalls: Vec::new(),
+            personality_modes: Rc::default(),
+            only_denied_failures: false,
         })
     }
+
+    /// Ignore failed syscalls, except the ones denied for lack of privileges, which the program may need when
+    /// running with other privileges than while profiling
+    pub(crate) fn only_denied_failures(mut self) -> Self {
+        self.only_denied_failures = true;
+        self
+    }
+
+    /// Personality modes (ie. `32 bit`) processes switched to, among the lines parsed so far
+    pub(crate) fn personality_modes(&self) -> Ref<'_, BTreeSet<String>> {
+        self.personality_modes.borrow()
+    }
+
+    /// Same as `personality_modes`, but can be read while the parser is iterated
+    pub(crate) fn shared_personality_modes(&self) -> Rc<RefCell<BTreeSet<String>>> {
+        Rc::clone(&self.personality_modes)
+    }
+}
+
+/// Is this a strace timestamp, as printed with `-r`, `-t`, `-tt` or `-ttt`
+fn is_timestamp(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_digit())
+        && token.contains([':', '.'])
+        && token
+            .chars()
+            .all(|c| c.is_ascii_digit() || (c == ':') || (c == '.'))
+}
+
+/// Is this a relative timestamp, as printed with `-r`, or an absolute one printed with `-ttt`
+fn is_seconds_timestamp(token: &str) -> bool {
+    token.split_once('.').is_some_and(|(s, us)| {
+        [s, us]
+            .iter()
+            .all(|p| !p.is_empty() && p.bytes().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Rewrite a strace line with the `PID REL_TS ` prefix the line parsers expect, whatever the strace flags used:
+/// pid as printed with `-f` to a file (`1234 `) or to a terminal (`[pid 1234] `), or missing for the initial
+/// process, timestamps printed with `-r`, `-t`, `-tt` or `-ttt`, or missing, and duration suffix printed with `-T`
+fn normalize_line(line: &str) -> Cow<'_, str> {
+    let mut rest = line;
+    let mut canonical = true;
+
+    // Pid
+    let pid = if let Some((pid, after)) = rest
+        .strip_prefix("[pid ")
+        .and_then(|r| r.trim_start().split_once(']'))
+    {
+        canonical = false;
+        rest = after.trim_start();
+        pid
+    } else if let Some((pid, after)) = rest
+        .split_once(' ')
+        .filter(|(p, _)| !p.is_empty() && p.bytes().all(|c| c.is_ascii_digit()))
+    {
+        rest = after.trim_start();
+        pid
+    } else {
+        canonical = false;
+        rest = rest.trim_start();
+        "0"
+    };
+
+    // Timestamps, keep the last one as the relative one, because it is the only one the parsers care about
+    let mut rel_ts = None;
+    let mut ts_count = 0;
+    while let Some((ts, after)) = rest.split_once(' ').filter(|(t, _)| is_timestamp(t)) {
+        rel_ts = Some(ts).filter(|t| is_seconds_timestamp(t));
+        ts_count += 1;
+        rest = after.trim_start();
+    }
+    if (ts_count != 1) || rel_ts.is_none() {
+        canonical = false;
+    }
+
+    // Duration
+    if let Some((before, duration)) = rest
+        .strip_suffix('>')
+        .and_then(|r| r.rsplit_once(" <"))
+        .filter(|(_, d)| is_seconds_timestamp(d))
+    {
+        canonical = false;
+        rest = before;
+    }
+
+    if canonical {
+        Cow::Borrowed(line)
+    } else {
+        Cow::Owned(format!("{pid} {} {rest}", rel_ts.unwrap_or("0.000000")))
+    }
+}
+
+/// Get errno from a failed syscall line, ie. `EPERM` for `... = -1 EPERM (Operation not permitted)`
+fn parse_failure_errno(line: &str) -> Option<&str> {
+    let (_, ret) = line.rsplit_once(") = ")?;
+    ret.strip_prefix("-1 ")
+        .or_else(|| ret.strip_prefix("? "))?
+        .split_whitespace()
+        .next()
+}
+
+/// Get pid and syscall name from a strace syscall end line, ie. `1234 0.000001 <... read resumed>...`
+fn parse_resumed_syscall(line: &str) -> Option<(u32, &str)> {
+    let (pid, rest) = line.split_once(' ')?;
+    let (_, rest) = rest.split_once("<... ")?;
+    let (name, _) = rest.split_once(" resumed>")?;
+    Some((pid.parse().ok()?, name))
+}
+
+/// Get syscall name from a normalized strace line, ie. `read` for `1234 0.000001 read(3, ...` or
+/// `1234 0.000001 <... read resumed>...`
+fn parse_syscall_name(line: &str) -> Option<&str> {
+    if let Some((_, name)) = parse_resumed_syscall(line) {
+        return Some(name);
+    }
+    let (_, rest) = line.split_once(' ')?;
+    let (_, rest) = rest.split_once(' ')?;
+    let (name, _) = rest.split_once('(')?;
+    Some(name)
+}
+
+/// Get mode from a strace personality change line, ie. `[ Process PID=1234 runs in 32 bit mode. ]`
+fn parse_personality_change(line: &str) -> Option<&str> {
+    let (_, msg) = line.split_once("[ Process PID=")?;
+    let (_, mode) = msg.split_once(" runs in ")?;
+    mode.strip_suffix(" mode. ]")
 }
 
 #[derive(Debug, PartialEq)]
@@ -71,11 +284,13 @@ impl SyscallStart {
     pub(crate) fn end(self, end: &SyscallEnd) -> Syscall {
         debug_assert_eq!(self.pid, end.pid);
         debug_assert_eq!(self.name, end.name);
+        let mut args = self.args;
+        args.extend(end.args.iter().cloned());
         Syscall {
             pid: self.pid,
             rel_ts: end.rel_ts,
             name: self.name,
-            args: self.args,
+            args,
             ret_val: end.ret_val,
         }
     }
@@ -87,6 +302,8 @@ pub(crate) struct SyscallEnd {
     pub pid: u32,
     pub rel_ts: f64,
     pub name: String,
+    /// Arguments that were not yet known when the syscall started
+    pub args: Vec<Expression>,
     pub ret_val: SyscallRetVal,
 }
 
@@ -109,13 +326,42 @@ impl Iterator for LogParser {
                 continue;
             }
 
+            // Failed syscalls ignored below are still kept in the log
             if let Some(log) = self.log.as_mut() {
                 if let Err(e) = writeln!(log, "{line}") {
                     return Some(Err(e.into()));
                 }
             }
 
-            match parse_line(line) {
+            let (raw_line, line) = (line, normalize_line(line));
+
+            if self.only_denied_failures
+                && parse_failure_errno(&line).is_some_and(|e| {
+                    e != DENIED_ERRNO
+                        || !parse_syscall_name(&line)
+                            .is_some_and(|n| CAPABILITY_GATED_SYSCALLS.contains(&n))
+                })
+            {
+                // Also forget the start of the syscall, if it was unfinished
+                if let Some((pid, name)) = parse_resumed_syscall(&line) {
+                    if let Some(unfinished_index) = self
+                        .unfinished_syscalls
+                        .iter()
+                        .position(|sc| (sc.name == name) && (sc.pid == pid))
+                    {
+                        self.unfinished_syscalls.swap_remove(unfinished_index);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(mode) = parse_personality_change(raw_line) {
+                // Not a syscall, but following syscalls of this process use another ABI
+                self.personality_modes.borrow_mut().insert(mode.to_owned());
+                continue;
+            }
+
+            match parse_line(&line) {
                 Ok(ParseResult::Syscall(sc)) => {
                     log::trace!("Parsed line: {line:?}");
                     break sc;
@@ -158,7 +404,7 @@ impl Iterator for LogParser {
 #[expect(clippy::unreadable_literal, clippy::shadow_unrelated)]
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, io::Cursor};
+    use std::{collections::HashMap, fs, io::Cursor};
 
     use pretty_assertions::assert_eq;
 
@@ -1419,6 +1665,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_interleave_resumed_args() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let lines = Cursor::new(
+            "10      0.000001 read(3,  <unfinished ...>
+11      0.000002 openat(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\", O_RDONLY <unfinished ...>
+11      0.000003 <... openat resumed>)  = 4
+10      0.000004 <... read resumed>\"\\x61\\x62\", 4096) = 2
+11      0.000005 read(4,  <unfinished ...>
+10      0.000006 close(3 <unfinished ...>
+11      0.000007 <... read resumed>\"\\x63\", 1024) = 1
+10      0.000008 <... close resumed> ) = 0"
+                .as_bytes()
+                .to_vec(),
+        );
+        let parser = LogParser::new(Box::new(lines), None).unwrap();
+        let syscalls: Vec<Syscall> = parser.into_iter().collect::<Result<_, _>>().unwrap();
+
+        let single_lines = Cursor::new(
+            "11      0.000003 openat(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\", O_RDONLY) = 4
+10      0.000004 read(3, \"\\x61\\x62\", 4096) = 2
+11      0.000007 read(4, \"\\x63\", 1024) = 1
+10      0.000008 close(3) = 0"
+                .as_bytes()
+                .to_vec(),
+        );
+        let parser = LogParser::new(Box::new(single_lines), None).unwrap();
+        let single_line_syscalls: Vec<Syscall> =
+            parser.into_iter().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(single_line_syscalls.len(), 4);
+        assert_eq!(syscalls, single_line_syscalls);
+    }
+
     #[test]
     fn test_getpid() {
         let _ = simple_logger::SimpleLogger::new().init();
@@ -1875,6 +2156,201 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_personality_change() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        assert_eq!(
+            parse_personality_change("1234 0.000010 [ Process PID=1234 runs in 32 bit mode. ]"),
+            Some("32 bit")
+        );
+        assert_eq!(
+            parse_personality_change("1234 0.000010 [ Process PID=1234 runs in x32 mode. ]"),
+            Some("x32")
+        );
+        assert_eq!(
+            parse_personality_change("1234 0.000010 getpid() = 1234"),
+            None
+        );
+
+        let mut parser = LogParser::new(
+            Box::new(Cursor::new(
+                concat!(
+                    "1234 0.000010 getpid() = 1234\n",
+                    "1234 0.000010 [ Process PID=1234 runs in 32 bit mode. ]\n",
+                    "1234 0.000010 getpid() = 1234\n",
+                )
+                .as_bytes(),
+            )),
+            None,
+        )
+        .unwrap();
+        let shared_modes = parser.shared_personality_modes();
+        assert_eq!(parser.by_ref().count(), 2);
+        assert_eq!(
+            *parser.personality_modes(),
+            BTreeSet::from(["32 bit".to_owned()])
+        );
+        assert_eq!(*shared_modes.borrow(), *parser.personality_modes());
+    }
+
+    #[test]
+    fn test_only_denied_failures() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let lines = concat!(
+            "1 0.000001 openat(AT_FDCWD, \"/home/foo/.config\", O_RDONLY) = -1 ENOENT (No such file or directory)\n",
+            "1 0.000001 bind(3, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"0.0.0.0\")}, 16) = -1 EPERM (Operation not permitted)\n",
+            "1 0.000001 openat(AT_FDCWD, \"/etc/shadow\", O_RDONLY) = -1 EPERM (Operation not permitted)\n",
+            "1 0.000001 read(4,  <unfinished ...>\n",
+            "2 0.000001 getpid() = 2\n",
+            "1 0.000001 <... read resumed>0x7ffc2f8e6a20, 4096) = ? ERESTARTSYS (To be restarted if SA_RESTART is set)\n",
+        );
+        let log_file = tempfile::NamedTempFile::new().unwrap();
+        let parse = |only_denied_failures: bool| {
+            let mut parser = LogParser::new(
+                Box::new(Cursor::new(lines.as_bytes())),
+                Some(log_file.path()),
+            )
+            .unwrap();
+            if only_denied_failures {
+                parser = parser.only_denied_failures();
+            }
+            let names: Vec<_> = parser.by_ref().map(|sc| sc.unwrap().name).collect();
+            (names, parser.unfinished_syscalls.len())
+        };
+
+        assert_eq!(
+            parse(false),
+            (
+                vec![
+                    "openat".to_owned(),
+                    "bind".to_owned(),
+                    "openat".to_owned(),
+                    "getpid".to_owned()
+                ],
+                1
+            )
+        );
+        log_file.as_file().set_len(0).unwrap();
+        assert_eq!(
+            parse(true),
+            (vec!["bind".to_owned(), "getpid".to_owned()], 0)
+        );
+        // Ignored lines are still logged
+        assert_eq!(fs::read_to_string(log_file.path()).unwrap(), lines);
+
+        assert_eq!(parse_failure_errno("1 0.000001 getpid() = 1234"), None);
+        assert_eq!(
+            parse_syscall_name("1 0.000001 openat(AT_FDCWD, \"/etc\", O_RDONLY) = 3"),
+            Some("openat")
+        );
+        assert_eq!(
+            parse_resumed_syscall("1 0.000001 <... read resumed>0x7ffc2f8e6a20, 4096) = 3"),
+            Some((1, "read"))
+        );
+    }
+
+    #[test]
+    fn test_normalize_line() {
+        for line in [
+            "1234 0.000010 getpid() = 1234",
+            "720313      0.000064 getpid() = 720313",
+            "1234 0.000010 read(3,  <unfinished ...>",
+        ] {
+            assert!(matches!(normalize_line(line), Cow::Borrowed(l) if l == line));
+        }
+
+        for (line, expected) in [
+            // -f -tt to a terminal
+            (
+                "[pid  1234] 12:34:56.123456 getpid() = 1234",
+                "1234 0.000000 getpid() = 1234",
+            ),
+            // -f -ttt to a file, with -T
+            (
+                "1234  1700000000.123456 getpid() = 1234 <0.000010>",
+                "1234 1700000000.123456 getpid() = 1234",
+            ),
+            // -f -t -r
+            (
+                "1234 12:34:56 0.000010 getpid() = 1234",
+                "1234 0.000010 getpid() = 1234",
+            ),
+            // -r only
+            (
+                "     0.000010 getpid() = 1234",
+                "0 0.000010 getpid() = 1234",
+            ),
+            // bare -f, initial process
+            ("getpid() = 1234", "0 0.000000 getpid() = 1234"),
+            // bare -f, child process
+            (
+                "[pid 5678] <... read resumed>\"\\x61\", 1) = 1 <0.000100>",
+                "5678 0.000000 <... read resumed>\"\\x61\", 1) = 1",
+            ),
+        ] {
+            assert_eq!(normalize_line(line), expected);
+        }
+    }
+
+    #[test]
+    fn test_strace_flags_fixtures() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let parse = |lines: &'static str| {
+            LogParser::new(Box::new(Cursor::new(lines.as_bytes())), None)
+                .unwrap()
+                .map(|sc| {
+                    let sc = sc.unwrap();
+                    (sc.pid, sc.name, sc.args.len())
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            (1234, "openat".to_owned(), 3),
+            (5678, "getpid".to_owned(), 0),
+            (1234, "read".to_owned(), 3),
+        ];
+
+        // strace -f -tt, to a terminal
+        assert_eq!(
+            parse(concat!(
+                "12:34:56.000001 openat(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\", O_RDONLY) = 3\n",
+                "12:34:56.000002 read(3,  <unfinished ...>\n",
+                "[pid  5678] 12:34:56.000003 getpid() = 5678\n",
+                "12:34:56.000004 <... read resumed>\"\\x61\", 1) = 1\n",
+                "[pid  5678] 12:34:56.000005 +++ exited with 0 +++\n",
+            )),
+            expected
+                .iter()
+                .map(|(p, n, a)| (if *p == 1234 { 0 } else { *p }, n.clone(), *a))
+                .collect::<Vec<_>>()
+        );
+
+        // strace -f -tt -T, to a file
+        assert_eq!(
+            parse(concat!(
+                "1234  12:34:56.000001 openat(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\", O_RDONLY) = 3 <0.000010>\n",
+                "1234  12:34:56.000002 read(3,  <unfinished ...>\n",
+                "5678  12:34:56.000003 getpid() = 5678 <0.000001>\n",
+                "1234  12:34:56.000004 <... read resumed>\"\\x61\", 1) = 1 <0.000002>\n",
+            )),
+            expected
+        );
+
+        // bare strace -f, to a file
+        assert_eq!(
+            parse(concat!(
+                "1234  openat(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\", O_RDONLY) = 3\n",
+                "1234  read(3,  <unfinished ...>\n",
+                "5678  getpid() = 5678\n",
+                "1234  <... read resumed>\"\\x61\", 1) = 1\n",
+            )),
+            expected
+        );
+    }
 }
 
 #[cfg(all(feature = "nightly", test))]
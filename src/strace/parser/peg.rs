@@ -295,6 +295,42 @@ impl TryFrom<Pair<'_, Rule>> for IntegerExpression {
     }
 }
 
+fn parse_args(args_pair: Pair<'_, Rule>) -> anyhow::Result<Vec<Expression>> {
+    let args_pair = pair_descend(args_pair, 1)?;
+    match args_pair.as_rule() {
+        Rule::unnamed_arguments => args_pair
+            .into_inner()
+            .map(|p| {
+                let p = pair_descend(p, 1)?;
+                match p.as_rule() {
+                    Rule::in_argument => pair_descend(p, 2)?.try_into(),
+                    Rule::in_out_argument => {
+                        // Only take the 'in' part, ignore the rest
+                        pair_descend(p, 2)?.try_into()
+                    }
+                    _ => anyhow::bail!("Unhandled pair: {p:?}"),
+                }
+            })
+            .collect(),
+        Rule::named_arguments => {
+            // Handle name arguments as a single struct
+            Ok(vec![Expression::Struct(
+                args_pair
+                    .into_inner()
+                    .map(|p| -> anyhow::Result<_> {
+                        let (n, v) = p
+                            .into_inner()
+                            .next_tuple()
+                            .ok_or_else(|| anyhow::anyhow!("Missing name arguments nodes"))?;
+                        Ok((n.as_str().to_owned(), pair_descend(v, 1)?.try_into()?))
+                    })
+                    .collect::<Result<_, _>>()?,
+            )])
+        }
+        _ => anyhow::bail!("Unhandled pair: {args_pair:?}"),
+    }
+}
+
 impl TryFrom<Pair<'_, Rule>> for Syscall {
     type Error = anyhow::Error;
 
@@ -317,42 +353,11 @@ impl TryFrom<Pair<'_, Rule>> for Syscall {
             .as_str()
             .to_owned();
 
-        let args_pair = subpairs
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing arguments node"))?;
-        let args_pair = pair_descend(args_pair, 1)?;
-        let args = match args_pair.as_rule() {
-            Rule::unnamed_arguments => args_pair
-                .into_inner()
-                .map(|p| {
-                    let p = pair_descend(p, 1)?;
-                    match p.as_rule() {
-                        Rule::in_argument => pair_descend(p, 2)?.try_into(),
-                        Rule::in_out_argument => {
-                            // Only take the 'in' part, ignore the rest
-                            pair_descend(p, 2)?.try_into()
-                        }
-                        _ => anyhow::bail!("Unhandled pair: {p:?}"),
-                    }
-                })
-                .collect::<Result<_, _>>()?,
-            Rule::named_arguments => {
-                // Handle name arguments as a single struct
-                vec![Expression::Struct(
-                    args_pair
-                        .into_inner()
-                        .map(|p| -> anyhow::Result<_> {
-                            let (n, v) = p
-                                .into_inner()
-                                .next_tuple()
-                                .ok_or_else(|| anyhow::anyhow!("Missing name arguments nodes"))?;
-                            Ok((n.as_str().to_owned(), pair_descend(v, 1)?.try_into()?))
-                        })
-                        .collect::<Result<_, _>>()?,
-                )]
-            }
-            _ => anyhow::bail!("Unhandled pair: {args_pair:?}"),
-        };
+        let args = parse_args(
+            subpairs
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing arguments node"))?,
+        )?;
 
         let ret_val_pair = pair_descend(
             subpairs
@@ -397,42 +402,11 @@ impl TryFrom<Pair<'_, Rule>> for SyscallStart {
             .as_str()
             .to_owned();
 
-        let args_pair = subpairs
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing arguments node"))?;
-        let args_pair = pair_descend(args_pair, 1)?;
-        let args = match args_pair.as_rule() {
-            Rule::unnamed_arguments => args_pair
-                .into_inner()
-                .map(|p| {
-                    let p = pair_descend(p, 1)?;
-                    match p.as_rule() {
-                        Rule::in_argument => pair_descend(p, 2)?.try_into(),
-                        Rule::in_out_argument => {
-                            // Only take the 'in' part, ignore the rest
-                            pair_descend(p, 2)?.try_into()
-                        }
-                        _ => anyhow::bail!("Unhandled pair: {p:?}"),
-                    }
-                })
-                .collect::<Result<_, _>>()?,
-            Rule::named_arguments => {
-                // Handle name arguments as a single struct
-                vec![Expression::Struct(
-                    args_pair
-                        .into_inner()
-                        .map(|p| -> anyhow::Result<_> {
-                            let (n, v) = p
-                                .into_inner()
-                                .next_tuple()
-                                .ok_or_else(|| anyhow::anyhow!("Missing name arguments nodes"))?;
-                            Ok((n.as_str().to_owned(), pair_descend(v, 1)?.try_into()?))
-                        })
-                        .collect::<Result<_, _>>()?,
-                )]
-            }
-            _ => anyhow::bail!("Unhandled pair: {args_pair:?}"),
-        };
+        let args = parse_args(
+            subpairs
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing arguments node"))?,
+        )?;
 
         Ok(Self {
             pid,
@@ -465,6 +439,12 @@ impl TryFrom<Pair<'_, Rule>> for SyscallEnd {
             .as_str()
             .to_owned();
 
+        let args = parse_args(
+            subpairs
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing arguments node"))?,
+        )?;
+
         let ret_val_pair = pair_descend(
             subpairs
                 .next()
@@ -481,6 +461,7 @@ impl TryFrom<Pair<'_, Rule>> for SyscallEnd {
             pid,
             rel_ts,
             name,
+            args,
             ret_val,
         })
     }
@@ -3,17 +3,106 @@
 use std::{
     fs::File,
     io::BufReader,
-    path::PathBuf,
-    process::{Child, Command, Stdio},
+    os::unix::process::CommandExt as _,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
 };
 
 use anyhow::Context as _;
 
 use crate::strace::parser::LogParser;
 
+/// Short strace flags changing the output format, or which processes or calls are traced, and that we set ourselves
+const RESERVED_SHORT_FLAGS: [char; 22] = [
+    'A', 'b', 'c', 'C', 'D', 'f', 'F', 'i', 'k', 'n', 'o', 'p', 'P', 'q', 'r', 't', 'T', 'x', 'X',
+    'y', 'z', 'Z',
+];
+/// Short strace flags taking a value, in the same argument or the next one
+const SHORT_FLAGS_WITH_VALUE: [char; 14] = [
+    'a', 'b', 'e', 'E', 'I', 'o', 'O', 'p', 'P', 's', 'S', 'u', 'U', 'X',
+];
+/// Long strace flags changing the output format, or which processes or calls are traced, and that we set ourselves
+const RESERVED_LONG_FLAGS: [&str; 22] = [
+    "attach",
+    "const-print-style",
+    "daemonize",
+    "decode-pids",
+    "detach-on",
+    "failed-only",
+    "follow-forks",
+    "instruction-pointer",
+    "output",
+    "output-append-mode",
+    "output-separately",
+    "relative-timestamps",
+    "stack-trace",
+    "strings-in-hex",
+    "successful-only",
+    "summary",
+    "summary-only",
+    "summary-wall-clock",
+    "syscall-number",
+    "syscall-times",
+    "timestamps",
+    "trace-path",
+];
+/// Strace qualifiers (`-e QUALIFIER=...` or `--QUALIFIER=...`) changing the output format, or which calls are
+/// reported
+const RESERVED_QUALIFIERS: [&str; 7] = [
+    "abbrev",
+    "decode-fds",
+    "quiet",
+    "raw",
+    "status",
+    "trace",
+    "verbose",
+];
+/// Alternative names of strace qualifiers, ie. `-e v=none` is the `verbose` qualifier, unrelated to the `-v` flag
+const QUALIFIER_ALIASES: [(&str, &str); 8] = [
+    ("a", "abbrev"),
+    ("decode-fd", "decode-fds"),
+    ("q", "quiet"),
+    ("silence", "quiet"),
+    ("silent", "quiet"),
+    ("t", "trace"),
+    ("v", "verbose"),
+    ("x", "raw"),
+];
+
+/// Handle to signal the process group of a traced command, which can be used from other threads
+#[derive(Clone)]
+pub struct TraceeGroup {
+    pid: nix::unistd::Pid,
+    reaped: Arc<Mutex<bool>>,
+}
+
+impl TraceeGroup {
+    pub(crate) fn new(pid: nix::unistd::Pid, reaped: Arc<Mutex<bool>>) -> Self {
+        Self { pid, reaped }
+    }
+
+    /// Send signal `sig` to the traced command and its descendants, unless the command was already reaped
+    pub fn signal(&self, sig: nix::sys::signal::Signal) {
+        // Hold the lock while signaling, so that the pid can not be reaped and reused meanwhile
+        #[expect(clippy::unwrap_used)]
+        let reaped = self.reaped.lock().unwrap();
+        if *reaped {
+            return;
+        }
+        // TODO APPROXIMATION
+        // Processes that moved to another process group or session (ie. daemons) are not signaled
+        if let Err(err) = nix::sys::signal::killpg(self.pid, sig) {
+            log::debug!("Failed to send {sig} to profiled program: {err}");
+        }
+    }
+}
+
 pub(crate) struct Strace {
     /// Strace process
     process: Child,
+    /// Traced command was reaped, so its pid may have been reused
+    reaped: Arc<Mutex<bool>>,
     /// Temp dir for pipe location
     pipe_dir: tempfile::TempDir,
     /// Strace log mirror path
@@ -21,7 +110,14 @@ pub(crate) struct Strace {
 }
 
 impl Strace {
-    pub(crate) fn run(command: &[&str], log_path: Option<PathBuf>) -> anyhow::Result<Self> {
+    pub(crate) fn run(
+        command: &[&str],
+        log_path: Option<PathBuf>,
+        strace_path: &Path,
+        extra_args: &[String],
+    ) -> anyhow::Result<Self> {
+        Self::check_extra_args(extra_args)?;
+
         // Create named pipe
         let pipe_dir = tempfile::tempdir()?;
         let pipe_path = Self::pipe_path(&pipe_dir);
@@ -30,43 +126,130 @@ impl Strace {
 
         // Start process
         // TODO setuid/setgid execution will be broken unless strace runs as root
-        let child = Command::new("strace")
-            .args([
-                "--daemonize=grandchild",
-                "--relative-timestamps",
-                "--follow-forks",
-                // TODO APPROXIMATION this can make us miss interesting stuff like open with O_EXCL|O_CREAT which
-                // returns -1 because file exists
-                "--successful-only",
-                "--strings-in-hex=all",
-                // Despite this, some structs are still truncated
-                "-e",
-                "abbrev=none",
-                // "-e",
-                // "read=all",
-                // "-e",
-                // "write=all",
-                "-e",
-                "decode-fds=path",
-                "--output-append-mode",
-                "-o",
-                #[expect(clippy::unwrap_used)]
-                pipe_path.to_str().unwrap(),
-                "--",
-            ])
+        #[expect(clippy::unwrap_used)]
+        let args = Self::args(pipe_path.to_str().unwrap(), extra_args);
+        let child = Command::new(strace_path)
+            .args(args)
+            .arg("--")
             .args(command)
             .env("LANG", "C") // avoids locale side effects
             .stdin(Stdio::null())
+            // Traced command leads its own process group, strace leaves it
+            .process_group(0)
             .spawn()
-            .context("Failed to start strace")?;
+            .with_context(|| format!("Failed to start strace from {strace_path:?}"))?;
 
         Ok(Self {
             process: child,
+            reaped: Arc::new(Mutex::new(false)),
             pipe_dir,
             log_path,
         })
     }
 
+    /// Strace arguments, the ones we need followed by the user supplied ones
+    fn args<'a>(pipe_path: &'a str, extra_args: &'a [String]) -> Vec<&'a str> {
+        [
+            "--daemonize=pgroup",
+            "--relative-timestamps",
+            "--follow-forks",
+            // Failed syscalls are filtered out when parsing, except the ones denied for lack of privileges
+            // TODO APPROXIMATION this can make us miss interesting stuff like open with O_EXCL|O_CREAT which
+            // returns -1 because file exists
+            "-e",
+            "status=successful,failed",
+            "--strings-in-hex=all",
+            // Despite this, some structs are still truncated
+            "-e",
+            "abbrev=none",
+            // "-e",
+            // "read=all",
+            // "-e",
+            // "write=all",
+            "-e",
+            "decode-fds=path",
+            "--output-append-mode",
+            "-o",
+            pipe_path,
+        ]
+        .into_iter()
+        .chain(extra_args.iter().map(String::as_str))
+        .collect()
+    }
+
+    /// Check user supplied strace arguments do not change the output format, or the processes or calls we trace
+    fn check_extra_args(extra_args: &[String]) -> anyhow::Result<()> {
+        let mut args = extra_args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            let reserved = if let Some(long_flag) = arg.strip_prefix("--") {
+                let name = long_flag.split_once('=').map_or(long_flag, |(n, _)| n);
+                // Long flags can be abbreviated to any unambiguous prefix
+                !name.is_empty()
+                    && RESERVED_LONG_FLAGS
+                        .iter()
+                        .chain(&RESERVED_QUALIFIERS)
+                        .any(|f| f.starts_with(name))
+            } else if let Some(short_flags) = arg.strip_prefix('-') {
+                // Several short flags can be combined in a single argument, ie. `-vf`
+                let mut reserved = false;
+                for (i, flag) in short_flags.char_indices() {
+                    if RESERVED_SHORT_FLAGS.contains(&flag) {
+                        reserved = true;
+                        break;
+                    }
+                    if SHORT_FLAGS_WITH_VALUE.contains(&flag) {
+                        let value = match short_flags.get(i + flag.len_utf8()..) {
+                            Some(value) if !value.is_empty() => value,
+                            _ => args.next().unwrap_or_default(),
+                        };
+                        reserved = flag == 'e' && Self::is_reserved_qualifier(value);
+                        break;
+                    }
+                }
+                reserved
+            } else {
+                false
+            };
+            anyhow::ensure!(
+                !reserved,
+                "Strace argument {arg:?} conflicts with the ones needed for profiling"
+            );
+        }
+        Ok(())
+    }
+
+    /// Check if a strace qualifier expression (ie. `abbrev=none` or `openat`) uses a reserved qualifier
+    fn is_reserved_qualifier(expr: &str) -> bool {
+        // Qualifier defaults to `trace` if not set
+        let qualifier = expr.split_once('=').map_or("trace", |(q, _)| q);
+        let qualifier = QUALIFIER_ALIASES
+            .iter()
+            .find_map(|(alias, name)| (*alias == qualifier).then_some(*name))
+            .unwrap_or(qualifier);
+        RESERVED_QUALIFIERS.contains(&qualifier)
+    }
+
+    /// Pid of the traced command, which remains our direct child because strace daemonizes, and leads its process
+    /// group
+    #[expect(clippy::cast_possible_wrap)]
+    pub(crate) fn tracee_pid(&self) -> nix::unistd::Pid {
+        nix::unistd::Pid::from_raw(self.process.id() as i32)
+    }
+
+    /// Exit status of the traced command, if it has exited
+    pub(crate) fn tracee_status(&mut self) -> anyhow::Result<Option<ExitStatus>> {
+        #[expect(clippy::unwrap_used)]
+        let mut reaped = self.reaped.lock().unwrap();
+        let status = self.process.try_wait()?;
+        *reaped = status.is_some();
+        Ok(status)
+    }
+
+    /// Handle to signal the process group of the traced command
+    pub(crate) fn tracee_group(&self) -> TraceeGroup {
+        TraceeGroup::new(self.tracee_pid(), Arc::clone(&self.reaped))
+    }
+
     fn pipe_path(dir: &tempfile::TempDir) -> PathBuf {
         dir.path().join("strace.pipe")
     }
@@ -74,7 +257,7 @@ impl Strace {
     pub(crate) fn log_lines(&self) -> anyhow::Result<LogParser> {
         let pipe_path = Self::pipe_path(&self.pipe_dir);
         let reader = BufReader::new(File::open(pipe_path)?);
-        LogParser::new(Box::new(reader), self.log_path.as_deref())
+        Ok(LogParser::new(Box::new(reader), self.log_path.as_deref())?.only_denied_failures())
     }
 }
 
@@ -84,3 +267,89 @@ impl Drop for Strace {
         let _ = self.process.wait();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let extra_args = vec![
+            "-s".to_owned(),
+            "4096".to_owned(),
+            "-etrace=%file".to_owned(),
+        ];
+        let args = Strace::args("/tmp/pipe", &extra_args);
+        assert_eq!(
+            &args[args.len() - 5..],
+            &["-o", "/tmp/pipe", "-s", "4096", "-etrace=%file"]
+        );
+        assert_eq!(
+            Strace::args("/tmp/pipe", &[]).len() + extra_args.len(),
+            args.len()
+        );
+    }
+
+    #[test]
+    fn test_check_extra_args() {
+        let check = |args: &[&str]| {
+            Strace::check_extra_args(&args.iter().map(|a| (*a).to_owned()).collect::<Vec<_>>())
+        };
+
+        assert!(check(&[]).is_ok());
+        assert!(check(&["-s", "4096", "-e", "signal=none"]).is_ok());
+        assert!(check(&["--string-limit=4096", "-esignal=none"]).is_ok());
+        assert!(check(&["-v"]).is_ok());
+        assert!(check(&["-vs", "4096"]).is_ok());
+        assert!(check(&["-s4096", "-v"]).is_ok());
+        assert!(check(&["-s", "-f"]).is_ok());
+        assert!(check(&["--no-abbrev"]).is_ok());
+
+        assert!(check(&["-f"]).is_err());
+        assert!(check(&["-ff"]).is_err());
+        assert!(check(&["-yy"]).is_err());
+        assert!(check(&["-o", "/tmp/log"]).is_err());
+        assert!(check(&["--output=/tmp/log"]).is_err());
+        assert!(check(&["--follow-forks"]).is_err());
+        assert!(check(&["-e", "abbrev=all"]).is_err());
+        assert!(check(&["-edecode-fds=none"]).is_err());
+        assert!(check(&["--decode-fds=none"]).is_err());
+        assert!(check(&["-e", "status=failed"]).is_err());
+        assert!(check(&["-vf"]).is_err());
+        assert!(check(&["-s4096f"]).is_ok());
+        assert!(check(&["-vsf", "4096"]).is_ok());
+        assert!(check(&["-ve", "abbrev=all"]).is_err());
+        assert!(check(&["-e", "v=none"]).is_err());
+        assert!(check(&["-e", "a=all"]).is_err());
+        assert!(check(&["-e", "trace=%file,%network"]).is_err());
+        assert!(check(&["-etrace=openat"]).is_err());
+        assert!(check(&["-e", "openat"]).is_err());
+        assert!(check(&["--trace=openat"]).is_err());
+        assert!(check(&["-P", "/etc"]).is_err());
+        assert!(check(&["--follow"]).is_err());
+    }
+
+    #[test]
+    fn test_tracee_group_signal() {
+        let mut child = Command::new("sleep")
+            .arg("60")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        #[expect(clippy::cast_possible_wrap)]
+        let group = TraceeGroup {
+            pid: nix::unistd::Pid::from_raw(child.id() as i32),
+            reaped: Arc::new(Mutex::new(false)),
+        };
+        group.signal(nix::sys::signal::Signal::SIGTERM);
+        let status = child.wait().unwrap();
+        assert_eq!(
+            std::os::unix::process::ExitStatusExt::signal(&status),
+            Some(nix::sys::signal::Signal::SIGTERM as i32)
+        );
+
+        // Reaped pid may have been reused
+        *group.reaped.lock().unwrap() = true;
+        group.signal(nix::sys::signal::Signal::SIGTERM);
+    }
+}
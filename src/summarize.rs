@@ -1,18 +1,27 @@
 //! Summarize program syscalls into higher level action
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
+    env,
     ffi::OsStr,
     fmt::{self, Display},
+    fs,
+    hash::{Hash, Hasher},
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     num::NonZeroU16,
     ops::{Add, RangeInclusive, Sub},
-    os::unix::ffi::OsStrExt,
+    os::unix::{ffi::OsStrExt, fs::FileTypeExt},
     path::{Path, PathBuf},
-    slice,
+    slice, str,
     sync::LazyLock,
+    time::{Duration, Instant},
 };
 
+use strum::IntoEnumIterator;
+
 use crate::{
+    error::ShhError,
     strace::{
         BufferExpression, BufferType, Expression, IntegerExpression, IntegerExpressionValue,
         Syscall,
@@ -30,6 +39,9 @@ pub(crate) enum ProgramAction {
     Write(PathBuf),
     /// Path was created
     Create(PathBuf),
+    /// Path in a temporary directory was created with a well-known name, or bound as a socket,
+    /// so it is likely shared with other processes
+    CreateShared(PathBuf),
     /// Network (socket) activity
     NetworkActivity(NetworkActivity),
     /// Memory mapping with write and execute bits
@@ -38,29 +50,210 @@ pub(crate) enum ProgramAction {
     SetRealtimeScheduler,
     /// Inhibit suspend
     Wakeup,
+    /// Get random bytes with `getrandom()`, unlike reading `/dev/urandom` or `/dev/random` this needs no device node
+    GetRandom,
     /// Create special files
     MknodSpecial,
+    /// Create SysV or POSIX IPC objects, which may outlive the program
+    CreateIpcObject,
+    /// Create files, or change their mode, with the setuid or setgid bits
+    SetSuidSgid,
+    /// Create files or directories, or change their mode, with permissions for group or other users
+    SetGroupOtherPermissions,
+    /// Change execution domain (personality)
+    SetPersonality,
+    /// Change file owner, or process credentials, to users or groups other than root and the program's own ones
+    UseForeignIds,
     /// Set privileged timer alarm
     SetAlarm,
+    /// Set system clock, or its adjustment parameters
+    SetClock,
+    /// Set system host or domain name
+    SetHostname,
+    /// Mount, unmount or move filesystems, or change the root mount
+    ManageMounts,
+    /// Load or unload kernel modules
+    ManageKernelModules,
+    /// Use a capability, ie. granted by the unit with `AmbientCapabilities=`
+    UseCapability(String),
+    /// Create a new namespace, or join an existing one
+    CreateNamespace(NamespaceType),
+    /// Device node was opened
+    DeviceAccess(SetSpecifier<DeviceAccess>),
+    /// Files were accessed on filesystems of these types
+    FileSystemAccess(SetSpecifier<String>),
+    /// Syscalls were made with the ABI of these non native architectures (systemd names, ie. `x86`)
+    NonNativeSyscalls(SetSpecifier<String>),
     /// Names of the syscalls made by the program
     Syscalls(HashSet<String>),
 }
 
+// Can not be derived because of `HashSet`, syscall names are not hashed but the result is still consistent with `Eq`
+impl Hash for ProgramAction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Self::Read(path)
+            | Self::Write(path)
+            | Self::Create(path)
+            | Self::CreateShared(path) => {
+                path.hash(state);
+            }
+            Self::NetworkActivity(activity) => activity.hash(state),
+            Self::UseCapability(cap) => cap.hash(state),
+            Self::CreateNamespace(ns) => ns.hash(state),
+            Self::DeviceAccess(devices) => devices.hash(state),
+            Self::FileSystemAccess(fs_types) => fs_types.hash(state),
+            Self::NonNativeSyscalls(archs) => archs.hash(state),
+            Self::WriteExecuteMemoryMapping
+            | Self::SetRealtimeScheduler
+            | Self::Wakeup
+            | Self::GetRandom
+            | Self::MknodSpecial
+            | Self::CreateIpcObject
+            | Self::SetSuidSgid
+            | Self::SetGroupOtherPermissions
+            | Self::SetPersonality
+            | Self::UseForeignIds
+            | Self::SetAlarm
+            | Self::SetClock
+            | Self::SetHostname
+            | Self::ManageMounts
+            | Self::ManageKernelModules
+            | Self::Syscalls(_) => {}
+        }
+    }
+}
+
+/// Group of actions, when listing them
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, strum::Display)]
+pub(crate) enum ActionGroup {
+    #[strum(serialize = "Paths read")]
+    Read,
+    #[strum(serialize = "Paths written")]
+    Write,
+    #[strum(serialize = "Paths created")]
+    Create,
+    #[strum(serialize = "Devices")]
+    Device,
+    #[strum(serialize = "File system types")]
+    FileSystem,
+    #[strum(serialize = "Network activity")]
+    Network,
+    #[strum(serialize = "Namespaces")]
+    Namespace,
+    #[strum(serialize = "Capabilities")]
+    Capability,
+    #[strum(serialize = "Syscall architectures")]
+    Architecture,
+    #[strum(serialize = "Syscalls")]
+    Syscall,
+    #[strum(serialize = "Other")]
+    Other,
+}
+
+impl ProgramAction {
+    /// Group of the action, and human readable descriptions of what it covers
+    pub(crate) fn explain(&self) -> (ActionGroup, Vec<String>) {
+        match self {
+            Self::Read(path) => (ActionGroup::Read, vec![path.display().to_string()]),
+            Self::Write(path) => (ActionGroup::Write, vec![path.display().to_string()]),
+            Self::Create(path) => (ActionGroup::Create, vec![path.display().to_string()]),
+            Self::CreateShared(path) => (
+                ActionGroup::Create,
+                vec![format!("{} (shared)", path.display())],
+            ),
+            Self::DeviceAccess(accesses) => (
+                ActionGroup::Device,
+                match accesses {
+                    SetSpecifier::One(_) | SetSpecifier::Some(_) => accesses
+                        .elements()
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|a| {
+                            format!(
+                                "{} ({:?}, {})",
+                                a.path.display(),
+                                a.type_,
+                                if a.write { "read-write" } else { "read" }
+                            )
+                        })
+                        .collect(),
+                    _ => vec![format!("{accesses:?}")],
+                },
+            ),
+            Self::FileSystemAccess(fs_types) => {
+                (ActionGroup::FileSystem, vec![format!("{fs_types:?}")])
+            }
+            Self::NetworkActivity(activity) => (
+                ActionGroup::Network,
+                vec![format!(
+                    "family {:?}, protocol {:?}, kind {:?}, local port {:?}, address {:?}",
+                    activity.af,
+                    activity.proto,
+                    activity.kind,
+                    activity.local_port,
+                    activity.address
+                )],
+            ),
+            Self::CreateNamespace(ns) => (ActionGroup::Namespace, vec![ns.to_string()]),
+            Self::UseCapability(cap) => (ActionGroup::Capability, vec![cap.clone()]),
+            Self::NonNativeSyscalls(archs) => {
+                (ActionGroup::Architecture, vec![format!("{archs:?}")])
+            }
+            Self::Syscalls(syscalls) => (ActionGroup::Syscall, syscalls.iter().cloned().collect()),
+            Self::WriteExecuteMemoryMapping
+            | Self::SetRealtimeScheduler
+            | Self::Wakeup
+            | Self::GetRandom
+            | Self::MknodSpecial
+            | Self::CreateIpcObject
+            | Self::SetSuidSgid
+            | Self::SetGroupOtherPermissions
+            | Self::SetPersonality
+            | Self::UseForeignIds
+            | Self::SetAlarm
+            | Self::SetClock
+            | Self::SetHostname
+            | Self::ManageMounts
+            | Self::ManageKernelModules => (ActionGroup::Other, vec![format!("{self:?}")]),
+        }
+    }
+}
+
 /// Network (socket) activity
-#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
 pub(crate) struct NetworkActivity {
     pub af: SetSpecifier<SocketFamily>,
     pub proto: SetSpecifier<SocketProtocol>,
     pub kind: SetSpecifier<NetworkActivityKind>,
     pub local_port: CountableSetSpecifier<NetworkPort>,
+    pub address: SetSpecifier<IpAddr>,
+}
+
+/// Device node access
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+pub(crate) struct DeviceAccess {
+    pub path: PathBuf,
+    pub type_: DeviceType,
+    pub write: bool,
+}
+
+/// Device node type
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+pub(crate) enum DeviceType {
+    Char,
+    Block,
 }
 
 /// Quantify something that is done or denied
-#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
 pub(crate) enum SetSpecifier<T> {
     None,
     One(T),
     Some(Vec<T>),
+    // Set is not countable, so this always contains an infinite number of elements
+    AllExcept(Vec<T>),
     All,
 }
 
@@ -70,6 +263,7 @@ impl<T: Eq + Clone> SetSpecifier<T> {
             Self::None => false,
             Self::One(e) => e == needle,
             Self::Some(es) => es.contains(needle),
+            Self::AllExcept(excs) => !excs.contains(needle),
             Self::All => true,
         }
     }
@@ -79,16 +273,21 @@ impl<T: Eq + Clone> SetSpecifier<T> {
             Self::None => false,
             Self::One(e) => other.contains_one(e),
             Self::Some(es) => es.iter().any(|e| other.contains_one(e)),
-            Self::All => !matches!(other, Self::None),
+            Self::AllExcept(_) | Self::All => match other {
+                Self::None => false,
+                Self::One(_) | Self::Some(_) => other.intersects(self),
+                Self::AllExcept(_) | Self::All => true,
+            },
         }
     }
 
-    pub(crate) fn elements(&self) -> &[T] {
+    /// Elements of the set, or `None` if it is not finite
+    pub(crate) fn elements(&self) -> Option<&[T]> {
         match self {
-            SetSpecifier::None => &[],
-            SetSpecifier::One(e) => slice::from_ref(e),
-            SetSpecifier::Some(es) => es.as_slice(),
-            SetSpecifier::All => unimplemented!(),
+            SetSpecifier::None => Some(&[]),
+            SetSpecifier::One(e) => Some(slice::from_ref(e)),
+            SetSpecifier::Some(es) => Some(es.as_slice()),
+            SetSpecifier::AllExcept(_) | SetSpecifier::All => None,
         }
     }
 }
@@ -104,7 +303,7 @@ pub(crate) trait ValueCounted {
 }
 
 /// Quantify something that is done or denied
-#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
 pub(crate) enum CountableSetSpecifier<T> {
     None,
     One(T),
@@ -209,17 +408,56 @@ impl<T: Eq + Ord + Clone + Display + ValueCounted + Sub<Output = T> + Add<Output
 }
 
 /// Socket activity
-#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
 pub(crate) enum NetworkActivityKind {
     SocketCreation,
     Bind,
-    // TODO
-    // Connect,
-    // Send,
-    // Recv,
+    Connect,
+    Send,
+    Recv,
+}
+
+/// Namespace type, as named by systemd
+#[derive(
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    strum::EnumIter,
+    strum::Display,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum NamespaceType {
+    Cgroup,
+    Ipc,
+    Net,
+    Mnt,
+    Pid,
+    User,
+    Uts,
+}
+
+impl NamespaceType {
+    /// Flag used by clone/unshare/setns to select this namespace type
+    fn clone_flag(&self) -> &'static str {
+        match self {
+            Self::Cgroup => "CLONE_NEWCGROUP",
+            Self::Ipc => "CLONE_NEWIPC",
+            Self::Net => "CLONE_NEWNET",
+            Self::Mnt => "CLONE_NEWNS",
+            Self::Pid => "CLONE_NEWPID",
+            Self::User => "CLONE_NEWUSER",
+            Self::Uts => "CLONE_NEWUTS",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize,
+)]
 pub(crate) struct NetworkPort(NonZeroU16);
 
 impl ValueCounted for NetworkPort {
@@ -262,6 +500,12 @@ impl Add<NetworkPort> for NetworkPort {
     }
 }
 
+impl From<NonZeroU16> for NetworkPort {
+    fn from(port: NonZeroU16) -> Self {
+        Self(port)
+    }
+}
+
 impl Display for NetworkPort {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -271,12 +515,19 @@ impl Display for NetworkPort {
 /// Meta structure to group syscalls that have similar summary handling
 /// and store argument indexes
 enum SyscallInfo {
+    AccessFd {
+        fd_idx: usize,
+        write: bool,
+    },
     Mknod {
         mode_idx: usize,
     },
     Mmap {
         prot_idx: usize,
     },
+    Mprotect {
+        prot_idx: usize,
+    },
     Network {
         sockaddr_idx: usize,
     },
@@ -292,6 +543,10 @@ enum SyscallInfo {
         path_dst_idx: usize,
         flags_idx: Option<usize>,
     },
+    SetIds {
+        uid_idxs: &'static [usize],
+        gid_idxs: &'static [usize],
+    },
     SetScheduler,
     Socket,
     StatFd {
@@ -311,22 +566,106 @@ enum SyscallInfo {
 //
 static SYSCALL_MAP: LazyLock<HashMap<&'static str, SyscallInfo>> = LazyLock::new(|| {
     HashMap::from([
+        // read or write fd
+        (
+            "read",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: false,
+            },
+        ),
+        (
+            "pread64",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: false,
+            },
+        ),
+        (
+            "readv",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: false,
+            },
+        ),
+        (
+            "preadv",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: false,
+            },
+        ),
+        (
+            "preadv2",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: false,
+            },
+        ),
+        (
+            "write",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: true,
+            },
+        ),
+        (
+            "pwrite64",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: true,
+            },
+        ),
+        (
+            "writev",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: true,
+            },
+        ),
+        (
+            "pwritev",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: true,
+            },
+        ),
+        (
+            "pwritev2",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: true,
+            },
+        ),
+        (
+            "ftruncate",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: true,
+            },
+        ),
+        (
+            "fallocate",
+            SyscallInfo::AccessFd {
+                fd_idx: 0,
+                write: true,
+            },
+        ),
         // mknod
         ("mknod", SyscallInfo::Mknod { mode_idx: 1 }),
         ("mknodat", SyscallInfo::Mknod { mode_idx: 2 }),
         // mmap
         ("mmap", SyscallInfo::Mmap { prot_idx: 2 }),
         ("mmap2", SyscallInfo::Mmap { prot_idx: 2 }),
-        ("shmat", SyscallInfo::Mmap { prot_idx: 2 }),
-        ("mprotect", SyscallInfo::Mmap { prot_idx: 2 }),
-        ("pkey_mprotect", SyscallInfo::Mmap { prot_idx: 2 }),
+        ("mprotect", SyscallInfo::Mprotect { prot_idx: 2 }),
+        ("pkey_mprotect", SyscallInfo::Mprotect { prot_idx: 2 }),
         // network
         ("connect", SyscallInfo::Network { sockaddr_idx: 1 }),
         ("bind", SyscallInfo::Network { sockaddr_idx: 1 }),
         ("recvfrom", SyscallInfo::Network { sockaddr_idx: 4 }),
+        ("recvmsg", SyscallInfo::Network { sockaddr_idx: 1 }),
+        ("sendmsg", SyscallInfo::Network { sockaddr_idx: 1 }),
         ("sendto", SyscallInfo::Network { sockaddr_idx: 4 }),
-        // TODO recvmsg/sendmsg
-
         // open
         (
             "open",
@@ -344,6 +683,14 @@ static SYSCALL_MAP: LazyLock<HashMap<&'static str, SyscallInfo>> = LazyLock::new
                 flags_idx: 2,
             },
         ),
+        (
+            "openat2",
+            SyscallInfo::Open {
+                relfd_idx: Some(0),
+                path_idx: 1,
+                flags_idx: 2,
+            },
+        ),
         // rename
         (
             "rename",
@@ -375,7 +722,184 @@ static SYSCALL_MAP: LazyLock<HashMap<&'static str, SyscallInfo>> = LazyLock::new
                 flags_idx: Some(4),
             },
         ),
+        // set ids
+        (
+            "chown",
+            SyscallInfo::SetIds {
+                uid_idxs: &[1],
+                gid_idxs: &[2],
+            },
+        ),
+        (
+            "chown32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[1],
+                gid_idxs: &[2],
+            },
+        ),
+        (
+            "fchown",
+            SyscallInfo::SetIds {
+                uid_idxs: &[1],
+                gid_idxs: &[2],
+            },
+        ),
+        (
+            "fchown32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[1],
+                gid_idxs: &[2],
+            },
+        ),
+        (
+            "fchownat",
+            SyscallInfo::SetIds {
+                uid_idxs: &[2],
+                gid_idxs: &[3],
+            },
+        ),
+        (
+            "lchown",
+            SyscallInfo::SetIds {
+                uid_idxs: &[1],
+                gid_idxs: &[2],
+            },
+        ),
+        (
+            "lchown32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[1],
+                gid_idxs: &[2],
+            },
+        ),
+        (
+            "setfsgid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0],
+            },
+        ),
+        (
+            "setfsgid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0],
+            },
+        ),
+        (
+            "setfsuid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0],
+                gid_idxs: &[],
+            },
+        ),
+        (
+            "setfsuid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0],
+                gid_idxs: &[],
+            },
+        ),
+        (
+            "setgid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0],
+            },
+        ),
+        (
+            "setgid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0],
+            },
+        ),
+        (
+            "setgroups",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[1],
+            },
+        ),
+        (
+            "setgroups32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[1],
+            },
+        ),
+        (
+            "setregid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0, 1],
+            },
+        ),
+        (
+            "setregid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0, 1],
+            },
+        ),
+        (
+            "setresgid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0, 1, 2],
+            },
+        ),
+        (
+            "setresgid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[],
+                gid_idxs: &[0, 1, 2],
+            },
+        ),
+        (
+            "setresuid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0, 1, 2],
+                gid_idxs: &[],
+            },
+        ),
+        (
+            "setresuid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0, 1, 2],
+                gid_idxs: &[],
+            },
+        ),
+        (
+            "setreuid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0, 1],
+                gid_idxs: &[],
+            },
+        ),
+        (
+            "setreuid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0, 1],
+                gid_idxs: &[],
+            },
+        ),
+        (
+            "setuid",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0],
+                gid_idxs: &[],
+            },
+        ),
+        (
+            "setuid32",
+            SyscallInfo::SetIds {
+                uid_idxs: &[0],
+                gid_idxs: &[],
+            },
+        ),
         // set scheduler
+        ("sched_setattr", SyscallInfo::SetScheduler),
         ("sched_setscheduler", SyscallInfo::SetScheduler),
         // socket
         ("socket", SyscallInfo::Socket),
@@ -404,118 +928,729 @@ static SYSCALL_MAP: LazyLock<HashMap<&'static str, SyscallInfo>> = LazyLock::new
                 path_idx: 1,
             },
         ),
+        (
+            "statx",
+            SyscallInfo::StatPath {
+                relfd_idx: Some(0),
+                path_idx: 1,
+            },
+        ),
     ])
 });
 
 /// Resolve relative path if possible, and normalize it
-fn resolve_path(path: &Path, relfd_idx: Option<usize>, syscall: &Syscall) -> Option<PathBuf> {
+/// `cwd` is the current directory of the calling process, if known
+fn resolve_path(
+    path: &Path,
+    relfd_idx: Option<usize>,
+    syscall: &Syscall,
+    cwd: Option<&Path>,
+    canonicalize: bool,
+) -> Option<PathBuf> {
     let path = if path.is_relative() {
-        let metadata = relfd_idx
-            .and_then(|idx| syscall.args.get(idx))
-            .and_then(|a| a.metadata());
-        if let Some(metadata) = metadata {
+        let relfd = relfd_idx.and_then(|idx| syscall.args.get(idx));
+        if let Some(metadata) = relfd.and_then(Expression::metadata) {
             if is_fd_pseudo_path(metadata) {
                 return None;
             }
             let rel_path = PathBuf::from(OsStr::from_bytes(metadata));
             rel_path.join(path)
+        } else if relfd.is_none_or(is_at_fdcwd) {
+            let Some(cwd) = cwd else {
+                log::warn!(
+                    "Current directory of process {} is unknown, ignoring relative path {path:?} of {}",
+                    syscall.pid,
+                    syscall.name
+                );
+                return None;
+            };
+            cwd.join(path)
         } else {
             return None;
         }
     } else {
         path.to_path_buf()
     };
+    if let Some(path) = proc_self_path(&path, syscall.pid) {
+        // Don't canonicalize, it would resolve to our own process
+        return Some(path);
+    }
+    if !canonicalize {
+        // Host filesystem paths may not match those seen by the program
+        return Some(path);
+    }
     // TODO APPROXIMATION
     // canonicalize relies on the FS state at profiling time which may have changed
     // and may follow links, therefore lead to different filesystem actions
     Some(path.canonicalize().unwrap_or(path))
 }
 
-#[expect(clippy::unwrap_used)]
-static FD_PSEUDO_PATH_REGEX: LazyLock<regex::bytes::Regex> =
-    LazyLock::new(|| regex::bytes::Regex::new(r"^[a-z]+:\[[0-9a-z]+\]/?$").unwrap());
+/// Current directory of a process, if known
+fn process_cwd<'a>(
+    cwds: &'a HashMap<u32, Option<PathBuf>>,
+    pid: u32,
+    initial_cwd: Option<&'a Path>,
+) -> Option<&'a Path> {
+    match cwds.get(&pid) {
+        Some(cwd) => cwd.as_deref(),
+        None => initial_cwd,
+    }
+}
 
-fn is_fd_pseudo_path(path: &[u8]) -> bool {
-    FD_PSEUDO_PATH_REGEX.is_match(path)
+/// Whether a directory fd argument is `AT_FDCWD`, ie. relative to the current directory
+fn is_at_fdcwd(arg: &Expression) -> bool {
+    matches!(arg, Expression::Integer(IntegerExpression {
+        value: IntegerExpressionValue::NamedConst(name),
+        ..
+    }) if name == "AT_FDCWD")
 }
 
-/// Extract path for socket address structure if it's a non abstract one
-fn socket_address_uds_path(
-    members: &HashMap<String, Expression>,
-    syscall: &Syscall,
-) -> Option<PathBuf> {
-    if let Some(Expression::Buffer(BufferExpression {
-        value: b,
-        type_: BufferType::Unknown,
-    })) = members.get("sun_path")
-    {
-        resolve_path(&PathBuf::from(OsStr::from_bytes(b)), None, syscall)
+/// If path is in the calling process own procfs directory, rewrite it under `/proc/self`
+fn proc_self_path(path: &Path, pid: u32) -> Option<PathBuf> {
+    let mut components = path.strip_prefix("/proc/").ok()?.components();
+    let dir = components.next()?.as_os_str();
+    if (dir != "self") && (dir != "thread-self") && (dir.to_str()? != pid.to_string()) {
+        return None;
+    }
+    let rel_path = components.as_path();
+    let self_dir = Path::new("/proc/self");
+    Some(if rel_path.as_os_str().is_empty() {
+        self_dir.to_path_buf()
     } else {
-        None
+        // TODO APPROXIMATION
+        // thread-self is the task directory of the thread, which we don't distinguish from its process
+        self_dir.join(rel_path)
+    })
+}
+
+/// Well known device nodes, used when the node can not be stat'ed
+const KNOWN_CHAR_DEVICES: [&str; 11] = [
+    "/dev/console",
+    "/dev/full",
+    "/dev/fuse",
+    "/dev/kvm",
+    "/dev/net/tun",
+    "/dev/null",
+    "/dev/ptmx",
+    "/dev/random",
+    "/dev/tty",
+    "/dev/urandom",
+    "/dev/zero",
+];
+
+/// Directories under `/dev` that contain regular files, and not device nodes
+const DEV_NON_DEVICE_DIRS: [&str; 3] = ["/dev/hugepages/", "/dev/mqueue/", "/dev/shm/"];
+
+/// Get device type for path if it is a device node
+fn device_type(path: &Path) -> Option<DeviceType> {
+    if !path.starts_with("/dev/") || DEV_NON_DEVICE_DIRS.iter().any(|d| path.starts_with(d)) {
+        return None;
+    }
+    match path.metadata() {
+        Ok(metadata) => {
+            let file_type = metadata.file_type();
+            if file_type.is_char_device() {
+                Some(DeviceType::Char)
+            } else if file_type.is_block_device() {
+                Some(DeviceType::Block)
+            } else {
+                None
+            }
+        }
+        Err(err) => {
+            // TODO APPROXIMATION
+            // node does not exist anymore or can not be accessed, assume this is a character device,
+            // which is by far the most common case
+            if !KNOWN_CHAR_DEVICES.iter().any(|d| path == Path::new(d)) {
+                log::debug!("Unable to stat {path:?} ({err}), assuming character device");
+            }
+            Some(DeviceType::Char)
+        }
     }
 }
 
-#[expect(clippy::too_many_lines)]
-pub(crate) fn summarize<I>(syscalls: I) -> anyhow::Result<Vec<ProgramAction>>
-where
-    I: IntoIterator<Item = anyhow::Result<Syscall>>,
-{
-    let mut actions = Vec::new();
-    let mut stats: HashMap<String, u64> = HashMap::new();
-    // Keep known socket protocols (per process) for bind handling, we don't care for the socket closings
-    // because the fd will be reused or never bound again
-    let mut known_sockets_proto: HashMap<(u32, i128), SocketProtocol> = HashMap::new();
-    for syscall in syscalls {
-        let syscall = syscall?;
-        log::trace!("{syscall:?}");
-        stats
-            .entry(syscall.name.clone())
-            .and_modify(|c| *c += 1)
-            .or_insert(1);
-        let name = syscall.name.as_str();
+/// Mounted filesystem
+#[derive(Debug)]
+struct Mount {
+    point: PathBuf,
+    fs_type: String,
+}
 
-        match SYSCALL_MAP.get(name) {
-            Some(SyscallInfo::Open {
-                relfd_idx,
-                path_idx,
-                flags_idx,
-            }) => {
-                let (mut path, flags) = if let (
-                    Some(Expression::Buffer(BufferExpression {
-                        value: b,
-                        type_: BufferType::Unknown,
-                    })),
-                    Some(Expression::Integer(IntegerExpression { value: e, .. })),
-                ) =
-                    (syscall.args.get(*path_idx), syscall.args.get(*flags_idx))
-                {
-                    (PathBuf::from(OsStr::from_bytes(b)), e)
-                } else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
-                };
+/// Parse mounted filesystems from `/proc/<pid>/mountinfo` content
+/// See <https://docs.kernel.org/filesystems/proc.html#proc-pid-mountinfo-information-about-mounts>
+fn parse_mountinfo(content: &str) -> anyhow::Result<Vec<Mount>> {
+    content
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let mut fields = line.split(' ');
+            let point = fields
+                .nth(4)
+                .ok_or_else(|| anyhow::anyhow!("Unable to parse mount point in {line:?}"))?;
+            let fs_type = fields
+                .skip_while(|f| *f != "-")
+                .nth(1)
+                .ok_or_else(|| anyhow::anyhow!("Unable to parse filesystem type in {line:?}"))?;
+            Ok(Mount {
+                point: PathBuf::from(OsStr::from_bytes(&unescape_mount_field(point))),
+                // Strip subtype, ie. 'fuse.sshfs'
+                fs_type: fs_type.split('.').next().unwrap_or(fs_type).to_owned(),
+            })
+        })
+        .collect()
+}
 
-                path = if let Some(path) = resolve_path(&path, *relfd_idx, &syscall) {
-                    path
-                } else {
-                    continue;
-                };
+/// Unescape octal sequences (ie. `\040` for space) in mountinfo field
+fn unescape_mount_field(field: &str) -> Vec<u8> {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(c) = (bytes[i] == b'\\')
+            .then(|| bytes.get(i + 1..i + 4))
+            .flatten()
+            .and_then(|o| str::from_utf8(o).ok())
+            .and_then(|o| u8::from_str_radix(o, 8).ok())
+        {
+            unescaped.push(c);
+            i += 4;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+    unescaped
+}
 
-                if flags.is_flag_set("O_CREAT") {
-                    actions.push(ProgramAction::Create(path.clone()));
-                }
-                if flags.is_flag_set("O_WRONLY")
-                    || flags.is_flag_set("O_RDWR")
-                    || flags.is_flag_set("O_TRUNC")
-                {
-                    actions.push(ProgramAction::Write(path.clone()));
-                }
-                if !flags.is_flag_set("O_WRONLY") {
-                    actions.push(ProgramAction::Read(path));
-                }
-            }
-            Some(SyscallInfo::Rename {
-                relfd_src_idx,
-                path_src_idx,
+/// Get type of the filesystem a path is on
+fn mount_fs_type<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a str> {
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.point))
+        // Last one wins if a mount point is mounted over
+        .max_by_key(|m| m.point.components().count())
+        .map(|m| m.fs_type.as_str())
+}
+
+/// Get types of the filesystems of all accessed paths
+fn file_system_types(actions: &[ProgramAction], mounts: &[Mount]) -> SetSpecifier<String> {
+    let mut fs_types = BTreeSet::new();
+    for action in actions {
+        if let ProgramAction::Read(path)
+        | ProgramAction::Write(path)
+        | ProgramAction::Create(path) = action
+        {
+            let Some(fs_type) = mount_fs_type(mounts, path) else {
+                log::warn!("Unable to find filesystem of {path:?}");
+                return SetSpecifier::All;
+            };
+            fs_types.insert(fs_type.to_owned());
+        }
+    }
+    SetSpecifier::Some(fs_types.into_iter().collect())
+}
+
+/// Add action for the types of the filesystems accessed, mapping paths to the local mounts
+pub(crate) fn add_file_system_action(actions: &mut Vec<ProgramAction>) {
+    // TODO APPROXIMATION
+    // When analyzing a log captured beforehand, mounts may have changed, or be on another system
+    let fs_types = match fs::read_to_string("/proc/self/mountinfo")
+        .map_err(anyhow::Error::from)
+        .and_then(|c| parse_mountinfo(&c))
+    {
+        Ok(mounts) => file_system_types(actions, &mounts),
+        Err(err) => {
+            log::warn!("Unable to get mounted filesystems: {err}");
+            SetSpecifier::All
+        }
+    };
+    actions.push(ProgramAction::FileSystemAccess(fs_types));
+}
+
+/// Remove actions from index `start` that are duplicates of any previous one, `seen` holding the actions before it
+fn dedup_new_actions(
+    actions: &mut Vec<ProgramAction>,
+    start: usize,
+    seen: &mut HashSet<ProgramAction>,
+) {
+    let new_actions = actions.split_off(start);
+    for action in new_actions {
+        if seen.insert(action.clone()) {
+            actions.push(action);
+        }
+    }
+}
+
+/// Add new actions to previous ones, skipping duplicates, and merging all syscalls into a single action
+pub(crate) fn merge_actions(actions: &mut Vec<ProgramAction>, new_actions: Vec<ProgramAction>) {
+    let mut syscalls = HashSet::new();
+    let mut seen = HashSet::with_capacity(actions.len() + new_actions.len());
+    let mut merged = Vec::with_capacity(actions.len() + new_actions.len());
+    for action in actions.drain(..).chain(new_actions) {
+        if let ProgramAction::Syscalls(action_syscalls) = action {
+            syscalls.extend(action_syscalls);
+        } else if seen.insert(action.clone()) {
+            merged.push(action);
+        }
+    }
+    if !syscalls.is_empty() {
+        merged.push(ProgramAction::Syscalls(syscalls));
+    }
+    *actions = merged;
+}
+
+/// Strace personality mode of the native architecture, on architectures supporting several ones
+const NATIVE_PERSONALITY_MODE: &str = "64 bit";
+
+/// Get systemd name of the architecture of a non native strace personality mode, for the architecture we run on
+fn personality_arch(mode: &str) -> Option<&'static str> {
+    match (env::consts::ARCH, mode) {
+        ("x86_64", "32 bit") => Some("x86"),
+        ("x86_64", "x32") => Some("x32"),
+        ("aarch64", "32 bit") => Some("arm"),
+        ("powerpc64", "32 bit") => Some("ppc"),
+        ("s390x", "32 bit") => Some("s390"),
+        _ => None,
+    }
+}
+
+/// Add action for the non native architectures syscalls were made with, from strace personality modes
+pub(crate) fn add_syscall_arch_action(
+    actions: &mut Vec<ProgramAction>,
+    personality_modes: &BTreeSet<String>,
+) {
+    let mut archs = Vec::new();
+    for mode in personality_modes
+        .iter()
+        .filter(|m| *m != NATIVE_PERSONALITY_MODE)
+    {
+        if let Some(arch) = personality_arch(mode) {
+            archs.push(arch.to_owned());
+        } else {
+            log::warn!("Unknown architecture for strace personality mode {mode:?}");
+            actions.push(ProgramAction::NonNativeSyscalls(SetSpecifier::All));
+            return;
+        }
+    }
+    if !archs.is_empty() {
+        actions.push(ProgramAction::NonNativeSyscalls(SetSpecifier::Some(archs)));
+    }
+}
+
+/// Directory where POSIX shared memory objects are created
+const POSIX_SHM_DIR: &str = "/dev/shm";
+
+/// Temporary directories, that `PrivateTmp=` makes private
+const TMP_DIRS: [&str; 2] = ["/tmp", "/var/tmp"];
+
+/// Check if a path is in a temporary directory
+fn is_tmp_path(path: &Path) -> bool {
+    TMP_DIRS
+        .iter()
+        .any(|d| path.starts_with(d) && path != Path::new(d))
+}
+
+/// Path whose permissions or ownership are changed by a syscall, if any
+fn attrs_changed_path(
+    syscall: &Syscall,
+    cwd: Option<&Path>,
+    canonicalize: bool,
+) -> anyhow::Result<Option<PathBuf>> {
+    let (relfd_idx, path_idx) = match syscall.name.as_str() {
+        "chmod" | "chown" | "chown32" | "lchown" | "lchown32" => (None, 0),
+        "fchmodat" | "fchmodat2" | "fchownat" => (Some(0), 1),
+        "fchmod" | "fchown" | "fchown32" => {
+            let Some(fd) = syscall.args.first() else {
+                anyhow::bail!(unexpected_args(syscall));
+            };
+            // Unknown if log was captured without fd path decoding, ie strace -y
+            return Ok(fd.metadata().and_then(|m| {
+                resolve_path(
+                    &PathBuf::from(OsStr::from_bytes(m)),
+                    None,
+                    syscall,
+                    None,
+                    canonicalize,
+                )
+            }));
+        }
+        _ => return Ok(None),
+    };
+    let Some(Expression::Buffer(BufferExpression {
+        value: b,
+        type_: BufferType::Unknown,
+    })) = syscall.args.get(path_idx)
+    else {
+        anyhow::bail!(unexpected_args(syscall));
+    };
+    // An empty path with AT_EMPTY_PATH targets the directory fd itself
+    Ok(resolve_path(
+        &PathBuf::from(OsStr::from_bytes(b)),
+        relfd_idx,
+        syscall,
+        cwd,
+        canonicalize,
+    ))
+}
+
+/// Build error for a syscall whose arguments do not have the expected layout
+fn unexpected_args(syscall: &Syscall) -> ShhError {
+    ShhError::SyscallArgs {
+        name: syscall.name.clone(),
+        args: format!("{:?}", syscall.args),
+    }
+}
+
+/// Check if a user or group id argument is set to an id other than root or the program's own one
+fn is_foreign_id(id: &Expression, own_id: u32) -> bool {
+    match id {
+        Expression::Integer(IntegerExpression {
+            value: IntegerExpressionValue::Literal(id),
+            ..
+        }) => {
+            // -1 leaves the id unchanged
+            ![-1, 0, i128::from(u32::MAX), i128::from(own_id)].contains(id)
+        }
+        // Unknown, assume the worst
+        _ => true,
+    }
+}
+
+/// Check if a file mode argument sets the setuid or setgid bits
+fn is_suid_sgid_mode(mode: &Expression) -> bool {
+    matches!(mode, Expression::Integer(IntegerExpression { value, .. }) if is_suid_sgid_mode_value(value))
+}
+
+/// Check if a file mode sets the setuid or setgid bits, either as named constants or octal literal
+fn is_suid_sgid_mode_value(mode: &IntegerExpressionValue) -> bool {
+    const SUID_SGID_BITS: i128 = 0o6000;
+    match mode {
+        IntegerExpressionValue::NamedConst(c) => (c == "S_ISUID") || (c == "S_ISGID"),
+        IntegerExpressionValue::Literal(v) => v & SUID_SGID_BITS != 0,
+        IntegerExpressionValue::BinaryOr(vs) => vs.iter().any(is_suid_sgid_mode_value),
+        _ => false,
+    }
+}
+
+fn is_group_other_mode(mode: &Expression) -> bool {
+    match mode {
+        Expression::Integer(IntegerExpression { value, .. }) => is_group_other_mode_value(value),
+        // Unknown, assume the worst
+        _ => true,
+    }
+}
+
+/// Check if a file mode grants permissions to group or other users, either as named constants or octal literal
+fn is_group_other_mode_value(mode: &IntegerExpressionValue) -> bool {
+    const GROUP_OTHER_BITS: i128 = 0o077;
+    match mode {
+        IntegerExpressionValue::NamedConst(c) => {
+            c.starts_with("S_I")
+                && (c.ends_with("GRP")
+                    || c.ends_with("OTH")
+                    || c.ends_with("RWXG")
+                    || c.ends_with("RWXO"))
+        }
+        IntegerExpressionValue::Literal(v) => v & GROUP_OTHER_BITS != 0,
+        IntegerExpressionValue::BinaryOr(vs) => vs.iter().any(is_group_other_mode_value),
+        // Unknown, assume the worst
+        _ => true,
+    }
+}
+
+#[expect(clippy::unwrap_used)]
+static FD_PSEUDO_PATH_REGEX: LazyLock<regex::bytes::Regex> =
+    LazyLock::new(|| regex::bytes::Regex::new(r"^[a-z]+:\[[0-9a-z]+\]/?$").unwrap());
+
+fn is_fd_pseudo_path(path: &[u8]) -> bool {
+    FD_PSEUDO_PATH_REGEX.is_match(path)
+}
+
+/// Extract path for socket address structure if it's a non abstract one
+fn socket_address_uds_path(
+    members: &HashMap<String, Expression>,
+    syscall: &Syscall,
+    cwd: Option<&Path>,
+    canonicalize_paths: bool,
+) -> Option<PathBuf> {
+    if let Some(Expression::Buffer(BufferExpression {
+        value: b,
+        type_: BufferType::Unknown,
+    })) = members.get("sun_path")
+    {
+        resolve_path(
+            &PathBuf::from(OsStr::from_bytes(b)),
+            None,
+            syscall,
+            cwd,
+            canonicalize_paths,
+        )
+    } else {
+        None
+    }
+}
+
+/// Extract IP address from socket address structure
+fn socket_address_ip(members: &HashMap<String, Expression>) -> Option<IpAddr> {
+    match (members.get("sin_addr"), members.get("sin6_addr")) {
+        (Some(Expression::Macro { name, args }), None) if name == "inet_addr" => {
+            if let Some(Expression::Buffer(BufferExpression { value: b, .. })) = args.first() {
+                str::from_utf8(b)
+                    .ok()?
+                    .parse::<Ipv4Addr>()
+                    .ok()
+                    .map(IpAddr::V4)
+            } else {
+                None
+            }
+        }
+        (None, Some(Expression::Macro { name, args })) if name == "inet_pton" => {
+            if let Some(Expression::Buffer(BufferExpression { value: b, .. })) = args.get(1) {
+                // Dual stack sockets send IPv4 packets to IPv4 mapped addresses
+                str::from_utf8(b)
+                    .ok()?
+                    .parse::<Ipv6Addr>()
+                    .ok()
+                    .map(|a| IpAddr::V6(a).to_canonical())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+#[expect(clippy::too_many_lines)]
+pub(crate) fn summarize<I>(syscalls: I) -> anyhow::Result<Vec<ProgramAction>>
+where
+    I: IntoIterator<Item = anyhow::Result<Syscall>>,
+{
+    summarize_with_checkpoints(syscalls, true, None, None, |_| Ok(()))
+}
+
+/// Same as `summarize`, but if `checkpoint_interval` is set, also periodically deduplicate actions to bound memory
+/// usage, and pass those observed so far to `checkpoint`.
+/// If `canonicalize_paths` is not set, paths are kept as the program sees them, ie. if it runs in its own root.
+/// If `initial_cwd` is set, paths relative to the current directory of processes are resolved from it.
+pub(crate) fn summarize_with_checkpoints<I, F>(
+    syscalls: I,
+    canonicalize_paths: bool,
+    initial_cwd: Option<&Path>,
+    checkpoint_interval: Option<Duration>,
+    mut checkpoint: F,
+) -> anyhow::Result<Vec<ProgramAction>>
+where
+    I: IntoIterator<Item = anyhow::Result<Syscall>>,
+    F: FnMut(Vec<ProgramAction>) -> anyhow::Result<()>,
+{
+    let mut actions = Vec::new();
+    // Actions before this index are deduplicated, and also stored in this set
+    let mut unique_actions_count = 0;
+    let mut unique_actions = HashSet::new();
+    let mut last_checkpoint = Instant::now();
+    let mut stats: HashMap<String, u64> = HashMap::new();
+    // TODO APPROXIMATION
+    // We run with the same credentials as the profiled program, at least until it changes them
+    let own_uid = nix::unistd::getuid().as_raw();
+    let own_gid = nix::unistd::getgid().as_raw();
+    // Keep known socket protocols (per process) for bind handling, we don't care for the socket closings
+    // because the fd will be reused or never bound again
+    let mut known_sockets_proto: HashMap<(u32, i128), SocketProtocol> = HashMap::new();
+    // Peers of IP sockets we already know of, to avoid generating an action for each packet
+    let mut known_peer_activities: HashSet<(
+        String,
+        Option<SocketProtocol>,
+        NetworkActivityKind,
+        Option<IpAddr>,
+    )> = HashSet::new();
+    // File accesses through fds we already know of, to avoid generating an action for each read or write
+    let mut known_fd_accesses: HashSet<(u32, Vec<u8>, bool)> = HashSet::new();
+    // io_uring use was already reported
+    let mut io_uring_warned = false;
+    // Current directories of processes that changed it, or inherited it from one that did, if known
+    let mut cwds: HashMap<u32, Option<PathBuf>> = HashMap::new();
+    // Programs processes last executed, or inherited from the process that did
+    let mut executables: HashMap<u32, PathBuf> = HashMap::new();
+    for syscall in syscalls {
+        if checkpoint_interval.is_some_and(|i| last_checkpoint.elapsed() >= i) {
+            dedup_new_actions(&mut actions, unique_actions_count, &mut unique_actions);
+            unique_actions_count = actions.len();
+            let mut checkpoint_actions = actions.clone();
+            checkpoint_actions.push(ProgramAction::Syscalls(stats.keys().cloned().collect()));
+            checkpoint(checkpoint_actions)?;
+            last_checkpoint = Instant::now();
+        }
+
+        let syscall = syscall?;
+        log::trace!("{:?}: {syscall:?}", executables.get(&syscall.pid));
+        stats
+            .entry(syscall.name.clone())
+            .and_modify(|c| *c += 1)
+            .or_insert(1);
+        let name = syscall.name.as_str();
+
+        // TODO APPROXIMATION
+        // Threads sharing their filesystem attributes (CLONE_FS) do not see a directory change made by another one
+        match name {
+            "clone" | "clone3" | "fork" | "vfork" => {
+                if let Ok(child_pid @ 1..) = u32::try_from(syscall.ret_val) {
+                    if let Some(parent_cwd) = cwds.get(&syscall.pid).cloned() {
+                        cwds.insert(child_pid, parent_cwd);
+                    }
+                    if let Some(parent_exe) = executables.get(&syscall.pid).cloned() {
+                        executables.insert(child_pid, parent_exe);
+                    }
+                }
+            }
+            "chdir" if syscall.ret_val == 0 => {
+                let Some(Expression::Buffer(BufferExpression {
+                    value: b,
+                    type_: BufferType::Unknown,
+                })) = syscall.args.first()
+                else {
+                    anyhow::bail!(unexpected_args(&syscall));
+                };
+                let dir = resolve_path(
+                    &PathBuf::from(OsStr::from_bytes(b)),
+                    None,
+                    &syscall,
+                    process_cwd(&cwds, syscall.pid, initial_cwd),
+                    false,
+                );
+                cwds.insert(syscall.pid, dir);
+            }
+            "fchdir" if syscall.ret_val == 0 => {
+                // Unknown if log was captured without fd path decoding, ie strace -y
+                let dir = syscall
+                    .args
+                    .first()
+                    .and_then(Expression::metadata)
+                    .map(|d| PathBuf::from(OsStr::from_bytes(d)));
+                cwds.insert(syscall.pid, dir);
+            }
+            _ => (),
+        }
+        let cwd = process_cwd(&cwds, syscall.pid, initial_cwd);
+
+        match SYSCALL_MAP.get(name) {
+            Some(SyscallInfo::AccessFd { fd_idx, write }) => {
+                // The fd may have been opened before profiling started, or by another process
+                let Some(fd_path) = syscall.args.get(*fd_idx).and_then(Expression::metadata) else {
+                    // Log was captured without fd path decoding, ie strace -y
+                    continue;
+                };
+                if !known_fd_accesses.insert((syscall.pid, fd_path.to_vec(), *write)) {
+                    continue;
+                }
+                // Relative fd paths are pseudo ones, ie. for sockets or pipes
+                let Some(path) = resolve_path(
+                    &PathBuf::from(OsStr::from_bytes(fd_path)),
+                    None,
+                    &syscall,
+                    None,
+                    canonicalize_paths,
+                ) else {
+                    continue;
+                };
+                actions.push(if *write {
+                    ProgramAction::Write(path)
+                } else {
+                    ProgramAction::Read(path)
+                });
+            }
+            Some(SyscallInfo::Open {
+                relfd_idx,
+                path_idx,
+                flags_idx,
+            }) => {
+                // openat2 flags & mode are members of an open_how struct
+                let (flags_arg, mode_arg, resolve_arg) = match syscall.args.get(*flags_idx) {
+                    Some(Expression::Struct(how)) => {
+                        (how.get("flags"), how.get("mode"), how.get("resolve"))
+                    }
+                    arg => (arg, syscall.args.get(*flags_idx + 1), None),
+                };
+                let (mut path, flags) = if let (
+                    Some(Expression::Buffer(BufferExpression {
+                        value: b,
+                        type_: BufferType::Unknown,
+                    })),
+                    Some(Expression::Integer(IntegerExpression { value: e, .. })),
+                ) = (syscall.args.get(*path_idx), flags_arg)
+                {
+                    (PathBuf::from(OsStr::from_bytes(b)), e)
+                } else {
+                    anyhow::bail!(unexpected_args(&syscall));
+                };
+                if flags.is_flag_set("O_CREAT") && mode_arg.is_some_and(is_suid_sgid_mode) {
+                    actions.push(ProgramAction::SetSuidSgid);
+                }
+                if flags.is_flag_set("O_CREAT") && mode_arg.is_none_or(is_group_other_mode) {
+                    actions.push(ProgramAction::SetGroupOtherPermissions);
+                }
+                if let Some(Expression::Integer(IntegerExpression { value: resolve, .. })) =
+                    resolve_arg
+                {
+                    if resolve.is_flag_set("RESOLVE_IN_ROOT") {
+                        // Absolute paths are resolved as if the directory fd was the root
+                        if let Ok(rel_path) = path.strip_prefix("/") {
+                            path = rel_path.to_owned();
+                        }
+                    }
+                }
+
+                path = if let Some(path) =
+                    resolve_path(&path, *relfd_idx, &syscall, cwd, canonicalize_paths)
+                {
+                    path
+                } else {
+                    continue;
+                };
+
+                let write = flags.is_flag_set("O_WRONLY")
+                    || flags.is_flag_set("O_RDWR")
+                    || flags.is_flag_set("O_TRUNC");
+                let read = !flags.is_flag_set("O_WRONLY");
+                if let Some(type_) = device_type(&path) {
+                    let mut accesses = Vec::with_capacity(2);
+                    if read {
+                        accesses.push(DeviceAccess {
+                            path: path.clone(),
+                            type_: type_.clone(),
+                            write: false,
+                        });
+                    }
+                    if write {
+                        accesses.push(DeviceAccess {
+                            path: path.clone(),
+                            type_,
+                            write: true,
+                        });
+                    }
+                    actions.push(ProgramAction::DeviceAccess(SetSpecifier::Some(accesses)));
+                }
+
+                if flags.is_flag_set("O_CREAT") {
+                    if path.starts_with(POSIX_SHM_DIR) {
+                        // shm_open
+                        actions.push(ProgramAction::CreateIpcObject);
+                    }
+                    if !flags.is_flag_set("O_EXCL") && is_tmp_path(&path) {
+                        // Not a private temporary file (mkstemp & co always set O_EXCL)
+                        actions.push(ProgramAction::CreateShared(path.clone()));
+                    }
+                    actions.push(ProgramAction::Create(path.clone()));
+                }
+                if write {
+                    actions.push(ProgramAction::Write(path.clone()));
+                }
+                if read {
+                    actions.push(ProgramAction::Read(path));
+                }
+            }
+            Some(SyscallInfo::Rename {
+                relfd_src_idx,
+                path_src_idx,
                 relfd_dst_idx,
                 path_dst_idx,
                 flags_idx,
@@ -538,12 +1673,12 @@ where
                         PathBuf::from(OsStr::from_bytes(b2)),
                     )
                 } else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                    anyhow::bail!(unexpected_args(&syscall));
                 };
 
                 let (Some(path_src), Some(path_dst)) = (
-                    resolve_path(&path_src, *relfd_src_idx, &syscall),
-                    resolve_path(&path_dst, *relfd_dst_idx, &syscall),
+                    resolve_path(&path_src, *relfd_src_idx, &syscall, cwd, canonicalize_paths),
+                    resolve_path(&path_dst, *relfd_dst_idx, &syscall, cwd, canonicalize_paths),
                 ) else {
                     continue;
                 };
@@ -552,7 +1687,7 @@ where
                     let Some(Expression::Integer(IntegerExpression { value: flags, .. })) =
                         syscall.args.get(*flags_idx)
                     else {
-                        anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                        anyhow::bail!(unexpected_args(&syscall));
                     };
 
                     flags.is_flag_set("RENAME_EXCHANGE")
@@ -570,13 +1705,19 @@ where
                 actions.push(ProgramAction::Write(path_dst.clone()));
             }
             Some(SyscallInfo::StatFd { fd_idx }) => {
-                let mut path = syscall
-                    .args
-                    .get(*fd_idx)
-                    .and_then(|a| a.metadata())
-                    .map(|m| PathBuf::from(OsStr::from_bytes(m)))
-                    .ok_or_else(|| anyhow::anyhow!("Unexpected args for {name}"))?;
-                path = if let Some(path) = resolve_path(&path, None, &syscall) {
+                let Some(fd) = syscall.args.get(*fd_idx) else {
+                    anyhow::bail!(unexpected_args(&syscall));
+                };
+                let Some(mut path) = fd.metadata().map(|m| PathBuf::from(OsStr::from_bytes(m)))
+                else {
+                    // Log was captured without fd path decoding, ie strace -y
+                    log::debug!("Unable to get path of fd {fd:?} for {name}, ignoring");
+                    continue;
+                };
+                // Relative fd paths are pseudo ones, ie. for sockets or pipes
+                path = if let Some(path) =
+                    resolve_path(&path, None, &syscall, None, canonicalize_paths)
+                {
                     path
                 } else {
                     continue;
@@ -594,9 +1735,11 @@ where
                 {
                     PathBuf::from(OsStr::from_bytes(b))
                 } else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                    anyhow::bail!(unexpected_args(&syscall));
                 };
-                path = if let Some(path) = resolve_path(&path, *relfd_idx, &syscall) {
+                path = if let Some(path) =
+                    resolve_path(&path, *relfd_idx, &syscall, cwd, canonicalize_paths)
+                {
                     path
                 } else {
                     continue;
@@ -604,25 +1747,36 @@ where
                 actions.push(ProgramAction::Read(path));
             }
             Some(SyscallInfo::Network { sockaddr_idx }) => {
-                let (af, addr) =
-                    if let Some(Expression::Struct(members)) = syscall.args.get(*sockaddr_idx) {
-                        let Some(Expression::Integer(IntegerExpression {
-                            value: IntegerExpressionValue::NamedConst(af),
-                            ..
-                        })) = members.get("sa_family")
-                        else {
-                            anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
-                        };
-                        (af.as_str(), members)
-                    } else {
-                        // Can be NULL in some cases, ie AF_NETLINK sockets
-                        continue;
+                let sockaddr = match syscall.args.get(*sockaddr_idx) {
+                    // Address for sendmsg/recvmsg is in the message header
+                    Some(Expression::Struct(members)) if members.contains_key("msg_name") => {
+                        members.get("msg_name")
+                    }
+                    a => a,
+                };
+                let (af, addr) = if let Some(Expression::Struct(members)) = sockaddr {
+                    let Some(Expression::Integer(IntegerExpression {
+                        value: IntegerExpressionValue::NamedConst(af),
+                        ..
+                    })) = members.get("sa_family")
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
                     };
+                    (af.as_str(), members)
+                } else {
+                    // Can be NULL in some cases, ie AF_NETLINK sockets
+                    continue;
+                };
 
                 #[expect(clippy::single_match)]
                 match af {
                     "AF_UNIX" => {
-                        if let Some(path) = socket_address_uds_path(addr, &syscall) {
+                        if let Some(path) =
+                            socket_address_uds_path(addr, &syscall, cwd, canonicalize_paths)
+                        {
+                            if name == "bind" && is_tmp_path(&path) {
+                                actions.push(ProgramAction::CreateShared(path.clone()));
+                            }
                             actions.push(ProgramAction::Read(path));
                         };
                     }
@@ -635,7 +1789,7 @@ where
                         ..
                     })) = syscall.args.first()
                     else {
-                        anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                        anyhow::bail!(unexpected_args(&syscall));
                     };
                     let af = af
                         .parse()
@@ -651,16 +1805,13 @@ where
                             Some(Expression::Integer(IntegerExpression {
                                 value: IntegerExpressionValue::Literal(port_val),
                                 ..
-                            })) =>
-                            {
-                                #[expect(
-                                    clippy::cast_possible_truncation,
-                                    clippy::cast_sign_loss,
-                                    clippy::unwrap_used
-                                )]
-                                CountableSetSpecifier::One(NetworkPort(
-                                    (*port_val as u16).try_into().unwrap(),
-                                ))
+                            })) => {
+                                #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                                NonZeroU16::new(*port_val as u16).map_or(
+                                    // Port 0, the kernel picks any free ephemeral port
+                                    CountableSetSpecifier::All,
+                                    |port| CountableSetSpecifier::One(NetworkPort(port)),
+                                )
                             }
                             _ => todo!(),
                         },
@@ -672,17 +1823,75 @@ where
                             proto: SetSpecifier::One(proto.to_owned()),
                             kind: SetSpecifier::One(NetworkActivityKind::Bind),
                             local_port,
+                            address: SetSpecifier::All,
+                        }));
+                    }
+                } else if matches!(af, "AF_INET" | "AF_INET6") {
+                    let Some(Expression::Integer(IntegerExpression {
+                        value: IntegerExpressionValue::Literal(fd),
+                        ..
+                    })) = syscall.args.first()
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    let kind = match name {
+                        "connect" => NetworkActivityKind::Connect,
+                        "sendto" | "sendmsg" => NetworkActivityKind::Send,
+                        _ => NetworkActivityKind::Recv,
+                    };
+                    let proto = known_sockets_proto.get(&(syscall.pid, *fd)).cloned();
+                    let address = socket_address_ip(addr);
+                    if known_peer_activities.insert((
+                        af.to_owned(),
+                        proto.clone(),
+                        kind.clone(),
+                        address,
+                    )) {
+                        actions.push(ProgramAction::NetworkActivity(NetworkActivity {
+                            af: SetSpecifier::One(af.parse().map_err(|()| {
+                                anyhow::anyhow!("Unable to parse socket family {af:?}")
+                            })?),
+                            proto: proto.map_or(SetSpecifier::All, SetSpecifier::One),
+                            kind: SetSpecifier::One(kind),
+                            local_port: CountableSetSpecifier::All,
+                            address: address.map_or(SetSpecifier::All, SetSpecifier::One),
                         }));
                     }
                 }
             }
+            Some(SyscallInfo::SetIds { uid_idxs, gid_idxs }) => {
+                let uids = uid_idxs.iter().map(|idx| (idx, own_uid));
+                let gids = gid_idxs.iter().map(|idx| (idx, own_gid));
+                let mut foreign = false;
+                for (idx, own_id) in uids.chain(gids) {
+                    // setgroups takes a list of ids
+                    let ids = match syscall.args.get(*idx) {
+                        Some(Expression::Collection { values, .. }) => values.iter().collect(),
+                        Some(arg) => vec![arg],
+                        None => {
+                            anyhow::bail!(unexpected_args(&syscall));
+                        }
+                    };
+                    foreign |= ids.into_iter().any(|id| is_foreign_id(id, own_id));
+                }
+                if foreign {
+                    actions.push(ProgramAction::UseForeignIds);
+                }
+            }
             Some(SyscallInfo::SetScheduler) => {
-                let Some(Expression::Integer(IntegerExpression { value: policy, .. })) =
-                    syscall.args.get(1)
+                // sched_setattr policy is a member of a sched_attr struct
+                let policy_arg = match syscall.args.get(1) {
+                    Some(Expression::Struct(attr)) => attr.get("sched_policy"),
+                    arg => arg,
+                };
+                let Some(Expression::Integer(IntegerExpression { value: policy, .. })) = policy_arg
                 else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                    anyhow::bail!(unexpected_args(&syscall));
                 };
-                if policy.is_flag_set("SCHED_FIFO") | policy.is_flag_set("SCHED_RR") {
+                if ["SCHED_FIFO", "SCHED_RR", "SCHED_DEADLINE"]
+                    .into_iter()
+                    .any(|p| policy.is_flag_set(p))
+                {
                     actions.push(ProgramAction::SetRealtimeScheduler);
                 }
             }
@@ -695,7 +1904,7 @@ where
                     af.parse()
                         .map_err(|()| anyhow::anyhow!("Unable to parse socket family {af:?}"))?
                 } else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                    anyhow::bail!(unexpected_args(&syscall));
                 };
 
                 let flags = if let Some(Expression::Integer(IntegerExpression { value, .. })) =
@@ -703,7 +1912,7 @@ where
                 {
                     value.flags()
                 } else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                    anyhow::bail!(unexpected_args(&syscall));
                 };
                 let proto_flag =
                     flags
@@ -722,6 +1931,7 @@ where
                     proto: SetSpecifier::One(proto),
                     kind: SetSpecifier::One(NetworkActivityKind::SocketCreation),
                     local_port: CountableSetSpecifier::All,
+                    address: SetSpecifier::All,
                 }));
             }
             Some(SyscallInfo::Mknod { mode_idx }) => {
@@ -733,21 +1943,140 @@ where
                     {
                         actions.push(ProgramAction::MknodSpecial);
                     }
+                    if is_suid_sgid_mode_value(&mode.value) {
+                        actions.push(ProgramAction::SetSuidSgid);
+                    }
+                    if is_group_other_mode_value(&mode.value) {
+                        actions.push(ProgramAction::SetGroupOtherPermissions);
+                    }
                 } else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                    anyhow::bail!(unexpected_args(&syscall));
                 }
             }
             Some(SyscallInfo::Mmap { prot_idx }) => {
                 let Some(Expression::Integer(IntegerExpression { value: prot, .. })) =
                     syscall.args.get(*prot_idx)
                 else {
-                    anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                    anyhow::bail!(unexpected_args(&syscall));
                 };
                 if prot.is_flag_set("PROT_WRITE") && prot.is_flag_set("PROT_EXEC") {
                     actions.push(ProgramAction::WriteExecuteMemoryMapping);
                 }
             }
+            Some(SyscallInfo::Mprotect { prot_idx }) => {
+                let Some(Expression::Integer(IntegerExpression { value: prot, .. })) =
+                    syscall.args.get(*prot_idx)
+                else {
+                    anyhow::bail!(unexpected_args(&syscall));
+                };
+                // We don't track previous mapping protection, and systemd denies any change to executable protection
+                if prot.is_flag_set("PROT_EXEC") {
+                    actions.push(ProgramAction::WriteExecuteMemoryMapping);
+                }
+            }
             None => match name {
+                "clone" | "clone3" | "unshare" => {
+                    // clone and clone3 flags are in a struct
+                    let flags_arg = match syscall.args.first() {
+                        Some(Expression::Struct(members)) => members.get("flags"),
+                        a => a,
+                    };
+                    let Some(Expression::Integer(IntegerExpression { value: flags, .. })) =
+                        flags_arg
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    actions.extend(
+                        NamespaceType::iter()
+                            .filter(|ns| flags.is_flag_set(ns.clone_flag()))
+                            .map(ProgramAction::CreateNamespace),
+                    );
+                }
+                "setns" => {
+                    let Some(Expression::Integer(IntegerExpression { value: nstype, .. })) =
+                        syscall.args.get(1)
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    // Namespace type 0 allows joining a namespace of any type
+                    let any_type = matches!(nstype, IntegerExpressionValue::Literal(0));
+                    actions.extend(
+                        NamespaceType::iter()
+                            .filter(|ns| any_type || nstype.is_flag_set(ns.clone_flag()))
+                            .map(ProgramAction::CreateNamespace),
+                    );
+                }
+                "shmat" => {
+                    let Some(Expression::Integer(IntegerExpression { value: flags, .. })) =
+                        syscall.args.get(2)
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    if flags.is_flag_set("SHM_EXEC") {
+                        actions.push(ProgramAction::WriteExecuteMemoryMapping);
+                    }
+                }
+                "execve" | "execveat" => {
+                    // Executing code from a memfd, which may have been written to by the process itself
+                    if (name == "execveat")
+                        && syscall
+                            .args
+                            .first()
+                            .and_then(Expression::metadata)
+                            .is_some_and(|p| p.starts_with(b"/memfd:"))
+                    {
+                        actions.push(ProgramAction::WriteExecuteMemoryMapping);
+                    }
+                    if syscall.ret_val != 0 {
+                        continue;
+                    }
+                    // The process keeps its pid and current directory, but now runs another program, which is
+                    // not opened by the program itself, unlike the libraries it loads
+                    let (relfd_idx, path_idx) = if name == "execveat" {
+                        (Some(0), 1)
+                    } else {
+                        (None, 0)
+                    };
+                    let Some(Expression::Buffer(BufferExpression {
+                        value: b,
+                        type_: BufferType::Unknown,
+                    })) = syscall.args.get(path_idx)
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    let path = if b.is_empty() {
+                        // AT_EMPTY_PATH, the fd is the program
+                        relfd_idx
+                            .and_then(|idx| syscall.args.get(idx))
+                            .and_then(Expression::metadata)
+                            // Anonymous file, not in any filesystem
+                            .filter(|p| !p.starts_with(b"/memfd:"))
+                            .and_then(|p| {
+                                resolve_path(
+                                    &PathBuf::from(OsStr::from_bytes(p)),
+                                    None,
+                                    &syscall,
+                                    None,
+                                    canonicalize_paths,
+                                )
+                            })
+                    } else {
+                        resolve_path(
+                            &PathBuf::from(OsStr::from_bytes(b)),
+                            relfd_idx,
+                            &syscall,
+                            cwd,
+                            canonicalize_paths,
+                        )
+                    };
+                    if let Some(path) = path {
+                        log::debug!("Process {} executes {path:?}", syscall.pid);
+                        executables.insert(syscall.pid, path.clone());
+                        actions.push(ProgramAction::Read(path));
+                    } else {
+                        executables.remove(&syscall.pid);
+                    }
+                }
                 "epoll_ctl" => {
                     if syscall.args.get(1).is_some_and(|op| {
                         matches!(op, Expression::Integer(IntegerExpression {
@@ -777,6 +2106,128 @@ where
                         }
                     }
                 }
+                "init_module" | "finit_module" | "delete_module" => {
+                    actions.push(ProgramAction::ManageKernelModules);
+                }
+                "mount" | "umount" | "umount2" | "pivot_root" | "move_mount" | "fsmount"
+                | "mount_setattr" => {
+                    actions.push(ProgramAction::ManageMounts);
+                    // New mounts change what is visible at their mount point
+                    let mount_point = match name {
+                        "mount" => Some((None, 1)),
+                        "move_mount" => Some((Some(2), 3)),
+                        _ => None,
+                    };
+                    if let Some((relfd_idx, path_idx)) = mount_point {
+                        let Some(Expression::Buffer(BufferExpression {
+                            value: b,
+                            type_: BufferType::Unknown,
+                        })) = syscall.args.get(path_idx)
+                        else {
+                            anyhow::bail!(unexpected_args(&syscall));
+                        };
+                        if let Some(path) = resolve_path(
+                            &PathBuf::from(OsStr::from_bytes(b)),
+                            relfd_idx,
+                            &syscall,
+                            cwd,
+                            canonicalize_paths,
+                        ) {
+                            actions.push(ProgramAction::Write(path));
+                        }
+                    }
+                }
+                "io_uring_setup" | "io_uring_enter" | "io_uring_register" => {
+                    if !io_uring_warned {
+                        // TODO APPROXIMATION
+                        // Operations submitted through the ring do not go through syscalls strace can see
+                        log::warn!(
+                            "Program uses io_uring, file and network accesses done through it can not be observed, \
+                             and may be missing from the profile"
+                        );
+                        io_uring_warned = true;
+                    }
+                }
+                "getrandom" => {
+                    actions.push(ProgramAction::GetRandom);
+                }
+                "sethostname" | "setdomainname" => {
+                    actions.push(ProgramAction::SetHostname);
+                }
+                "clock_settime" | "clock_settime64" | "settimeofday" | "stime" => {
+                    actions.push(ProgramAction::SetClock);
+                }
+                "adjtimex" | "clock_adjtime" | "clock_adjtime64" => {
+                    let timex_idx = usize::from(name != "adjtimex");
+                    let Some(Expression::Struct(timex)) = syscall.args.get(timex_idx) else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    // With no modes set, clock parameters are only read
+                    if !matches!(
+                        timex.get("modes"),
+                        Some(Expression::Integer(IntegerExpression {
+                            value: IntegerExpressionValue::Literal(0),
+                            ..
+                        }))
+                    ) {
+                        actions.push(ProgramAction::SetClock);
+                    }
+                }
+                "shmget" | "semget" | "msgget" | "mq_open" => {
+                    let (flags_idx, create_flag) = match name {
+                        "msgget" => (1, "IPC_CREAT"),
+                        "mq_open" => (1, "O_CREAT"),
+                        _ => (2, "IPC_CREAT"),
+                    };
+                    let Some(Expression::Integer(IntegerExpression { value: flags, .. })) =
+                        syscall.args.get(flags_idx)
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    if flags.is_flag_set(create_flag) {
+                        actions.push(ProgramAction::CreateIpcObject);
+                    }
+                }
+                "chmod" | "fchmod" | "fchmodat" | "fchmodat2" | "creat" => {
+                    let mode_idx = if name.starts_with("fchmodat") { 2 } else { 1 };
+                    let Some(mode) = syscall.args.get(mode_idx) else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    if is_suid_sgid_mode(mode) {
+                        actions.push(ProgramAction::SetSuidSgid);
+                    }
+                    if is_group_other_mode(mode) {
+                        actions.push(ProgramAction::SetGroupOtherPermissions);
+                    }
+                }
+                "mkdir" | "mkdirat" => {
+                    let mode_idx = if name == "mkdirat" { 2 } else { 1 };
+                    let Some(mode) = syscall.args.get(mode_idx) else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    if is_group_other_mode(mode) {
+                        actions.push(ProgramAction::SetGroupOtherPermissions);
+                    }
+                }
+                "personality" => {
+                    // Personality argument value only querying the current one
+                    const PERSONALITY_QUERY: i128 = 0xffff_ffff;
+                    let Some(Expression::Integer(IntegerExpression { value: persona, .. })) =
+                        syscall.args.first()
+                    else {
+                        anyhow::bail!(unexpected_args(&syscall));
+                    };
+                    let unchanged = match persona {
+                        IntegerExpressionValue::Literal(v) => {
+                            (*v == PERSONALITY_QUERY) || (*v == 0)
+                        }
+                        IntegerExpressionValue::NamedConst(c) => c == "PER_LINUX",
+                        _ => false,
+                    };
+                    if !unchanged {
+                        actions.push(ProgramAction::SetPersonality);
+                    }
+                }
                 "timer_create" => {
                     const PRIVILEGED_CLOCK_NAMES: [&str; 2] =
                         ["CLOCK_REALTIME_ALARM", "CLOCK_BOOTTIME_ALARM"];
@@ -785,7 +2236,7 @@ where
                         ..
                     })) = syscall.args.first()
                     else {
-                        anyhow::bail!("Unexpected args for {}: {:?}", name, syscall.args);
+                        anyhow::bail!(unexpected_args(&syscall));
                     };
                     if PRIVILEGED_CLOCK_NAMES.contains(&clock_name.as_str()) {
                         actions.push(ProgramAction::SetAlarm);
@@ -794,10 +2245,18 @@ where
                 _ => {}
             },
         }
+
+        // Changing permissions or ownership needs write access, even to a path whose content is only read
+        if let Some(path) = attrs_changed_path(&syscall, cwd, canonicalize_paths)? {
+            actions.push(ProgramAction::Write(path));
+        }
     }
 
     // Almost free optimization
     actions.dedup();
+    if checkpoint_interval.is_some() {
+        dedup_new_actions(&mut actions, unique_actions_count, &mut unique_actions);
+    }
 
     // Create single action with all syscalls for efficient handling of seccomp filters
     actions.push(ProgramAction::Syscalls(stats.keys().cloned().collect()));
@@ -827,6 +2286,155 @@ mod tests {
         assert!(is_fd_pseudo_path("socket:[1234]/".as_bytes()));
     }
 
+    #[test]
+    fn test_proc_self_path() {
+        assert_eq!(
+            proc_self_path(Path::new("/proc/self/status"), 12),
+            Some(PathBuf::from("/proc/self/status"))
+        );
+        assert_eq!(
+            proc_self_path(Path::new("/proc/12/fd/3"), 12),
+            Some(PathBuf::from("/proc/self/fd/3"))
+        );
+        assert_eq!(
+            proc_self_path(Path::new("/proc/thread-self"), 12),
+            Some(PathBuf::from("/proc/self"))
+        );
+        assert_eq!(proc_self_path(Path::new("/proc/13/status"), 12), None);
+        assert_eq!(proc_self_path(Path::new("/proc/meminfo"), 12), None);
+        assert_eq!(proc_self_path(Path::new("/etc/self"), 12), None);
+    }
+
+    #[test]
+    fn test_device_type() {
+        assert_eq!(device_type(Path::new("/dev/null")), Some(DeviceType::Char));
+        assert_eq!(
+            device_type(Path::new("/dev/this/does/not/exist")),
+            Some(DeviceType::Char)
+        );
+        assert_eq!(device_type(Path::new("/dev/shm/foo")), None);
+        assert_eq!(device_type(Path::new("/dev")), None);
+        assert_eq!(device_type(Path::new("/etc/passwd")), None);
+    }
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let mounts = parse_mountinfo(concat!(
+            "22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw\n",
+            "23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw\n",
+            "24 22 0:22 / /tmp rw,nosuid,nodev shared:13 - tmpfs tmpfs rw\n",
+            "25 22 0:23 / /mnt/remote\\040dir rw,nosuid,nodev shared:14 - fuse.sshfs host: rw\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            mounts
+                .iter()
+                .map(|m| (m.point.as_path(), m.fs_type.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Path::new("/"), "ext4"),
+                (Path::new("/proc"), "proc"),
+                (Path::new("/tmp"), "tmpfs"),
+                (Path::new("/mnt/remote dir"), "fuse"),
+            ]
+        );
+        assert!(parse_mountinfo("22 1 259:2 / /").is_err());
+    }
+
+    #[test]
+    fn test_file_system_types() {
+        let mounts = parse_mountinfo(concat!(
+            "22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw\n",
+            "24 22 0:22 / /tmp rw,nosuid,nodev shared:13 - tmpfs tmpfs rw\n",
+            "25 24 0:23 / /tmp/sub rw,nosuid,nodev shared:14 - btrfs /dev/sda1 rw\n",
+            "26 24 0:24 / /tmp/sub rw,nosuid,nodev shared:15 - xfs /dev/sdb1 rw\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            file_system_types(
+                &[
+                    ProgramAction::Read("/etc/passwd".into()),
+                    ProgramAction::Write("/tmp/foo".into()),
+                    ProgramAction::Create("/tmpfoo".into()),
+                    ProgramAction::WriteExecuteMemoryMapping,
+                ],
+                &mounts
+            ),
+            SetSpecifier::Some(vec!["ext4".to_owned(), "tmpfs".to_owned()])
+        );
+        assert_eq!(
+            file_system_types(&[ProgramAction::Read("/tmp/sub/foo".into())], &mounts),
+            SetSpecifier::Some(vec!["xfs".to_owned()])
+        );
+        assert_eq!(
+            file_system_types(&[ProgramAction::Read("/etc/passwd".into())], &[]),
+            SetSpecifier::All
+        );
+    }
+
+    #[test]
+    fn test_adjtimex() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        assert!(!summarize_lines(
+            "1 0.000001 adjtimex({modes=0, offset=0, freq=0, maxerror=16000000, esterror=16000000, status=STA_UNSYNC, constant=2, precision=1, tolerance=32768000, time={tv_sec=1700000000, tv_usec=0}, tick=10000, ppsfreq=0, jitter=0, shift=0, stabil=0, jitcnt=0, calcnt=0, errcnt=0, stbcnt=0, tai=0}) = 5 (TIME_ERROR)\n"
+        )
+        .contains(&ProgramAction::SetClock));
+        assert!(summarize_lines(
+            "1 0.000001 adjtimex({modes=ADJ_OFFSET|ADJ_STATUS, offset=-1000, status=STA_PLL}) = 0 (TIME_OK)\n"
+        )
+        .contains(&ProgramAction::SetClock));
+        assert!(!summarize_lines(
+            "1 0.000001 clock_adjtime(CLOCK_REALTIME, {modes=0, offset=0, freq=0, maxerror=16000000, esterror=16000000, status=STA_UNSYNC, constant=2, precision=1, tolerance=32768000, time={tv_sec=1700000000, tv_usec=0}, tick=10000, ppsfreq=0, jitter=0, shift=0, stabil=0, jitcnt=0, calcnt=0, errcnt=0, stbcnt=0, tai=0}) = 5 (TIME_ERROR)\n"
+        )
+        .contains(&ProgramAction::SetClock));
+        assert!(summarize_lines(
+            "1 0.000001 clock_settime(CLOCK_REALTIME, {tv_sec=1700000000, tv_nsec=0}) = 0\n"
+        )
+        .contains(&ProgramAction::SetClock));
+    }
+
+    #[test]
+    fn test_stat_fd_without_path() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let syscalls = [Ok(Syscall {
+            pid: 1,
+            rel_ts: 0.000001,
+            name: "fstat".to_owned(),
+            args: vec![
+                Expression::Integer(IntegerExpression {
+                    value: IntegerExpressionValue::Literal(3),
+                    metadata: None,
+                }),
+                Expression::Struct(HashMap::new()),
+            ],
+            ret_val: 0,
+        })];
+        assert_eq!(
+            summarize(syscalls).unwrap(),
+            vec![ProgramAction::Syscalls(["fstat".to_owned()].into())]
+        );
+    }
+
+    #[test]
+    fn test_unexpected_args() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let syscalls = [Ok(Syscall {
+            pid: 1,
+            rel_ts: 0.000001,
+            name: "chdir".to_owned(),
+            args: vec![],
+            ret_val: 0,
+        })];
+        let err = summarize(syscalls).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ShhError>(),
+            Some(ShhError::SyscallArgs { name, .. }) if name == "chdir"
+        ));
+    }
+
     #[test]
     fn test_relative_rename() {
         let _ = simple_logger::SimpleLogger::new().init();
@@ -918,6 +2526,499 @@ mod tests {
         );
     }
 
+    fn summarize_lines(lines: &str) -> Vec<ProgramAction> {
+        let parser = LogParser::new(
+            Box::new(std::io::Cursor::new(lines.as_bytes().to_vec())),
+            None,
+        )
+        .unwrap();
+        summarize(parser).unwrap()
+    }
+
+    #[test]
+    fn test_openat2() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(
+            "1 0.000001 openat2(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", {flags=O_RDONLY|O_CLOEXEC, mode=0, resolve=0}, 24) = 3\n",
+        );
+        assert!(actions.contains(&ProgramAction::Read("/etc/foo".into())));
+        assert!(!actions.contains(&ProgramAction::Write("/etc/foo".into())));
+
+        let actions = summarize_lines(
+            "1 0.000001 openat2(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", {flags=O_RDWR|O_CREAT|O_CLOEXEC, mode=04755, resolve=RESOLVE_NO_SYMLINKS}, 24) = 3\n",
+        );
+        assert!(actions.contains(&ProgramAction::Write("/etc/foo".into())));
+        assert!(actions.contains(&ProgramAction::Create("/etc/foo".into())));
+        assert!(actions.contains(&ProgramAction::SetSuidSgid));
+
+        let actions = summarize_lines(
+            "1 0.000001 openat2(3<\\x2f\\x73\\x72\\x76>, \"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", {flags=O_RDONLY, mode=0, resolve=RESOLVE_IN_ROOT|RESOLVE_NO_MAGICLINKS}, 24) = 4\n",
+        );
+        assert!(actions.contains(&ProgramAction::Read("/srv/etc/foo".into())));
+        assert!(!actions.contains(&ProgramAction::Read("/etc/foo".into())));
+    }
+
+    #[test]
+    fn test_group_other_permissions() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(concat!(
+            "1 0.000001 openat(AT_FDCWD, \"\\x66\\x6f\\x6f\", O_WRONLY|O_CREAT|O_TRUNC, 0600) = 3\n",
+            "1 0.000001 mkdirat(AT_FDCWD, \"\\x62\\x61\\x72\", 0700) = 0\n",
+            "1 0.000001 fchmod(3<\\x2f\\x66\\x6f\\x6f>, S_IRUSR|S_IWUSR) = 0\n",
+            "1 0.000001 openat(AT_FDCWD, \"\\x62\\x61\\x7a\", O_RDONLY) = 4\n",
+        ));
+        assert!(!actions.contains(&ProgramAction::SetGroupOtherPermissions));
+
+        for line in [
+            "1 0.000001 openat(AT_FDCWD, \"\\x66\\x6f\\x6f\", O_WRONLY|O_CREAT|O_TRUNC, 0640) = 3\n",
+            "1 0.000001 mkdir(\"\\x62\\x61\\x72\", 0755) = 0\n",
+            "1 0.000001 chmod(\"\\x66\\x6f\\x6f\", S_IRUSR|S_IWUSR|S_IROTH) = 0\n",
+        ] {
+            let actions = summarize_lines(line);
+            assert!(actions.contains(&ProgramAction::SetGroupOtherPermissions));
+        }
+    }
+
+    #[test]
+    fn test_attrs_change_paths() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        // Only read, but permissions changed later
+        let actions = summarize_lines(concat!(
+            "1 0.000001 openat(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", O_RDONLY) = 3\n",
+            "1 0.000001 chmod(\"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", 0600) = 0\n",
+        ));
+        assert!(actions.contains(&ProgramAction::Read("/etc/foo".into())));
+        assert!(actions.contains(&ProgramAction::Write("/etc/foo".into())));
+
+        for line in [
+            "1 0.000001 fchmod(3<\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f>, 0600) = 0\n",
+            "1 0.000001 fchmodat(3<\\x2f\\x65\\x74\\x63>, \"\\x66\\x6f\\x6f\", 0600) = 0\n",
+            "1 0.000001 chown(\"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", 0, 0) = 0\n",
+            "1 0.000001 lchown(\"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", 0, 0) = 0\n",
+            "1 0.000001 fchown(3<\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f>, 0, 0) = 0\n",
+            "1 0.000001 fchownat(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", 0, 0, AT_SYMLINK_NOFOLLOW) = 0\n",
+            "1 0.000001 fchownat(3<\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f>, \"\", 0, 0, AT_EMPTY_PATH) = 0\n",
+        ] {
+            let actions = summarize_lines(line);
+            assert!(
+                actions.contains(&ProgramAction::Write("/etc/foo".into())),
+                "{line}"
+            );
+        }
+
+        // Relative path without known current directory
+        let actions = summarize_lines("1 0.000001 chmod(\"\\x62\\x61\\x72\", 0600) = 0\n");
+        assert!(!actions.iter().any(|a| matches!(a, ProgramAction::Write(_))));
+    }
+
+    #[test]
+    fn test_shared_tmp_paths() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(concat!(
+            "1 0.000001 openat(AT_FDCWD, \"\\x2f\\x74\\x6d\\x70\\x2f\\x74\\x6d\\x70\\x61\\x38\\x5a\\x6b\\x32\\x51\", O_RDWR|O_CREAT|O_EXCL, 0600) = 3\n",
+            "1 0.000001 openat(AT_FDCWD, \"\\x2f\\x74\\x6d\\x70\\x2f\\x2e\\x77\\x65\\x6c\\x6c\\x2d\\x6b\\x6e\\x6f\\x77\\x6e\\x2d\\x73\\x6f\\x63\\x6b\\x65\\x74\", O_RDONLY) = 4\n",
+        ));
+        assert!(actions.contains(&ProgramAction::Create("/tmp/tmpa8Zk2Q".into())));
+        assert!(!actions
+            .iter()
+            .any(|a| matches!(a, ProgramAction::CreateShared(_))));
+
+        let actions = summarize_lines(
+            "1 0.000001 openat(AT_FDCWD, \"\\x2f\\x76\\x61\\x72\\x2f\\x74\\x6d\\x70\\x2f\\x66\\x6f\\x6f\\x2e\\x6c\\x6f\\x63\\x6b\", O_WRONLY|O_CREAT, 0600) = 3\n",
+        );
+        assert!(actions.contains(&ProgramAction::CreateShared("/var/tmp/foo.lock".into())));
+
+        let actions = summarize_lines(
+            "1 0.000001 bind(3, {sa_family=AF_UNIX, sun_path=\"\\x2f\\x74\\x6d\\x70\\x2f\\x2e\\x58\\x31\\x31\\x2d\\x75\\x6e\\x69\\x78\\x2f\\x58\\x30\"}, 110) = 0\n",
+        );
+        assert!(actions.contains(&ProgramAction::CreateShared("/tmp/.X11-unix/X0".into())));
+    }
+
+    #[test]
+    fn test_cwd_relative_paths() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        // Unknown current directory
+        let actions = summarize_lines(
+            "1 0.000001 openat(AT_FDCWD, \"\\x64\\x61\\x74\\x61\", O_RDONLY) = 3\n",
+        );
+        assert!(!actions
+            .iter()
+            .any(|a| matches!(a, ProgramAction::Read(p) if p.ends_with("data"))));
+
+        let actions = summarize_lines(concat!(
+            "1 0.000001 chdir(\"\\x2f\\x73\\x72\\x76\") = 0\n",
+            "1 0.000001 openat(AT_FDCWD, \"\\x64\\x61\\x74\\x61\", O_RDONLY) = 3\n",
+            "1 0.000001 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0e5a5e6a10) = 2\n",
+            "2 0.000001 chdir(\"\\x66\\x6f\\x6f\") = 0\n",
+            "2 0.000001 stat(\"\\x62\\x61\\x72\", 0x7ffd4b1c6a40) = -1 ENOENT (No such file or directory)\n",
+            "1 0.000001 chdir(\"\\x2f\\x6e\\x6f\\x6e\\x65\") = -1 ENOENT (No such file or directory)\n",
+            "1 0.000001 fchdir(4<\\x2f\\x76\\x61\\x72>) = 0\n",
+            "1 0.000001 openat(AT_FDCWD, \"\\x6c\\x6f\\x67\", O_WRONLY|O_CREAT|O_APPEND, 0644) = 5\n",
+        ));
+        assert!(actions.contains(&ProgramAction::Read("/srv/data".into())));
+        assert!(actions.contains(&ProgramAction::Read("/srv/foo/bar".into())));
+        assert!(actions.contains(&ProgramAction::Write("/var/log".into())));
+
+        let actions = summarize_with_checkpoints(
+            LogParser::new(
+                Box::new(std::io::Cursor::new(
+                    b"1 0.000001 openat(AT_FDCWD, \"\\x64\\x61\\x74\\x61\", O_RDONLY) = 3\n"
+                        .to_vec(),
+                )),
+                None,
+            )
+            .unwrap(),
+            false,
+            Some(Path::new("/srv")),
+            None,
+            |_| Ok(()),
+        )
+        .unwrap();
+        assert!(actions.contains(&ProgramAction::Read("/srv/data".into())));
+    }
+
+    #[test]
+    fn test_access_fd() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(concat!(
+            "1 0.000001 read(3<\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f>, \"\\x61\\x62\", 4096) = 2\n",
+            "1 0.000001 write(4<\\x2f\\x76\\x61\\x72\\x2f\\x6c\\x69\\x62\\x2f\\x66\\x6f\\x6f\\x2f\\x64\\x62>, \"\\x61\\x62\", 2) = 2\n",
+            "1 0.000001 write(4<\\x2f\\x76\\x61\\x72\\x2f\\x6c\\x69\\x62\\x2f\\x66\\x6f\\x6f\\x2f\\x64\\x62>, \"\\x63\\x64\", 2) = 2\n",
+            "1 0.000001 pwrite64(5<\\x2f\\x73\\x72\\x76\\x2f\\x62\\x61\\x72>, \"\\x61\\x62\", 2, 128) = 2\n",
+            "1 0.000001 write(1<\\x70\\x69\\x70\\x65\\x3a\\x5b\\x31\\x32\\x33\\x34\\x5d>, \"\\x61\\x62\", 2) = 2\n",
+            "1 0.000001 write(6, \"\\x61\\x62\", 2) = 2\n",
+        ));
+        assert!(actions.contains(&ProgramAction::Read("/etc/foo".into())));
+        assert!(!actions.contains(&ProgramAction::Write("/etc/foo".into())));
+        assert_eq!(
+            actions
+                .iter()
+                .filter(|a| **a == ProgramAction::Write("/var/lib/foo/db".into()))
+                .count(),
+            1
+        );
+        assert!(actions.contains(&ProgramAction::Write("/srv/bar".into())));
+        assert!(!actions.iter().any(|a| matches!(
+            a,
+            ProgramAction::Write(p) if p.to_string_lossy().contains("pipe")
+        )));
+    }
+
+    #[test]
+    fn test_manage_mounts() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(
+            "1 0.000001 mount(\"\\x74\\x6d\\x70\\x66\\x73\", \"\\x2f\\x6d\\x6e\\x74\\x2f\\x66\\x6f\\x6f\", \"\\x74\\x6d\\x70\\x66\\x73\", MS_NOSUID|MS_NODEV, \"\\x73\\x69\\x7a\\x65\\x3d\\x31\\x4d\") = 0\n",
+        );
+        assert!(actions.contains(&ProgramAction::ManageMounts));
+        assert!(actions.contains(&ProgramAction::Write("/mnt/foo".into())));
+
+        let actions = summarize_lines(
+            "1 0.000001 umount2(\"\\x2f\\x6d\\x6e\\x74\\x2f\\x66\\x6f\\x6f\", MNT_DETACH) = 0\n",
+        );
+        assert!(actions.contains(&ProgramAction::ManageMounts));
+        assert!(!actions.contains(&ProgramAction::Write("/mnt/foo".into())));
+
+        let actions = summarize_lines(
+            "1 0.000001 move_mount(3, \"\", AT_FDCWD, \"\\x2f\\x6d\\x6e\\x74\\x2f\\x62\\x61\\x72\", MOVE_MOUNT_F_EMPTY_PATH) = 0\n",
+        );
+        assert!(actions.contains(&ProgramAction::ManageMounts));
+        assert!(actions.contains(&ProgramAction::Write("/mnt/bar".into())));
+    }
+
+    #[test]
+    fn test_manage_kernel_modules() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        for line in [
+            "1 0.000001 finit_module(3<\\x2f\\x6c\\x69\\x62\\x2f\\x6d\\x6f\\x64\\x75\\x6c\\x65\\x73\\x2f\\x66\\x6f\\x6f\\x2e\\x6b\\x6f>, \"\", 0) = 0\n",
+            "1 0.000001 init_module(0x7f0000000000, 4096, \"\") = 0\n",
+            "1 0.000001 delete_module(\"\\x66\\x6f\\x6f\", O_NONBLOCK) = 0\n",
+        ] {
+            assert!(
+                summarize_lines(line).contains(&ProgramAction::ManageKernelModules),
+                "{line}"
+            );
+        }
+
+        let actions = summarize_lines(
+            "1 0.000001 openat(AT_FDCWD, \"\\x2f\\x6c\\x69\\x62\\x2f\\x6d\\x6f\\x64\\x75\\x6c\\x65\\x73\\x2f\\x66\\x6f\\x6f\\x2e\\x6b\\x6f\", O_RDONLY) = 3\n",
+        );
+        assert!(!actions.contains(&ProgramAction::ManageKernelModules));
+    }
+
+    #[test]
+    fn test_statx() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(
+            "1 0.000001 statx(AT_FDCWD, \"\\x2f\\x65\\x74\\x63\\x2f\\x66\\x6f\\x6f\", AT_STATX_SYNC_AS_STAT|AT_SYMLINK_NOFOLLOW, STATX_ALL, 0x7ffc2f8e6a20) = -1 ENOENT (No such file or directory)\n",
+        );
+        assert!(actions.contains(&ProgramAction::Read("/etc/foo".into())));
+        assert!(!actions.contains(&ProgramAction::Write("/etc/foo".into())));
+
+        let actions = summarize_lines(
+            "1 0.000001 statx(3<\\x2f\\x65\\x74\\x63>, \"\\x66\\x6f\\x6f\", AT_STATX_SYNC_AS_STAT, STATX_TYPE|STATX_SIZE, {stx_mask=STATX_BASIC_STATS|STATX_MNT_ID, stx_attributes=0, stx_mode=S_IFREG|0644, stx_size=42, ...}) = 0\n",
+        );
+        assert!(actions.contains(&ProgramAction::Read("/etc/foo".into())));
+    }
+
+    #[test]
+    fn test_root_confined_paths() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        // In the host filesystem, /etc/foo of the program is a symlink to another file
+        let host_dir = tempfile::tempdir().unwrap();
+        let host_link = host_dir.path().join("foo");
+        std::os::unix::fs::symlink(host_dir.path().join("bar"), &host_link).unwrap();
+        fs::write(host_dir.path().join("bar"), "").unwrap();
+        let host_link_hex: String = host_link
+            .as_os_str()
+            .as_bytes()
+            .iter()
+            .map(|b| format!("\\x{b:02x}"))
+            .collect();
+        let line = format!(
+            "1 0.000001 openat(AT_FDCWD, \"{host_link_hex}\", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 3\n"
+        );
+
+        let parser = LogParser::new(
+            Box::new(std::io::Cursor::new(line.as_bytes().to_vec())),
+            None,
+        )
+        .unwrap();
+        let actions = summarize_with_checkpoints(parser, true, None, None, |_| Ok(())).unwrap();
+        assert!(actions.contains(&ProgramAction::Write(
+            host_dir.path().join("bar").canonicalize().unwrap()
+        )));
+
+        let parser = LogParser::new(
+            Box::new(std::io::Cursor::new(line.as_bytes().to_vec())),
+            None,
+        )
+        .unwrap();
+        let actions = summarize_with_checkpoints(parser, false, None, None, |_| Ok(())).unwrap();
+        assert!(actions.contains(&ProgramAction::Write(host_link.clone())));
+        assert!(!actions.contains(&ProgramAction::Write(
+            host_dir.path().join("bar").canonicalize().unwrap()
+        )));
+    }
+
+    #[test]
+    fn test_summarize_with_checkpoints() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let lines = concat!(
+            "1 0.000001 clock_settime(CLOCK_REALTIME, {tv_sec=1700000000, tv_nsec=0}) = 0\n",
+            "1 0.000001 clock_settime(CLOCK_REALTIME, {tv_sec=1700000000, tv_nsec=0}) = 0\n",
+            "1 0.000001 sched_setscheduler(0, SCHED_FIFO, [99]) = 0\n",
+            "1 0.000001 clock_settime(CLOCK_REALTIME, {tv_sec=1700000000, tv_nsec=0}) = 0\n",
+        );
+        let parser = LogParser::new(
+            Box::new(std::io::Cursor::new(lines.as_bytes().to_vec())),
+            None,
+        )
+        .unwrap();
+        let mut checkpoints = Vec::new();
+        let actions =
+            summarize_with_checkpoints(parser, true, None, Some(Duration::ZERO), |actions| {
+                checkpoints.push(actions);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(checkpoints.len(), 4);
+        assert_eq!(
+            checkpoints[0],
+            vec![ProgramAction::Syscalls(HashSet::new())]
+        );
+        assert_eq!(
+            checkpoints[3],
+            vec![
+                ProgramAction::SetClock,
+                ProgramAction::SetRealtimeScheduler,
+                ProgramAction::Syscalls(
+                    ["clock_settime".to_owned(), "sched_setscheduler".to_owned()].into()
+                ),
+            ]
+        );
+        // Duplicates are removed
+        assert_eq!(
+            actions,
+            vec![
+                ProgramAction::SetClock,
+                ProgramAction::SetRealtimeScheduler,
+                ProgramAction::Syscalls(
+                    ["clock_settime".to_owned(), "sched_setscheduler".to_owned()].into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_random_sources() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(
+            "1 0.000001 getrandom(\"\\x8f\\x1c\\x3a\\x07\", 4, GRND_NONBLOCK) = 4\n",
+        );
+        assert!(actions.contains(&ProgramAction::GetRandom));
+        assert!(!actions
+            .iter()
+            .any(|a| matches!(a, ProgramAction::DeviceAccess(_))));
+
+        let actions = summarize_lines(
+            "1 0.000001 openat(AT_FDCWD, \"/dev/urandom\", O_RDONLY|O_CLOEXEC) = 3\n",
+        );
+        assert!(!actions.contains(&ProgramAction::GetRandom));
+        assert!(actions
+            .iter()
+            .any(|a| matches!(a, ProgramAction::DeviceAccess(_))));
+    }
+
+    #[test]
+    fn test_realtime_scheduler() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        for line in [
+            "1 0.000001 sched_setscheduler(0, SCHED_FIFO, [99]) = 0\n",
+            "1 0.000001 sched_setscheduler(0, SCHED_RR|SCHED_RESET_ON_FORK, [1]) = 0\n",
+            "1 0.000001 sched_setattr(0, {size=56, sched_policy=SCHED_DEADLINE, sched_flags=0, sched_nice=0, sched_priority=0, sched_runtime=10000000, sched_deadline=30000000, sched_period=30000000}, 0) = 0\n",
+        ] {
+            let actions = summarize_lines(line);
+            assert!(actions.contains(&ProgramAction::SetRealtimeScheduler), "{line}");
+        }
+
+        for line in [
+            "1 0.000001 sched_setscheduler(0, SCHED_OTHER, [0]) = 0\n",
+            "1 0.000001 sched_setscheduler(0, SCHED_BATCH, [0]) = 0\n",
+            "1 0.000001 sched_setattr(0, {size=56, sched_policy=SCHED_IDLE, sched_flags=0, sched_nice=0, sched_priority=0, sched_runtime=0, sched_deadline=0, sched_period=0}, 0) = 0\n",
+        ] {
+            let actions = summarize_lines(line);
+            assert!(!actions.contains(&ProgramAction::SetRealtimeScheduler), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_connect_ip() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(concat!(
+            "1 0.000001 socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP) = 3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x5d>\n",
+            "1 0.000001 connect(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x5d>, {sa_family=AF_INET, sin_port=htons(443), sin_addr=inet_addr(\"\\x39\\x33\\x2e\\x31\\x38\\x34\\x2e\\x32\\x31\\x35\\x2e\\x31\\x34\")}, 16) = 0\n",
+            "1 0.000001 connect(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x5d>, {sa_family=AF_INET, sin_port=htons(443), sin_addr=inet_addr(\"\\x39\\x33\\x2e\\x31\\x38\\x34\\x2e\\x32\\x31\\x35\\x2e\\x31\\x34\")}, 16) = 0\n",
+            "1 0.000001 sendto(4<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x5d>, \"\\x61\", 1, 0, {sa_family=AF_INET6, sin6_port=htons(53), sin6_flowinfo=htonl(0), inet_pton(AF_INET6, \"\\x3a\\x3a\\x66\\x66\\x66\\x66\\x3a\\x31\\x39\\x32\\x2e\\x30\\x2e\\x32\\x2e\\x31\", &sin6_addr), sin6_scope_id=0}, 28) = 1\n",
+            "1 0.000001 sendmsg(4<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x5d>, {msg_name={sa_family=AF_INET, sin_port=htons(53), sin_addr=inet_addr(\"\\x31\\x39\\x32\\x2e\\x30\\x2e\\x32\\x2e\\x32\")}, msg_namelen=16, msg_iov=[{iov_base=\"\\x61\", iov_len=1}], msg_iovlen=1, msg_controllen=0, msg_flags=0}, 0) = 1\n",
+        ));
+        assert_eq!(
+            actions,
+            vec![
+                ProgramAction::NetworkActivity(NetworkActivity {
+                    af: SetSpecifier::One(SocketFamily::Ipv4),
+                    proto: SetSpecifier::One(SocketProtocol::Tcp),
+                    kind: SetSpecifier::One(NetworkActivityKind::SocketCreation),
+                    local_port: CountableSetSpecifier::All,
+                    address: SetSpecifier::All,
+                }),
+                ProgramAction::NetworkActivity(NetworkActivity {
+                    af: SetSpecifier::One(SocketFamily::Ipv4),
+                    proto: SetSpecifier::One(SocketProtocol::Tcp),
+                    kind: SetSpecifier::One(NetworkActivityKind::Connect),
+                    local_port: CountableSetSpecifier::All,
+                    address: SetSpecifier::One("93.184.215.14".parse().unwrap()),
+                }),
+                ProgramAction::NetworkActivity(NetworkActivity {
+                    af: SetSpecifier::One(SocketFamily::Ipv6),
+                    proto: SetSpecifier::All,
+                    kind: SetSpecifier::One(NetworkActivityKind::Send),
+                    local_port: CountableSetSpecifier::All,
+                    address: SetSpecifier::One("192.0.2.1".parse().unwrap()),
+                }),
+                ProgramAction::NetworkActivity(NetworkActivity {
+                    af: SetSpecifier::One(SocketFamily::Ipv4),
+                    proto: SetSpecifier::All,
+                    kind: SetSpecifier::One(NetworkActivityKind::Send),
+                    local_port: CountableSetSpecifier::All,
+                    address: SetSpecifier::One("192.0.2.2".parse().unwrap()),
+                }),
+                ProgramAction::Syscalls(
+                    ["socket", "connect", "sendto", "sendmsg"]
+                        .into_iter()
+                        .map(ToOwned::to_owned)
+                        .collect()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_chain() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let actions = summarize_lines(concat!(
+            "1 0.000001 chdir(\"/opt/foo\") = 0\n",
+            "1 0.000002 execve(\"/opt/foo/wrapper\", [\"wrapper\"], [\"A=B\"]) = 0\n",
+            "1 0.000003 openat(AT_FDCWD, \"/etc/foo/wrapper.conf\", O_RDONLY) = 3\n",
+            "1 0.000004 execve(\"/usr/local/bin/foo\", [\"foo\"], [\"A=B\"]) = -1 ENOENT (No such file or directory)\n",
+            "1 0.000005 execve(\"bin/foo\", [\"foo\"], [\"A=B\"]) = 0\n",
+            "1 0.000006 openat(AT_FDCWD, \"data\", O_RDONLY) = 3\n",
+            "1 0.000007 execveat(3<\\x2f\\x6f\\x70\\x74\\x2f\\x66\\x6f\\x6f\\x2f\\x62\\x69\\x6e\\x2f\\x66\\x6f\\x6f\\x2d\\x68\\x65\\x6c\\x70\\x65\\x72>, \"\", [\"foo-helper\"], [\"A=B\"], AT_EMPTY_PATH) = 0\n",
+        ));
+        for path in [
+            "/opt/foo/wrapper",
+            "/etc/foo/wrapper.conf",
+            // Current directory is kept across exec
+            "/opt/foo/bin/foo",
+            "/opt/foo/data",
+            "/opt/foo/bin/foo-helper",
+        ] {
+            assert!(
+                actions.contains(&ProgramAction::Read(path.into())),
+                "{path}"
+            );
+        }
+        assert!(!actions.contains(&ProgramAction::Read("/usr/local/bin/foo".into())));
+    }
+
+    #[test]
+    fn test_write_execute_memory_mapping() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        for safe_line in [
+            "1 0.000001 mmap(NULL, 8192, PROT_READ|PROT_EXEC, MAP_PRIVATE|MAP_DENYWRITE, 3<\\x2f\\x75\\x73\\x72\\x2f\\x6c\\x69\\x62\\x2f\\x6c\\x69\\x62\\x63\\x2e\\x73\\x6f\\x2e\\x36>, 0) = 140737353879552",
+            "1 0.000001 mmap(NULL, 8192, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 140737353879552",
+            "1 0.000001 mprotect(0x7ffff7fb9000, 4096, PROT_READ) = 0",
+            "1 0.000001 shmat(1, NULL, SHM_RDONLY) = 140737353879552",
+            "1 0.000001 execveat(3<\\x2f\\x75\\x73\\x72\\x2f\\x62\\x69\\x6e\\x2f\\x74\\x72\\x75\\x65>, \"\", NULL, NULL, AT_EMPTY_PATH) = 0",
+        ] {
+            assert!(
+                !summarize_lines(safe_line).contains(&ProgramAction::WriteExecuteMemoryMapping),
+                "{safe_line}"
+            );
+        }
+
+        for unsafe_line in [
+            "1 0.000001 mmap(NULL, 8192, PROT_READ|PROT_WRITE|PROT_EXEC, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 140737353879552",
+            "1 0.000001 mprotect(0x7ffff7fb9000, 4096, PROT_READ|PROT_EXEC) = 0",
+            "1 0.000001 mprotect(0x7ffff7fb9000, 4096, PROT_WRITE|PROT_EXEC) = 0",
+            "1 0.000001 pkey_mprotect(0x7ffff7fb9000, 4096, PROT_WRITE|PROT_EXEC, 1) = 0",
+            "1 0.000001 shmat(1, NULL, SHM_EXEC) = 140737353879552",
+            "1 0.000001 execveat(3<\\x2f\\x6d\\x65\\x6d\\x66\\x64\\x3a\\x70\\x61\\x79\\x6c\\x6f\\x61\\x64\\x20\\x28\\x64\\x65\\x6c\\x65\\x74\\x65\\x64\\x29>, \"\", NULL, NULL, AT_EMPTY_PATH) = 0",
+        ] {
+            assert!(
+                summarize_lines(unsafe_line).contains(&ProgramAction::WriteExecuteMemoryMapping),
+                "{unsafe_line}"
+            );
+        }
+    }
+
     #[test]
     fn test_set_ranges() {
         let port = |p: u16| NetworkPort(p.try_into().unwrap());
@@ -0,0 +1,124 @@
+//! Journal reader using libsystemd sd-journal API
+
+use std::{
+    ffi::{c_char, c_int, c_void, CString},
+    io, ptr, slice,
+};
+
+/// Opaque `sd_journal` handle
+#[repr(C)]
+struct SdJournal {
+    _private: [u8; 0],
+}
+
+/// See <https://www.freedesktop.org/software/systemd/man/latest/sd_journal_open.html>
+const SD_JOURNAL_LOCAL_ONLY: c_int = 1 << 0;
+const SD_JOURNAL_SYSTEM: c_int = 1 << 2;
+const SD_JOURNAL_CURRENT_USER: c_int = 1 << 3;
+
+#[link(name = "systemd")]
+extern "C" {
+    fn sd_journal_open(ret: *mut *mut SdJournal, flags: c_int) -> c_int;
+    fn sd_journal_close(j: *mut SdJournal);
+    fn sd_journal_add_match(j: *mut SdJournal, data: *const c_void, size: usize) -> c_int;
+    fn sd_journal_seek_tail(j: *mut SdJournal) -> c_int;
+    fn sd_journal_previous(j: *mut SdJournal) -> c_int;
+    fn sd_journal_get_data(
+        j: *mut SdJournal,
+        field: *const c_char,
+        data: *mut *const c_void,
+        length: *mut usize,
+    ) -> c_int;
+}
+
+/// Convert sd-journal negative errno return values
+fn check(ret: c_int) -> io::Result<c_int> {
+    if ret < 0 {
+        Err(io::Error::from_raw_os_error(-ret))
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Journal filtered on the messages of a single unit
+pub(crate) struct Journal {
+    handle: *mut SdJournal,
+}
+
+impl Journal {
+    /// Open journal and only match messages of a given unit
+    pub(crate) fn open_unit(unit_name: &str, user: bool) -> anyhow::Result<Self> {
+        let mut handle = ptr::null_mut();
+        let flags = SD_JOURNAL_LOCAL_ONLY
+            | if user {
+                SD_JOURNAL_CURRENT_USER
+            } else {
+                SD_JOURNAL_SYSTEM
+            };
+        // SAFETY: handle is a valid pointer to write to
+        check(unsafe { sd_journal_open(&mut handle, flags) })?;
+        let journal = Self { handle };
+
+        let unit_match = format!(
+            "{}={}",
+            if user {
+                "_SYSTEMD_USER_UNIT"
+            } else {
+                "_SYSTEMD_UNIT"
+            },
+            unit_name
+        );
+        // SAFETY: handle is open, and match data is valid for its length
+        check(unsafe {
+            sd_journal_add_match(journal.handle, unit_match.as_ptr().cast(), unit_match.len())
+        })?;
+        // SAFETY: handle is open
+        check(unsafe { sd_journal_seek_tail(journal.handle) })?;
+
+        Ok(journal)
+    }
+
+    /// Iterate over messages in reverse chronological order
+    pub(crate) fn messages_rev(&self) -> impl Iterator<Item = anyhow::Result<String>> + '_ {
+        #[expect(clippy::unwrap_used)] // static string without nul byte
+        let field = CString::new("MESSAGE").unwrap();
+        let prefix_len = field.as_bytes().len() + 1;
+        std::iter::from_fn(move || {
+            // SAFETY: handle is open
+            match check(unsafe { sd_journal_previous(self.handle) }) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => return Some(Err(err.into())),
+            }
+            let mut data = ptr::null();
+            let mut len = 0;
+            // SAFETY: handle is open, field is nul terminated, and data/len are valid pointers to write to
+            match check(unsafe {
+                sd_journal_get_data(self.handle, field.as_ptr(), &mut data, &mut len)
+            }) {
+                Ok(_) => {}
+                // No MESSAGE field for this entry
+                Err(err) if err.raw_os_error() == Some(nix::libc::ENOENT) => {
+                    return Some(Ok(String::new()))
+                }
+                Err(err) => return Some(Err(err.into())),
+            }
+            // SAFETY: data is valid for len bytes until next journal call, and we copy it before
+            let data: &[u8] = unsafe { slice::from_raw_parts(data.cast(), len) };
+            // Data is 'MESSAGE=xxx'
+            Some(Ok(String::from_utf8_lossy(
+                data.get(prefix_len..).unwrap_or_default(),
+            )
+            .into_owned()))
+        })
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        // SAFETY: handle is open, and not used after this
+        unsafe {
+            sd_journal_close(self.handle);
+        }
+    }
+}
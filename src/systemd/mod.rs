@@ -1,25 +1,182 @@
 //! Systemd code
 
+use std::io::Write;
+
+#[cfg(feature = "sd-journal")]
+mod journal;
 mod options;
+mod progress;
 mod resolver;
+mod security;
 mod service;
 mod version;
 
 pub(crate) use options::{
-    build_options, OptionDescription, OptionValue, SocketFamily, SocketProtocol,
+    build_options, option_names, OptionDescription, SocketFamily, SocketProtocol, KNOWN_SYSCALLS,
+    SOCKET_FAMILIES, SUGGESTED_OPTIONS,
 };
-pub(crate) use resolver::resolve;
-pub(crate) use service::Service;
-pub(crate) use version::{KernelVersion, SystemdVersion};
+pub use options::{ListMode, OptionValue, OptionWithValue};
+pub use resolver::ResolvedOption;
+pub(crate) use resolver::{merge, reconcile_existing, resolve, resolve_with_rationale};
+pub use security::SecurityDelta;
+pub use service::{FragmentNaming, Service, UnitFragments};
+pub use version::{KernelVersion, SystemdVersion};
 
 const START_OPTION_OUTPUT_SNIPPET: &str = "-------- Start of suggested service options --------";
 const END_OPTION_OUTPUT_SNIPPET: &str = "-------- End of suggested service options --------";
+/// Prefix of comment lines explaining why the following option was chosen
+const RATIONALE_COMMENT_PREFIX: &str = "# ";
 
-pub(crate) fn report_options(opts: Vec<options::OptionWithValue>) {
+/// Report options as config lines, between markers so that they can be parsed back from service logs if `markers`
+/// is set
+pub fn report_options(opts: Vec<OptionWithValue>, markers: bool) {
     // Report (not through logging facility because we may need to parse it back from service logs)
-    println!("{START_OPTION_OUTPUT_SNIPPET}");
+    if markers {
+        println!("{START_OPTION_OUTPUT_SNIPPET}");
+    }
     for opt in opts {
+        if let Some(rationale) = &opt.rationale {
+            // Keep the comment on a single line
+            println!("{RATIONALE_COMMENT_PREFIX}{}", rationale.replace('\n', " "));
+        }
         println!("{opt}");
     }
-    println!("{END_OPTION_OUTPUT_SNIPPET}");
+    if markers {
+        println!("{END_OPTION_OUTPUT_SNIPPET}");
+    }
+}
+
+/// Options whose value is only safe if the second option is also applied, because they are resolved together
+const COUPLED_OPTIONS: [(&str, &str); 4] = [
+    ("ProtectSystem", "ReadWritePaths"),
+    ("DevicePolicy", "DeviceAllow"),
+    ("IPAddressDeny", "IPAddressAllow"),
+    ("SocketBindDeny", "SocketBindAllow"),
+];
+
+/// Filter out options by name, keeping only those in `only` if not empty, and removing those in `excluded`
+///
+/// Options coupled with a kept option are also kept, and options coupled with a removed option are also removed.
+pub fn filter_options(
+    opts: Vec<OptionWithValue>,
+    excluded: &[String],
+    only: &[String],
+) -> Vec<OptionWithValue> {
+    let only: Vec<&str> = only
+        .iter()
+        .map(String::as_str)
+        .chain(
+            COUPLED_OPTIONS
+                .iter()
+                .filter(|(opt, _)| only.iter().any(|n| n == opt))
+                .map(|(_, dep)| *dep),
+        )
+        .collect();
+    let excluded: Vec<&str> = excluded
+        .iter()
+        .map(String::as_str)
+        .chain(
+            COUPLED_OPTIONS
+                .iter()
+                .filter(|(_, dep)| excluded.iter().any(|n| n == dep))
+                .map(|(opt, _)| *opt),
+        )
+        .collect();
+    opts.into_iter()
+        .filter(|o| {
+            (only.is_empty() || only.contains(&o.name.as_str()))
+                && !excluded.contains(&o.name.as_str())
+        })
+        .collect()
+}
+
+/// Parse name of an option that can be generated
+pub fn parse_option_name(s: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(option_names().contains(s), "Unknown option {s:?}");
+    Ok(s.to_owned())
+}
+
+/// Report options, and their rationale, as JSON
+pub fn report_options_json<W: Write>(opts: &[ResolvedOption], mut writer: W) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, opts)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_options() {
+        let opts = || -> Vec<OptionWithValue> {
+            [
+                "PrivateDevices=true",
+                "ProtectSystem=strict",
+                "ReadWritePaths=-/var/lib/foo",
+                "PrivateTmp=true",
+                "SystemCallArchitectures=native",
+            ]
+            .iter()
+            .map(|o| o.parse().unwrap())
+            .collect()
+        };
+        let names = |opts: Vec<OptionWithValue>| -> Vec<String> {
+            opts.into_iter().map(|o| o.name).collect()
+        };
+
+        assert_eq!(
+            names(filter_options(opts(), &[], &[])),
+            vec![
+                "PrivateDevices",
+                "ProtectSystem",
+                "ReadWritePaths",
+                "PrivateTmp",
+                "SystemCallArchitectures"
+            ]
+        );
+        assert_eq!(
+            names(filter_options(
+                opts(),
+                &["PrivateDevices".to_owned(), "PrivateTmp".to_owned()],
+                &[]
+            )),
+            vec!["ProtectSystem", "ReadWritePaths", "SystemCallArchitectures"]
+        );
+        assert_eq!(
+            names(filter_options(
+                opts(),
+                &[],
+                &["PrivateTmp".to_owned(), "ProtectSystem".to_owned()]
+            )),
+            vec!["ProtectSystem", "ReadWritePaths", "PrivateTmp"]
+        );
+        assert_eq!(
+            names(filter_options(
+                opts(),
+                &["PrivateTmp".to_owned()],
+                &["PrivateTmp".to_owned(), "ProtectSystem".to_owned()]
+            )),
+            vec!["ProtectSystem", "ReadWritePaths"]
+        );
+        // ProtectSystem=strict is unsafe without the paths it needs to write to
+        assert_eq!(
+            names(filter_options(opts(), &["ReadWritePaths".to_owned()], &[])),
+            vec!["PrivateDevices", "PrivateTmp", "SystemCallArchitectures"]
+        );
+        // Allowed paths are harmless without ProtectSystem=strict
+        assert_eq!(
+            names(filter_options(opts(), &[], &["ReadWritePaths".to_owned()])),
+            vec!["ReadWritePaths"]
+        );
+    }
+
+    #[test]
+    fn test_parse_option_name() {
+        assert_eq!(parse_option_name("ProtectSystem").unwrap(), "ProtectSystem");
+        assert_eq!(parse_option_name("UMask").unwrap(), "UMask");
+        assert!(parse_option_name("DoesNotExist").is_err());
+        assert!(parse_option_name("protectsystem").is_err());
+    }
 }
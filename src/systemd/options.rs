@@ -1,8 +1,10 @@
 //! Systemd option model
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt, iter,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    num::NonZeroU16,
     os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
     str::FromStr,
@@ -13,26 +15,36 @@ use itertools::Itertools;
 use strum::IntoEnumIterator;
 
 use crate::{
-    cl::{HardeningMode, HardeningOptions},
+    hardening::{HardeningMode, HardeningOptions, IpAllowCoarsening, KnownBind},
     summarize::{
-        CountableSetSpecifier, NetworkActivity, NetworkActivityKind, ProgramAction, SetSpecifier,
+        CountableSetSpecifier, DeviceAccess, DeviceType, NamespaceType, NetworkActivity,
+        NetworkActivityKind, NetworkPort, ProgramAction, SetSpecifier,
     },
     systemd::{KernelVersion, SystemdVersion},
 };
 
 /// Callbacks to dynamically update an option to make it compatible with an action
-#[derive(Debug)]
 pub(crate) struct OptionUpdater {
     /// Generate a new option effect compatible with the previously incompatible action
     pub effect: fn(&OptionValueEffect, &ProgramAction) -> Option<OptionValueEffect>,
     /// Generate the option value from the new effect
-    pub value: fn(&OptionValueEffect) -> OptionValue,
+    pub value: Box<dyn Fn(&OptionValueEffect) -> OptionValue + Send + Sync>,
+}
+
+impl fmt::Debug for OptionUpdater {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OptionUpdater")
+            .field("effect", &self.effect)
+            .finish_non_exhaustive()
+    }
 }
 
 /// Systemd option with its possibles values, and their effect
 #[derive(Debug)]
 pub(crate) struct OptionDescription {
     pub name: &'static str,
+    /// Minimum systemd version supporting the option, if not supported by all versions
+    pub min_systemd_version: Option<SystemdVersion>,
     pub possible_values: Vec<OptionValueDescription>,
     pub updater: Option<OptionUpdater>,
 }
@@ -43,22 +55,33 @@ impl fmt::Display for OptionDescription {
     }
 }
 
+/// How values of a list option are interpreted
 #[derive(Debug, Clone)]
-pub(crate) enum ListMode {
+pub enum ListMode {
+    /// Values are allowed, everything else is denied
     WhiteList,
+    /// Values are denied, everything else is allowed
     BlackList,
 }
 
 /// Systemd option value
 #[derive(Debug, Clone)]
-pub(crate) enum OptionValue {
+pub enum OptionValue {
+    /// Boolean value
     Boolean(bool), // In most case we only model the 'true' value, because false is no-op and the default
+    /// String value
     String(String), // enum-like, or free string
+    /// List of values
     List {
+        /// Values
         values: Vec<String>,
+        /// Value to set if list is empty, if any
         value_if_empty: Option<String>,
+        /// Prefix values with '~'
         negation_prefix: bool,
+        /// Repeat option for each value, instead of separating values with spaces
         repeat_option: bool,
+        /// How values are interpreted
         mode: ListMode,
     },
 }
@@ -75,11 +98,46 @@ impl FromStr for OptionValue {
     }
 }
 
+/// Serialize list values as arrays, with the negation prefix applied to each value, which is equivalent for
+/// options supporting it since they are cumulative
+impl serde::Serialize for OptionValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            OptionValue::Boolean(v) => serializer.serialize_bool(*v),
+            OptionValue::String(v) => serializer.serialize_str(v),
+            OptionValue::List {
+                values,
+                value_if_empty,
+                negation_prefix,
+                ..
+            } => {
+                if values.is_empty() {
+                    if let Some(value_if_empty) = value_if_empty {
+                        return serializer.serialize_str(value_if_empty);
+                    }
+                }
+                serializer.collect_seq(values.iter().map(|v| {
+                    if *negation_prefix {
+                        format!("~{v}")
+                    } else {
+                        v.to_owned()
+                    }
+                }))
+            }
+        }
+    }
+}
+
 /// A systemd option value and its effects
 #[derive(Debug)]
 pub(crate) struct OptionValueDescription {
     pub value: OptionValue,
     pub desc: OptionEffect,
+    /// Minimum systemd version supporting this value, if more recent than the one of the option
+    pub min_systemd_version: Option<SystemdVersion>,
 }
 
 /// The effects a systemd option has if enabled
@@ -124,6 +182,8 @@ pub(crate) enum OptionValueEffect {
     Hide(PathDescription),
     /// Deny syscall(s)
     DenySyscalls(DenySyscalls),
+    /// Deny all syscalls, except given ones
+    AllowSyscalls(BTreeSet<String>),
     /// Union of multiple effects
     Multiple(Vec<OptionValueEffect>),
 }
@@ -141,6 +201,7 @@ pub(crate) enum DenySyscalls {
     Clone,
     Eq,
     PartialEq,
+    Hash,
     strum::EnumIter,
     strum::Display,
     serde::Serialize,
@@ -170,6 +231,7 @@ impl FromStr for SocketFamily {
     Clone,
     Eq,
     PartialEq,
+    Hash,
     strum::EnumIter,
     strum::Display,
     serde::Serialize,
@@ -219,19 +281,121 @@ impl DenySyscalls {
             Self::Single(sc) => HashSet::from([sc.to_owned()]),
         }
     }
+
+    /// Get names of classes included in this one, recursively
+    pub(crate) fn nested_classes(&self) -> HashSet<&'static str> {
+        match self {
+            Self::Class(class) => {
+                #[expect(clippy::unwrap_used)]
+                let mut to_visit: Vec<_> = SYSCALL_CLASSES
+                    .get(class)
+                    .unwrap()
+                    .iter()
+                    .filter_map(|e| e.strip_prefix('@'))
+                    .collect();
+                let mut nested = HashSet::new();
+                while let Some(nested_class) = to_visit.pop() {
+                    if nested.insert(nested_class) {
+                        #[expect(clippy::unwrap_used)]
+                        to_visit.extend(
+                            SYSCALL_CLASSES
+                                .get(nested_class)
+                                .unwrap()
+                                .iter()
+                                .filter_map(|e| e.strip_prefix('@')),
+                        );
+                    }
+                }
+                nested
+            }
+            Self::Single(_) => HashSet::new(),
+        }
+    }
 }
 
+/// Comment prefix of options that are only suggested
+pub(crate) const SUGGESTION_COMMENT_PREFIX: &str = "# Suggested: ";
+
+/// Options that are only suggested, unless in aggressive mode
+pub(crate) const SUGGESTED_OPTIONS: [&str; 1] = ["UMask"];
+
 /// A systemd option with a value, as would be present in a config file
-pub(crate) struct OptionWithValue {
+#[derive(serde::Serialize)]
+pub struct OptionWithValue {
+    /// Option name
     pub name: String,
+    /// Option value
     pub value: OptionValue,
+    /// Why this value was chosen, if known
+    #[serde(skip)]
+    pub rationale: Option<String>,
+    /// Option is only suggested, and written commented out
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub suggestion: bool,
+}
+
+/// Category of options, to group related ones when writing config fragments
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, strum::Display)]
+pub(crate) enum OptionCategory {
+    Filesystem,
+    Devices,
+    Kernel,
+    Network,
+    Process,
+    #[strum(serialize = "System calls")]
+    Syscalls,
+    Capabilities,
+    Other,
+}
+
+impl OptionWithValue {
+    /// Category of the option, based on its name
+    pub(crate) fn category(&self) -> OptionCategory {
+        match self.name.as_str() {
+            "ProtectSystem"
+            | "ProtectHome"
+            | "PrivateTmp"
+            | "ReadWritePaths"
+            | "ReadOnlyPaths"
+            | "RestrictFileSystems"
+            | "ProtectProc"
+            | "ProcSubset"
+            | "RestrictSUIDSGID"
+            | "UMask" => OptionCategory::Filesystem,
+            "PrivateDevices" | "DevicePolicy" | "DeviceAllow" => OptionCategory::Devices,
+            "ProtectKernelTunables"
+            | "ProtectKernelModules"
+            | "ProtectKernelLogs"
+            | "ProtectControlGroups"
+            | "ProtectClock"
+            | "ProtectHostname" => OptionCategory::Kernel,
+            "PrivateNetwork"
+            | "RestrictAddressFamilies"
+            | "IPAddressAllow"
+            | "IPAddressDeny"
+            | "SocketBindAllow"
+            | "SocketBindDeny" => OptionCategory::Network,
+            "PrivateUsers"
+            | "RestrictNamespaces"
+            | "RemoveIPC"
+            | "RestrictRealtime"
+            | "LockPersonality"
+            | "MemoryDenyWriteExecute" => OptionCategory::Process,
+            "SystemCallFilter" | "SystemCallArchitectures" => OptionCategory::Syscalls,
+            "CapabilityBoundingSet" => OptionCategory::Capabilities,
+            _ => OptionCategory::Other,
+        }
+    }
 }
 
 impl FromStr for OptionWithValue {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (name, value) = s
+        let (suggestion, opt) = s
+            .strip_prefix(SUGGESTION_COMMENT_PREFIX)
+            .map_or((false, s), |o| (true, o));
+        let (name, value) = opt
             .split_once('=')
             .ok_or_else(|| anyhow::anyhow!("Missing '=' char in {s:?}"))?;
 
@@ -239,17 +403,29 @@ impl FromStr for OptionWithValue {
             name: name.to_owned(),
             #[expect(clippy::unwrap_used)] // never fails
             value: value.parse().unwrap(),
+            rationale: None,
+            suggestion,
         })
     }
 }
 
 impl fmt::Display for OptionWithValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = if self.suggestion {
+            SUGGESTION_COMMENT_PREFIX
+        } else {
+            ""
+        };
         match &self.value {
             OptionValue::Boolean(value) => {
-                write!(f, "{}={}", self.name, if *value { "true" } else { "false" })
+                write!(
+                    f,
+                    "{prefix}{}={}",
+                    self.name,
+                    if *value { "true" } else { "false" }
+                )
             }
-            OptionValue::String(value) => write!(f, "{}={}", self.name, value),
+            OptionValue::String(value) => write!(f, "{prefix}{}={}", self.name, value),
             OptionValue::List {
                 values,
                 value_if_empty,
@@ -258,7 +434,7 @@ impl fmt::Display for OptionWithValue {
                 ..
             } => {
                 if values.is_empty() {
-                    write!(f, "{}=", self.name)?;
+                    write!(f, "{prefix}{}=", self.name)?;
                     if let Some(value_if_empty) = value_if_empty {
                         write!(f, "{value_if_empty}")
                     } else {
@@ -266,7 +442,7 @@ impl fmt::Display for OptionWithValue {
                     }
                 } else if *repeat_option {
                     for (i, value) in values.iter().enumerate() {
-                        write!(f, "{}=", self.name)?;
+                        write!(f, "{prefix}{}=", self.name)?;
                         if *negation_prefix {
                             write!(f, "~")?;
                         }
@@ -277,7 +453,7 @@ impl fmt::Display for OptionWithValue {
                     }
                     Ok(())
                 } else {
-                    write!(f, "{}=", self.name)?;
+                    write!(f, "{prefix}{}=", self.name)?;
                     if *negation_prefix {
                         write!(f, "~")?;
                     }
@@ -814,7 +990,1219 @@ static SYSCALL_CLASSES: LazyLock<HashMap<&'static str, HashSet<&'static str>>> =
         ])
     });
 
+/// Names of all socket address families, as decoded by strace
+///
+/// `AF_LOCAL` and `AF_ROUTE` are aliases of `AF_UNIX` and `AF_NETLINK`, strace never outputs them.
+// https://man7.org/linux/man-pages/man7/address_families.7.html
+// https://github.com/torvalds/linux/blob/v6.10/include/linux/socket.h
+// curl https://man7.org/linux/man-pages/man7/address_families.7.html | grep -o 'AF_[A-Za-z0-9]*' | sort -u | xargs -I'{}' echo \"'{}'\",
+pub(crate) const SOCKET_FAMILIES: [&str; 45] = [
+    "AF_ALG",
+    "AF_APPLETALK",
+    "AF_ASH",
+    "AF_ATMPVC",
+    "AF_ATMSVC",
+    "AF_AX25",
+    "AF_BLUETOOTH",
+    "AF_BRIDGE",
+    "AF_CAIF",
+    "AF_CAN",
+    "AF_DECnet",
+    "AF_ECONET",
+    "AF_IB",
+    "AF_IEEE802154",
+    "AF_INET",
+    "AF_INET6",
+    "AF_IPX",
+    "AF_IRDA",
+    "AF_ISDN",
+    "AF_IUCV",
+    "AF_KCM",
+    "AF_KEY",
+    "AF_LLC",
+    "AF_MCTP",
+    "AF_MPLS",
+    "AF_NETBEUI",
+    "AF_NETLINK",
+    "AF_NETROM",
+    "AF_NFC",
+    "AF_PACKET",
+    "AF_PHONET",
+    "AF_PPPOX",
+    "AF_QIPCRTR",
+    "AF_RDS",
+    "AF_ROSE",
+    "AF_RXRPC",
+    "AF_SECURITY",
+    "AF_SMC",
+    "AF_SNA",
+    "AF_TIPC",
+    "AF_UNIX",
+    "AF_VSOCK",
+    "AF_WANPIPE",
+    "AF_X25",
+    "AF_XDP",
+];
+
+/// Names of all syscalls known to systemd, on any architecture
+// https://github.com/systemd/systemd/blob/v252/src/shared/seccomp-util.c
+// systemd-analyze syscall-filter @known | grep -v '[#@]' | sort -u | xargs -I'{}' echo \"'{}'\",
+pub(crate) const KNOWN_SYSCALLS: [&str; 514] = [
+    "_llseek",
+    "_newselect",
+    "accept",
+    "accept4",
+    "access",
+    "acct",
+    "add_key",
+    "adjtimex",
+    "alarm",
+    "arc_gettls",
+    "arc_settls",
+    "arc_usr_cmpxchg",
+    "arch_prctl",
+    "arm_fadvise64_64",
+    "atomic_barrier",
+    "atomic_cmpxchg_32",
+    "bind",
+    "bpf",
+    "brk",
+    "cachectl",
+    "cacheflush",
+    "capget",
+    "capset",
+    "chdir",
+    "chmod",
+    "chown",
+    "chown32",
+    "chroot",
+    "clock_adjtime",
+    "clock_adjtime64",
+    "clock_getres",
+    "clock_getres_time64",
+    "clock_gettime",
+    "clock_gettime64",
+    "clock_nanosleep",
+    "clock_nanosleep_time64",
+    "clock_settime",
+    "clock_settime64",
+    "clone",
+    "clone3",
+    "close",
+    "close_range",
+    "connect",
+    "copy_file_range",
+    "creat",
+    "delete_module",
+    "dipc",
+    "dup",
+    "dup2",
+    "dup3",
+    "epoll_create",
+    "epoll_create1",
+    "epoll_ctl",
+    "epoll_ctl_old",
+    "epoll_pwait",
+    "epoll_pwait2",
+    "epoll_wait",
+    "epoll_wait_old",
+    "eventfd",
+    "eventfd2",
+    "exec_with_loader",
+    "execv",
+    "execve",
+    "execveat",
+    "exit",
+    "exit_group",
+    "faccessat",
+    "faccessat2",
+    "fadvise64",
+    "fadvise64_64",
+    "fallocate",
+    "fanotify_init",
+    "fanotify_mark",
+    "fchdir",
+    "fchmod",
+    "fchmodat",
+    "fchmodat2",
+    "fchown",
+    "fchown32",
+    "fchownat",
+    "fcntl",
+    "fcntl64",
+    "fdatasync",
+    "fgetxattr",
+    "finit_module",
+    "flistxattr",
+    "flock",
+    "fork",
+    "fremovexattr",
+    "fsconfig",
+    "fsetxattr",
+    "fsmount",
+    "fsopen",
+    "fspick",
+    "fstat",
+    "fstat64",
+    "fstatat64",
+    "fstatfs",
+    "fstatfs64",
+    "fsync",
+    "ftruncate",
+    "ftruncate64",
+    "futex",
+    "futex_requeue",
+    "futex_time64",
+    "futex_wait",
+    "futex_waitv",
+    "futex_wake",
+    "futimesat",
+    "get_mempolicy",
+    "get_robust_list",
+    "get_thread_area",
+    "getcpu",
+    "getcwd",
+    "getdents",
+    "getdents64",
+    "getdomainname",
+    "getdtablesize",
+    "getegid",
+    "getegid32",
+    "geteuid",
+    "geteuid32",
+    "getgid",
+    "getgid32",
+    "getgroups",
+    "getgroups32",
+    "gethostname",
+    "getitimer",
+    "getpagesize",
+    "getpeername",
+    "getpgid",
+    "getpgrp",
+    "getpid",
+    "getppid",
+    "getpriority",
+    "getrandom",
+    "getresgid",
+    "getresgid32",
+    "getresuid",
+    "getresuid32",
+    "getrlimit",
+    "getrusage",
+    "getsid",
+    "getsockname",
+    "getsockopt",
+    "gettid",
+    "gettimeofday",
+    "getuid",
+    "getuid32",
+    "getxattr",
+    "getxgid",
+    "getxpid",
+    "getxuid",
+    "init_module",
+    "inotify_add_watch",
+    "inotify_init",
+    "inotify_init1",
+    "inotify_rm_watch",
+    "io_cancel",
+    "io_destroy",
+    "io_getevents",
+    "io_pgetevents",
+    "io_pgetevents_time64",
+    "io_setup",
+    "io_submit",
+    "io_uring_enter",
+    "io_uring_register",
+    "io_uring_setup",
+    "ioctl",
+    "ioperm",
+    "iopl",
+    "ioprio_get",
+    "ioprio_set",
+    "ipc",
+    "kcmp",
+    "kern_features",
+    "kexec_file_load",
+    "kexec_load",
+    "keyctl",
+    "kill",
+    "landlock_add_rule",
+    "landlock_create_ruleset",
+    "landlock_restrict_self",
+    "lchown",
+    "lchown32",
+    "lgetxattr",
+    "link",
+    "linkat",
+    "listen",
+    "listxattr",
+    "llistxattr",
+    "lookup_dcookie",
+    "lremovexattr",
+    "lseek",
+    "lsetxattr",
+    "lstat",
+    "lstat64",
+    "madvise",
+    "map_shadow_stack",
+    "mbind",
+    "membarrier",
+    "memfd_create",
+    "memfd_secret",
+    "memory_ordering",
+    "migrate_pages",
+    "mincore",
+    "mkdir",
+    "mkdirat",
+    "mknod",
+    "mknodat",
+    "mlock",
+    "mlock2",
+    "mlockall",
+    "mmap",
+    "mmap2",
+    "modify_ldt",
+    "mount",
+    "mount_setattr",
+    "move_mount",
+    "move_pages",
+    "mprotect",
+    "mq_getsetattr",
+    "mq_notify",
+    "mq_open",
+    "mq_timedreceive",
+    "mq_timedreceive_time64",
+    "mq_timedsend",
+    "mq_timedsend_time64",
+    "mq_unlink",
+    "mremap",
+    "msgctl",
+    "msgget",
+    "msgrcv",
+    "msgsnd",
+    "msync",
+    "multiplexer",
+    "munlock",
+    "munlockall",
+    "munmap",
+    "name_to_handle_at",
+    "nanosleep",
+    "newfstatat",
+    "nice",
+    "old_adjtimex",
+    "oldfstat",
+    "oldlstat",
+    "oldolduname",
+    "oldstat",
+    "oldumount",
+    "olduname",
+    "open",
+    "open_by_handle_at",
+    "open_tree",
+    "openat",
+    "openat2",
+    "or1k_atomic",
+    "osf_fstat",
+    "osf_fstatfs",
+    "osf_fstatfs64",
+    "osf_getdirentries",
+    "osf_getdomainname",
+    "osf_getitimer",
+    "osf_getrusage",
+    "osf_getsysinfo",
+    "osf_gettimeofday",
+    "osf_lstat",
+    "osf_mount",
+    "osf_proplist_syscall",
+    "osf_select",
+    "osf_set_program_attributes",
+    "osf_setitimer",
+    "osf_setsysinfo",
+    "osf_settimeofday",
+    "osf_shmat",
+    "osf_sigprocmask",
+    "osf_sigstack",
+    "osf_stat",
+    "osf_statfs",
+    "osf_statfs64",
+    "osf_swapon",
+    "osf_syscall",
+    "osf_sysinfo",
+    "osf_usleep_thread",
+    "osf_utimes",
+    "osf_utsname",
+    "osf_wait4",
+    "pause",
+    "pciconfig_iobase",
+    "pciconfig_read",
+    "pciconfig_write",
+    "perf_event_open",
+    "perfctr",
+    "personality",
+    "pidfd_getfd",
+    "pidfd_open",
+    "pidfd_send_signal",
+    "pipe",
+    "pipe2",
+    "pivot_root",
+    "pkey_alloc",
+    "pkey_free",
+    "pkey_mprotect",
+    "poll",
+    "ppoll",
+    "ppoll_time64",
+    "prctl",
+    "pread64",
+    "preadv",
+    "preadv2",
+    "prlimit64",
+    "process_madvise",
+    "process_mrelease",
+    "process_vm_readv",
+    "process_vm_writev",
+    "pselect6",
+    "pselect6_time64",
+    "ptrace",
+    "pwrite64",
+    "pwritev",
+    "pwritev2",
+    "quotactl",
+    "quotactl_fd",
+    "read",
+    "readahead",
+    "readdir",
+    "readlink",
+    "readlinkat",
+    "readv",
+    "reboot",
+    "recv",
+    "recvfrom",
+    "recvmmsg",
+    "recvmmsg_time64",
+    "recvmsg",
+    "remap_file_pages",
+    "removexattr",
+    "rename",
+    "renameat",
+    "renameat2",
+    "request_key",
+    "restart_syscall",
+    "riscv_flush_icache",
+    "riscv_hwprobe",
+    "rmdir",
+    "rseq",
+    "rt_sigaction",
+    "rt_sigpending",
+    "rt_sigprocmask",
+    "rt_sigqueueinfo",
+    "rt_sigreturn",
+    "rt_sigsuspend",
+    "rt_sigtimedwait",
+    "rt_sigtimedwait_time64",
+    "rt_tgsigqueueinfo",
+    "rtas",
+    "s390_guarded_storage",
+    "s390_pci_mmio_read",
+    "s390_pci_mmio_write",
+    "s390_runtime_instr",
+    "s390_sthyi",
+    "sched_get_affinity",
+    "sched_get_priority_max",
+    "sched_get_priority_min",
+    "sched_getaffinity",
+    "sched_getattr",
+    "sched_getparam",
+    "sched_getscheduler",
+    "sched_rr_get_interval",
+    "sched_rr_get_interval_time64",
+    "sched_set_affinity",
+    "sched_setaffinity",
+    "sched_setattr",
+    "sched_setparam",
+    "sched_setscheduler",
+    "sched_yield",
+    "seccomp",
+    "select",
+    "semctl",
+    "semget",
+    "semop",
+    "semtimedop",
+    "semtimedop_time64",
+    "send",
+    "sendfile",
+    "sendfile64",
+    "sendmmsg",
+    "sendmsg",
+    "sendto",
+    "set_mempolicy",
+    "set_mempolicy_home_node",
+    "set_robust_list",
+    "set_thread_area",
+    "set_tid_address",
+    "setdomainname",
+    "setfsgid",
+    "setfsgid32",
+    "setfsuid",
+    "setfsuid32",
+    "setgid",
+    "setgid32",
+    "setgroups",
+    "setgroups32",
+    "sethae",
+    "sethostname",
+    "setitimer",
+    "setns",
+    "setpgid",
+    "setpgrp",
+    "setpriority",
+    "setregid",
+    "setregid32",
+    "setresgid",
+    "setresgid32",
+    "setresuid",
+    "setresuid32",
+    "setreuid",
+    "setreuid32",
+    "setrlimit",
+    "setsid",
+    "setsockopt",
+    "settimeofday",
+    "setuid",
+    "setuid32",
+    "setxattr",
+    "sgetmask",
+    "shmat",
+    "shmctl",
+    "shmdt",
+    "shmget",
+    "shutdown",
+    "sigaction",
+    "sigaltstack",
+    "signal",
+    "signalfd",
+    "signalfd4",
+    "sigpending",
+    "sigprocmask",
+    "sigreturn",
+    "sigsuspend",
+    "socket",
+    "socketcall",
+    "socketpair",
+    "splice",
+    "spu_create",
+    "spu_run",
+    "ssetmask",
+    "stat",
+    "stat64",
+    "statfs",
+    "statfs64",
+    "statx",
+    "stime",
+    "subpage_prot",
+    "swapcontext",
+    "swapoff",
+    "swapon",
+    "switch_endian",
+    "symlink",
+    "symlinkat",
+    "sync",
+    "sync_file_range",
+    "sync_file_range2",
+    "syncfs",
+    "sys_debug_setcontext",
+    "syscall",
+    "sysfs",
+    "sysinfo",
+    "syslog",
+    "sysmips",
+    "tee",
+    "tgkill",
+    "time",
+    "timer_create",
+    "timer_delete",
+    "timer_getoverrun",
+    "timer_gettime",
+    "timer_gettime64",
+    "timer_settime",
+    "timer_settime64",
+    "timerfd",
+    "timerfd_create",
+    "timerfd_gettime",
+    "timerfd_gettime64",
+    "timerfd_settime",
+    "timerfd_settime64",
+    "times",
+    "tkill",
+    "truncate",
+    "truncate64",
+    "ugetrlimit",
+    "umask",
+    "umount",
+    "umount2",
+    "uname",
+    "unlink",
+    "unlinkat",
+    "unshare",
+    "userfaultfd",
+    "ustat",
+    "utime",
+    "utimensat",
+    "utimensat_time64",
+    "utimes",
+    "utrap_install",
+    "vfork",
+    "vhangup",
+    "vm86",
+    "vm86old",
+    "vmsplice",
+    "wait4",
+    "waitid",
+    "waitpid",
+    "write",
+    "writev",
+];
+
+/// Default threshold below which binding to a port requires `CAP_NET_BIND_SERVICE` (`PROT_SOCK` in the kernel)
+const PROT_SOCK: u16 = 1024;
+
+/// Paths made read only by `ProtectSystem=true`
+const PROTECT_SYSTEM_PATHS: [&str; 7] = [
+    "/usr/", "/boot/", "/efi/", "/lib/", "/lib64/", "/bin/", "/sbin/",
+];
+
+/// Paths still writable with `ProtectSystem=strict`
+const PROTECT_SYSTEM_STRICT_WRITABLE_PATHS: [&str; 3] = ["/dev/", "/proc/", "/sys/"];
+
+/// Update write denial effect to allow writing to the action's path, if it is not a system path
+fn allow_write_path(
+    effect: &OptionValueEffect,
+    action: &ProgramAction,
+) -> Option<OptionValueEffect> {
+    let OptionValueEffect::DenyWrite(PathDescription::Base { base, exceptions }) = effect else {
+        unreachable!();
+    };
+    // Only the whole filesystem write denial can be relaxed, not the ones of less restrictive values
+    if base != Path::new("/") {
+        return None;
+    }
+    let (ProgramAction::Write(path) | ProgramAction::Create(path)) = action else {
+        return None;
+    };
+    if PROTECT_SYSTEM_PATHS.iter().any(|p| path.starts_with(p)) {
+        return None;
+    }
+    Some(OptionValueEffect::DenyWrite(PathDescription::Base {
+        base: base.to_owned(),
+        exceptions: exceptions.iter().chain(iter::once(path)).cloned().collect(),
+    }))
+}
+
+/// Build `ReadWritePaths=` value from write denial effect, excluding paths that were already writable
+fn read_write_paths_value(
+    effect: &OptionValueEffect,
+    writable_paths: &[PathBuf],
+    grouping_threshold: usize,
+) -> OptionValue {
+    let OptionValueEffect::DenyWrite(PathDescription::Base { exceptions, .. }) = effect else {
+        unreachable!();
+    };
+    let paths = exceptions
+        .iter()
+        .filter(|p| !writable_paths.contains(*p))
+        .cloned();
+    OptionValue::List {
+        // Prefix with '-' so that missing paths (ie. files created at runtime) don't prevent the service from starting
+        values: group_paths(paths, grouping_threshold, &[])
+            .into_iter()
+            .map(|p| format!("-{}", p.to_string_lossy()))
+            .collect(),
+        value_if_empty: None,
+        negation_prefix: false,
+        repeat_option: false,
+        mode: ListMode::WhiteList,
+    }
+}
+
+/// Update read only paths effect to track the action's read or written path
+fn allow_read_only_path(
+    effect: &OptionValueEffect,
+    action: &ProgramAction,
+) -> Option<OptionValueEffect> {
+    match (effect, action) {
+        (
+            OptionValueEffect::Hide(PathDescription::Base { base, exceptions }),
+            ProgramAction::Read(path),
+        ) => Some(OptionValueEffect::Hide(PathDescription::Base {
+            base: base.to_owned(),
+            exceptions: exceptions.iter().chain(iter::once(path)).cloned().collect(),
+        })),
+        (
+            OptionValueEffect::DenyWrite(PathDescription::Base { base, exceptions }),
+            ProgramAction::Write(path) | ProgramAction::Create(path),
+        ) => Some(OptionValueEffect::DenyWrite(PathDescription::Base {
+            base: base.to_owned(),
+            exceptions: exceptions.iter().chain(iter::once(path)).cloned().collect(),
+        })),
+        // Shared files are created where other programs can see them, and can not be read only
+        _ => None,
+    }
+}
+
+/// Build `ReadOnlyPaths=` value from read only paths effect, with the read paths not overlapping any written one
+fn read_only_paths_value(effect: &OptionValueEffect, grouping_threshold: usize) -> OptionValue {
+    let OptionValueEffect::Multiple(effects) = effect else {
+        unreachable!();
+    };
+    let [OptionValueEffect::Hide(PathDescription::Base {
+        exceptions: read_paths,
+        ..
+    }), OptionValueEffect::DenyWrite(PathDescription::Base {
+        exceptions: written_paths,
+        ..
+    })] = effects.as_slice()
+    else {
+        unreachable!();
+    };
+    let paths = read_paths
+        .iter()
+        .filter(|r| {
+            !written_paths
+                .iter()
+                .any(|w| w.starts_with(r) || r.starts_with(w))
+        })
+        .cloned();
+    OptionValue::List {
+        // Prefix with '-' so that missing paths don't prevent the service from starting
+        values: group_paths(paths, grouping_threshold, written_paths)
+            .into_iter()
+            .map(|p| format!("-{}", p.to_string_lossy()))
+            .collect(),
+        value_if_empty: None,
+        negation_prefix: false,
+        repeat_option: false,
+        mode: ListMode::BlackList,
+    }
+}
+
+/// Replace paths by their parent directory when at least `threshold` of them share it, unless it contains
+/// one of the `excluded` paths, and remove paths already covered by another one
+fn group_paths<I>(paths: I, threshold: usize, excluded: &[PathBuf]) -> Vec<PathBuf>
+where
+    I: IntoIterator<Item = PathBuf>,
+{
+    let mut paths: BTreeSet<PathBuf> = paths.into_iter().collect();
+    loop {
+        let mut dir_counts: BTreeMap<&Path, usize> = BTreeMap::new();
+        for path in &paths {
+            // Never group into the root directory
+            if let Some(dir) = path
+                .parent()
+                .filter(|d| d.parent().is_some())
+                .filter(|d| !excluded.iter().any(|e| e.starts_with(d)))
+            {
+                *dir_counts.entry(dir).or_default() += 1;
+            }
+        }
+        let dirs: Vec<PathBuf> = dir_counts
+            .into_iter()
+            .filter(|(_, count)| *count >= threshold)
+            .map(|(dir, _)| dir.to_path_buf())
+            .collect();
+        if dirs.is_empty() {
+            break;
+        }
+        paths.retain(|p| !dirs.iter().any(|d| p.starts_with(d)));
+        paths.extend(dirs);
+    }
+    paths
+        .iter()
+        .filter(|p| !paths.iter().any(|o| (o != *p) && p.starts_with(o)))
+        .cloned()
+        .collect()
+}
+
+/// Update IP filtering effect to allow traffic with the action's peer, if possible
+fn allow_ip_peer(effect: &OptionValueEffect, action: &ProgramAction) -> Option<OptionValueEffect> {
+    let OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(effect_na)) = effect else {
+        unreachable!();
+    };
+    // Bound sockets, or sockets with unknown peers may exchange traffic with anyone
+    let ProgramAction::NetworkActivity(NetworkActivity {
+        kind:
+            SetSpecifier::One(
+                NetworkActivityKind::Connect
+                | NetworkActivityKind::Send
+                | NetworkActivityKind::Recv,
+            ),
+        address: SetSpecifier::One(address),
+        ..
+    }) = action
+    else {
+        return None;
+    };
+    let address = match &effect_na.address {
+        SetSpecifier::All => SetSpecifier::AllExcept(vec![*address]),
+        SetSpecifier::AllExcept(excs) => {
+            SetSpecifier::AllExcept(excs.iter().chain(iter::once(address)).copied().collect())
+        }
+        _ => unreachable!(),
+    };
+    Some(OptionValueEffect::DenyAction(
+        ProgramAction::NetworkActivity(NetworkActivity {
+            address,
+            ..effect_na.clone()
+        }),
+    ))
+}
+
+/// Update a bind filtering effect to allow binding on the action's port, if possible
+fn allow_bind_port(
+    effect: &OptionValueEffect,
+    action: &ProgramAction,
+) -> Option<OptionValueEffect> {
+    let OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(denied_na)) = effect else {
+        unreachable!();
+    };
+    let ProgramAction::NetworkActivity(NetworkActivity {
+        af,
+        proto,
+        kind: SetSpecifier::One(NetworkActivityKind::Bind),
+        local_port,
+        ..
+    }) = action
+    else {
+        return None;
+    };
+    let mut denied_na = denied_na.clone();
+    if !denied_na.af.intersects(af)
+        || !denied_na.proto.intersects(proto)
+        || !denied_na.local_port.intersects(local_port)
+    {
+        return Some(effect.to_owned());
+    }
+    // Bind rules for other protocols can only be expressed per address family,
+    // which would also allow all TCP & UDP ports
+    if !matches!(denied_na.proto, SetSpecifier::One(_)) {
+        return None;
+    }
+    if let CountableSetSpecifier::One(port) = local_port {
+        denied_na.local_port.remove(port);
+    } else {
+        // Wildcard or ephemeral port, allow binding to any port
+        denied_na.local_port = CountableSetSpecifier::None;
+    }
+    Some(OptionValueEffect::DenyAction(
+        ProgramAction::NetworkActivity(denied_na),
+    ))
+}
+
+/// Build `SocketBindAllow` value from the ports no longer denied by the bind filtering effect
+fn socket_bind_allow_value(effect: &OptionValueEffect) -> OptionValue {
+    let OptionValueEffect::Multiple(effects) = effect else {
+        unreachable!();
+    };
+    OptionValue::List {
+        values: effects
+            .iter()
+            .flat_map(|e| {
+                let OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(denied_na)) = e
+                else {
+                    unreachable!();
+                };
+                let (SetSpecifier::One(af), SetSpecifier::One(proto)) =
+                    (&denied_na.af, &denied_na.proto)
+                else {
+                    return vec![];
+                };
+                match &denied_na.local_port {
+                    CountableSetSpecifier::None => vec![format!("{af}:{proto}")],
+                    CountableSetSpecifier::AllExcept(ports) => ports
+                        .iter()
+                        .map(|port| format!("{af}:{proto}:{port}"))
+                        .collect(),
+                    _ => vec![],
+                }
+            })
+            .collect(),
+        value_if_empty: None,
+        negation_prefix: false,
+        repeat_option: true,
+        mode: ListMode::WhiteList,
+    }
+}
+
+/// Device nodes always accessible with `DevicePolicy=closed`
+const DEVICE_POLICY_CLOSED_ALLOWED: [&str; 5] = [
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+];
+
+/// Directories of device nodes allowed as a whole by their device class (as in `/proc/devices`), because their
+/// node names are allocated dynamically
+const DEVICE_CLASS_DIRS: [(&str, DeviceType, &str); 1] = [("/dev/pts/", DeviceType::Char, "pts")];
+
+/// Make device access effect compatible with an observed device access
+fn allow_device(effect: &OptionValueEffect, action: &ProgramAction) -> Option<OptionValueEffect> {
+    let OptionValueEffect::DenyAction(ProgramAction::DeviceAccess(SetSpecifier::AllExcept(excs))) =
+        effect
+    else {
+        unreachable!();
+    };
+    let ProgramAction::DeviceAccess(accesses) = action else {
+        return None;
+    };
+    // Access to any device can not be allowed with a list
+    let accesses = accesses.elements()?;
+    Some(OptionValueEffect::DenyAction(ProgramAction::DeviceAccess(
+        SetSpecifier::AllExcept(
+            excs.iter()
+                .chain(accesses.iter().filter(|a| !excs.contains(a)))
+                .cloned()
+                .collect(),
+        ),
+    )))
+}
+
+/// Build `DeviceAllow=` value from device access effect
+fn device_allow_value(effect: &OptionValueEffect) -> OptionValue {
+    let OptionValueEffect::DenyAction(ProgramAction::DeviceAccess(SetSpecifier::AllExcept(excs))) =
+        effect
+    else {
+        unreachable!();
+    };
+    let mut devices: Vec<(String, bool, bool)> = Vec::new();
+    for access in excs.iter().filter(|a| {
+        !DEVICE_POLICY_CLOSED_ALLOWED
+            .iter()
+            .any(|p| a.path == Path::new(p))
+    }) {
+        let device = DEVICE_CLASS_DIRS
+            .iter()
+            .find(|(dir, type_, _)| access.path.starts_with(dir) && (access.type_ == *type_))
+            .map_or_else(
+                || access.path.display().to_string(),
+                |(_, type_, class)| match type_ {
+                    DeviceType::Char => format!("char-{class}"),
+                    DeviceType::Block => format!("block-{class}"),
+                },
+            );
+        let idx = if let Some(idx) = devices.iter().position(|(d, _, _)| *d == device) {
+            idx
+        } else {
+            devices.push((device, false, false));
+            devices.len() - 1
+        };
+        if access.write {
+            devices[idx].2 = true;
+        } else {
+            devices[idx].1 = true;
+        }
+    }
+    OptionValue::List {
+        values: devices
+            .into_iter()
+            .map(|(device, read, write)| {
+                format!(
+                    "{device} {}{}",
+                    if read { "r" } else { "" },
+                    if write { "w" } else { "" }
+                )
+            })
+            .collect(),
+        value_if_empty: None,
+        negation_prefix: false,
+        repeat_option: true,
+        mode: ListMode::WhiteList,
+    }
+}
+
+/// Filesystem types of the `@common-block` set
+/// See <https://github.com/systemd/systemd/blob/v254/src/shared/filesystems-gperf.gperf>
+const COMMON_BLOCK_FS_TYPES: [&str; 11] = [
+    "btrfs", "erofs", "exfat", "ext4", "f2fs", "iso9660", "ntfs3", "squashfs", "udf", "vfat", "xfs",
+];
+
+/// Make filesystem access effect compatible with the observed filesystem types
+fn allow_file_systems(
+    effect: &OptionValueEffect,
+    action: &ProgramAction,
+) -> Option<OptionValueEffect> {
+    let OptionValueEffect::DenyAction(ProgramAction::FileSystemAccess(SetSpecifier::AllExcept(
+        excs,
+    ))) = effect
+    else {
+        unreachable!();
+    };
+    let ProgramAction::FileSystemAccess(fs_types) = action else {
+        return None;
+    };
+    let fs_types = fs_types.elements()?;
+    Some(OptionValueEffect::DenyAction(
+        ProgramAction::FileSystemAccess(SetSpecifier::AllExcept(
+            excs.iter()
+                .chain(fs_types.iter().filter(|t| !excs.contains(t)))
+                .cloned()
+                .collect(),
+        )),
+    ))
+}
+
+/// Build `RestrictFileSystems=` value from filesystem access effect
+fn restrict_file_systems_value(effect: &OptionValueEffect) -> OptionValue {
+    let OptionValueEffect::DenyAction(ProgramAction::FileSystemAccess(SetSpecifier::AllExcept(
+        excs,
+    ))) = effect
+    else {
+        unreachable!();
+    };
+    OptionValue::List {
+        values: iter::once("@common-block".to_owned())
+            .chain(
+                excs.iter()
+                    .filter(|t| !COMMON_BLOCK_FS_TYPES.contains(&t.as_str()))
+                    .sorted()
+                    .cloned(),
+            )
+            .collect(),
+        value_if_empty: None,
+        negation_prefix: false,
+        repeat_option: false,
+        mode: ListMode::WhiteList,
+    }
+}
+
+/// Make non native syscalls effect compatible with the observed architectures
+fn allow_syscall_archs(
+    effect: &OptionValueEffect,
+    action: &ProgramAction,
+) -> Option<OptionValueEffect> {
+    let OptionValueEffect::DenyAction(ProgramAction::NonNativeSyscalls(SetSpecifier::AllExcept(
+        excs,
+    ))) = effect
+    else {
+        unreachable!();
+    };
+    let ProgramAction::NonNativeSyscalls(archs) = action else {
+        return None;
+    };
+    let archs = archs.elements()?;
+    Some(OptionValueEffect::DenyAction(
+        ProgramAction::NonNativeSyscalls(SetSpecifier::AllExcept(
+            excs.iter()
+                .chain(archs.iter().filter(|a| !excs.contains(a)))
+                .cloned()
+                .collect(),
+        )),
+    ))
+}
+
+/// Build `SystemCallArchitectures=` value from non native syscalls effect
+fn system_call_architectures_value(effect: &OptionValueEffect) -> OptionValue {
+    let OptionValueEffect::DenyAction(ProgramAction::NonNativeSyscalls(SetSpecifier::AllExcept(
+        excs,
+    ))) = effect
+    else {
+        unreachable!();
+    };
+    OptionValue::List {
+        values: iter::once("native".to_owned())
+            .chain(excs.iter().sorted().cloned())
+            .collect(),
+        value_if_empty: None,
+        negation_prefix: false,
+        repeat_option: false,
+        mode: ListMode::WhiteList,
+    }
+}
+
+/// Make syscall allow list effect compatible with the observed syscalls
+fn allow_syscalls(effect: &OptionValueEffect, action: &ProgramAction) -> Option<OptionValueEffect> {
+    let OptionValueEffect::AllowSyscalls(allowed) = effect else {
+        unreachable!();
+    };
+    let ProgramAction::Syscalls(syscalls) = action else {
+        return None;
+    };
+    Some(OptionValueEffect::AllowSyscalls(
+        allowed.iter().chain(syscalls).cloned().collect(),
+    ))
+}
+
+/// Build `SystemCallFilter=` allow list value from syscall allow list effect, collapsing syscalls into the classes
+/// whose syscalls are all allowed, and only listing the remaining ones explicitly
+fn system_call_filter_allow_value(effect: &OptionValueEffect) -> OptionValue {
+    let OptionValueEffect::AllowSyscalls(allowed) = effect else {
+        unreachable!();
+    };
+    let allowed_names: HashSet<&str> = allowed.iter().map(String::as_str).collect();
+    let full_classes: Vec<_> = SYSCALL_CLASSES
+        .keys()
+        .copied()
+        .sorted_unstable()
+        .filter(|&c| DenySyscalls::Class(c).syscalls().is_subset(&allowed_names))
+        .collect();
+    // Skip classes already included in another allowed one
+    let classes: Vec<_> = full_classes
+        .iter()
+        .copied()
+        .filter(|c| {
+            !full_classes
+                .iter()
+                .any(|&o| DenySyscalls::Class(o).nested_classes().contains(c))
+        })
+        .collect();
+    let covered: HashSet<_> = classes
+        .iter()
+        .flat_map(|&c| DenySyscalls::Class(c).syscalls())
+        .collect();
+    OptionValue::List {
+        values: classes
+            .into_iter()
+            .map(|c| format!("@{c}"))
+            .chain(
+                allowed
+                    .iter()
+                    .filter(|s| !covered.contains(s.as_str()))
+                    .cloned(),
+            )
+            .collect(),
+        value_if_empty: None,
+        negation_prefix: false,
+        repeat_option: false,
+        mode: ListMode::WhiteList,
+    }
+}
+
+/// Build `IPAddressAllow=` value from IP filtering effect
+fn ip_allow_value(effect: &OptionValueEffect, ipv4_prefix: u8, ipv6_prefix: u8) -> OptionValue {
+    let OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(NetworkActivity {
+        address: SetSpecifier::AllExcept(addresses),
+        ..
+    })) = effect
+    else {
+        unreachable!();
+    };
+    OptionValue::List {
+        values: ip_allow_list(addresses, ipv4_prefix, ipv6_prefix),
+        value_if_empty: Some(String::new()),
+        negation_prefix: false,
+        repeat_option: false,
+        mode: ListMode::WhiteList,
+    }
+}
+
+/// Build list of allowed IP networks, coarsening addresses to the given prefix lengths, and merging adjacent networks
+fn ip_allow_list(addresses: &[IpAddr], ipv4_prefix: u8, ipv6_prefix: u8) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut ipv4_addrs = Vec::new();
+    let mut ipv6_addrs = Vec::new();
+    for address in addresses {
+        // Use systemd's special values for those, which cover both IPv4 and IPv6
+        let special = if address.is_loopback() || address.is_unspecified() {
+            Some("localhost")
+        } else if match address {
+            IpAddr::V4(a) => a.is_link_local(),
+            IpAddr::V6(a) => (a.segments()[0] & 0xffc0) == 0xfe80,
+        } {
+            Some("link-local")
+        } else if address.is_multicast() {
+            Some("multicast")
+        } else {
+            None
+        };
+        match (special, address) {
+            (Some(special), _) => {
+                if !values.iter().any(|v| v == special) {
+                    values.push(special.to_owned());
+                }
+            }
+            (None, IpAddr::V4(a)) => ipv4_addrs.push(u128::from(a.to_bits()) << 96),
+            (None, IpAddr::V6(a)) => ipv6_addrs.push(a.to_bits()),
+        }
+    }
+    values.extend(
+        ip_networks(ipv4_addrs, ipv4_prefix)
+            .into_iter()
+            .map(|(net, len)| {
+                let net = Ipv4Addr::from_bits((net >> 96) as u32);
+                if len == 32 {
+                    net.to_string()
+                } else {
+                    format!("{net}/{len}")
+                }
+            }),
+    );
+    values.extend(
+        ip_networks(ipv6_addrs, ipv6_prefix)
+            .into_iter()
+            .map(|(net, len)| {
+                let net = Ipv6Addr::from_bits(net);
+                if len == 128 {
+                    net.to_string()
+                } else {
+                    format!("{net}/{len}")
+                }
+            }),
+    );
+    values
+}
+
+/// Build minimal set of networks covering addresses, with addresses as left aligned 128 bits integers
+fn ip_networks(addrs: Vec<u128>, prefix_len: u8) -> Vec<(u128, u8)> {
+    let mask = |len: u8| u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+    let mut nets: Vec<_> = addrs
+        .into_iter()
+        .map(|a| (a & mask(prefix_len), prefix_len))
+        .collect();
+    loop {
+        nets.sort_unstable();
+        nets.dedup();
+        // Remove networks included in a larger one
+        let prev_nets = nets.clone();
+        nets.retain(|(net, len)| {
+            !prev_nets.iter().any(|(other_net, other_len)| {
+                other_len < len && net & mask(*other_len) == *other_net
+            })
+        });
+        // Merge sibling networks into their parent
+        let mut merged = false;
+        let mut i = 0;
+        while i + 1 < nets.len() {
+            let (net, len) = nets[i];
+            if len > 0 {
+                let sibling_bit = 1 << (128 - u32::from(len));
+                if (net & sibling_bit == 0) && (nets[i + 1] == (net | sibling_bit, len)) {
+                    nets[i] = (net, len - 1);
+                    nets.remove(i + 1);
+                    merged = true;
+                }
+            }
+            i += 1;
+        }
+        if !merged {
+            break;
+        }
+    }
+    nets
+}
+
 #[expect(clippy::too_many_lines)]
+/// Names of all options that can be generated, whatever the target versions and hardening options
+pub(crate) fn option_names() -> BTreeSet<&'static str> {
+    let sd_version = SystemdVersion::new(u16::MAX, u16::MAX);
+    let kernel_version = KernelVersion::new(u16::MAX, u16::MAX, u16::MAX);
+    [HardeningOptions::safe(), HardeningOptions::strict()]
+        .iter()
+        .flat_map(|o| build_options(&sd_version, &kernel_version, o))
+        .map(|o| o.name)
+        .collect()
+}
+
 pub(crate) fn build_options(
     systemd_version: &SystemdVersion,
     kernel_version: &KernelVersion,
@@ -835,50 +2223,131 @@ pub(crate) fn build_options(
     // which we need to model
 
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectSystem=
-    let protect_system_yes_nowrite: Vec<_> = [
-        "/usr/", "/boot/", "/efi/", "/lib/", "/lib64/", "/bin/", "/sbin/",
-    ]
-    .iter()
-    .map(|p| {
-        OptionValueEffect::DenyWrite(PathDescription::Base {
-            base: p.into(),
-            exceptions: vec![],
+    let protect_system_yes_nowrite: Vec<_> = PROTECT_SYSTEM_PATHS
+        .iter()
+        .map(|p| {
+            OptionValueEffect::DenyWrite(PathDescription::Base {
+                base: p.into(),
+                exceptions: vec![],
+            })
         })
-    })
-    .collect();
+        .collect();
     let mut protect_system_full_nowrite = protect_system_yes_nowrite.clone();
     protect_system_full_nowrite.push(OptionValueEffect::DenyWrite(PathDescription::Base {
         base: "/etc/".into(),
         exceptions: vec![],
     }));
+    // Paths declared by the unit (ie. with StateDirectory=) are always writable
+    let protect_system_strict_writable: Vec<_> = PROTECT_SYSTEM_STRICT_WRITABLE_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .chain(hardening_opts.writable_paths.iter().cloned())
+        .collect();
+    let protect_system_strict_nowrite = OptionValueEffect::DenyWrite(PathDescription::Base {
+        base: "/".into(),
+        exceptions: protect_system_strict_writable.clone(),
+    });
+    let strict_min_version = SystemdVersion::new(232, 0);
     options.push(OptionDescription {
         name: "ProtectSystem",
+        min_systemd_version: Some(SystemdVersion::new(214, 0)),
         possible_values: vec![
             OptionValueDescription {
                 value: OptionValue::Boolean(true),
                 desc: OptionEffect::Simple(OptionValueEffect::Multiple(protect_system_yes_nowrite)),
+                min_systemd_version: None,
             },
             OptionValueDescription {
                 value: OptionValue::String("full".to_owned()),
                 desc: OptionEffect::Simple(OptionValueEffect::Multiple(
                     protect_system_full_nowrite,
                 )),
+                min_systemd_version: None,
             },
             OptionValueDescription {
                 value: OptionValue::String("strict".to_owned()),
-                desc: OptionEffect::Simple(OptionValueEffect::DenyWrite(PathDescription::Base {
-                    base: "/".into(),
-                    exceptions: vec!["/dev/".into(), "/proc/".into(), "/sys/".into()],
-                })),
+                desc: OptionEffect::Simple(protect_system_strict_nowrite.clone()),
+                min_systemd_version: Some(strict_min_version.clone()),
             },
         ],
-        updater: None,
+        // In aggressive mode, writable paths are allowed with ReadWritePaths=
+        updater: (matches!(hardening_opts.mode, HardeningMode::Aggressive)
+            && (systemd_version >= &strict_min_version))
+            .then(|| OptionUpdater {
+                effect: allow_write_path,
+                value: Box::new(|_| OptionValue::String("strict".to_owned())),
+            }),
     });
 
+    if let HardeningMode::Aggressive = hardening_opts.mode {
+        // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ReadWritePaths=
+        //
+        // This is only safe if the service does not write to paths that were not observed during profiling,
+        // so only enable it in aggressive mode
+        let grouping_threshold = hardening_opts.path_grouping_threshold;
+        let read_only_effect = OptionValueEffect::Multiple(vec![
+            OptionValueEffect::Hide(PathDescription::Base {
+                base: "/".into(),
+                exceptions: vec![],
+            }),
+            OptionValueEffect::DenyWrite(PathDescription::Base {
+                base: "/".into(),
+                exceptions: protect_system_strict_writable.clone(),
+            }),
+        ]);
+        options.push(OptionDescription {
+            name: "ReadWritePaths",
+            min_systemd_version: Some(SystemdVersion::new(231, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::List {
+                    values: vec![],
+                    value_if_empty: None,
+                    negation_prefix: false,
+                    repeat_option: false,
+                    mode: ListMode::WhiteList,
+                },
+                desc: OptionEffect::Simple(protect_system_strict_nowrite),
+                min_systemd_version: None,
+            }],
+            updater: Some(OptionUpdater {
+                effect: allow_write_path,
+                value: Box::new(move |e| {
+                    read_write_paths_value(e, &protect_system_strict_writable, grouping_threshold)
+                }),
+            }),
+        });
+
+        // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ReadOnlyPaths=
+        //
+        // Paths that were read but never written are made read only. This is modeled as also hiding the paths
+        // that were not read, so that reads can be tracked.
+        // Paths still writable with ProtectSystem=strict, and paths declared by the unit are left alone.
+        options.push(OptionDescription {
+            name: "ReadOnlyPaths",
+            min_systemd_version: Some(SystemdVersion::new(231, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::List {
+                    values: vec![],
+                    value_if_empty: None,
+                    negation_prefix: false,
+                    repeat_option: false,
+                    mode: ListMode::BlackList,
+                },
+                desc: OptionEffect::Simple(read_only_effect),
+                min_systemd_version: None,
+            }],
+            updater: Some(OptionUpdater {
+                effect: allow_read_only_path,
+                value: Box::new(move |e| read_only_paths_value(e, grouping_threshold)),
+            }),
+        });
+    }
+
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectHome=
     let home_paths = ["/home/", "/root/", "/run/user/"];
     options.push(OptionDescription {
         name: "ProtectHome",
+        min_systemd_version: Some(SystemdVersion::new(214, 0)),
         possible_values: vec![
             OptionValueDescription {
                 value: OptionValue::String("read-only".to_owned()),
@@ -893,6 +2362,7 @@ pub(crate) fn build_options(
                         })
                         .collect(),
                 )),
+                min_systemd_version: None,
             },
             OptionValueDescription {
                 value: OptionValue::Boolean(true),
@@ -907,6 +2377,7 @@ pub(crate) fn build_options(
                         })
                         .collect(),
                 )),
+                min_systemd_version: None,
             },
             OptionValueDescription {
                 value: OptionValue::String("tmpfs".to_owned()),
@@ -927,6 +2398,7 @@ pub(crate) fn build_options(
                         }))
                         .collect(),
                 )),
+                min_systemd_version: Some(SystemdVersion::new(242, 0)),
             },
         ],
         updater: None,
@@ -935,6 +2407,7 @@ pub(crate) fn build_options(
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#PrivateTmp=
     options.push(OptionDescription {
         name: "PrivateTmp",
+        min_systemd_version: None,
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
             desc: OptionEffect::Simple(OptionValueEffect::Multiple(vec![
@@ -947,6 +2420,7 @@ pub(crate) fn build_options(
                     exceptions: vec![],
                 }),
             ])),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -954,6 +2428,7 @@ pub(crate) fn build_options(
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#PrivateDevices=
     options.push(OptionDescription {
         name: "PrivateDevices",
+        min_systemd_version: Some(SystemdVersion::new(209, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
             desc: OptionEffect::Simple(OptionValueEffect::Multiple(vec![
@@ -980,13 +2455,65 @@ pub(crate) fn build_options(
                 }),
                 OptionValueEffect::DenySyscalls(DenySyscalls::Class("raw-io")),
             ])),
+            min_systemd_version: None,
         }],
         updater: None,
     });
 
+    // https://www.freedesktop.org/software/systemd/man/latest/systemd.resource-control.html#DevicePolicy=auto%7Cclosed%7Cstrict
+    //
+    // Deny access to all device nodes, except the standard pseudo devices, and the ones we have seen.
+    // Both options share the same effect and updater, so they are always enabled together.
+    let device_effect =
+        OptionValueEffect::DenyAction(ProgramAction::DeviceAccess(SetSpecifier::AllExcept(
+            DEVICE_POLICY_CLOSED_ALLOWED
+                .iter()
+                .flat_map(|p| {
+                    [false, true].into_iter().map(|write| DeviceAccess {
+                        path: p.into(),
+                        type_: DeviceType::Char,
+                        write,
+                    })
+                })
+                .collect(),
+        )));
+    options.push(OptionDescription {
+        name: "DevicePolicy",
+        min_systemd_version: None,
+        possible_values: vec![OptionValueDescription {
+            value: OptionValue::String("closed".to_owned()),
+            desc: OptionEffect::Simple(device_effect.clone()),
+            min_systemd_version: None,
+        }],
+        updater: Some(OptionUpdater {
+            effect: allow_device,
+            value: Box::new(|_| OptionValue::String("closed".to_owned())),
+        }),
+    });
+    options.push(OptionDescription {
+        name: "DeviceAllow",
+        min_systemd_version: None,
+        possible_values: vec![OptionValueDescription {
+            value: OptionValue::List {
+                values: vec![],
+                value_if_empty: None,
+                negation_prefix: false,
+                repeat_option: true,
+                mode: ListMode::WhiteList,
+            },
+            desc: OptionEffect::Simple(device_effect),
+            min_systemd_version: None,
+        }],
+        updater: Some(OptionUpdater {
+            effect: allow_device,
+            value: Box::new(device_allow_value),
+        }),
+    });
+
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectKernelTunables=
     options.push(OptionDescription {
         name: "ProtectKernelTunables",
+        min_systemd_version: Some(SystemdVersion::new(232, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
             desc: OptionEffect::Simple(OptionValueEffect::Multiple(
@@ -1025,8 +2552,14 @@ pub(crate) fn build_options(
                         exceptions: vec![],
                     })),
                 )
+                // Kernel API filesystems are made read-only, and mounts done in our private mount namespace are
+                // not propagated to the host
+                .chain(iter::once(OptionValueEffect::DenyAction(
+                    ProgramAction::ManageMounts,
+                )))
                 .collect(),
             )),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1034,6 +2567,7 @@ pub(crate) fn build_options(
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectKernelModules=
     options.push(OptionDescription {
         name: "ProtectKernelModules",
+        min_systemd_version: Some(SystemdVersion::new(232, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
             desc: OptionEffect::Simple(OptionValueEffect::Multiple(vec![
@@ -1047,7 +2581,9 @@ pub(crate) fn build_options(
                     exceptions: vec![],
                 }),
                 OptionValueEffect::DenySyscalls(DenySyscalls::Class("module")),
+                OptionValueEffect::DenyAction(ProgramAction::ManageKernelModules),
             ])),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1055,6 +2591,7 @@ pub(crate) fn build_options(
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectKernelLogs=
     options.push(OptionDescription {
         name: "ProtectKernelLogs",
+        min_systemd_version: Some(SystemdVersion::new(244, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
             desc: OptionEffect::Simple(OptionValueEffect::Multiple(vec![
@@ -1067,7 +2604,10 @@ pub(crate) fn build_options(
                     base: "/dev/kmsg".into(),
                     exceptions: vec![],
                 }),
+                // Also denied by a seccomp filter
+                OptionValueEffect::DenySyscalls(DenySyscalls::Single("syslog")),
             ])),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1075,12 +2615,17 @@ pub(crate) fn build_options(
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectControlGroups=
     options.push(OptionDescription {
         name: "ProtectControlGroups",
+        min_systemd_version: Some(SystemdVersion::new(232, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
-            desc: OptionEffect::Simple(OptionValueEffect::DenyWrite(PathDescription::Base {
-                base: "/sys/fs/cgroup/".into(),
-                exceptions: vec![],
-            })),
+            desc: OptionEffect::Simple(OptionValueEffect::Multiple(vec![
+                OptionValueEffect::DenyWrite(PathDescription::Base {
+                    base: "/sys/fs/cgroup/".into(),
+                    exceptions: vec![],
+                }),
+                OptionValueEffect::DenyAction(ProgramAction::ManageMounts),
+            ])),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1089,11 +2634,10 @@ pub(crate) fn build_options(
     // https://github.com/systemd/systemd/blob/v247/NEWS#L342
     // https://github.com/systemd/systemd/commit/4e39995371738b04d98d27b0d34ea8fe09ec9fab
     // https://docs.kernel.org/filesystems/proc.html#mount-options
-    if (systemd_version >= &SystemdVersion::new(247, 0))
-        && (kernel_version >= &KernelVersion::new(5, 8, 0))
-    {
+    if kernel_version >= &KernelVersion::new(5, 8, 0) {
         options.push(OptionDescription {
             name: "ProtectProc",
+            min_systemd_version: Some(SystemdVersion::new(247, 0)),
             // Since we have no easy & reliable (race free) way to know which process belongs to
             // which user, only support the most restrictive option
             possible_values: vec![OptionValueDescription {
@@ -1102,6 +2646,27 @@ pub(crate) fn build_options(
                     #[expect(clippy::unwrap_used)]
                     regex::bytes::Regex::new("^/proc/[0-9]+(/|$)").unwrap(),
                 ))),
+                min_systemd_version: None,
+            }],
+            updater: None,
+        });
+
+        // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProcSubset=
+        options.push(OptionDescription {
+            name: "ProcSubset",
+            min_systemd_version: Some(SystemdVersion::new(247, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::String("pid".to_owned()),
+                // Hide everything except process directories and self link
+                // (thread-self paths are normalized to self by summarize)
+                desc: OptionEffect::Simple(OptionValueEffect::Hide(PathDescription::Pattern(
+                    #[expect(clippy::unwrap_used)]
+                    regex::bytes::Regex::new(
+                        "^/proc/([^0-9s/][^/]*|[0-9]+[^0-9/][^/]*|s([^e/][^/]*|e([^l/][^/]*|l([^f/][^/]*|f[^/]+)?)?)?)(/|$)",
+                    )
+                    .unwrap(),
+                ))),
+                min_systemd_version: None,
             }],
             updater: None,
         });
@@ -1111,74 +2676,32 @@ pub(crate) fn build_options(
     // https://github.com/systemd/systemd/blob/v254/src/shared/seccomp-util.c#L1721
     options.push(OptionDescription {
         name: "MemoryDenyWriteExecute",
+        min_systemd_version: Some(SystemdVersion::new(231, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
             desc: OptionEffect::Simple(OptionValueEffect::DenyAction(
                 ProgramAction::WriteExecuteMemoryMapping,
             )),
+            min_systemd_version: None,
         }],
         updater: None,
     });
 
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#RestrictAddressFamilies=
-    // https://man7.org/linux/man-pages/man7/address_families.7.html
-    // curl https://man7.org/linux/man-pages/man7/address_families.7.html | grep -o 'AF_[A-Za-z0-9]*' | sort -u | xargs -I'{}' echo \"'{}'\",
-    let afs = [
-        "AF_ALG",
-        "AF_APPLETALK",
-        "AF_ATMPVC",
-        "AF_ATMSVC",
-        "AF_AX25",
-        "AF_BLUETOOTH",
-        "AF_BRIDGE",
-        "AF_CAIF",
-        "AF_CAN",
-        "AF_DECnet",
-        "AF_ECONET",
-        "AF_IB",
-        "AF_IEEE802154",
-        "AF_INET",
-        "AF_INET6",
-        "AF_IPX",
-        "AF_IRDA",
-        "AF_ISDN",
-        "AF_IUCV",
-        "AF_KCM",
-        "AF_KEY",
-        "AF_LLC",
-        "AF_LOCAL",
-        "AF_MPLS",
-        "AF_NETBEUI",
-        "AF_NETLINK",
-        "AF_NETROM",
-        "AF_PACKET",
-        "AF_PHONET",
-        "AF_PPPOX",
-        "AF_QIPCRTR",
-        "AF_RDS",
-        "AF_ROSE",
-        "AF_RXRPC",
-        "AF_SECURITY",
-        "AF_SMC",
-        "AF_TIPC",
-        "AF_UNIX",
-        "AF_VSOCK",
-        "AF_WANPIPE",
-        "AF_X25",
-        "AF_XDP",
-    ];
     options.push(OptionDescription {
         name: "RestrictAddressFamilies",
+        min_systemd_version: Some(SystemdVersion::new(211, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::List {
-                values: afs.iter().map(|s| (*s).to_owned()).collect(),
+                values: SOCKET_FAMILIES.iter().map(|s| (*s).to_owned()).collect(),
                 value_if_empty: Some("none".to_owned()),
                 negation_prefix: false,
                 repeat_option: false,
                 mode: ListMode::WhiteList,
             },
             desc: OptionEffect::Cumulative(
-                afs.into_iter()
+                SOCKET_FAMILIES
+                    .into_iter()
                     .map(|af| {
                         OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(
                             NetworkActivity {
@@ -1187,11 +2710,13 @@ pub(crate) fn build_options(
                                 proto: SetSpecifier::All,
                                 kind: SetSpecifier::All,
                                 local_port: CountableSetSpecifier::All,
+                                address: SetSpecifier::All,
                             },
                         ))
                     })
                     .collect(),
             ),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1204,6 +2729,7 @@ pub(crate) fn build_options(
         // Although this is probably a very rare/niche case, it is possible, so we consider it only in aggressive mode
         options.push(OptionDescription {
             name: "PrivateNetwork",
+            min_systemd_version: None,
             possible_values: vec![OptionValueDescription {
                 value: OptionValue::Boolean(true),
                 desc: OptionEffect::Simple(OptionValueEffect::DenyAction(
@@ -1212,116 +2738,197 @@ pub(crate) fn build_options(
                         proto: SetSpecifier::All,
                         kind: SetSpecifier::All,
                         local_port: CountableSetSpecifier::All,
+                        address: SetSpecifier::All,
                     }),
                 )),
+                min_systemd_version: None,
             }],
             updater: None,
         });
     }
 
     // https://www.freedesktop.org/software/systemd/man/systemd.resource-control.html#SocketBindAllow=bind-rule
-    //
-    // We don't go as far as allowing/denying individual ports, as that would easily break for example if a port is changed
-    // in a server configuration
     let deny_binds: Vec<_> = SocketFamily::iter()
         .take(2)
         .cartesian_product(SocketProtocol::iter().take(2))
         .collect();
-    options.push(OptionDescription {
-        name: "SocketBindDeny",
-        possible_values: vec![OptionValueDescription {
-            value: OptionValue::List {
-                values: deny_binds
-                    .iter()
-                    .map(|(af, proto)| format!("{af}:{proto}"))
-                    .collect(),
-                value_if_empty: None,
-                negation_prefix: false,
-                repeat_option: true,
-                mode: ListMode::BlackList,
-            },
-            desc: OptionEffect::Cumulative(
-                deny_binds
-                    .into_iter()
-                    .map(|(af, proto)| {
-                        OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(
-                            NetworkActivity {
-                                af: SetSpecifier::One(af),
-                                proto: SetSpecifier::One(proto),
-                                kind: SetSpecifier::One(NetworkActivityKind::Bind),
-                                local_port: CountableSetSpecifier::All,
-                            },
-                        ))
+    if hardening_opts.network_firewalling {
+        // Deny all binds, except on the ports we have seen.
+        // Binds to port 0 let the kernel pick an ephemeral port, so we allow any port for that family & protocol.
+        // Both options share the same effect and updater, so they are always enabled together.
+        let bind_effect = OptionValueEffect::Multiple(
+            deny_binds
+                .into_iter()
+                .map(|(af, proto)| (SetSpecifier::One(af), SetSpecifier::One(proto)))
+                .chain(iter::once((
+                    SetSpecifier::Some(vec![SocketFamily::Ipv4, SocketFamily::Ipv6]),
+                    SetSpecifier::AllExcept(vec![SocketProtocol::Tcp, SocketProtocol::Udp]),
+                )))
+                .map(|(af, proto)| {
+                    let effect = OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(
+                        NetworkActivity {
+                            af,
+                            proto,
+                            kind: SetSpecifier::One(NetworkActivityKind::Bind),
+                            local_port: CountableSetSpecifier::All,
+                            address: SetSpecifier::All,
+                        },
+                    ));
+                    known_bind_actions.iter().fold(effect, |effect, action| {
+                        allow_bind_port(&effect, action).unwrap_or(effect)
                     })
-                    .collect(),
-            ),
-        }],
-        updater: hardening_opts.network_firewalling.then_some(OptionUpdater {
-            effect: |e, a| {
-                let OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(effect_na)) = e
-                else {
-                    unreachable!();
-                };
-                let ProgramAction::NetworkActivity(NetworkActivity {
-                    local_port: CountableSetSpecifier::One(local_port),
-                    ..
-                }) = a
-                else {
-                    unreachable!();
-                };
-                let mut new_eff_local_port = effect_na.local_port.clone();
-                new_eff_local_port.remove(local_port);
-                Some(OptionValueEffect::DenyAction(
-                    ProgramAction::NetworkActivity(NetworkActivity {
-                        af: effect_na.af.clone(),
-                        proto: effect_na.proto.clone(),
-                        kind: effect_na.kind.clone(),
-                        local_port: new_eff_local_port,
-                    }),
-                ))
-            },
-            value: |e| {
-                let OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(denied_na)) = e
-                else {
-                    unreachable!();
-                };
-                OptionValue::List {
-                    values: denied_na
-                        .af
-                        .elements()
-                        .iter()
-                        .cartesian_product(denied_na.proto.elements())
-                        .cartesian_product(denied_na.local_port.ranges())
-                        .map(|((af, proto), port_range)| {
-                            format!(
-                                "{}:{}:{}-{}",
-                                af,
-                                proto,
-                                port_range.start(),
-                                port_range.end()
-                            )
-                        })
-                        .collect(),
-                    value_if_empty: None,
+                })
+                .collect(),
+        );
+        let bind_allow_value = socket_bind_allow_value(&bind_effect);
+        options.push(OptionDescription {
+            name: "SocketBindDeny",
+            min_systemd_version: Some(SystemdVersion::new(249, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::String("any".to_owned()),
+                desc: OptionEffect::Simple(bind_effect.clone()),
+                min_systemd_version: None,
+            }],
+            updater: Some(OptionUpdater {
+                effect: allow_bind_port,
+                value: Box::new(|_| OptionValue::String("any".to_owned())),
+            }),
+        });
+        options.push(OptionDescription {
+            name: "SocketBindAllow",
+            min_systemd_version: Some(SystemdVersion::new(249, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: bind_allow_value,
+                desc: OptionEffect::Simple(bind_effect),
+                min_systemd_version: None,
+            }],
+            updater: Some(OptionUpdater {
+                effect: allow_bind_port,
+                value: Box::new(socket_bind_allow_value),
+            }),
+        });
+    } else {
+        // We don't go as far as allowing/denying individual ports, as that would easily break for example if a port is changed
+        // in a server configuration.
+        // Address families & protocols of binds declared by the socket unit must remain possible.
+        let deny_binds: Vec<_> = deny_binds
+            .into_iter()
+            .filter(|(af, proto)| {
+                !hardening_opts
+                    .known_binds
+                    .iter()
+                    .any(|b| (b.af == *af) && (b.proto == *proto))
+            })
+            .collect();
+        if !deny_binds.is_empty() {
+            options.push(OptionDescription {
+                name: "SocketBindDeny",
+                min_systemd_version: Some(SystemdVersion::new(249, 0)),
+                possible_values: vec![OptionValueDescription {
+                    value: OptionValue::List {
+                        values: deny_binds
+                            .iter()
+                            .map(|(af, proto)| format!("{af}:{proto}"))
+                            .collect(),
+                        value_if_empty: None,
+                        negation_prefix: false,
+                        repeat_option: true,
+                        mode: ListMode::BlackList,
+                    },
+                    desc: OptionEffect::Cumulative(
+                        deny_binds
+                            .into_iter()
+                            .map(|(af, proto)| {
+                                OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(
+                                    NetworkActivity {
+                                        af: SetSpecifier::One(af),
+                                        proto: SetSpecifier::One(proto),
+                                        kind: SetSpecifier::One(NetworkActivityKind::Bind),
+                                        local_port: CountableSetSpecifier::All,
+                                        address: SetSpecifier::All,
+                                    },
+                                ))
+                            })
+                            .collect(),
+                    ),
+                    min_systemd_version: None,
+                }],
+                updater: None,
+            });
+        }
+    }
+
+    if hardening_opts.network_firewalling {
+        // https://www.freedesktop.org/software/systemd/man/latest/systemd.resource-control.html#IPAddressAllow=ADDRESS%5B/PREFIXLENGTH%5D%E2%80%A6
+        //
+        // Deny all IP traffic, except with the peers we have seen.
+        // Filtering applies to both incoming and outgoing packets, so we can not enable it if the program
+        // binds sockets, because the peers that will connect to it are unknown.
+        // For socket activated services, traffic of the passed sockets is filtered according to the options
+        // of the socket unit instead.
+        // Both options share the same effect and updater, so they are always enabled together.
+        let ip_effect =
+            OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(NetworkActivity {
+                af: SetSpecifier::Some(vec![SocketFamily::Ipv4, SocketFamily::Ipv6]),
+                proto: SetSpecifier::All,
+                kind: SetSpecifier::Some(vec![
+                    NetworkActivityKind::Bind,
+                    NetworkActivityKind::Connect,
+                    NetworkActivityKind::Send,
+                    NetworkActivityKind::Recv,
+                ]),
+                local_port: CountableSetSpecifier::All,
+                address: SetSpecifier::All,
+            }));
+        options.push(OptionDescription {
+            name: "IPAddressDeny",
+            min_systemd_version: Some(SystemdVersion::new(235, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::String("any".to_owned()),
+                desc: OptionEffect::Simple(ip_effect.clone()),
+                min_systemd_version: None,
+            }],
+            updater: Some(OptionUpdater {
+                effect: allow_ip_peer,
+                value: Box::new(|_| OptionValue::String("any".to_owned())),
+            }),
+        });
+        options.push(OptionDescription {
+            name: "IPAddressAllow",
+            min_systemd_version: Some(SystemdVersion::new(235, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::List {
+                    values: vec![],
+                    value_if_empty: Some(String::new()),
                     negation_prefix: false,
-                    repeat_option: true,
-                    mode: ListMode::BlackList,
-                }
-            },
-        }),
-    });
+                    repeat_option: false,
+                    mode: ListMode::WhiteList,
+                },
+                desc: OptionEffect::Simple(ip_effect),
+                min_systemd_version: None,
+            }],
+            updater: Some(OptionUpdater {
+                effect: allow_ip_peer,
+                value: match hardening_opts.ip_allow_coarsening {
+                    IpAllowCoarsening::Host => Box::new(|e| ip_allow_value(e, 32, 128)),
+                    IpAllowCoarsening::Subnet => Box::new(|e| ip_allow_value(e, 24, 64)),
+                    IpAllowCoarsening::Wide => Box::new(|e| ip_allow_value(e, 16, 48)),
+                },
+            }),
+        });
+    }
 
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#LockPersonality=
     options.push(OptionDescription {
         name: "LockPersonality",
+        min_systemd_version: Some(SystemdVersion::new(235, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
-            // In practice, the option allows the call if the default personality is set, but we don't
-            // need to model that level of precision.
-            // The "deny" model prevents false positives
-            desc: OptionEffect::Simple(OptionValueEffect::DenySyscalls(DenySyscalls::Single(
-                "personality",
-            ))),
+            // The option still allows querying the personality, or setting the default one
+            desc: OptionEffect::Simple(OptionValueEffect::DenyAction(
+                ProgramAction::SetPersonality,
+            )),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1329,11 +2936,39 @@ pub(crate) fn build_options(
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#RestrictRealtime=
     options.push(OptionDescription {
         name: "RestrictRealtime",
+        min_systemd_version: Some(SystemdVersion::new(231, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
             desc: OptionEffect::Simple(OptionValueEffect::DenyAction(
                 ProgramAction::SetRealtimeScheduler,
             )),
+            min_systemd_version: None,
+        }],
+        updater: None,
+    });
+
+    // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#RemoveIPC=
+    options.push(OptionDescription {
+        name: "RemoveIPC",
+        min_systemd_version: Some(SystemdVersion::new(232, 0)),
+        possible_values: vec![OptionValueDescription {
+            value: OptionValue::Boolean(true),
+            desc: OptionEffect::Simple(OptionValueEffect::DenyAction(
+                ProgramAction::CreateIpcObject,
+            )),
+            min_systemd_version: None,
+        }],
+        updater: None,
+    });
+
+    // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#RestrictSUIDSGID=
+    options.push(OptionDescription {
+        name: "RestrictSUIDSGID",
+        min_systemd_version: Some(SystemdVersion::new(242, 0)),
+        possible_values: vec![OptionValueDescription {
+            value: OptionValue::Boolean(true),
+            desc: OptionEffect::Simple(OptionValueEffect::DenyAction(ProgramAction::SetSuidSgid)),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1341,12 +2976,69 @@ pub(crate) fn build_options(
     // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectClock=
     options.push(OptionDescription {
         name: "ProtectClock",
+        min_systemd_version: Some(SystemdVersion::new(245, 0)),
         possible_values: vec![OptionValueDescription {
             value: OptionValue::Boolean(true),
-            // This option essentially does the same thing as deny @clock
-            desc: OptionEffect::Simple(OptionValueEffect::DenySyscalls(DenySyscalls::Class(
-                "clock",
-            ))),
+            // This option essentially does the same thing as deny @clock, and removing CAP_SYS_TIME.
+            // Note that the seccomp filter denies adjtimex & clock_adjtime even when they are only used to read
+            // clock parameters, so we can not enable it in that case either
+            desc: OptionEffect::Simple(OptionValueEffect::Multiple(vec![
+                OptionValueEffect::DenyAction(ProgramAction::SetClock),
+                OptionValueEffect::DenySyscalls(DenySyscalls::Class("clock")),
+            ])),
+            min_systemd_version: None,
+        }],
+        updater: None,
+    });
+
+    // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#ProtectHostname=
+    options.push(OptionDescription {
+        name: "ProtectHostname",
+        min_systemd_version: Some(SystemdVersion::new(242, 0)),
+        possible_values: vec![OptionValueDescription {
+            value: OptionValue::Boolean(true),
+            // Like for ProtectClock=, the syscalls are also denied by a seccomp filter, whatever their arguments
+            desc: OptionEffect::Simple(OptionValueEffect::Multiple(vec![
+                OptionValueEffect::DenyAction(ProgramAction::SetHostname),
+                OptionValueEffect::DenySyscalls(DenySyscalls::Single("sethostname")),
+                OptionValueEffect::DenySyscalls(DenySyscalls::Single("setdomainname")),
+            ])),
+            min_systemd_version: None,
+        }],
+        updater: None,
+    });
+
+    // https://www.freedesktop.org/software/systemd/man/latest/systemd.exec.html#RestrictNamespaces=
+    options.push(OptionDescription {
+        name: "RestrictNamespaces",
+        min_systemd_version: Some(SystemdVersion::new(233, 0)),
+        possible_values: vec![OptionValueDescription {
+            value: OptionValue::List {
+                values: NamespaceType::iter().map(|ns| ns.to_string()).collect(),
+                value_if_empty: Some("true".to_owned()),
+                negation_prefix: false,
+                repeat_option: false,
+                mode: ListMode::WhiteList,
+            },
+            desc: OptionEffect::Cumulative(
+                NamespaceType::iter()
+                    .map(|ns| {
+                        let is_mnt = matches!(ns, NamespaceType::Mnt);
+                        let create_ns =
+                            OptionValueEffect::DenyAction(ProgramAction::CreateNamespace(ns));
+                        if is_mnt {
+                            // Programs managing mounts usually do so in their own mount namespace
+                            OptionValueEffect::Multiple(vec![
+                                create_ns,
+                                OptionValueEffect::DenyAction(ProgramAction::ManageMounts),
+                            ])
+                        } else {
+                            create_ns
+                        }
+                    })
+                    .collect(),
+            ),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1392,9 +3084,32 @@ pub(crate) fn build_options(
             OptionValueEffect::DenyAction(ProgramAction::MknodSpecial),
         ),
         // CAP_NET_ADMIN: too complex?
-        // CAP_NET_BIND_SERVICE would be too complex/unreliable to handle:
-        // - for IPv4 sockets, either PROT_SOCK or net.ipv4.ip_unprivileged_port_start sysctl control the provileged port threshold
-        // - for other socket families, rules are different
+        // The privileged port threshold can be changed with the net.ipv4.ip_unprivileged_port_start sysctl, and
+        // rules are different for other socket families, so only consider binds below the default threshold
+        (
+            "CAP_NET_BIND_SERVICE",
+            OptionValueEffect::Multiple(
+                [SocketFamily::Ipv4, SocketFamily::Ipv6]
+                    .into_iter()
+                    .map(|af| {
+                        OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(
+                            NetworkActivity {
+                                af: SetSpecifier::One(af),
+                                proto: SetSpecifier::All,
+                                kind: SetSpecifier::One(NetworkActivityKind::Bind),
+                                local_port: CountableSetSpecifier::Some(
+                                    (1..PROT_SOCK)
+                                        .filter_map(NonZeroU16::new)
+                                        .map(NetworkPort::from)
+                                        .collect(),
+                                ),
+                                address: SetSpecifier::All,
+                            },
+                        ))
+                    })
+                    .collect(),
+            ),
+        ),
         // CAP_NET_BROADCAST: unused
         (
             "CAP_NET_RAW",
@@ -1405,21 +3120,28 @@ pub(crate) fn build_options(
                         proto: SetSpecifier::All,
                         kind: SetSpecifier::All,
                         local_port: CountableSetSpecifier::All,
+                        address: SetSpecifier::All,
                     }),
                 ))
                 .chain(
                     // AF_NETLINK sockets use SOCK_RAW, but does not require CAP_NET_RAW
-                    afs.iter().filter(|af| **af != "AF_NETLINK").map(|af| {
-                        OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(
-                            NetworkActivity {
-                                #[expect(clippy::unwrap_used)]
-                                af: SetSpecifier::One(af.parse().unwrap()),
-                                proto: SetSpecifier::One(SocketProtocol::Other("SOCK_RAW".into())),
-                                kind: SetSpecifier::All,
-                                local_port: CountableSetSpecifier::All,
-                            },
-                        ))
-                    }),
+                    SOCKET_FAMILIES
+                        .iter()
+                        .filter(|af| **af != "AF_NETLINK")
+                        .map(|af| {
+                            OptionValueEffect::DenyAction(ProgramAction::NetworkActivity(
+                                NetworkActivity {
+                                    #[expect(clippy::unwrap_used)]
+                                    af: SetSpecifier::One(af.parse().unwrap()),
+                                    proto: SetSpecifier::One(SocketProtocol::Other(
+                                        "SOCK_RAW".into(),
+                                    )),
+                                    kind: SetSpecifier::All,
+                                    local_port: CountableSetSpecifier::All,
+                                    address: SetSpecifier::All,
+                                },
+                            ))
+                        }),
                 )
                 .collect(),
                 // TODO non local bind
@@ -1433,9 +3155,46 @@ pub(crate) fn build_options(
             ]),
         ),
         // CAP_SETFCAP: too complex?
-        // TODO CAP_SETGID
+        // Setting ids to the current ones does not require the capability, but we can't know them
+        (
+            "CAP_SETGID",
+            OptionValueEffect::Multiple(
+                [
+                    "setgid",
+                    "setgid32",
+                    "setgroups",
+                    "setgroups32",
+                    "setregid",
+                    "setregid32",
+                    "setresgid",
+                    "setresgid32",
+                    "setfsgid",
+                    "setfsgid32",
+                ]
+                .into_iter()
+                .map(|s| OptionValueEffect::DenySyscalls(DenySyscalls::Single(s)))
+                .collect(),
+            ),
+        ),
         // TODO CAP_SETPCAP
-        // TODO CAP_SETUID
+        (
+            "CAP_SETUID",
+            OptionValueEffect::Multiple(
+                [
+                    "setuid",
+                    "setuid32",
+                    "setreuid",
+                    "setreuid32",
+                    "setresuid",
+                    "setresuid32",
+                    "setfsuid",
+                    "setfsuid32",
+                ]
+                .into_iter()
+                .map(|s| OptionValueEffect::DenySyscalls(DenySyscalls::Single(s)))
+                .collect(),
+            ),
+        ),
         // CAP_SYS_ADMIN: definitely too complex
         (
             "CAP_SYS_BOOT",
@@ -1464,7 +3223,7 @@ pub(crate) fn build_options(
             "CAP_SYS_PTRACE",
             OptionValueEffect::Multiple(vec![
                 // TODO distinguish other processes
-                OptionValueEffect::DenySyscalls(DenySyscalls::Single("ptrace)")),
+                OptionValueEffect::DenySyscalls(DenySyscalls::Single("ptrace")),
                 OptionValueEffect::DenySyscalls(DenySyscalls::Single("get_robust_list")),
                 OptionValueEffect::DenySyscalls(DenySyscalls::Single("process_vm_readv")),
                 OptionValueEffect::DenySyscalls(DenySyscalls::Single("process_vm_writev")),
@@ -1475,10 +3234,8 @@ pub(crate) fn build_options(
         // CAP_SYS_RESOURCE: too complex?
         (
             "CAP_SYS_TIME",
-            OptionValueEffect::Multiple(vec![
-                OptionValueEffect::DenySyscalls(DenySyscalls::Class("clock")),
-                OptionValueEffect::DenySyscalls(DenySyscalls::Single("stime")),
-            ]),
+            // Reading clock parameters does not require it
+            OptionValueEffect::DenyAction(ProgramAction::SetClock),
         ),
         (
             "CAP_SYS_TTY_CONFIG",
@@ -1495,9 +3252,43 @@ pub(crate) fn build_options(
             "CAP_WAKE_ALARM",
             OptionValueEffect::DenyAction(ProgramAction::SetAlarm),
         ),
-    ];
+    ]
+    .map(|(cap, effect)| {
+        // Capabilities granted by the unit (ie. with AmbientCapabilities=) may be needed in ways we do not model
+        (
+            cap,
+            OptionValueEffect::Multiple(vec![
+                effect,
+                OptionValueEffect::DenyAction(ProgramAction::UseCapability(cap.to_owned())),
+            ]),
+        )
+    });
+    if let HardeningMode::Aggressive = hardening_opts.mode {
+        // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#PrivateUsers=
+        //
+        // Ids other than root and the service's own ones are mapped to nobody, and capabilities are lost for
+        // resources of the host user namespace, so this is only compatible if no capability is needed.
+        // Accessing files owned by other users may also break, which we can not detect, so we consider it only in
+        // aggressive mode.
+        options.push(OptionDescription {
+            name: "PrivateUsers",
+            min_systemd_version: Some(SystemdVersion::new(232, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::Boolean(true),
+                desc: OptionEffect::Simple(OptionValueEffect::Multiple(
+                    iter::once(OptionValueEffect::DenyAction(ProgramAction::UseForeignIds))
+                        .chain(cap_effects.iter().map(|(_c, e)| e.clone()))
+                        .collect(),
+                )),
+                min_systemd_version: None,
+            }],
+            updater: None,
+        });
+    }
+
     options.push(OptionDescription {
         name: "CapabilityBoundingSet",
+        min_systemd_version: None,
         possible_values: vec![OptionValueDescription {
             value: OptionValue::List {
                 values: cap_effects.iter().map(|(c, _e)| (*c).to_owned()).collect(),
@@ -1507,6 +3298,7 @@ pub(crate) fn build_options(
                 mode: ListMode::BlackList,
             },
             desc: OptionEffect::Cumulative(cap_effects.into_iter().map(|(_c, e)| e).collect()),
+            min_systemd_version: None,
         }],
         updater: None,
     });
@@ -1521,46 +3313,162 @@ pub(crate) fn build_options(
     // signal when it makes the call, so change the default to just return EPERM.
     // Real world example: https://github.com/tjko/jpegoptim/blob/v1.5.5/jpegoptim.c#L1097-L1099
     //
-    let mut syscall_classes: Vec<_> = SYSCALL_CLASSES.keys().copied().collect();
-    syscall_classes.sort_unstable();
-    options.push(OptionDescription {
-        name: "SystemCallFilter",
-        possible_values: vec![OptionValueDescription {
-            value: OptionValue::List {
-                values: syscall_classes
+    if matches!(hardening_opts.mode, HardeningMode::Aggressive) && !hardening_opts.syscall_deny_list
+    {
+        // In aggressive mode, only allow the syscalls we have seen, collapsed into the classes whose syscalls have
+        // all been seen. Syscalls of code paths profiling did not cover will fail, which is why we only do it in
+        // aggressive mode.
+        let deny_all_effect = OptionValueEffect::AllowSyscalls(BTreeSet::new());
+        options.push(OptionDescription {
+            name: "SystemCallFilter",
+            min_systemd_version: Some(SystemdVersion::new(187, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: system_call_filter_allow_value(&deny_all_effect),
+                desc: OptionEffect::Simple(deny_all_effect),
+                min_systemd_version: None,
+            }],
+            updater: Some(OptionUpdater {
+                effect: allow_syscalls,
+                value: Box::new(system_call_filter_allow_value),
+            }),
+        });
+        // Allow list entries can not have an error number, so set the default one
+        options.push(OptionDescription {
+            name: "SystemCallErrorNumber",
+            min_systemd_version: Some(SystemdVersion::new(209, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::String("EPERM".to_owned()),
+                desc: OptionEffect::None,
+                min_systemd_version: None,
+            }],
+            updater: None,
+        });
+    } else {
+        let mut syscall_classes: Vec<_> = SYSCALL_CLASSES.keys().copied().collect();
+        syscall_classes.sort_unstable();
+        options.push(OptionDescription {
+            name: "SystemCallFilter",
+            min_systemd_version: Some(SystemdVersion::new(187, 0)),
+            possible_values: vec![OptionValueDescription {
+                value: OptionValue::List {
+                    values: syscall_classes
+                        .iter()
+                        .map(|c| format!("@{c}:EPERM"))
+                        .collect(),
+                    value_if_empty: None,
+                    negation_prefix: true,
+                    repeat_option: false,
+                    mode: ListMode::BlackList,
+                },
+                desc: OptionEffect::Cumulative(
+                    syscall_classes
+                        .into_iter()
+                        .map(|class| OptionValueEffect::DenySyscalls(DenySyscalls::Class(class)))
+                        .collect(),
+                ),
+                min_systemd_version: None,
+            }],
+            updater: None,
+        });
+    }
+
+    if matches!(hardening_opts.mode, HardeningMode::Aggressive)
+        && (kernel_version >= &KernelVersion::new(5, 7, 0))
+    {
+        // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#RestrictFileSystems=
+        //
+        // Allow common block device filesystems, and the other filesystem types we have seen accessed.
+        // Filesystem types are found by mapping accessed paths to the mount points at profiling time, which
+        // may change later (ie. with automounts), so we consider it only in aggressive mode.
+        // This requires the BPF LSM, if it is not enabled systemd ignores the option.
+        let common_block_effect = OptionValueEffect::DenyAction(ProgramAction::FileSystemAccess(
+            SetSpecifier::AllExcept(
+                COMMON_BLOCK_FS_TYPES
                     .iter()
-                    .map(|c| format!("@{c}:EPERM"))
-                    .collect(),
-                value_if_empty: None,
-                negation_prefix: true,
-                repeat_option: false,
-                mode: ListMode::BlackList,
-            },
-            desc: OptionEffect::Cumulative(
-                syscall_classes
-                    .into_iter()
-                    .map(|class| OptionValueEffect::DenySyscalls(DenySyscalls::Class(class)))
+                    .map(|t| (*t).to_owned())
                     .collect(),
             ),
-        }],
-        updater: None,
-    });
-
-    if let HardeningMode::Aggressive = hardening_opts.mode {
-        // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#SystemCallArchitectures=
-        //
-        // This is actually very safe to enable, but since we don't currently support checking for its
-        // compatibility during profiling, only enable it in aggressive mode
+        ));
         options.push(OptionDescription {
-            name: "SystemCallArchitectures",
+            name: "RestrictFileSystems",
+            min_systemd_version: Some(SystemdVersion::new(250, 0)),
             possible_values: vec![OptionValueDescription {
-                value: OptionValue::String("native".to_owned()),
-                desc: OptionEffect::None,
+                value: restrict_file_systems_value(&common_block_effect),
+                desc: OptionEffect::Simple(common_block_effect),
+                min_systemd_version: None,
             }],
-            updater: None,
+            updater: Some(OptionUpdater {
+                effect: allow_file_systems,
+                value: Box::new(restrict_file_systems_value),
+            }),
         });
     }
 
+    // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#SystemCallArchitectures=
+    //
+    // Allow the native architecture, and the other ones we have seen syscalls made with (ie. by 32-bit helpers).
+    // SystemCallFilter= is applied to all allowed architectures, and our syscall names are not architecture specific,
+    // so both options remain consistent.
+    let native_arch_effect = OptionValueEffect::DenyAction(ProgramAction::NonNativeSyscalls(
+        SetSpecifier::AllExcept(vec![]),
+    ));
+    options.push(OptionDescription {
+        name: "SystemCallArchitectures",
+        min_systemd_version: Some(SystemdVersion::new(209, 0)),
+        possible_values: vec![OptionValueDescription {
+            value: system_call_architectures_value(&native_arch_effect),
+            desc: OptionEffect::Simple(native_arch_effect),
+            min_systemd_version: None,
+        }],
+        updater: Some(OptionUpdater {
+            effect: allow_syscall_archs,
+            value: Box::new(system_call_architectures_value),
+        }),
+    });
+
+    // https://www.freedesktop.org/software/systemd/man/systemd.exec.html#UMask=
+    //
+    // Files created by the program with permissions for group or other users may be expected to be accessed by
+    // them, and changing modes after creation is not restricted by the umask, but we consider it anyway, because
+    // it hints that such files are expected. Unless in aggressive mode, it is only suggested.
+    options.push(OptionDescription {
+        name: "UMask",
+        min_systemd_version: None,
+        possible_values: vec![OptionValueDescription {
+            value: OptionValue::String("0077".to_owned()),
+            desc: OptionEffect::Simple(OptionValueEffect::DenyAction(
+                ProgramAction::SetGroupOtherPermissions,
+            )),
+            min_systemd_version: None,
+        }],
+        updater: None,
+    });
+
+    options.retain(|opt| match &opt.min_systemd_version {
+        Some(min_version) if min_version > systemd_version => {
+            log::warn!("Option {opt} requires systemd >= {min_version}, target systemd version is {systemd_version}, ignoring it");
+            false
+        }
+        _ => true,
+    });
+    for opt in &mut options {
+        let name = opt.name;
+        opt.possible_values
+            .retain(|val| match &val.min_systemd_version {
+                Some(min_version) if min_version > systemd_version => {
+                    let val_str = OptionWithValue {
+                        name: name.to_owned(),
+                        value: val.value.clone(),
+                        rationale: None,
+                        suggestion: false,
+                    };
+                    log::warn!("{val_str} requires systemd >= {min_version}, target systemd version is {systemd_version}, ignoring this value");
+                    false
+                }
+                _ => true,
+            });
+    }
+
     log::debug!("{options:#?}");
     options
 }
@@ -0,0 +1,211 @@
+//! Feedback on a unit while waiting for a blocking action
+
+use std::{
+    io::{self, BufRead as _, BufReader, Write as _},
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use crate::systemd::{END_OPTION_OUTPUT_SNIPPET, START_OPTION_OUTPUT_SNIPPET};
+
+/// Spinner animation frames
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+/// Delay between spinner updates
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+/// Maximum number of chars of the displayed log line
+const MAX_LOG_LINE_CHARS: usize = 60;
+/// Clear current terminal line
+const CLEAR_LINE: &str = "\r\x1b[K";
+
+/// Progress of the profiling result reporting, as seen in the unit logs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum ResultState {
+    /// No profiling result seen yet
+    Pending,
+    /// Profiling result is being logged
+    Reporting,
+    /// Profiling result is fully logged
+    Ready,
+}
+
+/// State of the progress line
+#[derive(Debug)]
+struct ProgressState {
+    frame: usize,
+    last_line: String,
+    result: ResultState,
+}
+
+impl ProgressState {
+    fn new() -> Self {
+        Self {
+            frame: 0,
+            last_line: String::new(),
+            result: ResultState::Pending,
+        }
+    }
+
+    /// Update state from a new unit log line
+    fn update(&mut self, line: &str) {
+        if line == START_OPTION_OUTPUT_SNIPPET {
+            self.result = ResultState::Reporting;
+        } else if line == END_OPTION_OUTPUT_SNIPPET {
+            self.result = ResultState::Ready;
+        }
+        let line = line.trim();
+        if !line.is_empty() {
+            line.clone_into(&mut self.last_line);
+        }
+    }
+
+    /// Move to the next spinner frame
+    fn tick(&mut self) {
+        self.frame = (self.frame + 1) % SPINNER_FRAMES.len();
+    }
+
+    /// Render the progress line
+    fn render(&self) -> String {
+        let status = match self.result {
+            ResultState::Pending => "waiting",
+            ResultState::Reporting => "profiling result incoming",
+            ResultState::Ready => "profiling result ready",
+        };
+        let mut line = format!("{} [{status}]", SPINNER_FRAMES[self.frame]);
+        if !self.last_line.is_empty() {
+            line.push(' ');
+            if self.last_line.chars().count() > MAX_LOG_LINE_CHARS {
+                line.extend(self.last_line.chars().take(MAX_LOG_LINE_CHARS - 1));
+                line.push('…');
+            } else {
+                line.push_str(&self.last_line);
+            }
+        }
+        line
+    }
+}
+
+/// Spinner and last log line of a unit, displayed on stderr until dropped
+pub(crate) struct JournalProgress {
+    journalctl: Child,
+    display: Option<thread::JoinHandle<()>>,
+}
+
+impl JournalProgress {
+    /// Start following new logs of a unit with a journalctl command
+    pub(crate) fn start(mut journalctl: Command, unit_name: &str) -> anyhow::Result<Self> {
+        let mut child = journalctl
+            .args([
+                "-f",
+                "-n",
+                "0",
+                "-o",
+                "cat",
+                "--output-fields=MESSAGE",
+                "-u",
+                unit_name,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .env("LANG", "C")
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Unable to get journalctl output"))?;
+
+        let (line_tx, line_rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("journal-reader".to_owned())
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if line_tx.send(line).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        let display = thread::Builder::new()
+            .name("journal-progress".to_owned())
+            .spawn(move || Self::display(&line_rx))?;
+
+        Ok(Self {
+            journalctl: child,
+            display: Some(display),
+        })
+    }
+
+    /// Refresh progress line until the log reader stops
+    fn display(line_rx: &mpsc::Receiver<String>) {
+        let mut state = ProgressState::new();
+        let mut stderr = io::stderr();
+        loop {
+            match line_rx.recv_timeout(REFRESH_INTERVAL) {
+                Ok(line) => state.update(&line),
+                Err(mpsc::RecvTimeoutError::Timeout) => state.tick(),
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            let _ = write!(stderr, "{CLEAR_LINE}{}", state.render());
+            let _ = stderr.flush();
+        }
+        let _ = write!(stderr, "{CLEAR_LINE}");
+        if state.result == ResultState::Ready {
+            let _ = writeln!(stderr, "Profiling result is ready");
+        }
+        let _ = stderr.flush();
+    }
+}
+
+impl Drop for JournalProgress {
+    fn drop(&mut self) {
+        // The reader thread stops when journalctl output is closed, which stops the display one
+        let _ = self.journalctl.kill();
+        let _ = self.journalctl.wait();
+        if let Some(display) = self.display.take() {
+            let _ = display.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_state() {
+        let mut state = ProgressState::new();
+        assert_eq!(state.render(), "| [waiting]");
+
+        state.update("Starting foo...");
+        state.tick();
+        assert_eq!(state.render(), "/ [waiting] Starting foo...");
+
+        state.update(START_OPTION_OUTPUT_SNIPPET);
+        assert_eq!(state.result, ResultState::Reporting);
+        state.update("ProtectSystem=strict");
+        state.update("");
+        assert_eq!(
+            state.render(),
+            "/ [profiling result incoming] ProtectSystem=strict"
+        );
+
+        state.update(END_OPTION_OUTPUT_SNIPPET);
+        assert_eq!(state.result, ResultState::Ready);
+
+        state.update(&"a".repeat(MAX_LOG_LINE_CHARS + 1));
+        state.tick();
+        state.tick();
+        state.tick();
+        assert_eq!(
+            state.render(),
+            format!(
+                "| [profiling result ready] {}…",
+                "a".repeat(MAX_LOG_LINE_CHARS - 1)
+            )
+        );
+    }
+}
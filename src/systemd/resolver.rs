@@ -1,9 +1,12 @@
 //! Resolver code that finds options compatible with program actions
 
+use itertools::Itertools as _;
+
 use crate::{
     summarize::{NetworkActivity, ProgramAction},
     systemd::options::{
-        ListMode, OptionDescription, OptionEffect, OptionValue, OptionValueEffect, OptionWithValue,
+        DenySyscalls, ListMode, OptionDescription, OptionEffect, OptionValue, OptionValueEffect,
+        OptionWithValue,
     },
 };
 
@@ -25,13 +28,40 @@ impl OptionValueEffect {
                             proto,
                             kind,
                             local_port,
+                            address,
                         }) = action
                         {
                             let af_match = denied.af.intersects(af);
                             let proto_match = denied.proto.intersects(proto);
                             let kind_match = denied.kind.intersects(kind);
                             let local_port_match = denied.local_port.intersects(local_port);
-                            !af_match || !proto_match || !kind_match || !local_port_match
+                            let address_match = denied.address.intersects(address);
+                            !af_match
+                                || !proto_match
+                                || !kind_match
+                                || !local_port_match
+                                || !address_match
+                        } else {
+                            true
+                        }
+                    }
+                    ProgramAction::DeviceAccess(denied) => {
+                        if let ProgramAction::DeviceAccess(accesses) = action {
+                            !denied.intersects(accesses)
+                        } else {
+                            true
+                        }
+                    }
+                    ProgramAction::FileSystemAccess(denied) => {
+                        if let ProgramAction::FileSystemAccess(fs_types) = action {
+                            !denied.intersects(fs_types)
+                        } else {
+                            true
+                        }
+                    }
+                    ProgramAction::NonNativeSyscalls(denied) => {
+                        if let ProgramAction::NonNativeSyscalls(archs) = action {
+                            !denied.intersects(archs)
                         } else {
                             true
                         }
@@ -39,45 +69,49 @@ impl OptionValueEffect {
                     ProgramAction::WriteExecuteMemoryMapping
                     | ProgramAction::SetRealtimeScheduler
                     | ProgramAction::Wakeup
+                    | ProgramAction::GetRandom
                     | ProgramAction::MknodSpecial
-                    | ProgramAction::SetAlarm => action != denied,
+                    | ProgramAction::CreateIpcObject
+                    | ProgramAction::SetSuidSgid
+                    | ProgramAction::SetGroupOtherPermissions
+                    | ProgramAction::SetPersonality
+                    | ProgramAction::UseForeignIds
+                    | ProgramAction::SetAlarm
+                    | ProgramAction::SetClock
+                    | ProgramAction::SetHostname
+                    | ProgramAction::ManageMounts
+                    | ProgramAction::ManageKernelModules
+                    | ProgramAction::UseCapability(_)
+                    | ProgramAction::CreateNamespace(_) => action != denied,
                     ProgramAction::Syscalls(_)
                     | ProgramAction::Read(_)
                     | ProgramAction::Write(_)
-                    | ProgramAction::Create(_) => unreachable!(),
+                    | ProgramAction::Create(_)
+                    | ProgramAction::CreateShared(_) => unreachable!(),
                 };
                 if compatible {
                     ActionOptionEffectCompatibility::Compatible
-                } else if let Some(updater) = updater {
-                    if let Some(new_eff) = (updater.effect)(self, action) {
-                        ActionOptionEffectCompatibility::CompatibleIfChanged(
-                            ChangedOptionValueDescription {
-                                value: (updater.value)(&new_eff),
-                                effect: new_eff,
-                            },
-                        )
-                    } else {
-                        ActionOptionEffectCompatibility::Incompatible
-                    }
                 } else {
-                    ActionOptionEffectCompatibility::Incompatible
+                    self.update(action, updater)
                 }
             }
             OptionValueEffect::DenyWrite(ro_paths) => match action {
-                ProgramAction::Write(path_action) | ProgramAction::Create(path_action) => {
-                    ActionOptionEffectCompatibility::from(!ro_paths.matches(path_action))
+                ProgramAction::Write(path_action) | ProgramAction::Create(path_action)
+                    if ro_paths.matches(path_action) =>
+                {
+                    self.update(action, updater)
                 }
                 _ => ActionOptionEffectCompatibility::Compatible,
             },
-            OptionValueEffect::Hide(hidden_paths) => {
-                if let ProgramAction::Read(path_action) = action {
-                    (!hidden_paths.matches(path_action)
-                        || prev_actions.contains(&ProgramAction::Create(path_action.clone())))
-                    .into()
-                } else {
-                    ActionOptionEffectCompatibility::Compatible
+            OptionValueEffect::Hide(hidden_paths) => match action {
+                ProgramAction::Read(path_action) => (!hidden_paths.matches(path_action)
+                    || prev_actions.contains(&ProgramAction::Create(path_action.clone())))
+                .into(),
+                ProgramAction::CreateShared(path_action) => {
+                    (!hidden_paths.matches(path_action)).into()
                 }
-            }
+                _ => ActionOptionEffectCompatibility::Compatible,
+            },
             OptionValueEffect::DenySyscalls(denied) => {
                 if let ProgramAction::Syscalls(syscalls) = action {
                     let denied_syscalls = denied.syscalls();
@@ -91,14 +125,79 @@ impl OptionValueEffect {
                     ActionOptionEffectCompatibility::Compatible
                 }
             }
-            OptionValueEffect::Multiple(effects) => effects
-                .iter()
-                .all(|e| match e.compatible(action, prev_actions, None) {
-                    ActionOptionEffectCompatibility::Compatible => true,
-                    ActionOptionEffectCompatibility::CompatibleIfChanged(_) => todo!(),
-                    ActionOptionEffectCompatibility::Incompatible => false,
-                })
-                .into(),
+            OptionValueEffect::AllowSyscalls(allowed) => {
+                if let ProgramAction::Syscalls(syscalls) = action {
+                    if syscalls.iter().all(|s| allowed.contains(s)) {
+                        ActionOptionEffectCompatibility::Compatible
+                    } else {
+                        self.update(action, updater)
+                    }
+                } else {
+                    ActionOptionEffectCompatibility::Compatible
+                }
+            }
+            OptionValueEffect::Multiple(effects) => {
+                // Only pass the updater the sub-effects incompatible with the action,
+                // the whole effect can only be changed if all of them can be updated
+                let mut new_effects = effects.clone();
+                let mut changed = false;
+                for new_effect in &mut new_effects {
+                    if let ActionOptionEffectCompatibility::Compatible =
+                        new_effect.compatible(action, prev_actions, None)
+                    {
+                        continue;
+                    }
+                    let Some(updated) =
+                        updater.and_then(|updater| (updater.effect)(new_effect, action))
+                    else {
+                        return ActionOptionEffectCompatibility::Incompatible;
+                    };
+                    *new_effect = updated;
+                    changed = true;
+                }
+                match updater {
+                    Some(updater) if changed => {
+                        let new_eff = OptionValueEffect::Multiple(new_effects);
+                        ActionOptionEffectCompatibility::CompatibleIfChanged(Box::new(
+                            ChangedOptionValueDescription {
+                                value: (updater.value)(&new_eff),
+                                effect: new_eff,
+                            },
+                        ))
+                    }
+                    _ => ActionOptionEffectCompatibility::Compatible,
+                }
+            }
+        }
+    }
+
+    /// Try to change an effect incompatible with an action, using the option updater if any
+    fn update(
+        &self,
+        action: &ProgramAction,
+        updater: Option<&OptionUpdater>,
+    ) -> ActionOptionEffectCompatibility {
+        if let Some(updater) = updater {
+            if let Some(new_eff) = (updater.effect)(self, action) {
+                return ActionOptionEffectCompatibility::CompatibleIfChanged(Box::new(
+                    ChangedOptionValueDescription {
+                        value: (updater.value)(&new_eff),
+                        effect: new_eff,
+                    },
+                ));
+            }
+        }
+        ActionOptionEffectCompatibility::Incompatible
+    }
+
+    /// Return true if this effect is already fully applied by another one
+    fn included_in(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                OptionValueEffect::DenySyscalls(DenySyscalls::Class(class)),
+                OptionValueEffect::DenySyscalls(other),
+            ) => other.nested_classes().contains(class),
+            _ => false,
         }
     }
 }
@@ -110,10 +209,20 @@ pub(crate) struct ChangedOptionValueDescription {
     pub effect: OptionValueEffect,
 }
 
+/// A resolved systemd option value, and why it was chosen
+#[derive(serde::Serialize)]
+pub struct ResolvedOption {
+    /// Option and its value
+    #[serde(flatten)]
+    pub option: OptionWithValue,
+    /// Human readable explanation of the observed actions that justified this value
+    pub rationale: String,
+}
+
 /// How compatible is an action with an option effect?
 pub(crate) enum ActionOptionEffectCompatibility {
     Compatible,
-    CompatibleIfChanged(ChangedOptionValueDescription),
+    CompatibleIfChanged(Box<ChangedOptionValueDescription>),
     Incompatible,
 }
 
@@ -132,7 +241,18 @@ pub(crate) fn actions_compatible(
     actions: &[ProgramAction],
     updater: Option<&OptionUpdater>,
 ) -> ActionOptionEffectCompatibility {
-    let mut changed_desc: Option<ChangedOptionValueDescription> = None;
+    actions_compatibility(eff, actions, updater).0
+}
+
+/// Check compatibility of an effect with actions, and also return the actions that made it
+/// incompatible, or that required it to be changed
+fn actions_compatibility<'a>(
+    eff: &OptionValueEffect,
+    actions: &'a [ProgramAction],
+    updater: Option<&OptionUpdater>,
+) -> (ActionOptionEffectCompatibility, Vec<&'a ProgramAction>) {
+    let mut changed_desc: Option<Box<ChangedOptionValueDescription>> = None;
+    let mut changing_actions = Vec::new();
     for i in 0..actions.len() {
         let cur_eff = changed_desc.as_ref().map_or(eff, |d| &d.effect);
         match cur_eff.compatible(&actions[i], &actions[..i], updater) {
@@ -145,6 +265,7 @@ pub(crate) fn actions_compatible(
                     new_desc.effect
                 );
                 changed_desc = Some(new_desc);
+                changing_actions.push(&actions[i]);
             }
             ActionOptionEffectCompatibility::Incompatible => {
                 log::debug!(
@@ -152,52 +273,137 @@ pub(crate) fn actions_compatible(
                     cur_eff,
                     actions[i]
                 );
-                return ActionOptionEffectCompatibility::Incompatible;
+                return (
+                    ActionOptionEffectCompatibility::Incompatible,
+                    vec![&actions[i]],
+                );
             }
         }
     }
 
     if let Some(new_desc) = changed_desc {
-        ActionOptionEffectCompatibility::CompatibleIfChanged(new_desc)
+        (
+            ActionOptionEffectCompatibility::CompatibleIfChanged(new_desc),
+            changing_actions,
+        )
     } else {
-        ActionOptionEffectCompatibility::Compatible
+        (ActionOptionEffectCompatibility::Compatible, vec![])
     }
 }
 
+/// Paths made read-only by options, whose observed reads are worth a warning, because the program may also write
+/// there in cases profiling did not cover
+const READ_ONLY_WARNING_PATHS: [(&str, &str); 1] = [("ProtectControlGroups", "/sys/fs/cgroup/")];
+
+/// Warn about reads of paths an option makes read-only, if any
+fn read_only_access_warning(opt_name: &str, actions: &[ProgramAction]) -> Option<String> {
+    let (_, base) = READ_ONLY_WARNING_PATHS
+        .iter()
+        .find(|(name, _)| *name == opt_name)?;
+    let read_actions: Vec<_> = actions
+        .iter()
+        .filter(|a| matches!(a, ProgramAction::Read(path) if path.starts_with(base)))
+        .collect();
+    (!read_actions.is_empty()).then(|| {
+        format!(
+            "warning: {base} is made read-only, but was observed being read by action {}",
+            actions_rationale(&read_actions)
+        )
+    })
+}
+
+/// Format actions for a human readable rationale
+fn actions_rationale(actions: &[&ProgramAction]) -> String {
+    actions.iter().map(|a| format!("{a:?}")).join(", ")
+}
+
 pub(crate) fn resolve(
-    opts: &Vec<OptionDescription>,
+    opts: &[OptionDescription],
     actions: &[ProgramAction],
 ) -> Vec<OptionWithValue> {
+    resolve_with_rationale(opts, actions)
+        .into_iter()
+        .map(|r| OptionWithValue {
+            rationale: Some(r.rationale),
+            ..r.option
+        })
+        .collect()
+}
+
+/// Same as `resolve`, but also explain which observed actions led to each option value
+pub(crate) fn resolve_with_rationale(
+    opts: &[OptionDescription],
+    actions: &[ProgramAction],
+) -> Vec<ResolvedOption> {
     let mut candidates = Vec::new();
     for opt in opts {
+        // Reasons more restrictive values were rejected
+        let mut rejected = Vec::new();
         // Options are in the less to most restrictive order,
         // so for non cumulative options, iterate from the end
         for opt_value_desc in opt.possible_values.iter().rev() {
             match &opt_value_desc.desc {
                 OptionEffect::None => {
-                    candidates.push(OptionWithValue {
-                        name: opt.name.to_owned(),
-                        value: opt_value_desc.value.clone(),
+                    candidates.push(ResolvedOption {
+                        option: OptionWithValue {
+                            name: opt.name.to_owned(),
+                            value: opt_value_desc.value.clone(),
+                            rationale: None,
+                            suggestion: false,
+                        },
+                        rationale: "Option has no modeled effect, it is always enabled".to_owned(),
                     });
                     break;
                 }
                 OptionEffect::Simple(effect) => {
-                    match actions_compatible(effect, actions, opt.updater.as_ref()) {
+                    let (compatible, reason_actions) =
+                        actions_compatibility(effect, actions, opt.updater.as_ref());
+                    match compatible {
                         ActionOptionEffectCompatibility::Compatible => {
-                            candidates.push(OptionWithValue {
-                                name: opt.name.to_owned(),
-                                value: opt_value_desc.value.clone(),
+                            rejected.push("compatible with all observed actions".to_owned());
+                            if let Some(warning) = read_only_access_warning(opt.name, actions) {
+                                log::warn!("{}: {warning}", opt.name);
+                                rejected.push(warning);
+                            }
+                            candidates.push(ResolvedOption {
+                                option: OptionWithValue {
+                                    name: opt.name.to_owned(),
+                                    value: opt_value_desc.value.clone(),
+                                    rationale: None,
+                                    suggestion: false,
+                                },
+                                rationale: rejected.join("; "),
                             });
                             break;
                         }
                         ActionOptionEffectCompatibility::CompatibleIfChanged(opt_new_desc) => {
-                            candidates.push(OptionWithValue {
-                                name: opt.name.to_owned(),
-                                value: opt_new_desc.value.clone(),
+                            rejected.push(format!(
+                                "value changed to allow observed actions: {}",
+                                actions_rationale(&reason_actions)
+                            ));
+                            candidates.push(ResolvedOption {
+                                option: OptionWithValue {
+                                    name: opt.name.to_owned(),
+                                    value: opt_new_desc.value.clone(),
+                                    rationale: None,
+                                    suggestion: false,
+                                },
+                                rationale: rejected.join("; "),
                             });
                             break;
                         }
-                        ActionOptionEffectCompatibility::Incompatible => {}
+                        ActionOptionEffectCompatibility::Incompatible => {
+                            let value_str = OptionWithValue {
+                                name: opt.name.to_owned(),
+                                value: opt_value_desc.value.clone(),
+                                rationale: None,
+                                suggestion: false,
+                            };
+                            rejected.push(format!(
+                                "{value_str} is incompatible with observed action {}",
+                                actions_rationale(&reason_actions)
+                            ));
+                        }
                     }
                 }
                 OptionEffect::Cumulative(effects) => {
@@ -209,18 +415,30 @@ pub(crate) fn resolve(
                             repeat_option,
                             mode,
                         } => {
-                            let mut compatible_opts = Vec::new();
+                            let mut enabled_opts = Vec::new();
+                            let mut reasons = Vec::new();
                             debug_assert_eq!(values.len(), effects.len());
                             let mut cur_effects = effects.clone();
-                            for (optv, opte) in values.iter().zip(&mut cur_effects) {
-                                let compatible =
-                                    actions_compatible(opte, actions, opt.updater.as_ref());
+                            for (idx, (optv, opte)) in
+                                values.iter().zip(&mut cur_effects).enumerate()
+                            {
+                                let (compatible, reason_actions) =
+                                    actions_compatibility(opte, actions, opt.updater.as_ref());
                                 let mut cur_opt_vals = vec![optv.to_owned()];
                                 let enable_opt = match mode {
-                                    ListMode::WhiteList => matches!(
-                                        compatible,
-                                        ActionOptionEffectCompatibility::Incompatible
-                                    ),
+                                    ListMode::WhiteList => {
+                                        let incompatible = matches!(
+                                            compatible,
+                                            ActionOptionEffectCompatibility::Incompatible
+                                        );
+                                        if incompatible {
+                                            reasons.push(format!(
+                                                "{optv} is allowed because of observed action {}",
+                                                actions_rationale(&reason_actions)
+                                            ));
+                                        }
+                                        incompatible
+                                    }
                                     ListMode::BlackList => match compatible {
                                         ActionOptionEffectCompatibility::Compatible => true,
                                         ActionOptionEffectCompatibility::CompatibleIfChanged(
@@ -236,29 +454,74 @@ pub(crate) fn resolve(
                                                     } else {
                                                         unreachable!();
                                                     };
+                                                    reasons.push(format!(
+                                                        "{optv} is changed to allow observed actions: {}",
+                                                        actions_rationale(&reason_actions)
+                                                    ));
                                                     true
                                                 },
                                                 ActionOptionEffectCompatibility::CompatibleIfChanged(_) => unreachable!(),
                                                 ActionOptionEffectCompatibility::Incompatible => false,
                                             }
                                         }
-                                        ActionOptionEffectCompatibility::Incompatible => false,
+                                        ActionOptionEffectCompatibility::Incompatible => {
+                                            reasons.push(format!(
+                                                "{optv} is not denied because of observed action {}",
+                                                actions_rationale(&reason_actions)
+                                            ));
+                                            false
+                                        }
                                     },
                                 };
                                 if enable_opt {
-                                    compatible_opts.append(&mut cur_opt_vals);
+                                    enabled_opts.push((idx, cur_opt_vals));
                                 }
                             }
+                            if let ListMode::BlackList = mode {
+                                // Skip values whose effect is already included in the one of another value
+                                let enabled_effects: Vec<_> =
+                                    enabled_opts.iter().map(|(i, _)| &cur_effects[*i]).collect();
+                                enabled_opts = enabled_opts
+                                    .into_iter()
+                                    .enumerate()
+                                    .filter(|(i, (idx, _))| {
+                                        !enabled_effects.iter().enumerate().any(|(j, e)| {
+                                            (j != *i) && cur_effects[*idx].included_in(e)
+                                        })
+                                    })
+                                    .map(|(_, o)| o)
+                                    .collect();
+                            }
+                            let compatible_opts: Vec<_> =
+                                enabled_opts.into_iter().flat_map(|(_, v)| v).collect();
                             if !compatible_opts.is_empty() || value_if_empty.is_some() {
-                                candidates.push(OptionWithValue {
-                                    name: opt.name.to_owned(),
-                                    value: OptionValue::List {
-                                        values: compatible_opts,
-                                        value_if_empty: value_if_empty.clone(),
-                                        negation_prefix: *negation_prefix,
-                                        repeat_option: *repeat_option,
-                                        mode: mode.clone(),
+                                if reasons.is_empty() {
+                                    reasons.push(
+                                        match mode {
+                                            ListMode::WhiteList => {
+                                                "no observed action requires any value"
+                                            }
+                                            ListMode::BlackList => {
+                                                "all values are compatible with observed actions"
+                                            }
+                                        }
+                                        .to_owned(),
+                                    );
+                                }
+                                candidates.push(ResolvedOption {
+                                    option: OptionWithValue {
+                                        name: opt.name.to_owned(),
+                                        value: OptionValue::List {
+                                            values: compatible_opts,
+                                            value_if_empty: value_if_empty.clone(),
+                                            negation_prefix: *negation_prefix,
+                                            repeat_option: *repeat_option,
+                                            mode: mode.clone(),
+                                        },
+                                        rationale: None,
+                                        suggestion: false,
                                     },
+                                    rationale: reasons.join("; "),
                                 });
                             }
                             break;
@@ -269,17 +532,265 @@ pub(crate) fn resolve(
             }
         }
     }
+    // An empty list can not be set without resetting the option, which would drop the values set by the unit
+    candidates.retain(|c| !is_empty_list(&c.option.value));
     candidates
 }
 
+/// Return true if the value is an empty list, with no explicit value for that case
+fn is_empty_list(value: &OptionValue) -> bool {
+    matches!(value, OptionValue::List { values, value_if_empty: None, .. } if values.is_empty())
+}
+
+/// Group option values by option name, preserving order
+fn group_option_values(opts: &[OptionWithValue]) -> Vec<(&str, Vec<String>)> {
+    let mut groups: Vec<(&str, Vec<String>)> = Vec::new();
+    for opt in opts {
+        let value = match &opt.value {
+            OptionValue::Boolean(v) => if *v { "true" } else { "false" }.to_owned(),
+            OptionValue::String(v) => v.to_owned(),
+            OptionValue::List { .. } => {
+                // Options parsed back from a config file are only booleans or strings
+                let opt_str = opt.to_string();
+                opt_str
+                    .split_once('=')
+                    .map_or(String::new(), |(_, v)| v.to_owned())
+            }
+        };
+        if let Some((_, values)) = groups.iter_mut().find(|(n, _)| *n == opt.name) {
+            values.push(value);
+        } else {
+            groups.push((&opt.name, vec![value]));
+        }
+    }
+    groups
+}
+
+/// Merge options from a previous resolution with new ones, keeping for each option the most permissive value
+pub(crate) fn merge(
+    opts: &[OptionDescription],
+    prev_opts: &[OptionWithValue],
+    new_opts: &[OptionWithValue],
+) -> Vec<OptionWithValue> {
+    let prev_groups = group_option_values(prev_opts);
+    let mut merged = Vec::new();
+    for (name, new_vals) in group_option_values(new_opts) {
+        let Some((_, prev_vals)) = prev_groups.iter().find(|(n, _)| *n == name) else {
+            log::info!("Option {name} was not previously enabled, skipping it");
+            continue;
+        };
+        let Some(opt) = opts.iter().find(|o| o.name == name) else {
+            log::warn!("Unsupported option {name}, skipping it");
+            continue;
+        };
+        let Some(first_value) = opt.possible_values.first() else {
+            continue;
+        };
+        match &first_value.value {
+            OptionValue::List {
+                value_if_empty,
+                negation_prefix,
+                repeat_option,
+                mode,
+                ..
+            } => {
+                // The form of the values wins over the description, because some options are allow lists or deny
+                // lists depending on the hardening mode (ie. SystemCallFilter=), which may differ between runs
+                let is_deny_list =
+                    |vals: &[String]| vals.first().is_some_and(|v| v.starts_with('~'));
+                let deny_list = is_deny_list(&new_vals);
+                if deny_list != is_deny_list(prev_vals) {
+                    log::warn!(
+                        "Unable to merge allow and deny lists for option {name}: {prev_vals:?} and {new_vals:?}, skipping it"
+                    );
+                    continue;
+                }
+                let (negation_prefix, mode) = if deny_list {
+                    (true, ListMode::BlackList)
+                } else {
+                    (*negation_prefix, mode.clone())
+                };
+                let list_values = |vals: &[String]| -> Vec<String> {
+                    vals.iter()
+                        .map(|v| {
+                            if negation_prefix {
+                                v.strip_prefix('~').unwrap_or(v)
+                            } else {
+                                v
+                            }
+                        })
+                        .flat_map(|v| {
+                            if *repeat_option {
+                                vec![v.to_owned()]
+                            } else {
+                                v.split_whitespace().map(ToOwned::to_owned).collect()
+                            }
+                        })
+                        .filter(|v| Some(v) != value_if_empty.as_ref())
+                        .collect()
+                };
+                let new_list = list_values(&new_vals);
+                let prev_list = list_values(prev_vals);
+                let values: Vec<_> = match mode {
+                    // Only deny what is denied in both
+                    ListMode::BlackList => new_list
+                        .into_iter()
+                        .filter(|v| prev_list.contains(v))
+                        .collect(),
+                    // Allow what is allowed in either
+                    ListMode::WhiteList => {
+                        let mut values = new_list;
+                        for prev_val in prev_list {
+                            if !values.contains(&prev_val) {
+                                values.push(prev_val);
+                            }
+                        }
+                        values
+                    }
+                };
+                if !values.is_empty() || value_if_empty.is_some() {
+                    merged.push(OptionWithValue {
+                        name: name.to_owned(),
+                        value: OptionValue::List {
+                            values,
+                            value_if_empty: value_if_empty.clone(),
+                            negation_prefix,
+                            repeat_option: *repeat_option,
+                            mode,
+                        },
+                        rationale: Some("Merged from all profiling runs".to_owned()),
+                        suggestion: false,
+                    });
+                }
+            }
+            OptionValue::Boolean(_) | OptionValue::String(_) => {
+                if new_vals == *prev_vals {
+                    merged.extend(new_opts.iter().filter(|o| o.name == name).map(|o| {
+                        OptionWithValue {
+                            name: o.name.clone(),
+                            value: o.value.clone(),
+                            rationale: o.rationale.clone(),
+                            suggestion: o.suggestion,
+                        }
+                    }));
+                    continue;
+                }
+                let value_idx = |vals: &[String]| {
+                    let [val] = vals else {
+                        return None;
+                    };
+                    possible_value_idx(opt, val)
+                };
+                if let (Some(new_idx), Some(prev_idx)) =
+                    (value_idx(&new_vals), value_idx(prev_vals))
+                {
+                    let value = opt.possible_values[new_idx.min(prev_idx)].value.clone();
+                    log::warn!(
+                        "Conflicting values for option {name}: {prev_vals:?} and {new_vals:?}, keeping the most permissive one"
+                    );
+                    merged.push(OptionWithValue {
+                        name: name.to_owned(),
+                        value,
+                        rationale: Some(
+                            "Most permissive value of conflicting profiling runs".to_owned(),
+                        ),
+                        suggestion: false,
+                    });
+                } else {
+                    log::warn!(
+                        "Unable to merge conflicting values for option {name}: {prev_vals:?} and {new_vals:?}, skipping it"
+                    );
+                }
+            }
+        }
+    }
+    merged
+}
+
+/// Index of a value among the possible ones of an option, which are in the less to most restrictive order
+fn possible_value_idx(opt: &OptionDescription, val: &str) -> Option<usize> {
+    opt.possible_values.iter().position(|v| {
+        OptionWithValue {
+            name: opt.name.to_owned(),
+            value: v.value.clone(),
+            rationale: None,
+            suggestion: false,
+        }
+        .to_string()
+            == format!("{}={val}", opt.name)
+    })
+}
+
+/// Reconcile new options with those already set by the unit config: options the unit already sets to an equal or
+/// more restrictive value are dropped, so that they are neither emitted again nor weakened
+pub(crate) fn reconcile_existing(
+    opts: &[OptionDescription],
+    existing_opts: &[OptionWithValue],
+    new_opts: Vec<OptionWithValue>,
+) -> Vec<OptionWithValue> {
+    let existing_groups = group_option_values(existing_opts);
+    new_opts
+        .into_iter()
+        .filter(|new_opt| {
+            let name = new_opt.name.as_str();
+            let Some((_, existing_vals)) = existing_groups.iter().find(|(n, _)| *n == name) else {
+                return true;
+            };
+            let Some(existing_val) = existing_vals.last() else {
+                return true;
+            };
+            let new_val = match &new_opt.value {
+                OptionValue::Boolean(v) => if *v { "true" } else { "false" }.to_owned(),
+                OptionValue::String(v) => v.to_owned(),
+                // Cumulative, and merged by systemd with the unit values
+                OptionValue::List { .. } => return true,
+            };
+            if *existing_val == new_val {
+                log::info!("Option {name} is already set to {existing_val:?} by the unit, skipping it");
+                return false;
+            }
+            let Some(opt) = opts.iter().find(|o| o.name == name) else {
+                return true;
+            };
+            match (
+                possible_value_idx(opt, existing_val),
+                possible_value_idx(opt, &new_val),
+            ) {
+                (Some(existing_idx), Some(new_idx)) if existing_idx >= new_idx => {
+                    log::info!(
+                        "Option {name} is already set to the more restrictive value {existing_val:?} by the unit, skipping it"
+                    );
+                    false
+                }
+                (Some(_), Some(_)) => true,
+                // Disabled, so anything is more restrictive
+                (None, _) if existing_val == "false" => true,
+                _ => {
+                    log::warn!(
+                        "Unable to compare value {existing_val:?} set by the unit for option {name} with {new_val:?}, keeping the unit value"
+                    );
+                    false
+                }
+            }
+        })
+        .collect()
+}
+
 #[expect(clippy::shadow_unrelated)]
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use std::{collections::HashSet, io, iter, net::IpAddr};
+
     use crate::{
-        cl::HardeningOptions,
-        systemd::{build_options, KernelVersion, SystemdVersion},
+        hardening::{HardeningMode, HardeningOptions, IpAllowCoarsening},
+        strace::LogParser,
+        summarize::{
+            add_syscall_arch_action, summarize, CountableSetSpecifier, NetworkActivityKind,
+            SetSpecifier,
+        },
+        systemd::{build_options, KernelVersion, SocketFamily, SocketProtocol, SystemdVersion},
     };
 
     fn test_options(names: &[&str]) -> Vec<OptionDescription> {
@@ -291,6 +802,1187 @@ mod tests {
             .collect()
     }
 
+    /// Parse strace log lines, and resolve options from the summarized actions
+    fn resolve_lines(opts: &[OptionDescription], lines: &str) -> Vec<String> {
+        let logs =
+            LogParser::new(Box::new(io::Cursor::new(lines.as_bytes().to_vec())), None).unwrap();
+        let actions = summarize(logs).unwrap();
+        resolve(opts, &actions)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_system_call_filter() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["SystemCallFilter"]);
+
+        let actions = vec![ProgramAction::Syscalls(
+            ["read", "write", "close"]
+                .into_iter()
+                .map(ToOwned::to_owned)
+                .collect(),
+        )];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(format!("{}", candidates[0]), "SystemCallFilter=~@aio:EPERM @cpu-emulation:EPERM @debug:EPERM @io-event:EPERM @ipc:EPERM @keyring:EPERM @memlock:EPERM @mount:EPERM @network-io:EPERM @obsolete:EPERM @pkey:EPERM @privileged:EPERM @process:EPERM @resources:EPERM @sandbox:EPERM @setuid:EPERM @signal:EPERM @sync:EPERM @timer:EPERM");
+
+        let actions = vec![ProgramAction::Syscalls(
+            ["read", "write", "close", "fchown"]
+                .into_iter()
+                .map(ToOwned::to_owned)
+                .collect(),
+        )];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(format!("{}", candidates[0]), "SystemCallFilter=~@aio:EPERM @clock:EPERM @cpu-emulation:EPERM @debug:EPERM @io-event:EPERM @ipc:EPERM @keyring:EPERM @memlock:EPERM @module:EPERM @mount:EPERM @network-io:EPERM @obsolete:EPERM @pkey:EPERM @process:EPERM @raw-io:EPERM @reboot:EPERM @resources:EPERM @sandbox:EPERM @setuid:EPERM @signal:EPERM @swap:EPERM @sync:EPERM @timer:EPERM");
+    }
+
+    #[test]
+    fn test_resolve_system_call_filter_allow_list() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let sd_version = SystemdVersion::new(254, 0);
+        let kernel_version = KernelVersion::new(6, 4, 0);
+        let opts: Vec<_> = build_options(&sd_version, &kernel_version, &HardeningOptions::strict())
+            .into_iter()
+            .filter(|o| o.name == "SystemCallFilter")
+            .collect();
+        let resolve_syscalls = |syscalls: &[&str]| {
+            resolve(
+                &opts,
+                &[ProgramAction::Syscalls(
+                    syscalls.iter().map(|s| (*s).to_owned()).collect(),
+                )],
+            )
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+        };
+
+        // Not all syscalls of @basic-io were seen
+        assert_eq!(
+            resolve_syscalls(&["read", "write", "close"]),
+            vec!["SystemCallFilter=close read write"]
+        );
+
+        let basic_io: Vec<_> = DenySyscalls::Class("basic-io")
+            .syscalls()
+            .into_iter()
+            .chain(["fchown", "getpid"])
+            .collect();
+        assert_eq!(
+            resolve_syscalls(&basic_io),
+            vec!["SystemCallFilter=@basic-io fchown getpid"]
+        );
+
+        // Nested classes are not repeated
+        let privileged: Vec<_> = DenySyscalls::Class("privileged")
+            .syscalls()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            resolve_syscalls(&privileged),
+            vec!["SystemCallFilter=@privileged"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_system_call_filter_io_uring() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["SystemCallFilter"]);
+
+        let logs = LogParser::new(
+            Box::new(io::Cursor::new(
+                concat!(
+                    "1 0.000001 io_uring_setup(32, {flags=0, sq_thread_cpu=0, sq_thread_idle=0}) = 3\n",
+                    "1 0.000001 io_uring_enter(3, 1, 1, IORING_ENTER_GETEVENTS, NULL, 8) = 1\n",
+                )
+                .as_bytes(),
+            )),
+            None,
+        )
+        .unwrap();
+        let actions = summarize(logs).unwrap();
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        let filter = candidates[0].to_string();
+        assert!(filter.starts_with("SystemCallFilter=~"), "{filter}");
+        assert!(!filter.contains("@aio:"), "{filter}");
+    }
+
+    #[test]
+    fn test_resolve_capability_bounding_set() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["CapabilityBoundingSet"]);
+
+        let logs = LogParser::new(
+            Box::new(io::Cursor::new(
+                concat!(
+                    "1 0.000001 socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP) = 3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>\n",
+                    "1 0.000001 connect(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>, {sa_family=AF_INET, sin_port=htons(443), sin_addr=inet_addr(\"\\x39\\x33\\x2e\\x31\\x38\\x34\\x2e\\x32\\x31\\x35\\x2e\\x31\\x34\")}, 16) = 0\n",
+                )
+                .as_bytes(),
+            )),
+            None,
+        )
+        .unwrap();
+        let actions = summarize(logs).unwrap();
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(format!("{}", candidates[0]), "CapabilityBoundingSet=~CAP_BLOCK_SUSPEND CAP_BPF CAP_CHOWN CAP_MKNOD CAP_NET_BIND_SERVICE CAP_NET_RAW CAP_PERFMON CAP_SETGID CAP_SETUID CAP_SYS_BOOT CAP_SYS_CHROOT CAP_SYS_MODULE CAP_SYS_NICE CAP_SYS_PACCT CAP_SYS_PTRACE CAP_SYS_TIME CAP_SYS_TTY_CONFIG CAP_SYSLOG CAP_WAKE_ALARM");
+
+        let actions = vec![ProgramAction::Syscalls(
+            ["read", "write", "setresuid"]
+                .into_iter()
+                .map(ToOwned::to_owned)
+                .collect(),
+        )];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(format!("{}", candidates[0]), "CapabilityBoundingSet=~CAP_BLOCK_SUSPEND CAP_BPF CAP_CHOWN CAP_MKNOD CAP_NET_BIND_SERVICE CAP_NET_RAW CAP_PERFMON CAP_SETGID CAP_SYS_BOOT CAP_SYS_CHROOT CAP_SYS_MODULE CAP_SYS_NICE CAP_SYS_PACCT CAP_SYS_PTRACE CAP_SYS_TIME CAP_SYS_TTY_CONFIG CAP_SYSLOG CAP_WAKE_ALARM");
+
+        // Capability is kept if its syscall was denied while profiling, but not if it failed otherwise
+        let resolve_failed_setuid = |errno: &str| {
+            let lines = format!("1 0.000001 setuid(0) = -1 {errno} (Some error)\n");
+            let logs = LogParser::new(Box::new(io::Cursor::new(lines.into_bytes())), None)
+                .unwrap()
+                .only_denied_failures();
+            let actions = summarize(logs).unwrap();
+            let candidates = resolve(&opts, &actions);
+            assert_eq!(candidates.len(), 1);
+            candidates[0].to_string()
+        };
+        assert!(!resolve_failed_setuid("EPERM").contains("CAP_SETUID"));
+        assert!(resolve_failed_setuid("EINVAL").contains("CAP_SETUID"));
+    }
+
+    #[test]
+    fn test_resolve_capability_net_bind_service() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["CapabilityBoundingSet"]);
+        let drops_cap = |lines: &str, cap: &str| {
+            let logs = LogParser::new(Box::new(io::Cursor::new(lines.as_bytes().to_vec())), None)
+                .unwrap()
+                .only_denied_failures();
+            let actions = summarize(logs).unwrap();
+            let candidates = resolve(&opts, &actions);
+            assert_eq!(candidates.len(), 1);
+            candidates[0]
+                .to_string()
+                .split(['~', ' '])
+                .any(|c| c == cap)
+        };
+
+        let socket = "1 0.000001 socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP) = 3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>\n";
+        assert!(drops_cap(socket, "CAP_NET_BIND_SERVICE"));
+
+        // Bind to an unprivileged port
+        let lines = [
+            socket,
+            "1 0.000001 bind(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>, {sa_family=AF_INET, sin_port=htons(8080), sin_addr=inet_addr(\"\\x30\\x2e\\x30\\x2e\\x30\\x2e\\x30\")}, 16) = 0\n",
+        ]
+        .concat();
+        assert!(drops_cap(&lines, "CAP_NET_BIND_SERVICE"));
+
+        // Bind to a privileged port, denied while profiling
+        let lines = [
+            socket,
+            "1 0.000001 bind(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"\\x30\\x2e\\x30\\x2e\\x30\\x2e\\x30\")}, 16) = -1 EPERM (Operation not permitted)\n",
+        ]
+        .concat();
+        assert!(!drops_cap(&lines, "CAP_NET_BIND_SERVICE"));
+        assert!(drops_cap(&lines, "CAP_SYS_TIME"));
+
+        // Other failures are ignored
+        let lines = [
+            socket,
+            "1 0.000001 bind(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"\\x30\\x2e\\x30\\x2e\\x30\\x2e\\x30\")}, 16) = -1 EADDRINUSE (Address already in use)\n",
+        ]
+        .concat();
+        assert!(drops_cap(&lines, "CAP_NET_BIND_SERVICE"));
+
+        // Capability granted by the unit
+        let actions = vec![ProgramAction::UseCapability("CAP_SYS_TIME".to_owned())];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        assert!(!candidates[0]
+            .to_string()
+            .split(['~', ' '])
+            .any(|c| c == "CAP_SYS_TIME"));
+    }
+
+    #[test]
+    fn test_resolve_protect_clock() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["ProtectClock", "CapabilityBoundingSet"]);
+        let drops_cap_sys_time = |opts: &[String]| {
+            opts.iter().any(|o| {
+                o.starts_with("CapabilityBoundingSet=~")
+                    && o.split(' ').any(|c| c == "CAP_SYS_TIME")
+            })
+        };
+
+        let opts_getpid = resolve_lines(&opts, "1 0.000001 getpid() = 1\n");
+        assert!(opts_getpid.contains(&"ProtectClock=true".to_owned()));
+        assert!(drops_cap_sys_time(&opts_getpid));
+
+        // Read only, denied by ProtectClock seccomp filter, but does not need CAP_SYS_TIME
+        let opts_read = resolve_lines(
+            &opts,
+            "1 0.000001 adjtimex({modes=0, offset=0, freq=0}) = 0 (TIME_OK)\n",
+        );
+        assert!(!opts_read.contains(&"ProtectClock=true".to_owned()));
+        assert!(drops_cap_sys_time(&opts_read));
+
+        let opts_write = resolve_lines(
+            &opts,
+            "1 0.000001 adjtimex({modes=ADJ_FREQUENCY, freq=1000}) = 0 (TIME_OK)\n",
+        );
+        assert!(!opts_write.contains(&"ProtectClock=true".to_owned()));
+        assert!(!drops_cap_sys_time(&opts_write));
+    }
+
+    #[test]
+    fn test_resolve_ip_address_allow() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let ip_opts = |ip_allow_coarsening| -> Vec<OptionDescription> {
+            build_options(
+                &SystemdVersion::new(254, 0),
+                &KernelVersion::new(6, 4, 0),
+                &HardeningOptions {
+                    network_firewalling: true,
+                    ip_allow_coarsening,
+                    ..HardeningOptions::safe()
+                },
+            )
+            .into_iter()
+            .filter(|o| o.name.starts_with("IPAddress"))
+            .collect()
+        };
+        let ip_activity = |kind, address: &str| {
+            let address: IpAddr = address.parse().unwrap();
+            ProgramAction::NetworkActivity(NetworkActivity {
+                af: SetSpecifier::One(if address.is_ipv4() {
+                    SocketFamily::Ipv4
+                } else {
+                    SocketFamily::Ipv6
+                }),
+                proto: SetSpecifier::One(SocketProtocol::Udp),
+                kind: SetSpecifier::One(kind),
+                local_port: CountableSetSpecifier::All,
+                address: SetSpecifier::One(address),
+            })
+        };
+        let resolve_strs = |opts: Vec<OptionDescription>, actions: &[ProgramAction]| {
+            resolve(&opts, actions)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let actions = vec![ProgramAction::Syscalls(HashSet::new())];
+        assert_eq!(
+            resolve_strs(ip_opts(IpAllowCoarsening::Host), &actions),
+            vec!["IPAddressDeny=any", "IPAddressAllow="]
+        );
+
+        let actions = vec![
+            ip_activity(NetworkActivityKind::Connect, "10.0.0.1"),
+            ip_activity(NetworkActivityKind::Connect, "10.0.0.2"),
+            ip_activity(NetworkActivityKind::Send, "10.0.0.3"),
+            ip_activity(NetworkActivityKind::Send, "127.0.0.53"),
+            ip_activity(NetworkActivityKind::Recv, "10.0.1.7"),
+            ip_activity(NetworkActivityKind::Send, "fe80::1"),
+            ip_activity(NetworkActivityKind::Connect, "2001:db8::1"),
+            ip_activity(NetworkActivityKind::Connect, "::1"),
+        ];
+        assert_eq!(
+            resolve_strs(ip_opts(IpAllowCoarsening::Host), &actions),
+            vec![
+                "IPAddressDeny=any",
+                "IPAddressAllow=localhost link-local 10.0.0.1 10.0.0.2/31 10.0.1.7 2001:db8::1"
+            ]
+        );
+        assert_eq!(
+            resolve_strs(ip_opts(IpAllowCoarsening::Subnet), &actions),
+            vec![
+                "IPAddressDeny=any",
+                "IPAddressAllow=localhost link-local 10.0.0.0/23 2001:db8::/64"
+            ]
+        );
+        assert_eq!(
+            resolve_strs(ip_opts(IpAllowCoarsening::Wide), &actions),
+            vec![
+                "IPAddressDeny=any",
+                "IPAddressAllow=localhost link-local 10.0.0.0/16 2001:db8::/48"
+            ]
+        );
+
+        // Peers of bound sockets are unknown
+        let mut actions = actions;
+        actions.push(ProgramAction::NetworkActivity(NetworkActivity {
+            af: SetSpecifier::One(SocketFamily::Ipv4),
+            proto: SetSpecifier::One(SocketProtocol::Udp),
+            kind: SetSpecifier::One(NetworkActivityKind::Bind),
+            local_port: CountableSetSpecifier::All,
+            address: SetSpecifier::All,
+        }));
+        assert!(resolve_strs(ip_opts(IpAllowCoarsening::Host), &actions).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_socket_bind_allow() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts: Vec<_> = build_options(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions {
+                network_firewalling: true,
+                ..HardeningOptions::safe()
+            },
+        )
+        .into_iter()
+        .filter(|o| o.name.starts_with("SocketBind"))
+        .collect();
+
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 getpid() = 1\n"),
+            vec!["SocketBindDeny=any"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, concat!(
+                "1 0.000001 socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP) = 3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>\n",
+                "1 0.000001 bind(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>, {sa_family=AF_INET, sin_port=htons(8080), sin_addr=inet_addr(\"\\x30\\x2e\\x30\\x2e\\x30\\x2e\\x30\")}, 16) = 0\n",
+            )),
+            vec!["SocketBindDeny=any", "SocketBindAllow=ipv4:tcp:8080"]
+        );
+        // Ephemeral port
+        assert_eq!(
+            resolve_lines(&opts, concat!(
+                "1 0.000001 socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP) = 3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>\n",
+                "1 0.000001 bind(3<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x36\\x5d>, {sa_family=AF_INET, sin_port=htons(8080), sin_addr=inet_addr(\"\\x30\\x2e\\x30\\x2e\\x30\\x2e\\x30\")}, 16) = 0\n",
+                "1 0.000001 socket(AF_INET6, SOCK_DGRAM|SOCK_CLOEXEC, IPPROTO_UDP) = 4<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x37\\x5d>\n",
+                "1 0.000001 bind(4<\\x73\\x6f\\x63\\x6b\\x65\\x74\\x3a\\x5b\\x31\\x32\\x33\\x34\\x35\\x37\\x5d>, {sa_family=AF_INET6, sin6_port=htons(0), sin6_flowinfo=htonl(0), inet_pton(AF_INET6, \"\\x3a\\x3a\", &sin6_addr), sin6_scope_id=0}, 28) = 0\n",
+            )),
+            vec![
+                "SocketBindDeny=any",
+                "SocketBindAllow=ipv4:tcp:8080\nSocketBindAllow=ipv6:udp"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_resolve_system_call_architectures() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["SystemCallArchitectures"]);
+        let resolve_arch_lines = |lines: &'static str| {
+            let mut logs =
+                LogParser::new(Box::new(io::Cursor::new(lines.as_bytes())), None).unwrap();
+            let mut actions = summarize(&mut logs).unwrap();
+            add_syscall_arch_action(&mut actions, &logs.personality_modes());
+            resolve(&opts, &actions)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            resolve_arch_lines("1 0.000001 getpid() = 1\n"),
+            vec!["SystemCallArchitectures=native"]
+        );
+        // Mixed architecture capture, with a 32-bit child
+        assert_eq!(
+            resolve_arch_lines(concat!(
+                "1 0.000001 getpid() = 1\n",
+                "[ Process PID=2 runs in 32 bit mode. ]\n",
+                "2 0.000001 getpid() = 2\n",
+                "[ Process PID=1 runs in 64 bit mode. ]\n",
+            )),
+            vec!["SystemCallArchitectures=native x86"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_restrict_namespaces() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["RestrictNamespaces"]);
+
+        assert_eq!(
+            resolve_lines(&opts,
+                "1 0.000001 clone(child_stack=0x7f3b7b800000, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM|CLONE_SETTLS|CLONE_PARENT_SETTID|CLONE_CHILD_CLEARTID, parent_tid=[2], tls=0x7f3b7c0006c0, child_tidptr=0x7f3b7c000990) = 2\n"
+            ),
+            vec!["RestrictNamespaces=true"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 unshare(CLONE_NEWNET) = 0\n"),
+            vec!["RestrictNamespaces=net"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, concat!(
+                "1 0.000001 clone3({flags=CLONE_NEWUSER|CLONE_NEWPID, exit_signal=SIGCHLD, stack=NULL, stack_size=0}, 88) = 2\n",
+                "1 0.000001 setns(3, CLONE_NEWNS) = 0\n",
+            )),
+            vec!["RestrictNamespaces=mnt pid user"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 setns(3, 0) = 0\n"),
+            vec!["RestrictNamespaces=cgroup ipc net mnt pid user uts"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_manage_mounts() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&[
+            "ProtectKernelTunables",
+            "ProtectControlGroups",
+            "RestrictNamespaces",
+        ]);
+
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 getpid() = 1\n"),
+            vec![
+                "ProtectKernelTunables=true",
+                "ProtectControlGroups=true",
+                "RestrictNamespaces=true"
+            ]
+        );
+        assert_eq!(
+            resolve_lines(&opts,
+                "1 0.000001 mount(\"\\x74\\x6d\\x70\\x66\\x73\", \"\\x2f\\x6d\\x6e\\x74\", \"\\x74\\x6d\\x70\\x66\\x73\", 0, NULL) = 0\n"
+            ),
+            vec!["RestrictNamespaces=mnt"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_protect_hostname() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["ProtectHostname"]);
+
+        assert_eq!(
+            resolve_lines(&opts, concat!(
+                "1 0.000001 uname({sysname=\"\\x4c\\x69\\x6e\\x75\\x78\", nodename=\"\\x66\\x6f\\x6f\", ...}) = 0\n",
+                "1 0.000001 gethostname(\"\\x66\\x6f\\x6f\", 64) = 0\n",
+            )),
+            vec!["ProtectHostname=true"]
+        );
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 sethostname(\"\\x66\\x6f\\x6f\", 3) = 0\n"
+        )
+        .is_empty());
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 setdomainname(\"\\x62\\x61\\x72\", 3) = 0\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_protect_kernel_logs() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["ProtectKernelLogs"]);
+
+        assert_eq!(
+            resolve_lines(&opts, concat!(
+                "1 0.000001 socket(AF_UNIX, SOCK_DGRAM|SOCK_CLOEXEC, 0) = 3\n",
+                "1 0.000001 connect(3, {sa_family=AF_UNIX, sun_path=\"/run/systemd/journal/socket\"}, 29) = 0\n",
+                "1 0.000001 sendmsg(3, {msg_name=NULL, msg_namelen=0, msg_iov=[{iov_base=\"\\x61\", iov_len=1}], msg_iovlen=1, msg_controllen=0, msg_flags=0}, MSG_NOSIGNAL) = 1\n",
+            )),
+            vec!["ProtectKernelLogs=true"]
+        );
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 openat(AT_FDCWD, \"/dev/kmsg\", O_RDONLY|O_CLOEXEC) = 3\n"
+        )
+        .is_empty());
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 syslog(SYSLOG_ACTION_READ_ALL, \"\", 0) = 0\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_protect_control_groups() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["ProtectControlGroups"]);
+
+        let actions = vec![ProgramAction::Read("/etc/foo".into())];
+        let resolved = resolve_with_rationale(&opts, &actions);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].option.to_string(), "ProtectControlGroups=true");
+        assert!(!resolved[0].rationale.contains("warning"));
+
+        let actions = vec![ProgramAction::Read(
+            "/sys/fs/cgroup/foo/cgroup.procs".into(),
+        )];
+        let resolved = resolve_with_rationale(&opts, &actions);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].option.to_string(), "ProtectControlGroups=true");
+        assert!(
+            resolved[0]
+                .rationale
+                .contains("warning: /sys/fs/cgroup/ is made read-only"),
+            "{}",
+            resolved[0].rationale
+        );
+
+        let actions = vec![
+            ProgramAction::Read("/sys/fs/cgroup/foo/cgroup.procs".into()),
+            ProgramAction::Write("/sys/fs/cgroup/foo/cgroup.procs".into()),
+        ];
+        assert!(resolve(&opts, &actions).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_private_users() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts: Vec<_> = build_options(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions {
+                mode: HardeningMode::Aggressive,
+                ..HardeningOptions::safe()
+            },
+        )
+        .into_iter()
+        .filter(|o| o.name == "PrivateUsers")
+        .collect();
+        let own_uid = nix::unistd::getuid().as_raw();
+        let own_gid = nix::unistd::getgid().as_raw();
+        let foreign_id = if [own_uid, own_gid].contains(&1000) {
+            1001
+        } else {
+            1000
+        };
+
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 getpid() = 1234\n"),
+            vec!["PrivateUsers=true"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, &format!(
+                "1 0.000001 chown(\"\\x2f\\x74\\x6d\\x70\\x2f\\x66\\x6f\\x6f\", {own_uid}, -1) = 0\n"
+            )),
+            vec!["PrivateUsers=true"]
+        );
+        assert!(resolve_lines(&opts, &format!(
+            "1 0.000001 chown(\"\\x2f\\x74\\x6d\\x70\\x2f\\x66\\x6f\\x6f\", {foreign_id}, {foreign_id}) = 0\n"
+        ))
+        .is_empty());
+        assert!(resolve_lines(
+            &opts,
+            &format!("1 0.000001 setresuid({foreign_id}, {foreign_id}, {foreign_id}) = 0\n")
+        )
+        .is_empty());
+        assert!(resolve_lines(
+            &opts,
+            &format!("1 0.000001 setgroups(2, [{own_gid}, {foreign_id}]) = 0\n")
+        )
+        .is_empty());
+        // Needs a capability
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 syslog(SYSLOG_ACTION_READ_ALL, \"\", 0) = 0\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_lock_personality() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["LockPersonality"]);
+
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1 0.000001 personality(0xffffffff) = 0 (PER_LINUX)\n"
+            ),
+            vec!["LockPersonality=true"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 personality(PER_LINUX) = 0 (PER_LINUX)\n"),
+            vec!["LockPersonality=true"]
+        );
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 personality(PER_LINUX|ADDR_NO_RANDOMIZE) = 0 (PER_LINUX)\n"
+        )
+        .is_empty());
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 personality(ADDR_NO_RANDOMIZE) = 0 (PER_LINUX)\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_restrict_realtime() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["RestrictRealtime"]);
+
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 sched_setscheduler(0, SCHED_FIFO, [99]) = 0\n"
+        )
+        .is_empty());
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1 0.000001 sched_setscheduler(0, SCHED_OTHER, [0]) = 0\n"
+            ),
+            vec!["RestrictRealtime=true"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_remove_ipc() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["RemoveIPC"]);
+
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 shmget(0x1234, 4096, 0600) = 1\n"),
+            vec!["RemoveIPC=true"]
+        );
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 shmget(IPC_PRIVATE, 4096, IPC_CREAT|0600) = 1\n"
+        )
+        .is_empty());
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 semget(0x1234, 1, IPC_CREAT|IPC_EXCL|0600) = 1\n"
+        )
+        .is_empty());
+        assert!(resolve_lines(&opts, "1 0.000001 msgget(0x1234, IPC_CREAT|0600) = 1\n").is_empty());
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 mq_open(\"\\x66\\x6f\\x6f\", O_RDWR|O_CREAT, 0600, NULL) = 3\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_restrict_suid_sgid() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["RestrictSUIDSGID"]);
+
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1 0.000001 chmod(\"\\x2f\\x74\\x6d\\x70\\x2f\\x66\\x6f\\x6f\", 0755) = 0\n"
+            ),
+            vec!["RestrictSUIDSGID=true"]
+        );
+        assert!(resolve_lines(
+            &opts,
+            "1 0.000001 chmod(\"\\x2f\\x74\\x6d\\x70\\x2f\\x66\\x6f\\x6f\", 04755) = 0\n"
+        )
+        .is_empty());
+        assert!(resolve_lines(&opts, "1 0.000001 fchmod(3, 02755) = 0\n").is_empty());
+        assert!(resolve_lines(&opts,
+            "1 0.000001 openat(AT_FDCWD, \"\\x2f\\x74\\x6d\\x70\\x2f\\x66\\x6f\\x6f\", O_WRONLY|O_CREAT|O_TRUNC, 04755) = 3\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_restrict_address_families() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["RestrictAddressFamilies"]);
+
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1 0.000001 socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE) = 3\n"
+            ),
+            vec!["RestrictAddressFamilies=AF_NETLINK"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, concat!(
+                "1 0.000001 socket(AF_VSOCK, SOCK_STREAM, 0) = 3\n",
+                "1 0.000001 socket(AF_NETLINK, SOCK_RAW|SOCK_CLOEXEC, NETLINK_KOBJECT_UEVENT) = 4\n",
+                "1 0.000001 socket(AF_ALG, SOCK_SEQPACKET, 0) = 5\n",
+            )),
+            vec!["RestrictAddressFamilies=AF_ALG AF_NETLINK AF_VSOCK"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 getpid() = 1\n"),
+            vec!["RestrictAddressFamilies=none"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_proc() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["ProtectProc", "ProcSubset"]);
+
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                concat!(
+                    "1234 0.000001 openat(AT_FDCWD, \"/proc/self/status\", O_RDONLY) = 3\n",
+                    "1234 0.000001 openat(AT_FDCWD, \"/proc/1234/stat\", O_RDONLY) = 3\n",
+                )
+            ),
+            vec!["ProtectProc=ptraceable", "ProcSubset=pid"]
+        );
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1234 0.000001 openat(AT_FDCWD, \"/proc/meminfo\", O_RDONLY) = 3\n"
+            ),
+            vec!["ProtectProc=ptraceable"]
+        );
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1234 0.000001 openat(AT_FDCWD, \"/proc/1/cmdline\", O_RDONLY|O_CLOEXEC) = 3\n"
+            ),
+            vec!["ProcSubset=pid"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_restrict_file_systems() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let sd_version = SystemdVersion::new(254, 0);
+        let kernel_version = KernelVersion::new(6, 4, 0);
+        let opts: Vec<_> = build_options(&sd_version, &kernel_version, &HardeningOptions::strict())
+            .into_iter()
+            .filter(|o| o.name == "RestrictFileSystems")
+            .collect();
+        let resolve_fs_types = |fs_types: SetSpecifier<String>| {
+            resolve(&opts, &[ProgramAction::FileSystemAccess(fs_types)])
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            resolve_fs_types(SetSpecifier::Some(vec![
+                "ext4".to_owned(),
+                "tmpfs".to_owned()
+            ])),
+            vec!["RestrictFileSystems=@common-block tmpfs"]
+        );
+        assert_eq!(
+            resolve_fs_types(SetSpecifier::Some(vec![
+                "sysfs".to_owned(),
+                "proc".to_owned(),
+                "xfs".to_owned()
+            ])),
+            vec!["RestrictFileSystems=@common-block proc sysfs"]
+        );
+        assert_eq!(
+            resolve_fs_types(SetSpecifier::Some(vec![])),
+            vec!["RestrictFileSystems=@common-block"]
+        );
+        assert!(resolve_fs_types(SetSpecifier::All).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_device_allow() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["DevicePolicy", "DeviceAllow"]);
+
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1 0.000001 openat(AT_FDCWD, \"/dev/net/tun\", O_RDWR) = 3\n"
+            ),
+            vec!["DevicePolicy=closed", "DeviceAllow=/dev/net/tun rw"]
+        );
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                concat!(
+                    "1 0.000001 openat(AT_FDCWD, \"/dev/null\", O_WRONLY) = 3\n",
+                    "1 0.000001 openat(AT_FDCWD, \"/dev/shm/foo\", O_RDWR|O_CREAT, 0600) = 4\n",
+                    "1 0.000001 openat(AT_FDCWD, \"/dev/net/tun\", O_RDONLY) = 5\n",
+                )
+            ),
+            vec!["DevicePolicy=closed", "DeviceAllow=/dev/net/tun r"]
+        );
+        // Pseudo terminals are allowed by class
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                concat!(
+                    "1 0.000001 openat(AT_FDCWD, \"/dev/pts/3\", O_RDWR|O_NOCTTY) = 3\n",
+                    "1 0.000001 openat(AT_FDCWD, \"/dev/pts/12\", O_RDONLY) = 4\n",
+                )
+            ),
+            vec!["DevicePolicy=closed", "DeviceAllow=char-pts rw"]
+        );
+        assert_eq!(
+            resolve_lines(&opts, "1 0.000001 getpid() = 1\n"),
+            vec!["DevicePolicy=closed"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_random_sources() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["PrivateDevices", "DevicePolicy", "DeviceAllow"]);
+
+        // getrandom() needs no device node
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1 0.000001 getrandom(\"\\x8f\\x1c\\x3a\\x07\", 4, GRND_NONBLOCK) = 4\n"
+            ),
+            vec!["PrivateDevices=true", "DevicePolicy=closed"]
+        );
+        // The random pseudo devices remain accessible without being explicitly allowed
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                concat!(
+                    "1 0.000001 openat(AT_FDCWD, \"/dev/urandom\", O_RDONLY|O_CLOEXEC) = 3\n",
+                    "1 0.000001 openat(AT_FDCWD, \"/dev/random\", O_RDONLY|O_CLOEXEC) = 4\n",
+                )
+            ),
+            vec!["PrivateDevices=true", "DevicePolicy=closed"]
+        );
+        // Hardware random number generators do not
+        assert_eq!(
+            resolve_lines(
+                &opts,
+                "1 0.000001 openat(AT_FDCWD, \"/dev/hwrng\", O_RDONLY) = 3\n"
+            ),
+            vec!["DevicePolicy=closed", "DeviceAllow=/dev/hwrng r"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_read_write_paths() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts: Vec<_> = build_options(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions {
+                mode: HardeningMode::Aggressive,
+                ..HardeningOptions::safe()
+            },
+        )
+        .into_iter()
+        .filter(|o| ["ProtectSystem", "ReadWritePaths"].contains(&o.name))
+        .collect();
+        let resolve_actions = |actions: &[ProgramAction]| {
+            resolve(&opts, actions)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(resolve_actions(&[]), vec!["ProtectSystem=strict"]);
+        assert_eq!(
+            resolve_actions(
+                &["a", "b", "c", "d", "e"]
+                    .into_iter()
+                    .map(|f| ProgramAction::Write(format!("/var/lib/foo/{f}").into()))
+                    .chain(iter::once(ProgramAction::Write("/etc/foo.conf".into())))
+                    .collect::<Vec<_>>()
+            ),
+            vec![
+                "ProtectSystem=strict",
+                "ReadWritePaths=-/etc/foo.conf -/var/lib/foo"
+            ]
+        );
+        assert_eq!(
+            resolve_actions(&[
+                ProgramAction::Create("/var/lib/foo/a".into()),
+                ProgramAction::Write("/var/lib/foo/a".into()),
+                ProgramAction::Write("/var/lib/foo/b".into()),
+            ]),
+            vec![
+                "ProtectSystem=strict",
+                "ReadWritePaths=-/var/lib/foo/a -/var/lib/foo/b"
+            ]
+        );
+        assert!(resolve_actions(&[ProgramAction::Write("/usr/lib/foo".into())]).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_read_only_paths() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts: Vec<_> = build_options(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions {
+                mode: HardeningMode::Aggressive,
+                ..HardeningOptions::safe()
+            },
+        )
+        .into_iter()
+        .filter(|o| o.name == "ReadOnlyPaths")
+        .collect();
+        let resolve_actions = |actions: &[ProgramAction]| {
+            resolve(&opts, actions)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        let reads: Vec<_> = ["a", "b", "c", "d", "e"]
+            .into_iter()
+            .map(|f| ProgramAction::Read(format!("/var/lib/foo/{f}").into()))
+            .chain(iter::once(ProgramAction::Read("/etc/foo.conf".into())))
+            .collect();
+
+        assert!(resolve_actions(&[]).is_empty());
+        assert_eq!(
+            resolve_actions(&reads),
+            vec!["ReadOnlyPaths=-/etc/foo.conf -/var/lib/foo"]
+        );
+        // Directories containing written paths are not grouped, and written paths are not read only
+        assert_eq!(
+            resolve_actions(
+                &reads
+                    .iter()
+                    .cloned()
+                    .chain([
+                        ProgramAction::Write("/var/lib/foo/a".into()),
+                        ProgramAction::Create("/var/lib/foo/f".into()),
+                    ])
+                    .collect::<Vec<_>>()
+            ),
+            vec!["ReadOnlyPaths=-/etc/foo.conf -/var/lib/foo/b -/var/lib/foo/c -/var/lib/foo/d -/var/lib/foo/e"]
+        );
+        // Pseudo filesystems are left alone
+        assert!(resolve_actions(&[ProgramAction::Read("/proc/1/stat".into())]).is_empty());
+        assert!(resolve_actions(&[
+            ProgramAction::CreateShared("/tmp/foo.sock".into()),
+            ProgramAction::Read("/tmp/foo.sock".into()),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_with_rationale() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["ProtectSystem", "SystemCallFilter"]);
+
+        let actions = vec![
+            ProgramAction::Write("/etc/plop.conf".into()),
+            ProgramAction::Syscalls(
+                ["read", "write", "fchown"]
+                    .into_iter()
+                    .map(ToOwned::to_owned)
+                    .collect(),
+            ),
+        ];
+        let resolved = resolve_with_rationale(&opts, &actions);
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].option.to_string(), "ProtectSystem=true");
+        assert!(resolved[0].rationale.contains(
+            "ProtectSystem=full is incompatible with observed action Write(\"/etc/plop.conf\")"
+        ));
+        assert!(resolved[1]
+            .rationale
+            .contains("@chown:EPERM is not denied because of observed action Syscalls("));
+
+        let json = serde_json::to_value(&resolved).unwrap();
+        assert_eq!(json[0]["name"], "ProtectSystem");
+        assert_eq!(json[0]["value"], true);
+        assert_eq!(json[1]["name"], "SystemCallFilter");
+        assert_eq!(json[1]["value"][0], "~@aio:EPERM");
+        assert!(json[1]["rationale"].is_string());
+    }
+
+    #[test]
+    fn test_merge() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&[
+            "ProtectSystem",
+            "PrivateTmp",
+            "RestrictAddressFamilies",
+            "SystemCallFilter",
+            "LockPersonality",
+        ]);
+
+        let prev_opts: Vec<OptionWithValue> = [
+            "ProtectSystem=strict",
+            "PrivateTmp=true",
+            "RestrictAddressFamilies=AF_INET",
+            "SystemCallFilter=~@aio:EPERM @chown:EPERM @clock:EPERM",
+            "LockPersonality=true",
+        ]
+        .into_iter()
+        .map(|o| o.parse().unwrap())
+        .collect();
+        let new_opts: Vec<OptionWithValue> = [
+            "ProtectSystem=full",
+            "PrivateTmp=true",
+            "RestrictAddressFamilies=AF_UNIX",
+            "SystemCallFilter=~@aio:EPERM @clock:EPERM @debug:EPERM",
+        ]
+        .into_iter()
+        .map(|o| o.parse().unwrap())
+        .collect();
+
+        let merged = merge(&opts, &prev_opts, &new_opts);
+        assert_eq!(
+            merged.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "ProtectSystem=full",
+                "PrivateTmp=true",
+                "RestrictAddressFamilies=AF_UNIX AF_INET",
+                "SystemCallFilter=~@aio:EPERM @clock:EPERM",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_syscall_filter_forms() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        // Options of finish-profile are described for aggressive mode, where SystemCallFilter= is an allow list
+        let opts: Vec<_> = build_options(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::strict(),
+        )
+        .into_iter()
+        .filter(|o| o.name == "SystemCallFilter")
+        .collect();
+        let merge_filters = |prev: &str, new: &str| {
+            let parse = |o: &str| -> Vec<OptionWithValue> { vec![o.parse().unwrap()] };
+            merge(&opts, &parse(prev), &parse(new))
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        // Safe mode deny lists, only deny what both deny
+        assert_eq!(
+            merge_filters(
+                "SystemCallFilter=~@aio:EPERM @chown:EPERM @clock:EPERM",
+                "SystemCallFilter=~@aio:EPERM @clock:EPERM @debug:EPERM",
+            ),
+            vec!["SystemCallFilter=~@aio:EPERM @clock:EPERM"]
+        );
+        // Aggressive mode allow lists, allow what either allows
+        assert_eq!(
+            merge_filters(
+                "SystemCallFilter=@basic-io read",
+                "SystemCallFilter=@basic-io openat",
+            ),
+            vec!["SystemCallFilter=@basic-io openat read"]
+        );
+        // Allow and deny lists can not be merged without the syscalls they were generated from
+        assert!(merge_filters(
+            "SystemCallFilter=@basic-io read",
+            "SystemCallFilter=~@aio:EPERM @clock:EPERM",
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_existing() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&[
+            "ProtectSystem",
+            "ProtectHome",
+            "PrivateTmp",
+            "RestrictAddressFamilies",
+        ]);
+        let parse_opts = |opts: &[&str]| -> Vec<OptionWithValue> {
+            opts.iter().map(|o| o.parse().unwrap()).collect()
+        };
+        let new_opts = [
+            "ProtectSystem=full",
+            "ProtectHome=true",
+            "PrivateTmp=true",
+            "RestrictAddressFamilies=AF_UNIX",
+        ];
+
+        let reconciled = reconcile_existing(
+            &opts,
+            &parse_opts(&[
+                "ProtectSystem=strict",
+                "PrivateTmp=true",
+                "RestrictAddressFamilies=AF_UNIX",
+            ]),
+            parse_opts(&new_opts),
+        );
+        assert_eq!(
+            reconciled
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["ProtectHome=true", "RestrictAddressFamilies=AF_UNIX"]
+        );
+
+        let reconciled = reconcile_existing(
+            &opts,
+            &parse_opts(&["ProtectSystem=true", "PrivateTmp=false"]),
+            parse_opts(&new_opts),
+        );
+        assert_eq!(
+            reconciled
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "ProtectSystem=full",
+                "ProtectHome=true",
+                "PrivateTmp=true",
+                "RestrictAddressFamilies=AF_UNIX"
+            ]
+        );
+
+        let reconciled = reconcile_existing(
+            &opts,
+            &parse_opts(&["ProtectSystem=unknown"]),
+            parse_opts(&new_opts),
+        );
+        assert_eq!(
+            reconciled
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "ProtectHome=true",
+                "PrivateTmp=true",
+                "RestrictAddressFamilies=AF_UNIX"
+            ]
+        );
+    }
+
     #[test]
     fn test_resolve_protect_system() {
         let _ = simple_logger::SimpleLogger::new().init();
@@ -322,6 +2014,71 @@ mod tests {
         assert_eq!(candidates.len(), 0);
     }
 
+    #[test]
+    fn test_resolve_protect_system_writable_paths() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let writable_paths = vec!["/var/lib/foo".into(), "/var/cache/foo".into()];
+        for (mode, writes, expected) in [
+            (HardeningMode::Safe, vec![], vec!["ProtectSystem=strict"]),
+            (
+                HardeningMode::Safe,
+                vec!["/var/lib/foo/state.db", "/var/cache/foo/a/b"],
+                vec!["ProtectSystem=strict"],
+            ),
+            (
+                HardeningMode::Safe,
+                vec!["/var/lib/foo/state.db", "/var/lib/bar/state.db"],
+                vec!["ProtectSystem=full"],
+            ),
+            (
+                HardeningMode::Safe,
+                vec!["/var/lib/foo/state.db", "/etc/foo.conf"],
+                vec!["ProtectSystem=true"],
+            ),
+            (HardeningMode::Safe, vec!["/boot/foo"], vec![]),
+            (
+                HardeningMode::Aggressive,
+                vec!["/var/lib/foo/state.db"],
+                vec!["ProtectSystem=strict"],
+            ),
+            (
+                HardeningMode::Aggressive,
+                vec!["/var/lib/foo/state.db", "/var/lib/bar/state.db"],
+                vec![
+                    "ProtectSystem=strict",
+                    "ReadWritePaths=-/var/lib/bar/state.db",
+                ],
+            ),
+            (HardeningMode::Aggressive, vec!["/usr/lib/foo"], vec![]),
+        ] {
+            let opts: Vec<_> = build_options(
+                &SystemdVersion::new(254, 0),
+                &KernelVersion::new(6, 4, 0),
+                &HardeningOptions {
+                    mode: mode.clone(),
+                    writable_paths: writable_paths.clone(),
+                    ..HardeningOptions::safe()
+                },
+            )
+            .into_iter()
+            .filter(|o| ["ProtectSystem", "ReadWritePaths"].contains(&o.name))
+            .collect();
+            let actions: Vec<_> = writes
+                .iter()
+                .map(|p| ProgramAction::Write((*p).into()))
+                .collect();
+            assert_eq!(
+                resolve(&opts, &actions)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                expected,
+                "{mode} {writes:?}"
+            );
+        }
+    }
+
     #[test]
     fn test_resolve_protect_home() {
         let _ = simple_logger::SimpleLogger::new().init();
@@ -352,6 +2109,27 @@ mod tests {
         assert_eq!(format!("{}", candidates[0]), "ProtectHome=true");
     }
 
+    #[test]
+    fn test_resolve_protect_kernel_modules() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let opts = test_options(&["ProtectKernelModules"]);
+
+        let actions = vec![ProgramAction::Read("/etc/modprobe.d/foo.conf".into())];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(format!("{}", candidates[0]), "ProtectKernelModules=true");
+
+        let actions = vec![ProgramAction::ManageKernelModules];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 0);
+
+        // Module directories are made inaccessible
+        let actions = vec![ProgramAction::Read("/usr/lib/modules/foo.ko".into())];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 0);
+    }
+
     #[test]
     fn test_resolve_private_tmp() {
         let _ = simple_logger::SimpleLogger::new().init();
@@ -379,5 +2157,27 @@ mod tests {
         let candidates = resolve(&opts, &actions);
         assert_eq!(candidates.len(), 1);
         assert_eq!(format!("{}", candidates[0]), "PrivateTmp=true");
+
+        // mkstemp
+        let actions = vec![
+            ProgramAction::Create("/tmp/tmpa8Zk2Q".into()),
+            ProgramAction::Write("/tmp/tmpa8Zk2Q".into()),
+            ProgramAction::Read("/tmp/tmpa8Zk2Q".into()),
+        ];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(format!("{}", candidates[0]), "PrivateTmp=true");
+
+        let actions = vec![ProgramAction::Read("/tmp/.well-known-socket".into())];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 0);
+
+        let actions = vec![
+            ProgramAction::CreateShared("/tmp/.well-known-socket".into()),
+            ProgramAction::Create("/tmp/.well-known-socket".into()),
+            ProgramAction::Read("/tmp/.well-known-socket".into()),
+        ];
+        let candidates = resolve(&opts, &actions);
+        assert_eq!(candidates.len(), 0);
     }
 }
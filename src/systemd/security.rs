@@ -0,0 +1,169 @@
+//! Unit security assessment, as reported by `systemd-analyze security`
+
+use std::{collections::BTreeMap, fmt, io::BufRead as _};
+
+/// Marks prefixing satisfied checks, with and without UTF-8 support
+const CHECK_MARKS: [&str; 2] = ["✓", "+"];
+/// Marks prefixing unsatisfied checks, with and without UTF-8 support
+const CROSS_MARKS: [&str; 2] = ["✗", "-"];
+const OVERALL_EXPOSURE_PREFIX: &str = "Overall exposure level for ";
+
+/// Result of a `systemd-analyze security` run on a unit
+#[derive(Debug, PartialEq)]
+pub(crate) struct SecurityReport {
+    /// Overall exposure level, from 0.0 (safe) to 10.0 (unsafe)
+    exposure: f64,
+    /// Check names, and whether they are satisfied
+    checks: BTreeMap<String, bool>,
+}
+
+impl SecurityReport {
+    /// Parse `systemd-analyze security UNIT` output
+    pub(crate) fn parse(output: &[u8]) -> anyhow::Result<Self> {
+        let mut exposure = None;
+        let mut checks = BTreeMap::new();
+        for line in output.lines() {
+            let line = line?;
+            let mut tokens = line.split_whitespace();
+            let (Some(mark), Some(name)) = (tokens.next(), tokens.next()) else {
+                continue;
+            };
+            if CHECK_MARKS.contains(&mark) {
+                checks.insert(name.to_owned(), true);
+            } else if CROSS_MARKS.contains(&mark) {
+                checks.insert(name.to_owned(), false);
+            } else if let Some((_, level)) = line
+                .split_once(OVERALL_EXPOSURE_PREFIX)
+                .and_then(|(_, l)| l.split_once(": "))
+            {
+                let level = level.split_whitespace().next().unwrap_or_default();
+                exposure =
+                    Some(level.parse().map_err(|_| {
+                        anyhow::anyhow!("Unable to parse exposure level {level:?}")
+                    })?);
+            }
+        }
+        Ok(Self {
+            exposure: exposure
+                .ok_or_else(|| anyhow::anyhow!("Unable to find overall exposure level"))?,
+            checks,
+        })
+    }
+}
+
+/// Security assessment of a unit before and after hardening
+pub struct SecurityDelta {
+    before: SecurityReport,
+    after: SecurityReport,
+}
+
+impl SecurityDelta {
+    pub(crate) fn new(before: SecurityReport, after: SecurityReport) -> Self {
+        Self { before, after }
+    }
+
+    /// Names of checks whose state changed to `satisfied`
+    fn changed_checks(&self, satisfied: bool) -> Vec<&str> {
+        self.after
+            .checks
+            .iter()
+            .filter(|(name, after)| {
+                (**after == satisfied) && self.before.checks.get(*name) == Some(&!satisfied)
+            })
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+impl fmt::Display for SecurityDelta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Exposure level: {:.1} -> {:.1} ({:+.1})",
+            self.before.exposure,
+            self.after.exposure,
+            self.after.exposure - self.before.exposure
+        )?;
+        for (title, satisfied) in [("Newly satisfied", true), ("Newly unsatisfied", false)] {
+            let checks = self.changed_checks(satisfied);
+            if !checks.is_empty() {
+                write!(f, "\n{title} checks:")?;
+                for check in checks {
+                    write!(f, "\n  {check}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEFORE_OUTPUT: &str = "  NAME                                                        DESCRIPTION                                                             EXPOSURE
+✗ RemoveIPC=                                                  Service user may leave SysV IPC objects around                                   0.1
+✗ RootDirectory=/RootImage=                                   Service runs within the host's root directory                                    0.1
+✓ User=/DynamicUser=                                          Service runs under a static non-root user identity
+✗ PrivateNetwork=                                             Service has access to the host's network                                         0.5
+✗ ProtectSystem=                                              Service has full access to the OS file hierarchy                                 0.2
+✗ SystemCallFilter=~@clock                                    Service does not filter system calls                                             0.2
+
+→ Overall exposure level for foo.service: 9.2 UNSAFE 😨
+";
+
+    const AFTER_OUTPUT: &str = "  NAME                                                        DESCRIPTION                                                             EXPOSURE
+- RemoveIPC=                                                  Service user may leave SysV IPC objects around                                   0.1
+- RootDirectory=/RootImage=                                   Service runs within the host's root directory                                    0.1
++ User=/DynamicUser=                                          Service runs under a static non-root user identity
++ PrivateNetwork=                                             Service has no access to the host's network
++ ProtectSystem=                                              Service has strict read-only access to the OS file hierarchy
++ SystemCallFilter=~@clock                                    System call deny list defined for service, and @clock is included
+
+-> Overall exposure level for foo.service: 4.8 OK :-)
+";
+
+    #[test]
+    fn test_parse_security_report() {
+        let report = SecurityReport::parse(BEFORE_OUTPUT.as_bytes()).unwrap();
+        assert_eq!(
+            report,
+            SecurityReport {
+                exposure: 9.2,
+                checks: [
+                    ("RemoveIPC=", false),
+                    ("RootDirectory=/RootImage=", false),
+                    ("User=/DynamicUser=", true),
+                    ("PrivateNetwork=", false),
+                    ("ProtectSystem=", false),
+                    ("SystemCallFilter=~@clock", false),
+                ]
+                .into_iter()
+                .map(|(n, s)| (n.to_owned(), s))
+                .collect()
+            }
+        );
+
+        let report = SecurityReport::parse(AFTER_OUTPUT.as_bytes()).unwrap();
+        assert!((report.exposure - 4.8).abs() < f64::EPSILON);
+        assert_eq!(report.checks.get("PrivateNetwork="), Some(&true));
+
+        assert!(SecurityReport::parse(b"").is_err());
+    }
+
+    #[test]
+    fn test_security_delta() {
+        let delta = SecurityDelta::new(
+            SecurityReport::parse(BEFORE_OUTPUT.as_bytes()).unwrap(),
+            SecurityReport::parse(AFTER_OUTPUT.as_bytes()).unwrap(),
+        );
+        assert_eq!(
+            delta.to_string(),
+            "Exposure level: 9.2 -> 4.8 (-4.4)
+Newly satisfied checks:
+  PrivateNetwork=
+  ProtectSystem=
+  SystemCallFilter=~@clock"
+        );
+    }
+}
@@ -1,48 +1,295 @@
 //! Systemd service actions
 
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
     env,
+    ffi::OsStr,
     fs::{self, File},
-    io::{BufRead, BufReader, BufWriter, Write},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal as _, Write},
+    iter,
+    net::Ipv4Addr,
+    num::NonZeroU16,
+    os::unix::fs::PermissionsExt as _,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+    time::Duration,
 };
 
 use itertools::Itertools;
 use rand::Rng;
 
+#[cfg(feature = "sd-journal")]
+use crate::systemd::journal::Journal;
 use crate::{
-    cl::HardeningOptions,
-    systemd::{options::OptionWithValue, END_OPTION_OUTPUT_SNIPPET, START_OPTION_OUTPUT_SNIPPET},
+    error::{ShhError, SnippetMarker},
+    hardening::{HardeningOptions, KnownBind},
+    systemd::{
+        options::{
+            OptionValue, OptionWithValue, SocketFamily, SocketProtocol, SUGGESTION_COMMENT_PREFIX,
+        },
+        progress::JournalProgress,
+        security::{SecurityDelta, SecurityReport},
+        END_OPTION_OUTPUT_SNIPPET, RATIONALE_COMMENT_PREFIX, START_OPTION_OUTPUT_SNIPPET,
+    },
+    TracingBackend,
 };
 
-pub(crate) struct Service {
+/// Systemd service unit
+#[derive(Clone)]
+pub struct Service {
     name: String,
     arg: Option<String>,
+    /// Service is managed by the per-user instance of the service manager
+    user: bool,
+    /// Maximum number of retries of systemctl/journalctl commands failing transiently
+    command_retries: u32,
+    /// How our config fragments are named, and where persistent ones are written
+    fragment_naming: FragmentNaming,
 }
 
+/// Service unit with some of our config fragments
+#[derive(Debug, Eq, PartialEq, serde::Serialize)]
+pub struct UnitFragments {
+    /// Unit name, ending with `@.service` for templates
+    pub unit: String,
+    /// Names of the fragments present (`harden`, `profile`)
+    pub fragments: Vec<&'static str>,
+}
+
+/// How config fragments are named, and where persistent ones are written
+#[derive(Debug, Clone, clap::Args)]
+pub struct FragmentNaming {
+    /// Config fragment filename prefix, which sets their order relative to other drop-ins
+    #[arg(long = "fragment-prefix", default_value = DEFAULT_FRAGMENT_PREFIX)]
+    pub prefix: String,
+    /// Tool name component of config fragment filenames
+    #[arg(long = "fragment-tool-name", default_value = env!("CARGO_PKG_NAME"))]
+    pub tool_name: String,
+    /// Base directory of persistent config fragments, instead of the service manager's default one.
+    /// Profiling fragments are always written in the runtime directory
+    #[arg(long = "fragment-dir")]
+    pub persistent_dir: Option<PathBuf>,
+}
+
+impl Default for FragmentNaming {
+    fn default() -> Self {
+        Self {
+            prefix: DEFAULT_FRAGMENT_PREFIX.to_owned(),
+            tool_name: env!("CARGO_PKG_NAME").to_owned(),
+            persistent_dir: None,
+        }
+    }
+}
+
+/// Default config fragment filename prefix, so that they are applied after other drop-ins
+const DEFAULT_FRAGMENT_PREFIX: &str = "zz_";
+/// Maximum number of random transient unit names tried, before giving up finding an unused one
+const TRANSIENT_UNIT_NAME_TRIES: u32 = 16;
+
+/// Drop-in filename used by `systemctl edit`, which sorts after the numbered drop-ins shipped by distributions
+const SYSTEMCTL_EDIT_DROP_IN_NAME: &str = "override.conf";
+
+/// Additional time given to services profiled with a timeout, before systemd kills them
+const PROFILE_TIMEOUT_GRACE: Duration = Duration::from_secs(30);
+
+/// Environment variables set for the profiled commands
+const PROFILING_ENV_VARS: [(&str, &str); 1] = [("RUST_BACKTRACE", "1")];
+
 const PROFILING_FRAGMENT_NAME: &str = "profile";
 const HARDENING_FRAGMENT_NAME: &str = "harden";
+/// Tree drawing characters prefixing drop-in filenames in `systemctl status` output, with and without UTF-8 support
+const DROP_IN_TREE_CHARS: [char; 7] = ['└', '├', '─', '│', '`', '|', '-'];
+/// Output path meaning stdout
+const STDOUT_OUTPUT_PATH: &str = "-";
+/// Minimum length of an instance name to replace it in paths
+const MIN_GENERALIZED_INSTANCE_LEN: usize = 4;
+/// Comment prefix recording the hardening level preset in generated fragments
+const HARDENING_LEVEL_COMMENT_PREFIX: &str = "# Hardening level: ";
+/// Comment prefix recording the hardening options used while profiling in the profiling fragment, as a JSON array of
+/// command line arguments
+const HARDENING_OPTIONS_COMMENT_PREFIX: &str = "# Hardening options: ";
+/// Comment prefix recording actions allowed even if not observed while profiling in generated fragments
+const ALLOWANCE_COMMENT_PREFIX: &str = "# Allowed even if unobserved: ";
+/// Rationale written above options without a more specific one
+const DEFAULT_RATIONALE: &str = "Compatible with all profiled program actions";
 /// Command line prefix for `ExecStartXxx`= that bypasses all hardening options
 /// See <https://www.freedesktop.org/software/systemd/man/255/systemd.service.html#Command%20lines>
 const PRIVILEGED_PREFIX: &str = "+";
+/// Command line prefix for `ExecStartXxx`= that passes the second token as argv\[0\]
+const ARGV0_PREFIX: &str = "@";
+/// All characters that can be combined as command line special prefixes
+const EXEC_PREFIX_CHARS: [char; 5] = ['@', '-', ':', '+', '!'];
+/// Unit directory options, with their base directory for system and per-user (relative to home) services
+/// See <https://www.freedesktop.org/software/systemd/man/255/systemd.exec.html#RuntimeDirectory=>
+const UNIT_DIRECTORY_OPTS: [(&str, &str, &str); 3] = [
+    ("StateDirectory", "/var/lib", ".local/state"),
+    ("CacheDirectory", "/var/cache", ".cache"),
+    ("LogsDirectory", "/var/log", ".local/state/log"),
+];
+/// `StandardOutput=` values already sending output to the journal, like the default one
+const JOURNAL_STANDARD_OUTPUTS: [&str; 2] = ["journal", "journal+console"];
+/// Subdirectory of unit directory bases where directories of units with `DynamicUser=yes` really are, the unit
+/// directory itself being a symlink to it
+const DYNAMIC_USER_PRIVATE_DIR: &str = "private";
+/// Options implied by `DynamicUser=yes`, with their value
+/// See <https://www.freedesktop.org/software/systemd/man/255/systemd.exec.html#DynamicUser=>
+const DYNAMIC_USER_IMPLIED_OPTS: [(&str, &str); 6] = [
+    ("ProtectSystem", "strict"),
+    ("ProtectHome", "read-only"),
+    ("PrivateTmp", "true"),
+    ("RemoveIPC", "true"),
+    ("NoNewPrivileges", "true"),
+    ("RestrictSUIDSGID", "true"),
+];
+/// systemctl error messages of failures that may not happen again, when the service manager is not reachable yet
+/// or overloaded. systemctl exit codes can not tell them apart from other failures.
+const SYSTEMCTL_TRANSIENT_ERRORS: [&str; 2] = ["Failed to connect to bus", "Connection timed out"];
+/// Delay before the first retry of a failed command, doubled for each following retry
+const COMMAND_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(250);
+/// Options whose values are sets, that can be assigned several space separated values on a single line
+const SPACE_LIST_OPTS: [&str; 18] = [
+    "AmbientCapabilities",
+    "CacheDirectory",
+    "CapabilityBoundingSet",
+    "ConfigurationDirectory",
+    "ExecPaths",
+    "InaccessiblePaths",
+    "LogsDirectory",
+    "NoExecPaths",
+    "ReadOnlyPaths",
+    "ReadWritePaths",
+    "RestrictAddressFamilies",
+    "RestrictNamespaces",
+    "RuntimeDirectory",
+    "Sockets",
+    "StateDirectory",
+    "SupplementaryGroups",
+    "SystemCallArchitectures",
+    "TemporaryFileSystem",
+];
+
+/// Command failure
+#[derive(Debug)]
+enum CommandError {
+    /// Failure that may not happen again, ie. if the service manager is not ready yet
+    Transient(anyhow::Error),
+    /// Failure that will happen again
+    Fatal(anyhow::Error),
+}
+
+/// Add context to a failure to write config in `path`, with guidance about its likely cause
+fn write_error_context(err: io::Error, path: &Path) -> anyhow::Error {
+    let hint = match err.kind() {
+        io::ErrorKind::PermissionDenied => {
+            ": missing privileges, run as root for system services, or pass --user for per-user ones"
+        }
+        io::ErrorKind::ReadOnlyFilesystem => ": filesystem is read-only",
+        io::ErrorKind::StorageFull => ": no space left on filesystem",
+        _ => "",
+    };
+    anyhow::Error::new(err).context(format!("Unable to write config in {path:?}{hint}"))
+}
+
+/// Writer for a config fragment, so that it only appears at its path once completely written, and systemd never
+/// parses a truncated one
+enum FragmentWriter {
+    Stdout(io::Stdout),
+    /// Temporary file in the same directory as the fragment, renamed to it on commit
+    File {
+        file: BufWriter<tempfile::NamedTempFile>,
+        path: PathBuf,
+    },
+}
+
+impl FragmentWriter {
+    /// Permissions of the fragments we write
+    const MODE: u32 = 0o644;
+
+    fn new(path: &Path) -> anyhow::Result<Self> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        // Not loaded by systemd while being written, because it does not have the .conf extension
+        let file = tempfile::Builder::new()
+            .prefix(".")
+            .suffix(".tmp")
+            .tempfile_in(dir)
+            .map_err(|err| write_error_context(err, dir))?;
+        file.as_file()
+            .set_permissions(fs::Permissions::from_mode(Self::MODE))?;
+        Ok(Self::File {
+            file: BufWriter::new(file),
+            path: path.to_owned(),
+        })
+    }
+
+    /// Make the fragment appear at its path, replacing the previous one if any. If not called, the fragment is left
+    /// untouched
+    fn commit(self) -> anyhow::Result<()> {
+        match self {
+            Self::Stdout(mut stdout) => stdout.flush()?,
+            Self::File { file, path } => {
+                let file = file.into_inner().map_err(io::IntoInnerError::into_error)?;
+                file.as_file().sync_all()?;
+                file.persist(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for FragmentWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::File { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::File { file, .. } => file.flush(),
+        }
+    }
+}
 
 impl Service {
-    pub(crate) fn new(unit: &str) -> Self {
+    /// Default maximum number of retries of systemctl/journalctl commands failing transiently
+    pub const DEFAULT_COMMAND_RETRIES: u32 = 3;
+
+    /// Build service from its unit name without the `.service` suffix (`foo@` for a template unit), managed by the
+    /// per-user service manager if `user` is set
+    pub fn new(
+        unit: &str,
+        user: bool,
+        command_retries: u32,
+        fragment_naming: FragmentNaming,
+    ) -> Self {
         if let Some((name, arg)) = unit.split_once('@') {
             Self {
                 name: name.to_owned(),
                 arg: Some(arg.to_owned()),
+                user,
+                command_retries,
+                fragment_naming,
             }
         } else {
             Self {
                 name: unit.to_owned(),
                 arg: None,
+                user,
+                command_retries,
+                fragment_naming,
             }
         }
     }
 
-    fn unit_name(&self) -> String {
+    /// Unit name, including the instance argument for template units
+    pub fn unit_name(&self) -> String {
         format!(
             "{}{}.service",
             &self.name,
@@ -54,98 +301,407 @@ impl Service {
         )
     }
 
-    pub(crate) fn add_profile_fragment(
+    /// Is this a template unit, without an instance
+    fn is_template(&self) -> bool {
+        self.arg.as_deref() == Some("")
+    }
+
+    /// Build another instance of the template unit this is an instance of
+    pub fn instance(&self, arg: &str) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            self.arg.is_some(),
+            "{} is not a template unit instance",
+            self.unit_name()
+        );
+        Ok(Self {
+            name: self.name.clone(),
+            arg: Some(arg.to_owned()),
+            user: self.user,
+            command_retries: self.command_retries,
+            fragment_naming: self.fragment_naming.clone(),
+        })
+    }
+
+    /// Build transient service to profile a command in, with a random name not used by any unit
+    pub fn new_transient(user: bool, command_retries: u32) -> anyhow::Result<Self> {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRANSIENT_UNIT_NAME_TRIES {
+            let service = Self::new(
+                &format!(
+                    "{}-profile-transient_{:08x}",
+                    env!("CARGO_PKG_NAME"),
+                    rng.gen::<u32>()
+                ),
+                user,
+                command_retries,
+                FragmentNaming::default(),
+            );
+            if service.load_state()? == "not-found" {
+                return Ok(service);
+            }
+            log::debug!("{} already exists", service.unit_name());
+        }
+        anyhow::bail!("Unable to find an unused transient unit name")
+    }
+
+    /// Get unit load state (`loaded`, `not-found`...)
+    fn load_state(&self) -> anyhow::Result<String> {
+        let output = self
+            .systemctl()
+            .args(["show", "-p", "LoadState", "--value", &self.unit_name()])
+            .env("LANG", "C")
+            .output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "Failed to get load state of {}: {}",
+            self.unit_name(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    }
+
+    /// Profile command in this transient service, and wait for it to exit. The unit is removed when it exits, even
+    /// if it failed
+    pub fn run_transient(
+        &self,
+        command: &[String],
+        hardening_opts: &HardeningOptions,
+    ) -> anyhow::Result<()> {
+        let shh_bin = env::current_exe()?;
+        let unit_name = self.unit_name();
+        log::info!("Profiling {command:?} in transient unit {unit_name}");
+        let status = self
+            .systemd_run()
+            .args([
+                &format!("--unit={unit_name}"),
+                "--wait",
+                "--collect",
+                "--quiet",
+                "--same-dir",
+                "--service-type=exec",
+                "--",
+            ])
+            .arg(shh_bin)
+            .arg("run")
+            .args(hardening_opts.to_args())
+            .arg("--")
+            .args(command)
+            .status()?;
+        if !status.success() {
+            // The profiling result may still have been logged, if only the profiled command failed
+            log::warn!("Transient unit {unit_name} failed: {status}");
+        }
+        Ok(())
+    }
+
+    /// Resolve the unit name if it is an alias, because systemd only loads drop-ins of the canonical unit
+    pub fn canonical(self) -> anyhow::Result<Self> {
+        if self.is_template() {
+            // Templates can not be queried with show
+            return Ok(self);
+        }
+        let output = self
+            .systemctl()
+            .args(["show", "-p", "Id", &self.unit_name()])
+            .env("LANG", "C")
+            .output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "Failed to get id of {}: {}",
+            self.unit_name(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        let id = Self::parse_unit_id(&output.stdout)?;
+        self.with_unit_id(&id)
+    }
+
+    /// Build service from its canonical unit id, if it differs from our unit name
+    fn with_unit_id(self, id: &str) -> anyhow::Result<Self> {
+        if id == self.unit_name() {
+            return Ok(self);
+        }
+        let unit = id
+            .strip_suffix(".service")
+            .ok_or_else(|| anyhow::anyhow!("Unexpected unit id {id:?}"))?;
+        log::info!("{} is an alias of {id}", self.unit_name());
+        Ok(Self::new(
+            unit,
+            self.user,
+            self.command_retries,
+            self.fragment_naming,
+        ))
+    }
+
+    /// Parse unit id from `systemctl show -p Id` output
+    fn parse_unit_id(output: &[u8]) -> anyhow::Result<String> {
+        for line in output.lines() {
+            if let Some(id) = line?.strip_prefix("Id=").filter(|i| !i.is_empty()) {
+                return Ok(id.to_owned());
+            }
+        }
+        anyhow::bail!("Failed to parse unit id")
+    }
+
+    /// Fail if this is a template unit, which can not be run
+    fn ensure_instance(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.is_template(),
+            "{} is a template unit, specify an instance to run, ie. {}@INSTANCE (the configuration will apply to all instances)",
+            self.unit_name(),
+            self.name
+        );
+        Ok(())
+    }
+
+    /// Add config fragment wrapping the service commands to profile them
+    #[expect(clippy::too_many_arguments)]
+    pub fn add_profile_fragment(
         &self,
         hardening_opts: &HardeningOptions,
+        profile_stop_reload: bool,
+        profile_timeout: Option<Duration>,
+        tracer: TracingBackend,
+        profile_memory_max: Option<&str>,
+        merge: bool,
+        dry_run: bool,
+        keep_profile_data_dir: Option<&Path>,
     ) -> anyhow::Result<()> {
+        self.ensure_instance()?;
+        if let Some(dir) = keep_profile_data_dir {
+            anyhow::ensure!(
+                dir.is_absolute(),
+                "Profile data directory {dir:?} must be an absolute path"
+            );
+        }
+        if let Some(limit) = profile_memory_max {
+            anyhow::ensure!(
+                Self::is_valid_memory_limit(limit),
+                "Invalid memory limit {limit:?}, expected a size in bytes with an optional K/M/G/T suffix, a \
+                 percentage, or 'infinity'"
+            );
+        }
+        let mut hardening_opts = hardening_opts.clone();
         // Check first if our fragment does not yet exist
-        let fragment_path = self.fragment_path(PROFILING_FRAGMENT_NAME, false);
+        let fragment_path = self.fragment_path(PROFILING_FRAGMENT_NAME, false)?;
         anyhow::ensure!(
             !fragment_path.is_file(),
             "Fragment config already exists at {fragment_path:?}"
         );
-        let harden_fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true);
-        anyhow::ensure!(
-            !harden_fragment_path.is_file(),
-            "Hardening config already exists at {harden_fragment_path:?} and may conflict with profiling"
-        );
+        Self::warn_fragment_order(&fragment_path);
+        let harden_fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true)?;
 
         let config_paths_bufs = self.config_paths()?;
         let config_paths = config_paths_bufs
             .iter()
             .map(PathBuf::as_path)
+            // Our hardening fragment is moved away below
+            .filter(|p| *p != harden_fragment_path)
             .collect::<Vec<_>>();
         log::info!("Located unit config file(s): {config_paths:?}");
 
+        // Wrap ExecStartXxx directives, and optionally ExecReload & ExecStop
+        let mut exec_opts = vec!["ExecStartPre", "ExecStart", "ExecStartPost"];
+        if profile_stop_reload {
+            exec_opts.extend(["ExecReload", "ExecStop"]);
+        }
+        let exec_cmds = self.exec_cmds(&exec_opts, &config_paths)?;
+
+        if Self::is_notify_type(&config_paths)? {
+            log::warn!(
+                "{} is a Type=notify service: strace becomes its main process during profiling, which may interfere \
+                 with its readiness notifications",
+                self.unit_name()
+            );
+        }
+        let is_forking = Self::is_forking_type(&config_paths)?;
+        if is_forking {
+            log::warn!(
+                "{} is a Type=forking service: it will be profiled as Type=simple, because strace keeps tracing its \
+                 daemonized child, and ExecStartPost= commands may run before it is ready",
+                self.unit_name()
+            );
+        }
+
+        let prev_harden_fragment_path = if harden_fragment_path.is_file() {
+            anyhow::ensure!(
+                merge,
+                "Hardening config already exists at {harden_fragment_path:?} and may conflict with profiling, \
+                 remove it or pass --merge to merge it with the new config"
+            );
+            let prev_harden_fragment_path = self.prev_hardening_fragment_path()?;
+            anyhow::ensure!(
+                !prev_harden_fragment_path.is_file(),
+                "Previous hardening config already exists at {prev_harden_fragment_path:?}"
+            );
+            Some(prev_harden_fragment_path)
+        } else {
+            None
+        };
+
+        let writable_paths = self.writable_paths(&config_paths)?;
+        if !writable_paths.is_empty() {
+            log::info!("Paths declared as writable by the unit: {writable_paths:?}");
+        }
+        hardening_opts.writable_paths.extend(writable_paths);
+
+        let known_binds = self.socket_known_binds(&config_paths)?;
+        if !known_binds.is_empty() {
+            log::info!(
+                "Binds declared by the unit socket(s): {}",
+                known_binds.iter().join(", ")
+            );
+        }
+        hardening_opts.known_binds.extend(known_binds);
+
+        // Our fragment does not change the unit capabilities, so profiling runs with the same ones,
+        // but explicitly granted ones may be needed in ways we do not observe
+        let ambient_caps = Self::ambient_capabilities(&config_paths)?;
+        if !ambient_caps.is_empty() {
+            log::info!(
+                "Capabilities granted by the unit: {}",
+                ambient_caps.join(" ")
+            );
+        }
+        hardening_opts.allow_capabilities.extend(ambient_caps);
+
+        let unit_env = Self::environment(&config_paths)?;
+        let env_paths = Self::environment_paths(&unit_env);
+        if !env_paths.is_empty() {
+            log::info!("Paths referenced by the unit environment: {env_paths:?}");
+        }
+        hardening_opts.readable_paths.extend(env_paths);
+
+        if Self::is_root_confined(&config_paths)? {
+            log::info!("Unit runs in its own root, paths will not be canonicalized against the host filesystem");
+            hardening_opts.root_confined = true;
+        }
+
         // Write new fragment
-        #[expect(clippy::unwrap_used)] // fragment_path guarantees by construction we have a parent
-        fs::create_dir_all(fragment_path.parent().unwrap())?;
-        let mut fragment_file = BufWriter::new(File::create(&fragment_path)?);
+        let mut fragment_file = Self::fragment_writer(&fragment_path, dry_run)?;
         writeln!(
             fragment_file,
             "# This file has been autogenerated by {}",
             env!("CARGO_PKG_NAME")
         )?;
+        if let Some(level) = &hardening_opts.level {
+            writeln!(fragment_file, "{HARDENING_LEVEL_COMMENT_PREFIX}{level}")?;
+        }
+        writeln!(
+            fragment_file,
+            "{HARDENING_OPTIONS_COMMENT_PREFIX}{}",
+            serde_json::to_string(&hardening_opts.to_args())?
+        )?;
+        for (kind, val) in hardening_opts.allowances() {
+            writeln!(fragment_file, "{ALLOWANCE_COMMENT_PREFIX}{kind} {val}")?;
+        }
         writeln!(fragment_file, "[Service]")?;
         // writeln!(fragment_file, "AmbientCapabilities=CAP_SYS_PTRACE")?;
-        // needed because strace becomes the main process
+        // needed because strace becomes the main process, the original value is restored in the hardening fragment
         writeln!(fragment_file, "NotifyAccess=all")?;
-        writeln!(fragment_file, "Environment=RUST_BACKTRACE=1")?;
+        if is_forking {
+            // strace only exits with the last traced process, so the initial one never appears to exit,
+            // the original type is restored when the profiling fragment is removed
+            writeln!(fragment_file, "Type=simple")?;
+            writeln!(fragment_file, "PIDFile=")?;
+        }
+        for (name, val) in Self::profiling_env_vars(&unit_env) {
+            writeln!(fragment_file, "Environment={name}={val}")?;
+        }
         if !Self::config_vals("SystemCallFilter", &config_paths)?.is_empty() {
             // Allow ptracing, only if a syscall filter is already in place, otherwise it becomes a whitelist
             writeln!(fragment_file, "SystemCallFilter=@debug")?;
         }
-        // strace may slow down enough to risk reaching some service timeouts
-        writeln!(fragment_file, "TimeoutStartSec=infinity")?;
+        // strace may slow down enough to risk reaching some service timeouts, and eBPF tracing falls back to it
+        // when not available
+        if let Some(profile_timeout) = profile_timeout {
+            // Leave time for our profiling watchdog to trigger, and write the partial profile first
+            writeln!(
+                fragment_file,
+                "TimeoutStartSec={}s",
+                (profile_timeout + PROFILE_TIMEOUT_GRACE).as_secs()
+            )?;
+        } else {
+            writeln!(fragment_file, "TimeoutStartSec=infinity")?;
+        }
         writeln!(fragment_file, "KillMode=control-group")?;
+        // strace memory usage adds up to the program's one, and getting killed leaves a partial profile,
+        // the original limits are restored when the profiling fragment is removed
+        if let Some(limit) = profile_memory_max {
+            writeln!(fragment_file, "MemoryMax={limit}")?;
+            writeln!(fragment_file, "MemoryHigh={limit}")?;
+        }
+        // needed to parse back our options from the journal
+        if let Some(warning) = Self::standard_output_override_warning(&config_paths)? {
+            log::warn!("{warning}");
+        }
         writeln!(fragment_file, "StandardOutput=journal")?;
 
-        // Profile data dir
-        let mut rng = rand::thread_rng();
-        let profile_data_dir = PathBuf::from(format!(
-            "/run/{}-profile-data_{:08x}",
-            env!("CARGO_PKG_NAME"),
-            rng.gen::<u32>()
-        ));
-        #[expect(clippy::unwrap_used)]
-        writeln!(
-            fragment_file,
-            "RuntimeDirectory={}",
-            profile_data_dir.file_name().unwrap().to_str().unwrap()
-        )?;
+        // Profile data files are specific to each instance because the fragment applies to all of them
+        let instance_suffix = if self.arg.is_some() { "_%i" } else { "" };
+        let (profile_data_dir, profile_data_suffix) = if let Some(dir) = keep_profile_data_dir {
+            // Persistent directory, so that profile data and strace logs can be inspected after profiling
+            if dry_run {
+                log::info!("Profile data directory {dir:?} would be created");
+            } else {
+                fs::create_dir_all(dir)?;
+            }
+            writeln!(
+                fragment_file,
+                "ReadWritePaths={}",
+                dir.to_str()
+                    .ok_or_else(|| anyhow::anyhow!("Unable to decode profile data directory"))?
+            )?;
+            (dir.to_owned(), instance_suffix)
+        } else {
+            let mut rng = rand::thread_rng();
+            let dir = self.runtime_dir()?.join(format!(
+                "{}-profile-data_{:08x}{}",
+                env!("CARGO_PKG_NAME"),
+                rng.gen::<u32>(),
+                instance_suffix
+            ));
+            #[expect(clippy::unwrap_used)]
+            writeln!(
+                fragment_file,
+                "RuntimeDirectory={}",
+                dir.file_name().unwrap().to_str().unwrap()
+            )?;
+            (dir, "")
+        };
 
         let shh_bin = env::current_exe()?
             .to_str()
             .ok_or_else(|| anyhow::anyhow!("Unable to decode current executable path"))?
             .to_owned();
 
-        // Wrap ExecStartXxx directives
         let mut exec_start_idx = 1;
         let mut profile_data_paths = Vec::new();
-        for exec_start_opt in ["ExecStartPre", "ExecStart", "ExecStartPost"] {
-            let exec_start_cmds = Self::config_vals(exec_start_opt, &config_paths)?;
+        for (exec_start_opt, exec_start_cmds) in exec_cmds {
             if !exec_start_cmds.is_empty() {
                 writeln!(fragment_file, "{exec_start_opt}=")?;
             }
             for cmd in exec_start_cmds {
-                if cmd.starts_with(PRIVILEGED_PREFIX) {
-                    // TODO handle other special prefixes?
-                    // Write command unchanged
-                    writeln!(fragment_file, "{exec_start_opt}={cmd}")?;
-                } else {
-                    let profile_data_path = profile_data_dir.join(format!("{exec_start_idx:03}"));
+                let profile_data_path =
+                    profile_data_dir.join(format!("{exec_start_idx:03}{profile_data_suffix}"));
+                let strace_log_path = keep_profile_data_dir.map(|_| {
+                    profile_data_dir
+                        .join(format!("{exec_start_idx:03}{profile_data_suffix}.strace"))
+                });
+                if let Some(wrapped_cmd) = Self::wrap_exec_cmd(
+                    &cmd,
+                    &shh_bin,
+                    &hardening_opts,
+                    &profile_data_path,
+                    strace_log_path.as_deref(),
+                    profile_timeout,
+                    tracer,
+                )? {
+                    writeln!(fragment_file, "{exec_start_opt}={wrapped_cmd}")?;
                     exec_start_idx += 1;
-                    #[expect(clippy::unwrap_used)]
-                    writeln!(
-                        fragment_file,
-                        "{}={} run {} -p {} -- {}",
-                        exec_start_opt,
-                        shh_bin,
-                        hardening_opts.to_cmdline(),
-                        profile_data_path.to_str().unwrap(),
-                        cmd
-                    )?;
                     profile_data_paths.push(profile_data_path);
+                } else {
+                    // Write command unchanged
+                    writeln!(fragment_file, "{exec_start_opt}={cmd}")?;
                 }
             }
         }
@@ -154,312 +710,3451 @@ impl Service {
         #[expect(clippy::unwrap_used)]
         writeln!(
             fragment_file,
-            "ExecStopPost={} merge-profile-data {} {}",
-            shh_bin,
-            hardening_opts.to_cmdline(),
+            "ExecStopPost={} merge-profile-data {}{} {}",
+            Self::quote_exec_word(&shh_bin),
+            Self::quote_exec_args(&hardening_opts.to_args()),
+            if keep_profile_data_dir.is_some() {
+                " --keep-files"
+            } else {
+                ""
+            },
             profile_data_paths
                 .iter()
-                .map(|p| p.to_str().unwrap())
+                .map(|p| Self::quote_exec_word(p.to_str().unwrap()))
                 .join(" ")
         )?;
+        fragment_file.commit()?;
 
-        log::info!("Config fragment written in {fragment_path:?}");
-        Ok(())
-    }
+        // Move the hardening fragment away, because it would conflict with profiling, only now that the profiling
+        // fragment is written, so that the unit is never left without both
+        if let Some(prev_harden_fragment_path) = prev_harden_fragment_path {
+            if dry_run {
+                log::info!("Hardening config {harden_fragment_path:?} would be moved to {prev_harden_fragment_path:?} during profiling");
+            } else if let Err(err) = fs::rename(&harden_fragment_path, &prev_harden_fragment_path) {
+                fs::remove_file(&fragment_path)?;
+                return Err(anyhow::Error::new(err).context(format!(
+                    "Failed to move hardening config {harden_fragment_path:?} away, profiling config was removed"
+                )));
+            } else {
+                log::info!(
+                    "Hardening config moved to {prev_harden_fragment_path:?} during profiling"
+                );
+            }
+        }
 
-    pub(crate) fn remove_profile_fragment(&self) -> anyhow::Result<()> {
-        let fragment_path = self.fragment_path(PROFILING_FRAGMENT_NAME, false);
-        fs::remove_file(&fragment_path)?;
-        log::info!("{fragment_path:?} removed");
-        // let mut parent_dir = fragment_path;
-        // while let Some(parent_dir) = parent_dir.parent() {
-        //     if fs::remove_dir(parent_dir).is_err() {
-        //         // Likely directory not empty
-        //         break;
-        //     }
-        //     log::info!("{parent_dir:?} removed");
-        // }
+        if !dry_run {
+            log::info!("Config fragment written in {fragment_path:?}");
+        }
         Ok(())
     }
 
-    pub(crate) fn remove_hardening_fragment(&self) -> anyhow::Result<()> {
-        let fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true);
-        fs::remove_file(&fragment_path)?;
-        log::info!("{fragment_path:?} removed");
-        Ok(())
+    /// Get commands of the given `ExecXxx=` options, failing if there are none at all
+    fn exec_cmds<'a>(
+        &self,
+        exec_opts: &[&'a str],
+        config_paths: &[&Path],
+    ) -> anyhow::Result<Vec<(&'a str, Vec<String>)>> {
+        let exec_cmds = exec_opts
+            .iter()
+            .map(|o| Self::config_vals(o, config_paths).map(|c| (*o, c)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        anyhow::ensure!(
+            exec_cmds.iter().any(|(_, c)| !c.is_empty()),
+            "No {} command found for unit {}, make sure it is a service running commands",
+            exec_opts.iter().map(|o| format!("{o}=")).join("/"),
+            self.unit_name()
+        );
+        Ok(exec_cmds)
     }
 
-    pub(crate) fn add_hardening_fragment(&self, opts: Vec<OptionWithValue>) -> anyhow::Result<()> {
-        let fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true);
-        #[expect(clippy::unwrap_used)]
-        fs::create_dir_all(fragment_path.parent().unwrap())?;
-
-        let mut fragment_file = BufWriter::new(File::create(&fragment_path)?);
-        writeln!(
-            fragment_file,
-            "# This file has been autogenerated by {}",
-            env!("CARGO_PKG_NAME")
-        )?;
-        writeln!(fragment_file, "[Service]")?;
-        for opt in opts {
-            writeln!(fragment_file, "{opt}")?;
+    /// Get paths the unit configuration allows writing to, even with `ProtectSystem=strict`
+    fn writable_paths(&self, config_paths: &[&Path]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        let runtime_dir = self.runtime_dir()?;
+        let home_dir = env::var_os("HOME").map(PathBuf::from);
+        let unit_dir_opts = iter::once(("RuntimeDirectory", Some(runtime_dir))).chain(
+            UNIT_DIRECTORY_OPTS
+                .iter()
+                .map(|(opt, system_dir, user_dir)| {
+                    let base_dir = if self.user {
+                        home_dir.as_ref().map(|h| h.join(user_dir))
+                    } else {
+                        Some(PathBuf::from(system_dir))
+                    };
+                    (*opt, base_dir)
+                }),
+        );
+        // DynamicUser= is ignored for per-user services
+        let dynamic_user = !self.user && Self::is_dynamic_user(config_paths)?;
+        for (opt, base_dir) in unit_dir_opts {
+            let dirs = Self::config_tokens(opt, config_paths)?;
+            let Some(base_dir) = base_dir else {
+                if !dirs.is_empty() {
+                    log::warn!("Unable to locate base directory for {opt}=, ignoring it");
+                }
+                continue;
+            };
+            for dir in &dirs {
+                // Directories can have a symlink specified after ':'
+                let dir = dir.split_once(':').map_or(dir.as_str(), |(d, _)| d);
+                paths.push(base_dir.join(dir));
+                if dynamic_user && (opt != "RuntimeDirectory") {
+                    // Written paths are canonicalized, so they are seen in the private directory
+                    paths.push(base_dir.join(DYNAMIC_USER_PRIVATE_DIR).join(dir));
+                }
+            }
         }
-
-        log::info!("Config fragment written in {fragment_path:?}");
-        Ok(())
+        paths.extend(
+            Self::config_tokens("ReadWritePaths", config_paths)?
+                .iter()
+                .map(|p| PathBuf::from(p.trim_start_matches(['-', '+']))),
+        );
+        Ok(paths)
     }
 
-    #[expect(clippy::unused_self)]
-    pub(crate) fn reload_unit_config(&self) -> anyhow::Result<()> {
-        let status = Command::new("systemctl").arg("daemon-reload").status()?;
-        if !status.success() {
-            anyhow::bail!("systemctl failed: {status}");
+    /// Get capabilities granted by the unit config with `AmbientCapabilities=`
+    fn ambient_capabilities(config_paths: &[&Path]) -> anyhow::Result<Vec<String>> {
+        let mut caps: Vec<String> = Vec::new();
+        for val in Self::config_vals("AmbientCapabilities", config_paths)? {
+            if let Some(excluded) = val.strip_prefix('~') {
+                // Inverted set, all capabilities except these ones
+                log::warn!("Ignoring inverted AmbientCapabilities={val}, capabilities other than {excluded} will not be kept");
+                continue;
+            }
+            caps.extend(val.split_whitespace().map(ToOwned::to_owned));
         }
-        Ok(())
+        Ok(caps.into_iter().unique().collect())
     }
 
-    pub(crate) fn action(&self, verb: &str, block: bool) -> anyhow::Result<()> {
-        let unit_name = self.unit_name();
-        log::info!("{} {}", verb, unit_name);
-        let mut cmd = vec![verb];
-        if !block {
-            cmd.push("--no-block");
+    /// Get environment variables set by the unit config, with `Environment=` and `EnvironmentFile=`
+    fn environment(config_paths: &[&Path]) -> anyhow::Result<Vec<(String, String)>> {
+        let mut env = Vec::new();
+        for val in Self::config_vals("Environment", config_paths)? {
+            env.extend(
+                Self::split_quoted(&val)
+                    .into_iter()
+                    .filter_map(|a| a.split_once('=').map(|(n, v)| (n.to_owned(), v.to_owned()))),
+            );
         }
-        cmd.push(&unit_name);
-        let status = Command::new("systemctl").args(cmd).status()?;
-        if !status.success() {
-            anyhow::bail!("systemctl failed: {status}");
+        for path in Self::config_vals("EnvironmentFile", config_paths)? {
+            // Missing files are only allowed with the '-' prefix, but this is for systemd to enforce
+            let path = path.trim_start_matches('-');
+            let content = match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(err) => {
+                    log::warn!("Unable to read environment file {path:?}: {err}");
+                    continue;
+                }
+            };
+            env.extend(content.lines().filter_map(|l| {
+                let l = l.trim();
+                if l.is_empty() || l.starts_with(['#', ';']) {
+                    return None;
+                }
+                let (name, val) = l.trim_start_matches("export ").split_once('=')?;
+                let val = val.trim();
+                let val = Self::split_quoted(val)
+                    .into_iter()
+                    .next()
+                    .filter(|_| val.starts_with(['"', '\'']))
+                    .unwrap_or_else(|| val.to_owned());
+                Some((name.trim().to_owned(), val))
+            }));
         }
-        Ok(())
+        Ok(env)
     }
 
-    pub(crate) fn profiling_result(&self) -> anyhow::Result<Vec<OptionWithValue>> {
-        // Start journalctl process
-        let mut child = Command::new("journalctl")
-            .args([
-                "-r",
-                "-o",
-                "cat",
-                "--output-fields=MESSAGE",
-                "--no-tail",
-                "-u",
-                &self.unit_name(),
-            ])
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .env("LANG", "C")
-            .spawn()?;
-
-        // Parse its output
-        #[expect(clippy::unwrap_used)]
-        let reader = BufReader::new(child.stdout.take().unwrap());
-        let snippet_lines: Vec<_> = reader
-            .lines()
-            // Stream lines but bubble up errors
-            .skip_while(|r| {
-                r.as_ref()
-                    .map(|l| l != END_OPTION_OUTPUT_SNIPPET)
-                    .unwrap_or(false)
-            })
-            .take_while_inclusive(|r| {
-                r.as_ref()
-                    .map(|l| l != START_OPTION_OUTPUT_SNIPPET)
-                    .unwrap_or(true)
+    /// Get environment variables to set for the profiled commands, that do not shadow the unit ones
+    fn profiling_env_vars(unit_env: &[(String, String)]) -> Vec<(&'static str, &'static str)> {
+        PROFILING_ENV_VARS
+            .into_iter()
+            .filter(|(name, _)| {
+                let set = unit_env.iter().any(|(n, _)| n == name);
+                if set {
+                    log::warn!("{name} is already set by the unit, keeping its value");
+                }
+                !set
             })
-            .collect::<Result<_, _>>()?;
-        if (snippet_lines.len() < 2)
-            || (snippet_lines
-                .last()
-                .ok_or_else(|| anyhow::anyhow!("Unable to get profiling result lines"))?
-                != START_OPTION_OUTPUT_SNIPPET)
-        {
-            anyhow::bail!("Unable to get profiling result snippet");
-        }
-        // The output with '-r' flag is in reverse chronological order
-        // (to get the end as fast as possible), so reverse it, after we have
-        // removed marker lines
-        let opts = snippet_lines[1..snippet_lines.len() - 1]
-            .iter()
-            .rev()
-            .map(|l| l.parse::<OptionWithValue>())
-            .collect::<anyhow::Result<_>>()?;
-
-        // Stop journalctl
-        child.kill()?;
-        child.wait()?;
-
-        Ok(opts)
+            .collect()
     }
 
-    fn config_vals(key: &str, config_paths: &[&Path]) -> anyhow::Result<Vec<String>> {
-        // Note: we could use 'systemctl show -p xxx' but its output is different from config
-        // files, and we would need to interpret it anyway
-        let mut vals = Vec::new();
-        for config_path in config_paths {
-            let config_file = BufReader::new(File::open(config_path)?);
-            let prefix = format!("{key}=");
-            let mut file_vals = vec![];
-            let mut lines = config_file.lines();
-            while let Some(line) = lines.next() {
-                let line = line?;
-                if line.starts_with(&prefix) {
-                    let val = if line.ends_with('\\') {
-                        let mut val = line
-                            .split_once('=')
-                            .ok_or_else(|| anyhow::anyhow!("Unable to parse service option line"))?
-                            .1
-                            .trim()
-                            .to_owned();
-                        // Remove trailing '\'
-                        val.pop();
-                        // Append next lines
-                        loop {
-                            let next_line = lines
-                                .next()
-                                .ok_or_else(|| anyhow::anyhow!("Unexpected end of file"))??;
-                            val = format!("{} {}", val, next_line.trim_start());
-                            if next_line.ends_with('\\') {
-                                // Remove trailing '\'
-                                val.pop();
-                            } else {
-                                break;
-                            }
-                        }
-                        val
-                    } else {
-                        line.split_once('=')
-                            .ok_or_else(|| anyhow::anyhow!("Unable to parse service option line"))?
-                            .1
-                            .trim()
-                            .to_owned()
-                    };
-                    file_vals.push(val);
+    /// Split a config value into words, removing quotes around them, like systemd does
+    fn split_quoted(val: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut word: Option<String> = None;
+        let mut quote = None;
+        let mut chars = val.chars();
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', _) => {
+                    if let Some(escaped) = chars.next() {
+                        word.get_or_insert_with(String::new).push(escaped);
+                    }
                 }
+                (c, Some(q)) if c == q => quote = None,
+                ('"' | '\'', None) => {
+                    quote = Some(c);
+                    word.get_or_insert_with(String::new);
+                }
+                (c, None) if c.is_whitespace() => words.extend(word.take()),
+                (c, _) => word.get_or_insert_with(String::new).push(c),
             }
-            while let Some(clear_idx) = file_vals.iter().position(String::is_empty) {
-                file_vals = file_vals[clear_idx + 1..].to_vec();
-                vals.clear();
-            }
-            vals.extend(file_vals);
         }
-        Ok(vals)
+        words.extend(word);
+        words
     }
 
-    fn config_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
-        let output = Command::new("systemctl")
-            .args(["status", "-n", "0", &self.unit_name()])
-            .env("LANG", "C")
-            .output()?;
-        let mut paths = Vec::new();
-        let mut drop_in_dir = None;
-        for line in output.stdout.lines() {
-            let line = line?;
-            let line = line.trim_start();
-            if line.starts_with("Loaded:") {
-                // Main unit file
-                anyhow::ensure!(paths.is_empty());
-                let path = line
-                    .split_once('(')
-                    .ok_or_else(|| anyhow::anyhow!("Failed to locate main unit file"))?
-                    .1
-                    .split_once(';')
-                    .ok_or_else(|| anyhow::anyhow!("Failed to locate main unit file"))?
-                    .0;
-                paths.push(PathBuf::from(path));
-            } else if line.starts_with("Drop-In:") {
-                // Drop in base dir
-                anyhow::ensure!(paths.len() == 1);
-                anyhow::ensure!(drop_in_dir.is_none());
-                let dir = line
-                    .split_once(':')
-                    .ok_or_else(|| anyhow::anyhow!("Failed to locate unit config fragment dir"))?
-                    .1
-                    .trim_start();
-                drop_in_dir = Some(PathBuf::from(dir));
-            } else if let Some(dir) = drop_in_dir.as_ref() {
-                if line.contains(':') {
-                    // Not a path, next key: val line
-                    break;
-                } else if line.starts_with('/') {
-                    // New base dir
-                    drop_in_dir = Some(PathBuf::from(line));
-                } else {
-                    for filename in line.trim().chars().skip(2).collect::<String>().split(", ") {
-                        let path = dir.join(filename);
-                        paths.push(path);
-                    }
+    /// Get existing files referenced by environment variable values, possibly as `:` separated lists
+    ///
+    /// Directories are left out, because variables like `HOME=/root` or `DATA_DIR=/var/lib` do not mean the whole
+    /// tree is read, and allowing it would disable options like `ProtectHome=`.
+    fn environment_paths(env: &[(String, String)]) -> Vec<PathBuf> {
+        env.iter()
+            .flat_map(|(_, v)| v.split(':'))
+            .filter(|p| p.starts_with('/'))
+            .map(PathBuf::from)
+            .filter(|p| p.is_file())
+            .unique()
+            .collect()
+    }
+
+    /// Get binds done on behalf of the service by the socket units activating it
+    fn socket_known_binds(&self, config_paths: &[&Path]) -> anyhow::Result<Vec<KnownBind>> {
+        let mut binds = Vec::new();
+        for socket_config_paths in self.socket_units_config_paths(config_paths)? {
+            let socket_config_paths: Vec<_> =
+                socket_config_paths.iter().map(PathBuf::as_path).collect();
+            for (opt, proto) in [
+                ("ListenStream", SocketProtocol::Tcp),
+                ("ListenDatagram", SocketProtocol::Udp),
+            ] {
+                for listen in Self::config_vals(opt, &socket_config_paths)? {
+                    binds.extend(Self::parse_listen_binds(&listen, &proto));
                 }
             }
         }
-        Ok(paths)
+        Ok(binds)
     }
 
-    fn fragment_path(&self, name: &str, persistent: bool) -> PathBuf {
-        [
-            if persistent { "/etc" } else { "/run" },
-            "systemd/system/",
-            &format!(
-                "{}{}.service.d",
-                self.name,
+    /// Get config paths of the socket units activating the service
+    fn socket_units_config_paths(
+        &self,
+        config_paths: &[&Path],
+    ) -> anyhow::Result<Vec<Vec<PathBuf>>> {
+        let socket_units = Self::config_tokens("Sockets", config_paths)?;
+        if !socket_units.is_empty() {
+            return socket_units
+                .iter()
+                .map(|u| {
+                    self.cat_config_paths(u)?.ok_or_else(|| {
+                        anyhow::anyhow!("Failed to locate config of socket unit {u}")
+                    })
+                })
+                .collect();
+        }
+
+        // Implicit socket unit with the same name
+        let mut socket_units = Vec::new();
+        if let Some(arg) = &self.arg {
+            // Socket units with Accept=no activate the service instance with the same name
+            socket_units.push(format!("{}@{arg}.socket", self.name));
+        }
+        // Socket units with Accept=yes spawn a service template instance for each connection
+        socket_units.push(format!("{}.socket", self.name));
+        for socket_unit in socket_units {
+            let Some(socket_config_paths) = self.cat_config_paths(&socket_unit)? else {
+                continue;
+            };
+            if self.arg.is_some() && !socket_unit.contains('@') {
+                let socket_config_paths: Vec<_> =
+                    socket_config_paths.iter().map(PathBuf::as_path).collect();
+                let accept = Self::config_vals("Accept", &socket_config_paths)?
+                    .last()
+                    .is_some_and(|v| ["yes", "true", "on", "1"].contains(&v.as_str()));
+                if !accept {
+                    continue;
+                }
+            }
+            log::info!("Found socket unit {socket_unit}");
+            return Ok(vec![socket_config_paths]);
+        }
+        Ok(vec![])
+    }
+
+    /// Get binds of a socket unit `ListenStream=`/`ListenDatagram=` value, with the given protocol
+    fn parse_listen_binds(listen: &str, proto: &SocketProtocol) -> Vec<KnownBind> {
+        let (afs, port) = if listen.starts_with(['/', '@']) {
+            // Unix socket
+            return vec![];
+        } else if let Some(addr_port) = listen.strip_prefix('[') {
+            (
+                vec![SocketFamily::Ipv6],
+                addr_port.split_once("]:").map(|(_, p)| p),
+            )
+        } else if let Some((addr, port)) = listen.rsplit_once(':') {
+            if addr.parse::<Ipv4Addr>().is_err() {
+                // ie. vsock:CID:PORT
+                return vec![];
+            }
+            (vec![SocketFamily::Ipv4], Some(port))
+        } else {
+            // Port only, bound on both address families
+            (vec![SocketFamily::Ipv4, SocketFamily::Ipv6], Some(listen))
+        };
+        let Some(port) = port.and_then(|p| p.parse::<u16>().ok()) else {
+            log::warn!("Unable to parse socket listen address {listen:?}, ignoring it");
+            return vec![];
+        };
+        afs.into_iter()
+            .map(|af| KnownBind {
+                af,
+                proto: proto.clone(),
+                port: NonZeroU16::new(port),
+            })
+            .collect()
+    }
+
+    /// Get unit config file paths from `systemctl cat`, or `None` if the unit does not exist
+    fn cat_config_paths(&self, unit_name: &str) -> anyhow::Result<Option<Vec<PathBuf>>> {
+        let output = self
+            .systemctl()
+            .args(["cat", unit_name])
+            .env("LANG", "C")
+            .output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Self::parse_cat_config_paths(&output.stdout).map(Some)
+    }
+
+    /// Get writer for a new fragment, or for its content to be printed if in dry run mode
+    fn fragment_writer(fragment_path: &Path, dry_run: bool) -> anyhow::Result<FragmentWriter> {
+        if dry_run {
+            let mut stdout = io::stdout();
+            writeln!(stdout, "# {}", fragment_path.display())?;
+            Ok(FragmentWriter::Stdout(stdout))
+        } else {
+            #[expect(clippy::unwrap_used)]
+            // fragment_path guarantees by construction we have a parent
+            let dir = fragment_path.parent().unwrap();
+            fs::create_dir_all(dir).map_err(|err| write_error_context(err, dir))?;
+            FragmentWriter::new(fragment_path)
+        }
+    }
+
+    /// Fail early, with guidance, if the profiling and hardening config fragments can not be written or removed,
+    /// before changing anything
+    pub fn ensure_config_writable(&self) -> anyhow::Result<()> {
+        for fragment_path in [
+            self.fragment_path(PROFILING_FRAGMENT_NAME, false)?,
+            self.fragment_path(HARDENING_FRAGMENT_NAME, true)?,
+        ] {
+            #[expect(clippy::unwrap_used)]
+            // fragment_path guarantees by construction we have a parent
+            Self::check_dir_writable(fragment_path.parent().unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Check files can be written in a directory, which may not exist yet, without writing anything
+    fn check_dir_writable(dir: &Path) -> anyhow::Result<()> {
+        // Missing directories are created when writing, so check the deepest existing one
+        let existing_dir = dir.ancestors().find(|d| d.is_dir()).unwrap_or(dir);
+        // Unnamed if supported, and removed on drop anyway
+        tempfile::tempfile_in(existing_dir)
+            .map(drop)
+            .map_err(|err| write_error_context(err, dir))
+    }
+
+    /// Get writer for a fragment at an arbitrary path, or stdout if path is `-`
+    fn output_writer(output_path: &Path) -> anyhow::Result<FragmentWriter> {
+        if output_path == Path::new(STDOUT_OUTPUT_PATH) {
+            Ok(FragmentWriter::Stdout(io::stdout()))
+        } else {
+            FragmentWriter::new(output_path)
+        }
+    }
+
+    /// Split special prefixes from the actual command line
+    fn split_exec_prefixes(cmd: &str) -> (&str, &str) {
+        let cmd_start = cmd
+            .find(|c| !EXEC_PREFIX_CHARS.contains(&c))
+            .unwrap_or(cmd.len());
+        cmd.split_at(cmd_start)
+    }
+
+    /// Split the first word of a command line from the rest, honoring quoting like systemd, so that
+    /// the words are kept unchanged, with their quotes, specifiers and variables for systemd to expand
+    fn split_exec_word(cmd: &str) -> (&str, &str) {
+        let mut quote = None;
+        let mut escaped = false;
+        for (i, c) in cmd.char_indices() {
+            match (c, quote) {
+                _ if escaped => escaped = false,
+                ('\\', _) => escaped = true,
+                ('"' | '\'', None) => quote = Some(c),
+                (_, Some(q)) if c == q => quote = None,
+                (_, None) if c.is_whitespace() => {
+                    let (word, rest) = cmd.split_at(i);
+                    return (word, rest.trim_start());
+                }
+                _ => (),
+            }
+        }
+        (cmd, "")
+    }
+
+    /// Quote arguments we insert in a command line, and join them
+    fn quote_exec_args(args: &[String]) -> String {
+        args.iter().map(|a| Self::quote_exec_word(a)).join(" ")
+    }
+
+    /// Quote a word we insert in a command line, so that systemd passes it unchanged as a single argument, even if it
+    /// contains whitespace, quotes, specifiers or variables
+    fn quote_exec_word(word: &str) -> Cow<'_, str> {
+        if word
+            .chars()
+            .all(|c| !c.is_whitespace() && !['"', '\'', '\\', '%', '$', ';'].contains(&c))
+        {
+            return Cow::Borrowed(word);
+        }
+        let mut quoted = String::with_capacity(word.len() + 2);
+        quoted.push('"');
+        for c in word.chars() {
+            match c {
+                '"' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                '%' | '$' => {
+                    // Escaped by doubling them
+                    quoted.push(c);
+                    quoted.push(c);
+                }
+                _ => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        Cow::Owned(quoted)
+    }
+
+    /// Build command line running the command under profiling, preserving its special prefixes,
+    /// or `None` if the command must be left unchanged
+    fn wrap_exec_cmd(
+        cmd: &str,
+        shh_bin: &str,
+        hardening_opts: &HardeningOptions,
+        profile_data_path: &Path,
+        strace_log_path: Option<&Path>,
+        timeout: Option<Duration>,
+        tracer: TracingBackend,
+    ) -> anyhow::Result<Option<String>> {
+        let (prefixes, cmd) = Self::split_exec_prefixes(cmd);
+        if prefixes.contains(PRIVILEGED_PREFIX) {
+            // Not affected by hardening, no need to profile
+            return Ok(None);
+        }
+        let (shh_argv0, cmd) = if prefixes.contains(ARGV0_PREFIX) {
+            // argv[0] is now the one of our binary, and the original one can not go through strace
+            let (exe, args) = Self::split_exec_word(cmd);
+            let (argv0, args) = Self::split_exec_word(args);
+            log::warn!("Unable to preserve argv[0] {argv0:?} of command {exe:?} while profiling");
+            (
+                format!(" {}", Self::quote_exec_word(shh_bin)),
+                if args.is_empty() {
+                    exe.to_owned()
+                } else {
+                    format!("{exe} {args}")
+                },
+            )
+        } else {
+            (String::new(), cmd.to_owned())
+        };
+        if cmd.contains("$MAINPID") || cmd.contains("${MAINPID}") {
+            log::warn!(
+                "Command {cmd:?} refers to the main process, which is our profiler while profiling"
+            );
+        }
+        // Specifiers, variables and quoted arguments (ie. of `sh -c` wrappers, or paths with spaces) are passed
+        // through unchanged, so that systemd expands and unquotes them before running our binary, like it would for
+        // the original command. Words we insert are quoted instead, so that systemd passes them unchanged
+        let strace_log_arg = if let Some(strace_log_path) = strace_log_path {
+            format!(
+                " -l {}",
+                Self::quote_exec_word(
+                    strace_log_path
+                        .to_str()
+                        .ok_or_else(|| anyhow::anyhow!("Unable to decode strace log path"))?
+                )
+            )
+        } else {
+            String::new()
+        };
+        // Only passed if not the default, so that profiling fragments do not change for strace users
+        let tracer_arg = if tracer == TracingBackend::default() {
+            String::new()
+        } else {
+            format!(" --tracer {tracer}")
+        };
+        Ok(Some(format!(
+            "{}{}{} run {} -p {}{}{}{} -- {}",
+            prefixes,
+            Self::quote_exec_word(shh_bin),
+            shh_argv0,
+            Self::quote_exec_args(&hardening_opts.to_args()),
+            Self::quote_exec_word(
+                profile_data_path
+                    .to_str()
+                    .ok_or_else(|| anyhow::anyhow!("Unable to decode profile data path"))?
+            ),
+            strace_log_arg,
+            timeout
+                .map(|t| format!(" --timeout {}", t.as_secs()))
+                .unwrap_or_default(),
+            tracer_arg,
+            cmd
+        )))
+    }
+
+    /// Remove profiling config fragment
+    pub fn remove_profile_fragment(&self) -> anyhow::Result<()> {
+        let fragment_path = self.fragment_path(PROFILING_FRAGMENT_NAME, false)?;
+        fs::remove_file(&fragment_path)?;
+        log::info!("{fragment_path:?} removed");
+        Ok(())
+    }
+
+    /// Get hardening level preset recorded in the profiling fragment, if any
+    pub fn profiling_hardening_level(&self) -> anyhow::Result<Option<String>> {
+        let fragment_path = self.fragment_path(PROFILING_FRAGMENT_NAME, false)?;
+        if !fragment_path.is_file() {
+            return Ok(None);
+        }
+        Self::fragment_hardening_level(&fragment_path)
+    }
+
+    /// Get hardening level preset recorded in a fragment, if any
+    fn fragment_hardening_level(fragment_path: &Path) -> anyhow::Result<Option<String>> {
+        let fragment_file = BufReader::new(File::open(fragment_path)?);
+        for line in fragment_file.lines() {
+            if let Some(level) = line?.strip_prefix(HARDENING_LEVEL_COMMENT_PREFIX) {
+                return Ok(Some(level.trim().to_owned()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get hardening options used while profiling (including the target systemd version), as recorded in the
+    /// profiling fragment, if any
+    pub fn profiling_hardening_options(&self) -> anyhow::Result<Option<HardeningOptions>> {
+        let fragment_path = self.fragment_path(PROFILING_FRAGMENT_NAME, false)?;
+        if !fragment_path.is_file() {
+            return Ok(None);
+        }
+        Self::fragment_hardening_options(&fragment_path)
+    }
+
+    /// Get hardening options recorded in a fragment, if any
+    fn fragment_hardening_options(
+        fragment_path: &Path,
+    ) -> anyhow::Result<Option<HardeningOptions>> {
+        let fragment_file = BufReader::new(File::open(fragment_path)?);
+        for line in fragment_file.lines() {
+            if let Some(args) = line?.strip_prefix(HARDENING_OPTIONS_COMMENT_PREFIX) {
+                let mut opts = serde_json::from_str(args.trim())
+                    .map_err(anyhow::Error::new)
+                    .and_then(|args: Vec<String>| HardeningOptions::from_args(&args))
+                    .map_err(|err| {
+                        err.context(format!("Invalid hardening options in {fragment_path:?}"))
+                    })?;
+                opts.level = Self::fragment_hardening_level(fragment_path)?;
+                return Ok(Some(opts));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get actions allowed even if not observed, as recorded in the profiling fragment
+    pub fn profiling_allowances(&self) -> anyhow::Result<Vec<String>> {
+        let fragment_path = self.fragment_path(PROFILING_FRAGMENT_NAME, false)?;
+        if !fragment_path.is_file() {
+            return Ok(vec![]);
+        }
+        Self::fragment_allowances(&fragment_path)
+    }
+
+    /// Get actions allowed even if not observed, as recorded in a fragment
+    fn fragment_allowances(fragment_path: &Path) -> anyhow::Result<Vec<String>> {
+        let fragment_file = BufReader::new(File::open(fragment_path)?);
+        let mut allowances = Vec::new();
+        for line in fragment_file.lines() {
+            if let Some(allowance) = line?.strip_prefix(ALLOWANCE_COMMENT_PREFIX) {
+                allowances.push(allowance.trim().to_owned());
+            }
+        }
+        Ok(allowances)
+    }
+
+    /// Remove hardening config fragment
+    pub fn remove_hardening_fragment(&self) -> anyhow::Result<()> {
+        let fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true)?;
+        fs::remove_file(&fragment_path)?;
+        log::info!("{fragment_path:?} removed");
+        Ok(())
+    }
+
+    /// Remove all our config fragments that exist, and the drop-in directories left empty, returning the removed paths
+    pub fn remove_fragments(&self) -> anyhow::Result<Vec<PathBuf>> {
+        Self::remove_fragment_files(&[
+            self.fragment_path(PROFILING_FRAGMENT_NAME, false)?,
+            self.fragment_path(HARDENING_FRAGMENT_NAME, true)?,
+            self.prev_hardening_fragment_path()?,
+        ])
+    }
+
+    /// Remove fragment files if they exist, and their drop-in directories if left empty
+    fn remove_fragment_files(fragment_paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        for fragment_path in fragment_paths {
+            match fs::remove_file(fragment_path) {
+                Ok(()) => {
+                    log::info!("{fragment_path:?} removed");
+                    removed.push(fragment_path.to_owned());
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let drop_in_dirs: BTreeSet<_> = removed
+            .iter()
+            .filter_map(|p| p.parent())
+            .filter(|d| d.extension() == Some(OsStr::new("d")))
+            .map(Path::to_owned)
+            .collect();
+        for drop_in_dir in drop_in_dirs {
+            if fs::read_dir(&drop_in_dir)?.next().is_none() {
+                fs::remove_dir(&drop_in_dir)?;
+                log::info!("{drop_in_dir:?} removed");
+                removed.push(drop_in_dir);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Path of the hardening fragment set aside during profiling
+    fn prev_hardening_fragment_path(&self) -> anyhow::Result<PathBuf> {
+        Ok(self
+            .fragment_path(HARDENING_FRAGMENT_NAME, true)?
+            .with_extension("conf.prev"))
+    }
+
+    /// Get options of hardening fragment set aside during profiling, if any
+    pub fn prev_hardening_options(&self) -> anyhow::Result<Option<Vec<OptionWithValue>>> {
+        let fragment_path = self.prev_hardening_fragment_path()?;
+        if !fragment_path.is_file() {
+            return Ok(None);
+        }
+        Self::fragment_options(BufReader::new(File::open(&fragment_path)?)).map(Some)
+    }
+
+    /// Parse options of a hardening fragment, including the ones only suggested in comments
+    fn fragment_options<R: BufRead>(reader: R) -> anyhow::Result<Vec<OptionWithValue>> {
+        let mut opts = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty()
+                || (line.starts_with('#') && !line.starts_with(SUGGESTION_COMMENT_PREFIX))
+                || line.starts_with('[')
+            {
+                continue;
+            }
+            opts.push(line.parse()?);
+        }
+        Ok(opts)
+    }
+
+    /// Restore hardening fragment set aside during profiling, if any
+    pub fn restore_prev_hardening_fragment(&self) -> anyhow::Result<()> {
+        let prev_fragment_path = self.prev_hardening_fragment_path()?;
+        if prev_fragment_path.is_file() {
+            let fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true)?;
+            fs::rename(&prev_fragment_path, &fragment_path)?;
+            log::info!("{fragment_path:?} restored");
+        }
+        Ok(())
+    }
+
+    /// Remove hardening fragment set aside during profiling, if any
+    pub fn remove_prev_hardening_fragment(&self) -> anyhow::Result<()> {
+        let fragment_path = self.prev_hardening_fragment_path()?;
+        if fragment_path.is_file() {
+            fs::remove_file(&fragment_path)?;
+            log::info!("{fragment_path:?} removed");
+        }
+        Ok(())
+    }
+
+    /// Add hardening config fragment with the given options, generated with the given hardening level preset if any.
+    /// If `verify` is set, the fragment is checked with `systemd-analyze verify` before it is written.
+    pub fn add_hardening_fragment(
+        &self,
+        opts: Vec<OptionWithValue>,
+        level: Option<&str>,
+        allowances: &[String],
+        dry_run: bool,
+        output_path: Option<&Path>,
+        verify: bool,
+    ) -> anyhow::Result<()> {
+        let notify_access = self.unit_notify_access()?;
+        let mut fragment = Vec::new();
+        writeln!(
+            fragment,
+            "# This file has been autogenerated by {}",
+            env!("CARGO_PKG_NAME")
+        )?;
+        if let Some(level) = level {
+            writeln!(fragment, "{HARDENING_LEVEL_COMMENT_PREFIX}{level}")?;
+        }
+        for allowance in allowances {
+            writeln!(fragment, "{ALLOWANCE_COMMENT_PREFIX}{allowance}")?;
+        }
+        writeln!(fragment, "[Service]")?;
+        if let Some(notify_access) = notify_access {
+            // Pin the value the unit had before profiling, which overrode it
+            writeln!(fragment, "NotifyAccess={notify_access}")?;
+        }
+        Self::write_grouped_options(&mut fragment, opts)?;
+
+        let (mut fragment_file, fragment_path) = if let Some(output_path) = output_path {
+            // Deployed by the caller, so we don't touch the unit config directories
+            (Self::output_writer(output_path)?, output_path.to_owned())
+        } else {
+            let fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true)?;
+            Self::warn_fragment_order(&fragment_path);
+            if verify && !dry_run {
+                // Before anything is written, so that a rejected fragment leaves the unit config untouched
+                self.verify_fragment(&fragment_path, &String::from_utf8_lossy(&fragment))?;
+            }
+            (
+                Self::fragment_writer(&fragment_path, dry_run)?,
+                fragment_path,
+            )
+        };
+        fragment_file.write_all(&fragment)?;
+        fragment_file.commit()?;
+
+        if !dry_run && (fragment_path != Path::new(STDOUT_OUTPUT_PATH)) {
+            log::info!("Config fragment written in {fragment_path:?}");
+        }
+        Ok(())
+    }
+
+    /// Write options with their rationale, grouped by category with a comment heading each group. Options of the
+    /// same category keep their relative order, which matters for some of them (ie. `SocketBindDeny=`)
+    fn write_grouped_options<W: Write>(
+        writer: &mut W,
+        mut opts: Vec<OptionWithValue>,
+    ) -> io::Result<()> {
+        opts.sort_by_key(OptionWithValue::category);
+        let mut prev_category = None;
+        for opt in opts {
+            let category = opt.category();
+            if prev_category != Some(category) {
+                writeln!(writer, "\n{RATIONALE_COMMENT_PREFIX}{category}")?;
+                prev_category = Some(category);
+            }
+            writeln!(
+                writer,
+                "{RATIONALE_COMMENT_PREFIX}{}",
+                opt.rationale.as_deref().unwrap_or(DEFAULT_RATIONALE)
+            )?;
+            writeln!(writer, "{opt}")?;
+        }
+        Ok(())
+    }
+
+    /// Check unit config with `systemd-analyze verify`, as if `fragment` was written at `fragment_path` and the
+    /// profiling fragment was removed, and fail if it reports issues in the fragment. The live unit config is not
+    /// changed.
+    fn verify_fragment(&self, fragment_path: &Path, fragment: &str) -> anyhow::Result<()> {
+        // Drop-ins shadow the ones with the same name in the following directories of the unit search path
+        let unit_dir = tempfile::tempdir()?;
+        Self::shadow_drop_in(
+            unit_dir.path(),
+            &self.fragment_path(PROFILING_FRAGMENT_NAME, false)?,
+            "",
+        )?;
+        let fragment_path = Self::shadow_drop_in(unit_dir.path(), fragment_path, fragment)?;
+        // Trailing separator keeps the default search path after ours
+        let mut unit_path = unit_dir.path().as_os_str().to_owned();
+        unit_path.push(":");
+
+        let output = match self
+            .systemd_analyze()
+            .args(["verify", "--man=no", &self.unit_name()])
+            .env("LANG", "C")
+            .env("SYSTEMD_UNIT_PATH", unit_path)
+            .output()
+        {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                log::warn!("systemd-analyze is not available on this system, unable to verify hardening config");
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let issues = Self::parse_verify_issues(
+            &String::from_utf8_lossy(&output.stderr),
+            &fragment_path,
+            fragment,
+        );
+        if !output.status.success() && issues.is_empty() {
+            // Not caused by our fragment
+            log::warn!(
+                "systemd-analyze verify reported issues with unit {}: {}",
+                self.unit_name(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        anyhow::ensure!(
+            issues.is_empty(),
+            "Hardening config rejected by systemd-analyze verify:\n{}",
+            issues.join("\n")
+        );
+        log::info!("Hardening config verified with systemd-analyze");
+        Ok(())
+    }
+
+    /// Write a drop-in with `content` in `unit_dir`, in a drop-in directory named like the one of `drop_in_path`, and
+    /// return its path
+    fn shadow_drop_in(
+        unit_dir: &Path,
+        drop_in_path: &Path,
+        content: &str,
+    ) -> anyhow::Result<PathBuf> {
+        let (Some(drop_in_dir_name), Some(file_name)) = (
+            drop_in_path.parent().and_then(Path::file_name),
+            drop_in_path.file_name(),
+        ) else {
+            anyhow::bail!("Invalid drop-in path {drop_in_path:?}");
+        };
+        let drop_in_dir = unit_dir.join(drop_in_dir_name);
+        fs::create_dir_all(&drop_in_dir)?;
+        let shadow_path = drop_in_dir.join(file_name);
+        fs::write(&shadow_path, content)?;
+        Ok(shadow_path)
+    }
+
+    /// Extract messages of `systemd-analyze verify` output about a fragment, along with the directive they refer to
+    fn parse_verify_issues(output: &str, fragment_path: &Path, fragment: &str) -> Vec<String> {
+        let prefix = format!("{}:", fragment_path.display());
+        output
+            .lines()
+            .filter_map(|l| l.strip_prefix(&prefix))
+            .map(|l| {
+                let located = l.split_once(':').and_then(|(line_num, msg)| {
+                    let line_num = line_num.parse::<usize>().ok()?;
+                    let directive = fragment.lines().nth(line_num.checked_sub(1)?)?;
+                    Some((line_num, directive, msg))
+                });
+                if let Some((line_num, directive, msg)) = located {
+                    format!("  line {line_num} ({}): {}", directive.trim(), msg.trim())
+                } else {
+                    format!("  {}", l.trim())
+                }
+            })
+            .collect()
+    }
+
+    /// Get unit config paths, ignoring our own fragments
+    fn unit_config_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let own_fragment_paths = [
+            self.fragment_path(PROFILING_FRAGMENT_NAME, false)?,
+            self.fragment_path(HARDENING_FRAGMENT_NAME, true)?,
+            self.prev_hardening_fragment_path()?,
+        ];
+        Ok(self
+            .config_paths()?
+            .into_iter()
+            .filter(|p| !own_fragment_paths.contains(p))
+            .collect())
+    }
+
+    /// Get `NotifyAccess=` value of the unit config, ignoring our own fragments, if set
+    fn unit_notify_access(&self) -> anyhow::Result<Option<String>> {
+        let config_paths_bufs = self.unit_config_paths()?;
+        let config_paths = config_paths_bufs
+            .iter()
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>();
+        Ok(Self::config_vals("NotifyAccess", &config_paths)?.pop())
+    }
+
+    /// Get values of options set by the unit config, ignoring our own fragments
+    pub fn unit_options(&self, names: &[&str]) -> anyhow::Result<Vec<OptionWithValue>> {
+        let config_paths_bufs = self.unit_config_paths()?;
+        let config_paths = config_paths_bufs
+            .iter()
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>();
+        Self::config_options(names, &config_paths)
+    }
+
+    /// Get effective values of options in config files, with boolean values normalized
+    fn config_options(
+        names: &[&str],
+        config_paths: &[&Path],
+    ) -> anyhow::Result<Vec<OptionWithValue>> {
+        let mut opts = Vec::new();
+        for name in names {
+            let Some(val) = Self::config_vals(name, config_paths)?.pop() else {
+                continue;
+            };
+            let val = match val.as_str() {
+                "yes" | "on" | "1" | "true" => "true",
+                "no" | "off" | "0" | "false" => "false",
+                v => v,
+            };
+            opts.push(format!("{name}={val}").parse()?);
+        }
+        Ok(opts)
+    }
+
+    /// Get values of options implied by `DynamicUser=yes` in the unit config, among `names`, ignoring our own
+    /// fragments
+    pub fn dynamic_user_options(&self, names: &[&str]) -> anyhow::Result<Vec<OptionWithValue>> {
+        if self.user {
+            return Ok(vec![]);
+        }
+        let config_paths_bufs = self.unit_config_paths()?;
+        let config_paths = config_paths_bufs
+            .iter()
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>();
+        Self::config_dynamic_user_options(names, &config_paths)
+    }
+
+    /// Get values of options implied by `DynamicUser=yes` in config files, among `names`
+    fn config_dynamic_user_options(
+        names: &[&str],
+        config_paths: &[&Path],
+    ) -> anyhow::Result<Vec<OptionWithValue>> {
+        if !Self::is_dynamic_user(config_paths)? {
+            return Ok(vec![]);
+        }
+        DYNAMIC_USER_IMPLIED_OPTS
+            .iter()
+            .filter(|(name, _)| names.contains(name))
+            .map(|(name, val)| format!("{name}={val}").parse())
+            .collect()
+    }
+
+    /// Return true if the unit runs with `DynamicUser=yes`
+    fn is_dynamic_user(config_paths: &[&Path]) -> anyhow::Result<bool> {
+        Ok(Self::config_vals("DynamicUser", config_paths)?
+            .last()
+            .is_some_and(|v| ["yes", "on", "1", "true"].contains(&v.as_str())))
+    }
+
+    /// Get a warning message if the unit config sends standard output elsewhere than to the journal, because the
+    /// profiling fragment overrides it
+    fn standard_output_override_warning(config_paths: &[&Path]) -> anyhow::Result<Option<String>> {
+        let Some(stdout) = Self::config_vals("StandardOutput", config_paths)?
+            .pop()
+            .filter(|v| !JOURNAL_STANDARD_OUTPUTS.contains(&v.as_str()))
+        else {
+            return Ok(None);
+        };
+        // Standard error follows standard output by default
+        let stderr_follows = Self::config_vals("StandardError", config_paths)?
+            .pop()
+            .is_none_or(|v| v == "inherit");
+        Ok(Some(format!(
+            "Unit standard output{} is sent to {stdout:?}, it will be sent to the journal instead while profiling, so the profiled behavior may differ",
+            if stderr_follows { " and error" } else { "" }
+        )))
+    }
+
+    /// Return true if the unit is of type `notify` or `notify-reload`
+    fn is_notify_type(config_paths: &[&Path]) -> anyhow::Result<bool> {
+        Ok(Self::config_vals("Type", config_paths)?
+            .last()
+            .is_some_and(|t| (t == "notify") || (t == "notify-reload")))
+    }
+
+    /// Return true if the unit is of type `forking`
+    fn is_forking_type(config_paths: &[&Path]) -> anyhow::Result<bool> {
+        Ok(Self::config_vals("Type", config_paths)?
+            .last()
+            .is_some_and(|t| t == "forking"))
+    }
+
+    /// Whether the unit runs in its own root directory or image
+    fn is_root_confined(config_paths: &[&Path]) -> anyhow::Result<bool> {
+        for key in ["RootDirectory", "RootImage"] {
+            if Self::config_vals(key, config_paths)?
+                .last()
+                .is_some_and(|v| !v.is_empty())
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Compare `systemd-analyze security` assessments of the unit without and with its hardening fragment
+    pub fn security_delta(&self) -> anyhow::Result<SecurityDelta> {
+        self.ensure_instance()?;
+        let fragment_path = self.fragment_path(HARDENING_FRAGMENT_NAME, true)?;
+        Self::check_security_delta_fragments(
+            &self.unit_name(),
+            &fragment_path,
+            &self.fragment_path(PROFILING_FRAGMENT_NAME, false)?,
+        )?;
+
+        // Assess copies of the unit config files in a temporary root directory, so that the live unit is never
+        // changed
+        let config_paths = self.config_paths()?;
+        let after = self.offline_security_report(&config_paths)?;
+        let before_config_paths: Vec<_> = config_paths
+            .iter()
+            .filter(|p| **p != fragment_path)
+            .cloned()
+            .collect();
+        let before = self.offline_security_report(&before_config_paths)?;
+
+        Ok(SecurityDelta::new(before, after))
+    }
+
+    /// Check the unit has a hardening fragment to assess, and is not being profiled, since the profiling fragment
+    /// would be assessed instead of the hardened state
+    fn check_security_delta_fragments(
+        unit_name: &str,
+        hardening_fragment_path: &Path,
+        profiling_fragment_path: &Path,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            hardening_fragment_path.is_file(),
+            "No hardening fragment found for unit {unit_name}"
+        );
+        anyhow::ensure!(
+            !profiling_fragment_path.exists(),
+            "Unit {unit_name} is being profiled"
+        );
+        Ok(())
+    }
+
+    /// Get `systemd-analyze security` assessment of the unit, as configured by the given config files
+    fn offline_security_report(&self, config_paths: &[PathBuf]) -> anyhow::Result<SecurityReport> {
+        let root_dir = tempfile::tempdir()?;
+        let unit_dir = root_dir.path().join(if self.user {
+            "etc/systemd/user"
+        } else {
+            "etc/systemd/system"
+        });
+        // The main unit file comes first, followed by drop-ins in their own directories
+        let mut config_paths = config_paths.iter();
+        let unit_path = config_paths
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Failed to locate main unit file"))?;
+        fs::create_dir_all(&unit_dir)?;
+        fs::copy(unit_path, unit_dir.join(Self::config_file_name(unit_path)?))?;
+        for drop_in_path in config_paths {
+            let drop_in_dir_name = drop_in_path
+                .parent()
+                .and_then(Path::file_name)
+                .ok_or_else(|| anyhow::anyhow!("Invalid drop-in path {drop_in_path:?}"))?;
+            let drop_in_dir = unit_dir.join(drop_in_dir_name);
+            fs::create_dir_all(&drop_in_dir)?;
+            fs::copy(
+                drop_in_path,
+                drop_in_dir.join(Self::config_file_name(drop_in_path)?),
+            )?;
+        }
+
+        let output = match self
+            .systemd_analyze()
+            .args(["security", "--no-pager", "--offline=true", "--root"])
+            .arg(root_dir.path())
+            .arg(self.unit_name())
+            .env("LANG", "C.UTF-8")
+            .output()
+        {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                anyhow::bail!("systemd-analyze is not available on this system, unable to assess unit security");
+            }
+            Err(err) => return Err(err.into()),
+        };
+        anyhow::ensure!(
+            output.status.success(),
+            "systemd-analyze security failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        SecurityReport::parse(&output.stdout)
+    }
+
+    /// Get the file name of a unit config file
+    fn config_file_name(path: &Path) -> anyhow::Result<&OsStr> {
+        path.file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid unit config path {path:?}"))
+    }
+
+    /// Make the service manager reload unit config files
+    pub fn reload_unit_config(&self) -> anyhow::Result<()> {
+        self.run_systemctl(&["daemon-reload"])
+    }
+
+    /// Run systemctl action (start, stop...) on the service
+    pub fn action(&self, verb: &str, block: bool) -> anyhow::Result<()> {
+        self.ensure_instance()?;
+        let unit_name = self.unit_name();
+        log::info!("{} {}", verb, unit_name);
+        let mut cmd = vec![verb];
+        if !block {
+            cmd.push("--no-block");
+        }
+        cmd.push(&unit_name);
+        // Blocking actions may take a while, especially when stopping a profiled service
+        let progress = if block && io::stderr().is_terminal() {
+            match JournalProgress::start(self.journalctl(), &unit_name) {
+                Ok(progress) => Some(progress),
+                Err(err) => {
+                    log::warn!("Unable to follow {unit_name} logs: {err:#}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let res = self.run_systemctl(&cmd);
+        drop(progress);
+        res
+    }
+
+    /// Get options resulting from profiling, from the service logs
+    pub fn profiling_result(&self) -> anyhow::Result<Vec<OptionWithValue>> {
+        let opts = Self::with_retries("Getting profiling result", self.command_retries, || {
+            self.read_profiling_result()
+                .map_err(Self::profiling_result_error)
+        })?;
+        Ok(match self.arg.as_deref() {
+            Some(arg) if !arg.is_empty() && !Self::is_distinctive_instance(arg) => {
+                log::warn!(
+                    "Instance name {arg:?} is too short or numeric to be told apart from other path components, \
+                     paths containing it are not generalized to other instances"
+                );
+                opts
+            }
+            // Our hardening fragment applies to all instances
+            Some(arg) if !arg.is_empty() => opts
+                .into_iter()
+                .map(|opt| match opt.value {
+                    OptionValue::String(value) => OptionWithValue {
+                        name: opt.name,
+                        value: OptionValue::String(Self::generalize_instance_paths(&value, arg)),
+                        rationale: opt.rationale,
+                        suggestion: opt.suggestion,
+                    },
+                    _ => opt,
+                })
+                .collect(),
+            _ => opts,
+        })
+    }
+
+    /// Check if value is a valid `MemoryMax=` limit: bytes with an optional binary unit suffix, a percentage of the
+    /// physical memory, or `infinity`
+    fn is_valid_memory_limit(val: &str) -> bool {
+        if val == "infinity" {
+            return true;
+        }
+        if let Some(percent) = val.strip_suffix('%') {
+            return percent
+                .parse::<f64>()
+                .is_ok_and(|p| (0.0..=100.0).contains(&p));
+        }
+        let num = val
+            .strip_suffix(['K', 'M', 'G', 'T', 'P', 'E'])
+            .unwrap_or(val);
+        !num.is_empty() && num.chars().all(|c| c.is_ascii_digit())
+    }
+
+    /// Check if instance name is unlikely to be equal to unrelated path components, ie. `1` in `/proc/1/status`
+    fn is_distinctive_instance(arg: &str) -> bool {
+        arg.len() >= MIN_GENERALIZED_INSTANCE_LEN && !arg.chars().all(|c| c.is_ascii_digit())
+    }
+
+    /// Replace path components equal to the instance name by the `%i` specifier, in space separated values
+    fn generalize_instance_paths(value: &str, arg: &str) -> String {
+        value
+            .split(' ')
+            .map(|token| {
+                if token.contains('/') {
+                    token
+                        .split('/')
+                        .map(|c| if c == arg { "%i" } else { c })
+                        .join("/")
+                } else {
+                    token.to_owned()
+                }
+            })
+            .join(" ")
+    }
+
+    /// Read options resulting from profiling, from the service logs
+    #[cfg(feature = "sd-journal")]
+    fn read_profiling_result(&self) -> anyhow::Result<Vec<OptionWithValue>> {
+        let journal =
+            Journal::open_unit(&self.unit_name(), self.user).map_err(ShhError::JournalRead)?;
+        Self::parse_profiling_result(journal.messages_rev())
+    }
+
+    /// Classify a failure to get the profiling result
+    fn profiling_result_error(err: anyhow::Error) -> CommandError {
+        match err.downcast_ref::<ShhError>() {
+            // Logs may not have been flushed to the journal yet
+            Some(
+                ShhError::ProfilingSnippetMissing {
+                    missing_marker: None,
+                }
+                | ShhError::JournalRead(_),
+            ) => CommandError::Transient(err),
+            _ => CommandError::Fatal(err),
+        }
+    }
+
+    /// Read options resulting from profiling, from the service logs
+    #[cfg(not(feature = "sd-journal"))]
+    fn read_profiling_result(&self) -> anyhow::Result<Vec<OptionWithValue>> {
+        // Start journalctl process
+        let mut child = self
+            .journalctl()
+            .args([
+                "-r",
+                "-o",
+                "cat",
+                "--output-fields=MESSAGE",
+                "--no-tail",
+                "-u",
+                &self.unit_name(),
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .env("LANG", "C")
+            .spawn()
+            .map_err(|err| ShhError::JournalRead(err.into()))?;
+
+        // Parse its output
+        #[expect(clippy::unwrap_used)]
+        let reader = BufReader::new(child.stdout.take().unwrap());
+        let opts = Self::parse_profiling_result(reader.lines().map(|r| r.map_err(Into::into)))?;
+
+        // Stop journalctl
+        child.kill()?;
+        child.wait()?;
+
+        Ok(opts)
+    }
+
+    /// Parse options from service log lines, in reverse chronological order
+    fn parse_profiling_result<I>(lines: I) -> anyhow::Result<Vec<OptionWithValue>>
+    where
+        I: Iterator<Item = anyhow::Result<String>>,
+    {
+        // The lines are in reverse chronological order (to get the end as fast as possible),
+        // so the end marker comes first
+        let mut snippet_lines: Option<Vec<String>> = None;
+        for line in lines {
+            let line = line.map_err(ShhError::JournalRead)?;
+            match snippet_lines.as_mut() {
+                None if line == END_OPTION_OUTPUT_SNIPPET => snippet_lines = Some(Vec::new()),
+                None if line == START_OPTION_OUTPUT_SNIPPET => {
+                    anyhow::bail!(ShhError::ProfilingSnippetMissing {
+                        missing_marker: Some(SnippetMarker::End),
+                    });
+                }
+                None => (),
+                Some(_) if line == END_OPTION_OUTPUT_SNIPPET => {
+                    anyhow::bail!(ShhError::ProfilingSnippetMissing {
+                        missing_marker: Some(SnippetMarker::Start),
+                    });
+                }
+                Some(snippet_lines) if line == START_OPTION_OUTPUT_SNIPPET => {
+                    // Rationale comments precede the option they apply to
+                    let mut opts = Vec::new();
+                    let mut rationale = None;
+                    for l in snippet_lines.iter().rev() {
+                        if let Some(comment) = l
+                            .strip_prefix(RATIONALE_COMMENT_PREFIX)
+                            .filter(|_| !l.starts_with(SUGGESTION_COMMENT_PREFIX))
+                        {
+                            rationale = Some(comment.to_owned());
+                        } else {
+                            let mut opt = l.parse::<OptionWithValue>().map_err(|err| {
+                                ShhError::ConfigParse {
+                                    line: l.to_owned(),
+                                    reason: err.to_string(),
+                                }
+                            })?;
+                            opt.rationale = rationale.take();
+                            opts.push(opt);
+                        }
+                    }
+                    return Ok(opts);
+                }
+                Some(snippet_lines) => snippet_lines.push(line),
+            }
+        }
+        if snippet_lines.is_some() {
+            anyhow::bail!(ShhError::ProfilingSnippetMissing {
+                missing_marker: Some(SnippetMarker::Start),
+            });
+        }
+        anyhow::bail!(ShhError::ProfilingSnippetMissing {
+            missing_marker: None
+        });
+    }
+
+    /// Return true if line ends with a continuation backslash, and not an escaped one,
+    /// like systemd's config parser
+    fn is_continued_line(line: &str) -> bool {
+        line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+    }
+
+    fn config_vals(key: &str, config_paths: &[&Path]) -> anyhow::Result<Vec<String>> {
+        // Note: we could use 'systemctl show -p xxx' but its output is different from config
+        // files, and we would need to interpret it anyway
+        let mut vals = Vec::new();
+        for config_path in config_paths {
+            let config_file = BufReader::new(File::open(config_path)?);
+            let mut lines = config_file.lines();
+            // Set if the previous line is continued, and is not a value of the key
+            let mut skip_continued = false;
+            while let Some(line) = lines.next() {
+                let line = line?;
+                if skip_continued {
+                    skip_continued = Self::is_continued_line(&line);
+                    continue;
+                }
+                // Whitespace around '=' is ignored
+                let Some((_, val)) = line
+                    .split_once('=')
+                    .filter(|(line_key, _)| line_key.trim() == key)
+                else {
+                    skip_continued = Self::is_continued_line(&line);
+                    continue;
+                };
+                let mut val = val.trim().to_owned();
+                let mut continued = Self::is_continued_line(&line);
+                while continued {
+                    // Remove trailing '\'
+                    val.pop();
+                    // Append next line
+                    let next_line = lines
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("Unexpected end of file"))??;
+                    val = format!("{} {}", val, next_line.trim_start());
+                    continued = Self::is_continued_line(&next_line);
+                }
+                if val.is_empty() {
+                    // Files are applied in order, so this also resets values of previous files
+                    vals.clear();
+                } else {
+                    vals.push(val);
+                }
+            }
+        }
+        Ok(vals)
+    }
+
+    /// Same as `config_vals`, but for options whose values are sets, split values assigned on a single line, so that
+    /// they are returned the same way as if they were assigned on separate lines
+    fn config_tokens(key: &str, config_paths: &[&Path]) -> anyhow::Result<Vec<String>> {
+        let vals = Self::config_vals(key, config_paths)?;
+        Ok(if SPACE_LIST_OPTS.contains(&key) {
+            vals.iter()
+                .flat_map(|v| v.split_whitespace())
+                .map(ToOwned::to_owned)
+                .collect()
+        } else {
+            vals
+        })
+    }
+
+    fn config_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        if self.is_template() {
+            // Template units can not be queried with status, but their config can be printed
+            let output = self
+                .systemctl()
+                .args(["cat", &self.unit_name()])
+                .env("LANG", "C")
+                .output()?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if stderr.contains("No files found") {
+                    anyhow::bail!(ShhError::UnitNotFound {
+                        state: "not-found".to_owned(),
+                        details: format!("({})", stderr.trim()),
+                    });
+                }
+                return Err(anyhow::Error::new(ShhError::SystemctlFailed {
+                    status: output.status,
+                })
+                .context(format!(
+                    "Failed to locate config of {}: {}",
+                    self.unit_name(),
+                    stderr.trim()
+                )));
+            }
+            return Self::parse_cat_config_paths(&output.stdout);
+        }
+        let output = self
+            .systemctl()
+            .args(["status", "-n", "0", &self.unit_name()])
+            .env("LANG", "C")
+            .output()?;
+        Self::parse_config_paths(&output.stdout)
+    }
+
+    /// Parse unit config file paths from `systemctl cat` output
+    fn parse_cat_config_paths(output: &[u8]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        let mut prev_line_empty = true;
+        for line in output.lines() {
+            let line = line?;
+            // Each file content is preceded by an empty line (except the first one), and a comment with its path
+            if let Some(path) = line.strip_prefix("# /").filter(|_| prev_line_empty) {
+                paths.push(Path::new("/").join(path));
+            }
+            prev_line_empty = line.is_empty();
+        }
+        anyhow::ensure!(!paths.is_empty(), "Failed to locate main unit file");
+        Ok(paths)
+    }
+
+    /// Parse unit config file paths from `systemctl status` output
+    fn parse_config_paths(output: &[u8]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        let mut drop_in_dir = None;
+        for line in output.lines() {
+            let line = line?;
+            let line = line.trim_start();
+            if let Some(loaded) = line.strip_prefix("Loaded:") {
+                // Main unit file
+                anyhow::ensure!(paths.is_empty());
+                let loaded = loaded.trim();
+                let (state, details) = loaded.split_once(' ').unwrap_or((loaded, ""));
+                match state {
+                    "loaded" => {}
+                    "masked" | "not-found" | "bad-setting" | "error" => {
+                        anyhow::bail!(ShhError::UnitNotFound {
+                            state: state.to_owned(),
+                            details: details.to_owned(),
+                        });
+                    }
+                    _ => anyhow::bail!("Unexpected unit load state {state:?}"),
+                }
+                let path = line
+                    .split_once('(')
+                    .ok_or_else(|| anyhow::anyhow!("Failed to locate main unit file"))?
+                    .1
+                    .split_once(';')
+                    .ok_or_else(|| anyhow::anyhow!("Failed to locate main unit file"))?
+                    .0;
+                paths.push(PathBuf::from(path));
+            } else if let Some(dir) = line.strip_prefix("Drop-In:") {
+                // First drop-in base dir
+                anyhow::ensure!(paths.len() == 1, "Unexpected drop-in before main unit file");
+                anyhow::ensure!(
+                    drop_in_dir.is_none(),
+                    "Unexpected multiple drop-in sections"
+                );
+                drop_in_dir = Some(PathBuf::from(dir.trim()));
+            } else if let Some(dir) = drop_in_dir.as_ref() {
+                if line.starts_with('/') {
+                    // Next base dir
+                    drop_in_dir = Some(PathBuf::from(line.trim_end()));
+                } else if line.starts_with(DROP_IN_TREE_CHARS) {
+                    // Filenames in the previous base dir
+                    paths.extend(
+                        line.trim_start_matches(DROP_IN_TREE_CHARS)
+                            .trim_end()
+                            .split(", ")
+                            .map(|f| dir.join(f)),
+                    );
+                } else {
+                    // Next "Key: value" line, end of drop-ins
+                    break;
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    fn fragment_path(&self, name: &str, persistent: bool) -> anyhow::Result<PathBuf> {
+        let base_dir = Self::fragment_base_dir(self.user, persistent, &self.fragment_naming)?;
+        let filename = Self::fragment_filename(&self.fragment_naming, name)?;
+        Ok(base_dir
+            .join(format!(
+                "{}{}.service.d",
+                self.name,
                 if self.arg.is_some() { "@" } else { "" }
+            ))
+            .join(filename))
+    }
+
+    /// Base directory of the drop-in directories our config fragments are written to
+    fn fragment_base_dir(
+        user: bool,
+        persistent: bool,
+        fragment_naming: &FragmentNaming,
+    ) -> anyhow::Result<PathBuf> {
+        Ok(match (user, persistent, &fragment_naming.persistent_dir) {
+            (_, true, Some(dir)) => dir.to_owned(),
+            (false, false, _) => PathBuf::from("/run/systemd/system"),
+            (false, true, None) => PathBuf::from("/etc/systemd/system"),
+            (true, false, _) => Self::user_runtime_dir()?.join("systemd/user"),
+            (true, true, None) => Self::user_config_dir()?.join("systemd/user"),
+        })
+    }
+
+    fn fragment_filename(fragment_naming: &FragmentNaming, name: &str) -> anyhow::Result<String> {
+        let filename = format!(
+            "{}{}-{}.conf",
+            fragment_naming.prefix, fragment_naming.tool_name, name
+        );
+        anyhow::ensure!(
+            Path::new(&filename).file_name() == Some(OsStr::new(&filename))
+                && filename.ends_with(".conf"),
+            "Invalid config fragment filename {filename:?}"
+        );
+        Ok(filename)
+    }
+
+    /// List units having some of our config fragments, sorted by unit name
+    pub fn list_fragments(
+        user: bool,
+        fragment_naming: &FragmentNaming,
+    ) -> anyhow::Result<Vec<UnitFragments>> {
+        let mut units: BTreeMap<String, BTreeSet<&'static str>> = BTreeMap::new();
+        for (name, persistent) in [
+            (PROFILING_FRAGMENT_NAME, false),
+            (HARDENING_FRAGMENT_NAME, true),
+        ] {
+            let base_dir = Self::fragment_base_dir(user, persistent, fragment_naming)?;
+            let filename = Self::fragment_filename(fragment_naming, name)?;
+            for unit in Self::units_with_fragment(&base_dir, &filename)? {
+                units.entry(unit).or_default().insert(name);
+            }
+        }
+        Ok(units
+            .into_iter()
+            .map(|(unit, fragments)| UnitFragments {
+                unit,
+                fragments: fragments.into_iter().collect(),
+            })
+            .collect())
+    }
+
+    /// Get names of service units whose drop-in directory in `base_dir` contains a fragment file
+    fn units_with_fragment(base_dir: &Path, filename: &str) -> anyhow::Result<Vec<String>> {
+        let entries = match fs::read_dir(base_dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut units = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(unit) = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.strip_suffix(".d"))
+                .filter(|n| n.ends_with(".service"))
+                .map(ToOwned::to_owned)
+            else {
+                continue;
+            };
+            if entry.path().join(filename).is_file() {
+                units.push(unit);
+            }
+        }
+        Ok(units)
+    }
+
+    /// Warn if a fragment would be applied before common drop-ins, which could then override its options
+    fn warn_fragment_order(fragment_path: &Path) {
+        if fragment_path
+            .file_name()
+            .is_some_and(|f| f < OsStr::new(SYSTEMCTL_EDIT_DROP_IN_NAME))
+        {
+            log::warn!(
+                "Config fragment {fragment_path:?} sorts before common drop-ins like {SYSTEMCTL_EDIT_DROP_IN_NAME:?}, which may override its options"
+            );
+        }
+    }
+
+    /// Base directory for `RuntimeDirectory=`
+    fn runtime_dir(&self) -> anyhow::Result<PathBuf> {
+        if self.user {
+            Self::user_runtime_dir()
+        } else {
+            Ok(PathBuf::from("/run"))
+        }
+    }
+
+    fn user_runtime_dir() -> anyhow::Result<PathBuf> {
+        env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!("XDG_RUNTIME_DIR is not set"))
+    }
+
+    fn user_config_dir() -> anyhow::Result<PathBuf> {
+        if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+            Ok(PathBuf::from(dir))
+        } else {
+            env::var_os("HOME")
+                .map(|h| PathBuf::from(h).join(".config"))
+                .ok_or_else(|| anyhow::anyhow!("Unable to locate user config directory"))
+        }
+    }
+
+    /// Run systemctl with the given arguments, retrying if it fails transiently
+    fn run_systemctl(&self, args: &[&str]) -> anyhow::Result<()> {
+        let desc = format!("systemctl {}", args.join(" "));
+        Self::with_retries(&desc, self.command_retries, || {
+            let output = self
+                .systemctl()
+                .args(args)
+                .env("LANG", "C")
+                .stdin(Stdio::inherit())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::piped())
+                .output()
+                .map_err(|e| CommandError::Fatal(e.into()))?;
+            if output.status.success() {
+                Ok(())
+            } else {
+                Err(Self::systemctl_error(
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim(),
+                ))
+            }
+        })
+    }
+
+    /// Classify a systemctl failure from its error output
+    fn systemctl_error(status: ExitStatus, stderr: &str) -> CommandError {
+        let transient = SYSTEMCTL_TRANSIENT_ERRORS
+            .iter()
+            .any(|e| stderr.contains(e));
+        let mut err = anyhow::Error::new(ShhError::SystemctlFailed { status });
+        if !stderr.is_empty() {
+            err = err.context(stderr.to_owned());
+        }
+        if transient {
+            CommandError::Transient(err)
+        } else {
+            CommandError::Fatal(err)
+        }
+    }
+
+    /// Call function until it succeeds, retrying with exponential backoff at most `retries` times if it fails
+    /// transiently
+    fn with_retries<T, F>(desc: &str, retries: u32, mut f: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Result<T, CommandError>,
+    {
+        let mut delay = COMMAND_RETRY_INITIAL_DELAY;
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Ok(v) => return Ok(v),
+                Err(CommandError::Transient(err)) if attempt < retries => {
+                    attempt += 1;
+                    log::debug!(
+                        "{desc} failed: {err:#}, retrying in {delay:?} ({attempt}/{retries})"
+                    );
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(CommandError::Transient(err) | CommandError::Fatal(err)) => return Err(err),
+            }
+        }
+    }
+
+    /// Build systemctl command targeting the service manager of this service
+    fn systemctl(&self) -> Command {
+        let mut cmd = Command::new("systemctl");
+        if self.user {
+            cmd.arg("--user");
+        }
+        cmd
+    }
+
+    /// Build systemd-run command targeting the service manager of this service
+    fn systemd_run(&self) -> Command {
+        let mut cmd = Command::new("systemd-run");
+        if self.user {
+            cmd.arg("--user");
+        }
+        cmd
+    }
+
+    /// Build systemd-analyze command targeting the service manager of this service
+    fn systemd_analyze(&self) -> Command {
+        let mut cmd = Command::new("systemd-analyze");
+        if self.user {
+            cmd.arg("--user");
+        }
+        cmd
+    }
+
+    /// Build journalctl command targeting the journal of this service
+    fn journalctl(&self) -> Command {
+        let mut cmd = Command::new("journalctl");
+        if self.user {
+            cmd.arg("--user");
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::shadow_unrelated)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::process::ExitStatusExt as _;
+
+    use crate::{
+        hardening::HardeningMode,
+        summarize::ProgramAction,
+        systemd::{build_options, reconcile_existing, resolve, KernelVersion, SystemdVersion},
+    };
+
+    #[test]
+    fn test_split_exec_prefixes() {
+        assert_eq!(Service::split_exec_prefixes("/bin/foo"), ("", "/bin/foo"));
+        assert_eq!(
+            Service::split_exec_prefixes("-@/bin/foo foo"),
+            ("-@", "/bin/foo foo")
+        );
+        assert_eq!(
+            Service::split_exec_prefixes("!!/bin/foo -a"),
+            ("!!", "/bin/foo -a")
+        );
+        assert_eq!(Service::split_exec_prefixes("+!"), ("+!", ""));
+    }
+
+    #[test]
+    fn test_wrap_exec_cmd() {
+        let hardening_opts = HardeningOptions::safe();
+        let profile_data_path = Path::new("/run/p/001");
+
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "/bin/foo -a",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "/shh run -m safe -p /run/p/001 -- /bin/foo -a"
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "-@/bin/foo bar -a b",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "-@/shh /shh run -m safe -p /run/p/001 -- /bin/foo -a b"
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "-@/bin/foo bar",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "-@/shh /shh run -m safe -p /run/p/001 -- /bin/foo"
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "!/bin/foo",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "!/shh run -m safe -p /run/p/001 -- /bin/foo"
+        );
+        assert!(Service::wrap_exec_cmd(
+            "+!/bin/foo",
+            "/shh",
+            &hardening_opts,
+            profile_data_path,
+            None,
+            None,
+            TracingBackend::Strace
+        )
+        .unwrap()
+        .is_none());
+        assert!(Service::wrap_exec_cmd(
+            "!+/bin/foo",
+            "/shh",
+            &hardening_opts,
+            profile_data_path,
+            None,
+            None,
+            TracingBackend::Strace
+        )
+        .unwrap()
+        .is_none());
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "/bin/foo -a",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                Some(Duration::from_secs(600)),
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "/shh run -m safe -p /run/p/001 --timeout 600 -- /bin/foo -a"
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "/bin/foo -a",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                Some(Duration::from_secs(600)),
+                TracingBackend::Ebpf
+            )
+            .unwrap()
+            .unwrap(),
+            "/shh run -m safe -p /run/p/001 --timeout 600 --tracer ebpf -- /bin/foo -a"
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "/bin/foo -a",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                Some(Path::new("/var/tmp/p/001.strace")),
+                None,
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "/shh run -m safe -p /run/p/001 -l /var/tmp/p/001.strace -- /bin/foo -a"
+        );
+
+        // Quoted paths with spaces and embedded quotes are left for systemd to unquote into the argv of our run
+        // command, while the words we insert are quoted
+        let wrapped_cmd = Service::wrap_exec_cmd(
+            "\"/opt/my app/bin/foo\" --name \"say \\\"hi\\\"\" 'a b'",
+            "/opt/my tools/shh",
+            &hardening_opts,
+            Path::new("/run/p 1/001"),
+            None,
+            None,
+            TracingBackend::Strace,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            wrapped_cmd,
+            "\"/opt/my tools/shh\" run -m safe -p \"/run/p 1/001\" -- \"/opt/my app/bin/foo\" --name \"say \\\"hi\\\"\" 'a b'"
+        );
+        assert_eq!(
+            Service::split_quoted(&wrapped_cmd),
+            vec![
+                "/opt/my tools/shh",
+                "run",
+                "-m",
+                "safe",
+                "-p",
+                "/run/p 1/001",
+                "--",
+                "/opt/my app/bin/foo",
+                "--name",
+                "say \"hi\"",
+                "a b"
+            ]
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "@\"/opt/my app/bin/foo\" foo -a",
+                "/opt/my tools/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+ TracingBackend::Strace
+)
+            .unwrap()
+            .unwrap(),
+            "@\"/opt/my tools/shh\" \"/opt/my tools/shh\" run -m safe -p /run/p/001 -- \"/opt/my app/bin/foo\" -a"
+        );
+        assert_eq!(Service::quote_exec_word("/shh"), "/shh");
+        assert_eq!(
+            Service::quote_exec_word("/opt/100%/$HOME"),
+            "\"/opt/100%%/$$HOME\""
+        );
+
+        // Paths from the unit environment are quoted too
+        let mut env_hardening_opts = HardeningOptions::safe();
+        env_hardening_opts
+            .readable_paths
+            .push(PathBuf::from("/var/lib/qux/some file"));
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "/bin/foo -a",
+                "/shh",
+                &env_hardening_opts,
+                profile_data_path,
+                None,
+                None,
+ TracingBackend::Strace
+)
+            .unwrap()
+            .unwrap(),
+            "/shh run -m safe --readable-path \"/var/lib/qux/some file\" -p /run/p/001 -- /bin/foo -a"
+        );
+
+        // Specifiers and shell wrappers are left for systemd to expand and unquote
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "/usr/bin/foo --name %i --home %h",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "/shh run -m safe -p /run/p/001 -- /usr/bin/foo --name %i --home %h"
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "/bin/sh -c 'exec /opt/app --instance \"%i\" --port ${PORT}'",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+ TracingBackend::Strace
+)
+            .unwrap()
+            .unwrap(),
+            "/shh run -m safe -p /run/p/001 -- /bin/sh -c 'exec /opt/app --instance \"%i\" --port ${PORT}'"
+        );
+        assert_eq!(
+            Service::wrap_exec_cmd(
+                "@/bin/sh \"my sh\" -c \"exec /opt/app %i\"",
+                "/shh",
+                &hardening_opts,
+                profile_data_path,
+                None,
+                None,
+                TracingBackend::Strace
+            )
+            .unwrap()
+            .unwrap(),
+            "@/shh /shh run -m safe -p /run/p/001 -- /bin/sh -c \"exec /opt/app %i\""
+        );
+    }
+
+    #[test]
+    fn test_split_exec_word() {
+        assert_eq!(Service::split_exec_word("/bin/foo"), ("/bin/foo", ""));
+        assert_eq!(
+            Service::split_exec_word("/bin/foo  -a b"),
+            ("/bin/foo", "-a b")
+        );
+        assert_eq!(
+            Service::split_exec_word("\"my sh\" -c 'a b'"),
+            ("\"my sh\"", "-c 'a b'")
+        );
+        assert_eq!(Service::split_exec_word("'it\\'s' x"), ("'it\\'s'", "x"));
+        assert_eq!(Service::split_exec_word("my\\ sh -c"), ("my\\ sh", "-c"));
+    }
+
+    #[test]
+    fn test_parse_config_paths() {
+        let system_output = b"* foo.service - Foo
+     Loaded: loaded (/usr/lib/systemd/system/foo.service; enabled; preset: disabled)
+    Drop-In: /etc/systemd/system/foo.service.d
+             `-override.conf
+             /run/systemd/system/foo.service.d
+             `-zz_shh-profile.conf
+     Active: active (running) since Mon 2024-06-03 10:00:00 CEST; 1h ago
+";
+        assert_eq!(
+            Service::parse_config_paths(system_output).unwrap(),
+            vec![
+                PathBuf::from("/usr/lib/systemd/system/foo.service"),
+                PathBuf::from("/etc/systemd/system/foo.service.d/override.conf"),
+                PathBuf::from("/run/systemd/system/foo.service.d/zz_shh-profile.conf"),
+            ]
+        );
+
+        let user_output = b"* bar.service - Bar
+     Loaded: loaded (/home/user/.config/systemd/user/bar.service; enabled; preset: enabled)
+    Drop-In: /home/user/.config/systemd/user/bar.service.d
+             `-a.conf, b.conf
+             /run/user/1000/systemd/user/bar.service.d
+             `-zz_shh-profile.conf
+     Active: active (running) since Mon 2024-06-03 10:00:00 CEST; 1h ago
+";
+        assert_eq!(
+            Service::parse_config_paths(user_output).unwrap(),
+            vec![
+                PathBuf::from("/home/user/.config/systemd/user/bar.service"),
+                PathBuf::from("/home/user/.config/systemd/user/bar.service.d/a.conf"),
+                PathBuf::from("/home/user/.config/systemd/user/bar.service.d/b.conf"),
+                PathBuf::from("/run/user/1000/systemd/user/bar.service.d/zz_shh-profile.conf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_paths_versions() {
+        // systemd 219, one base dir
+        let v219_output = "\u{25cf} foo.service - Foo
+   Loaded: loaded (/usr/lib/systemd/system/foo.service; enabled; vendor preset: disabled)
+  Drop-In: /etc/systemd/system/foo.service.d
+           \u{2514}\u{2500}override.conf
+   Active: active (running) since Mon 2024-06-03 10:00:00 CEST; 1h ago
+ Main PID: 1234 (foo)
+";
+        assert_eq!(
+            Service::parse_config_paths(v219_output.as_bytes()).unwrap(),
+            vec![
+                PathBuf::from("/usr/lib/systemd/system/foo.service"),
+                PathBuf::from("/etc/systemd/system/foo.service.d/override.conf"),
+            ]
+        );
+
+        // systemd 255, with top level drop-ins and several base dirs
+        let v255_output = "\u{25cf} foo.service - Foo
+     Loaded: loaded (/usr/lib/systemd/system/foo.service; enabled; preset: disabled)
+    Drop-In: /usr/lib/systemd/system/service.d
+             \u{2514}\u{2500}10-timeout-abort.conf
+             /etc/systemd/system/foo.service.d
+             \u{2514}\u{2500}override.conf, zz_shh-harden.conf
+             /run/systemd/system/foo.service.d
+             \u{2514}\u{2500}zz_shh-profile.conf
+     Active: active (running) since Mon 2024-06-03 10:00:00 CEST; 1h ago
+       Docs: man:foo(8)
+   Main PID: 1234 (foo)
+";
+        assert_eq!(
+            Service::parse_config_paths(v255_output.as_bytes()).unwrap(),
+            vec![
+                PathBuf::from("/usr/lib/systemd/system/foo.service"),
+                PathBuf::from("/usr/lib/systemd/system/service.d/10-timeout-abort.conf"),
+                PathBuf::from("/etc/systemd/system/foo.service.d/override.conf"),
+                PathBuf::from("/etc/systemd/system/foo.service.d/zz_shh-harden.conf"),
+                PathBuf::from("/run/systemd/system/foo.service.d/zz_shh-profile.conf"),
+            ]
+        );
+
+        // Drop-ins section at the end of the output
+        let last_output = "\u{25cf} foo.service - Foo
+     Loaded: loaded (/usr/lib/systemd/system/foo.service; static)
+    Drop-In: /run/systemd/system/foo.service.d
+             `-zz_shh-profile.conf";
+        assert_eq!(
+            Service::parse_config_paths(last_output.as_bytes()).unwrap(),
+            vec![
+                PathBuf::from("/usr/lib/systemd/system/foo.service"),
+                PathBuf::from("/run/systemd/system/foo.service.d/zz_shh-profile.conf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_paths_not_loaded() {
+        let masked_output = b"o foo.service
+     Loaded: masked (Reason: Unit foo.service is masked.)
+     Active: inactive (dead)
+";
+        let err = Service::parse_config_paths(masked_output).unwrap_err();
+        assert!(err.to_string().contains("masked"), "{err}");
+
+        let not_found_output = b"o foo.service
+     Loaded: not-found (Reason: Unit foo.service not found.)
+     Active: inactive (dead)
+";
+        let err = Service::parse_config_paths(not_found_output).unwrap_err();
+        assert!(err.to_string().contains("not-found"), "{err}");
+
+        let bad_setting_output = b"x foo.service - Foo
+     Loaded: bad-setting (Reason: Unit foo.service has a bad unit file setting.)
+    Drop-In: /etc/systemd/system/foo.service.d
+             `-override.conf
+     Active: inactive (dead)
+";
+        let err = Service::parse_config_paths(bad_setting_output).unwrap_err();
+        assert!(err.to_string().contains("bad-setting"), "{err}");
+    }
+
+    #[test]
+    fn test_parse_cat_config_paths() {
+        let output = b"# /usr/lib/systemd/system/foo@.service
+[Unit]
+Description=Foo %i
+
+[Service]
+# /var/lib/foo is a comment
+ExecStart=/usr/bin/foo %i
+
+# /etc/systemd/system/foo@.service.d/override.conf
+[Service]
+Environment=A=B
+";
+        assert_eq!(
+            Service::parse_cat_config_paths(output).unwrap(),
+            vec![
+                PathBuf::from("/usr/lib/systemd/system/foo@.service"),
+                PathBuf::from("/etc/systemd/system/foo@.service.d/override.conf"),
+            ]
+        );
+        assert!(Service::parse_cat_config_paths(b"").is_err());
+    }
+
+    #[test]
+    fn test_canonical_alias() {
+        let service = Service::new("foo-alias", false, 0, FragmentNaming::default());
+        let id = Service::parse_unit_id(b"Id=foo.service\n").unwrap();
+        let service = service.with_unit_id(&id).unwrap();
+        assert_eq!(service.unit_name(), "foo.service");
+        assert_eq!(
+            service.fragment_path("harden", true).unwrap(),
+            PathBuf::from("/etc/systemd/system/foo.service.d/zz_shh-harden.conf")
+        );
+
+        let service = Service::new("bar-alias@baz", false, 0, FragmentNaming::default());
+        let service = service.with_unit_id("bar@baz.service").unwrap();
+        assert_eq!(service.unit_name(), "bar@baz.service");
+
+        let service = Service::new("foo", false, 0, FragmentNaming::default());
+        let service = service.with_unit_id("foo.service").unwrap();
+        assert_eq!(service.unit_name(), "foo.service");
+        assert!(service.with_unit_id("foo.socket").is_err());
+
+        assert!(Service::parse_unit_id(b"Id=\n").is_err());
+    }
+
+    #[test]
+    fn test_check_security_delta_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        let hardening_path = dir.path().join("zz_shh-harden.conf");
+        let profiling_path = dir.path().join("zz_shh-profile.conf");
+        let check = || {
+            Service::check_security_delta_fragments("foo.service", &hardening_path, &profiling_path)
+        };
+
+        assert!(check().is_err());
+        fs::write(&hardening_path, "[Service]\nProtectSystem=strict\n").unwrap();
+        check().unwrap();
+        // Profiled without --merge, so no previous hardening fragment was set aside
+        fs::write(&profiling_path, "[Service]\n").unwrap();
+        let err = check().unwrap_err();
+        assert!(err.to_string().contains("being profiled"), "{err}");
+        // Profiled with --merge, the previous hardening fragment is set aside along with the profiling one
+        fs::write(dir.path().join("zz_shh-harden.conf.prev"), "[Service]\n").unwrap();
+        assert!(check().is_err());
+        fs::remove_file(&profiling_path).unwrap();
+        check().unwrap();
+    }
+
+    #[test]
+    fn test_fragment_options() {
+        let opts = Service::fragment_options(io::Cursor::new(
+            "# This file has been autogenerated by shh\n\
+             # Hardening level: balanced\n\
+             [Service]\n\
+             ProtectSystem=strict\n\
+             # Suggested: UMask=0077\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            opts.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["ProtectSystem=strict", "# Suggested: UMask=0077"]
+        );
+        assert!(!opts[0].suggestion);
+        assert!(opts[1].suggestion);
+    }
+
+    #[test]
+    fn test_template() {
+        let service = Service::new("foo@", false, 0, FragmentNaming::default());
+        assert_eq!(service.unit_name(), "foo@.service");
+        assert!(service.is_template());
+        assert_eq!(
+            service.fragment_path("harden", true).unwrap(),
+            PathBuf::from("/etc/systemd/system/foo@.service.d/zz_shh-harden.conf")
+        );
+        let err = service
+            .add_profile_fragment(
+                &HardeningOptions::safe(),
+                false,
+                None,
+                TracingBackend::Strace,
+                None,
+                false,
+                true,
+                None,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("foo@INSTANCE"), "{err}");
+
+        let service = Service::new("foo@bar", false, 0, FragmentNaming::default());
+        assert_eq!(service.unit_name(), "foo@bar.service");
+        assert!(!service.is_template());
+        assert_eq!(
+            service.fragment_path("harden", true).unwrap(),
+            PathBuf::from("/etc/systemd/system/foo@.service.d/zz_shh-harden.conf")
+        );
+    }
+
+    #[test]
+    fn test_fragment_naming() {
+        let service = Service::new(
+            "foo",
+            false,
+            0,
+            FragmentNaming {
+                prefix: "50-".to_owned(),
+                tool_name: "hardening".to_owned(),
+                persistent_dir: Some("/usr/local/lib/systemd/system".into()),
+            },
+        );
+        assert_eq!(
+            service.fragment_path("harden", true).unwrap(),
+            PathBuf::from("/usr/local/lib/systemd/system/foo.service.d/50-hardening-harden.conf")
+        );
+        assert_eq!(
+            service.fragment_path("profile", false).unwrap(),
+            PathBuf::from("/run/systemd/system/foo.service.d/50-hardening-profile.conf")
+        );
+
+        let service = Service::new(
+            "foo",
+            false,
+            0,
+            FragmentNaming {
+                prefix: "../".to_owned(),
+                ..FragmentNaming::default()
+            },
+        );
+        assert!(service.fragment_path("harden", true).is_err());
+    }
+
+    #[test]
+    fn test_output_writer() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("harden.conf");
+        let mut writer = Service::output_writer(&output_path).unwrap();
+        writeln!(writer, "[Service]").unwrap();
+        writer.commit().unwrap();
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), "[Service]\n");
+        assert_eq!(
+            fs::metadata(&output_path).unwrap().permissions().mode() & 0o777,
+            0o644
+        );
+
+        // Interrupted write leaves the previous fragment untouched, and no partial one
+        let mut writer = Service::output_writer(&output_path).unwrap();
+        writeln!(writer, "[Service]\nProtectSystem=").unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), "[Service]\n");
+        let other_path = temp_dir.path().join("other.conf");
+        let mut writer = Service::output_writer(&other_path).unwrap();
+        writeln!(writer, "[Service]").unwrap();
+        drop(writer);
+        assert!(!other_path.exists());
+        assert_eq!(
+            fs::read_dir(temp_dir.path()).unwrap().count(),
+            1,
+            "Temporary file left behind"
+        );
+
+        // Parent directories are not created
+        assert!(Service::output_writer(&temp_dir.path().join("foo/harden.conf")).is_err());
+    }
+
+    #[test]
+    fn test_remove_fragment_files() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let drop_in_dir = temp_dir.path().join("foo.service.d");
+        let fragment_paths: Vec<_> = ["zz_shh-profile.conf", "zz_shh-harden.conf"]
+            .iter()
+            .map(|f| drop_in_dir.join(f))
+            .collect();
+        let other_path = drop_in_dir.join("override.conf");
+
+        for (profile, harden, other) in (0..8).map(|i| ((i & 1) != 0, (i & 2) != 0, (i & 4) != 0)) {
+            fs::create_dir_all(&drop_in_dir).unwrap();
+            for (path, present) in [
+                (&fragment_paths[0], profile),
+                (&fragment_paths[1], harden),
+                (&other_path, other),
+            ] {
+                if present {
+                    File::create(path).unwrap();
+                }
+            }
+
+            let removed = Service::remove_fragment_files(&fragment_paths).unwrap();
+
+            let mut expected: Vec<_> = fragment_paths
+                .iter()
+                .zip([profile, harden])
+                .filter(|(_, present)| *present)
+                .map(|(p, _)| p.to_owned())
+                .collect();
+            if !other && !expected.is_empty() {
+                expected.push(drop_in_dir.clone());
+            }
+            assert_eq!(removed, expected, "{profile} {harden} {other}");
+            assert!(fragment_paths.iter().all(|p| !p.exists()));
+            assert_eq!(other_path.is_file(), other);
+            if other {
+                fs::remove_file(&other_path).unwrap();
+            }
+            let _ = fs::remove_dir(&drop_in_dir);
+        }
+    }
+
+    #[test]
+    fn test_units_with_fragment() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for (dir, file) in [
+            ("foo.service.d", "zz_shh-harden.conf"),
+            ("bar@.service.d", "zz_shh-harden.conf"),
+            ("baz.service.d", "override.conf"),
+            ("foo.socket.d", "zz_shh-harden.conf"),
+        ] {
+            let dir = temp_dir.path().join(dir);
+            fs::create_dir(&dir).unwrap();
+            File::create(dir.join(file)).unwrap();
+        }
+        File::create(temp_dir.path().join("qux.service")).unwrap();
+
+        let mut units =
+            Service::units_with_fragment(temp_dir.path(), "zz_shh-harden.conf").unwrap();
+        units.sort();
+        assert_eq!(units, vec!["bar@.service", "foo.service"]);
+
+        assert!(Service::units_with_fragment(
+            &temp_dir.path().join("missing"),
+            "zz_shh-harden.conf"
+        )
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn test_is_valid_memory_limit() {
+        for val in ["1073741824", "2G", "512M", "80%", "12.5%", "infinity"] {
+            assert!(Service::is_valid_memory_limit(val), "{val}");
+        }
+        for val in ["", "G", "2GB", "-1", "150%", "lots"] {
+            assert!(!Service::is_valid_memory_limit(val), "{val}");
+        }
+    }
+
+    #[test]
+    fn test_generalize_instance_paths() {
+        assert_eq!(
+            Service::generalize_instance_paths(
+                "-/var/lib/foo/main -/var/log/foo-main/main.log",
+                "main"
+            ),
+            "-/var/lib/foo/%i -/var/log/foo-main/main.log"
+        );
+        assert_eq!(
+            Service::generalize_instance_paths("main /run/main/main", "main"),
+            "main /run/%i/%i"
+        );
+        assert_eq!(
+            Service::generalize_instance_paths("strict", "strict"),
+            "strict"
+        );
+
+        assert!(Service::is_distinctive_instance("main"));
+        assert!(Service::is_distinctive_instance("tty1"));
+        assert!(!Service::is_distinctive_instance("1"));
+        assert!(!Service::is_distinctive_instance("8080"));
+        assert!(!Service::is_distinctive_instance("lib"));
+    }
+
+    #[test]
+    fn test_instance() {
+        let service = Service::new("foo@1", false, 0, FragmentNaming::default());
+        let other = service.instance("2").unwrap();
+        assert_eq!(other.unit_name(), "foo@2.service");
+        assert_eq!(
+            other.fragment_path("harden", true).unwrap(),
+            service.fragment_path("harden", true).unwrap()
+        );
+
+        let service = Service::new("foo", false, 0, FragmentNaming::default());
+        assert!(service.instance("2").is_err());
+    }
+
+    #[test]
+    fn test_write_grouped_options() {
+        let opts = [
+            "SystemCallFilter=~@mount",
+            "ProtectSystem=strict",
+            "SocketBindAllow=tcp:80",
+            "CapabilityBoundingSet=~CAP_SYS_TIME",
+            "PrivateDevices=true",
+            "SocketBindDeny=any",
+            "LockPersonality=true",
+            "ProtectClock=true",
+            "ProtectHome=read-only",
+            "FooBar=baz",
+        ]
+        .into_iter()
+        .map(|o| o.parse().unwrap())
+        .collect();
+        let mut fragment = Vec::new();
+        Service::write_grouped_options(&mut fragment, opts).unwrap();
+        let opt_lines: Vec<_> = String::from_utf8(fragment)
+            .unwrap()
+            .lines()
+            .filter(|l| !l.is_empty() && (*l != format!("# {DEFAULT_RATIONALE}")))
+            .map(ToOwned::to_owned)
+            .collect();
+        assert_eq!(
+            opt_lines,
+            vec![
+                "# Filesystem",
+                "ProtectSystem=strict",
+                "ProtectHome=read-only",
+                "# Devices",
+                "PrivateDevices=true",
+                "# Kernel",
+                "ProtectClock=true",
+                "# Network",
+                "SocketBindAllow=tcp:80",
+                "SocketBindDeny=any",
+                "# Process",
+                "LockPersonality=true",
+                "# System calls",
+                "SystemCallFilter=~@mount",
+                "# Capabilities",
+                "CapabilityBoundingSet=~CAP_SYS_TIME",
+                "# Other",
+                "FooBar=baz",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_profiling_result() {
+        let lines = [
+            "unrelated",
+            END_OPTION_OUTPUT_SNIPPET,
+            "ProtectSystem=strict",
+            "PrivateTmp=true",
+            START_OPTION_OUTPUT_SNIPPET,
+            END_OPTION_OUTPUT_SNIPPET,
+            "ProtectSystem=full",
+            START_OPTION_OUTPUT_SNIPPET,
+            "older",
+        ];
+        let opts =
+            Service::parse_profiling_result(lines.into_iter().map(|l| Ok(l.to_owned()))).unwrap();
+        assert_eq!(
+            opts.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec!["PrivateTmp=true", "ProtectSystem=strict"]
+        );
+
+        assert!(Service::parse_profiling_result(
+            [
+                "unrelated",
+                END_OPTION_OUTPUT_SNIPPET,
+                "ProtectSystem=strict"
+            ]
+            .into_iter()
+            .map(|l| Ok(l.to_owned()))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_profiling_result_rationale() {
+        // Reverse chronological order, so comments follow the option they apply to
+        let lines = [
+            END_OPTION_OUTPUT_SNIPPET,
+            "ProtectSystem=strict",
+            "PrivateTmp=true",
+            "# no write to /tmp",
+            START_OPTION_OUTPUT_SNIPPET,
+        ];
+        let opts =
+            Service::parse_profiling_result(lines.into_iter().map(|l| Ok(l.to_owned()))).unwrap();
+        assert_eq!(
+            opts.iter()
+                .map(|o| (o.to_string(), o.rationale.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "PrivateTmp=true".to_owned(),
+                    Some("no write to /tmp".to_owned())
+                ),
+                ("ProtectSystem=strict".to_owned(), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_profiling_result_truncated() {
+        let parse_err = |lines: &[&str]| {
+            Service::parse_profiling_result(lines.iter().map(|l| Ok((*l).to_owned())))
+                .unwrap_err()
+                .to_string()
+        };
+
+        // Start marker rotated out of the journal
+        let err = parse_err(&[
+            "unrelated",
+            END_OPTION_OUTPUT_SNIPPET,
+            "ProtectSystem=strict",
+        ]);
+        assert!(err.contains("start marker is missing"));
+        assert!(err.contains("rotated"));
+
+        // Start marker lost, followed by an older complete snippet
+        let err = parse_err(&[
+            END_OPTION_OUTPUT_SNIPPET,
+            "ProtectSystem=strict",
+            END_OPTION_OUTPUT_SNIPPET,
+            "ProtectSystem=full",
+            START_OPTION_OUTPUT_SNIPPET,
+        ]);
+        assert!(err.contains("start marker is missing"));
+
+        // End marker missing
+        let err = parse_err(&[
+            "unrelated",
+            "ProtectSystem=strict",
+            START_OPTION_OUTPUT_SNIPPET,
+        ]);
+        assert!(err.contains("end marker is missing"));
+        assert!(err.contains("rotated"));
+
+        // No snippet at all
+        let err = parse_err(&["unrelated"]);
+        assert_eq!(err, "Unable to get profiling result snippet");
+    }
+
+    #[test]
+    fn test_structured_errors() {
+        let parse_err = |lines: &[&str]| {
+            Service::parse_profiling_result(lines.iter().map(|l| Ok((*l).to_owned()))).unwrap_err()
+        };
+
+        let err = parse_err(&[END_OPTION_OUTPUT_SNIPPET, "ProtectSystem=strict"]);
+        assert!(matches!(
+            err.downcast_ref::<ShhError>(),
+            Some(ShhError::ProfilingSnippetMissing {
+                missing_marker: Some(SnippetMarker::Start)
+            })
+        ));
+
+        let err = parse_err(&["unrelated"]);
+        assert!(matches!(
+            err.downcast_ref::<ShhError>(),
+            Some(ShhError::ProfilingSnippetMissing {
+                missing_marker: None
+            })
+        ));
+
+        let err = parse_err(&[
+            END_OPTION_OUTPUT_SNIPPET,
+            "ProtectSystem",
+            START_OPTION_OUTPUT_SNIPPET,
+        ]);
+        assert!(matches!(
+            err.downcast_ref::<ShhError>(),
+            Some(ShhError::ConfigParse { line, .. }) if line == "ProtectSystem"
+        ));
+
+        let err = Service::parse_profiling_result(iter::once(Err(anyhow::anyhow!("I/O error"))))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ShhError>(),
+            Some(ShhError::JournalRead(_))
+        ));
+        assert_eq!(format!("{err:#}"), "Unable to read unit logs: I/O error");
+
+        let err = Service::parse_config_paths(
+            b"o foo.service
+     Loaded: not-found (Reason: Unit foo.service not found.)
+",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ShhError>(),
+            Some(ShhError::UnitNotFound { state, .. }) if state == "not-found"
+        ));
+    }
+
+    #[test]
+    fn test_config_vals() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file1 = tempfile::NamedTempFile::new().unwrap();
+        let mut cfg_file2 = tempfile::NamedTempFile::new().unwrap();
+        let mut cfg_file3 = tempfile::NamedTempFile::new().unwrap();
+
+        writeln!(cfg_file1, "blah=a").unwrap();
+        writeln!(cfg_file1, "blah=b").unwrap();
+        writeln!(cfg_file2, "blah=").unwrap();
+        writeln!(cfg_file2, "blah=c").unwrap();
+        writeln!(cfg_file2, "blih=e").unwrap();
+        writeln!(cfg_file2, "bloh=f").unwrap();
+        writeln!(cfg_file3, "blah=d").unwrap();
+
+        assert_eq!(
+            Service::config_vals(
+                "blah",
+                &[cfg_file1.path(), cfg_file2.path(), cfg_file3.path()]
+            )
+            .unwrap(),
+            vec!["c", "d"]
+        );
+    }
+
+    #[test]
+    fn test_config_tokens() {
+        let mut single_line_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(single_line_file, "[Service]").unwrap();
+        writeln!(single_line_file, "RestrictAddressFamilies=AF_INET AF_UNIX").unwrap();
+        writeln!(single_line_file, "ReadWritePaths=/srv/foo  -/srv/bar").unwrap();
+        writeln!(single_line_file, "ExecStart=/bin/foo -a -b").unwrap();
+
+        let mut multi_line_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(multi_line_file, "[Service]").unwrap();
+        writeln!(multi_line_file, "RestrictAddressFamilies=AF_INET").unwrap();
+        writeln!(multi_line_file, "RestrictAddressFamilies=AF_UNIX").unwrap();
+        writeln!(multi_line_file, "ReadWritePaths=/srv/foo").unwrap();
+        writeln!(multi_line_file, "ReadWritePaths=-/srv/bar").unwrap();
+
+        for key in ["RestrictAddressFamilies", "ReadWritePaths"] {
+            assert_eq!(
+                Service::config_tokens(key, &[single_line_file.path()]).unwrap(),
+                Service::config_tokens(key, &[multi_line_file.path()]).unwrap()
+            );
+        }
+        assert_eq!(
+            Service::config_tokens("ReadWritePaths", &[multi_line_file.path()]).unwrap(),
+            vec!["/srv/foo", "-/srv/bar"]
+        );
+        // Not a set
+        assert_eq!(
+            Service::config_tokens("ExecStart", &[single_line_file.path()]).unwrap(),
+            vec!["/bin/foo -a -b"]
+        );
+    }
+
+    #[test]
+    fn test_config_vals_reset() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut unit_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(unit_file, "[Service]").unwrap();
+        writeln!(unit_file, "ReadWritePaths=/var/lib/a").unwrap();
+        writeln!(unit_file, "ReadWritePaths=/var/lib/b").unwrap();
+        writeln!(unit_file, "#ReadWritePaths=/var/lib/c").unwrap();
+        writeln!(unit_file, "ReadWritePathsFoo=/var/lib/d").unwrap();
+        writeln!(unit_file, "ExecStart=/bin/foo \\").unwrap();
+        writeln!(unit_file, "  ReadWritePaths=/var/lib/h").unwrap();
+
+        // Reset at the start of a later drop-in
+        let mut drop_in_file1 = tempfile::NamedTempFile::new().unwrap();
+        writeln!(drop_in_file1, "[Service]").unwrap();
+        writeln!(drop_in_file1, "ReadWritePaths=").unwrap();
+        writeln!(drop_in_file1, "ReadWritePaths=/var/lib/e").unwrap();
+        assert_eq!(
+            Service::config_vals("ReadWritePaths", &[unit_file.path()]).unwrap(),
+            vec!["/var/lib/a", "/var/lib/b"]
+        );
+        assert_eq!(
+            Service::config_vals("ReadWritePaths", &[unit_file.path(), drop_in_file1.path()])
+                .unwrap(),
+            vec!["/var/lib/e"]
+        );
+
+        // Values added before a reset in a later drop-in, with whitespace around '='
+        let mut drop_in_file2 = tempfile::NamedTempFile::new().unwrap();
+        writeln!(drop_in_file2, "[Service]").unwrap();
+        writeln!(drop_in_file2, "ReadWritePaths = /var/lib/f").unwrap();
+        writeln!(drop_in_file2, "ReadWritePaths =").unwrap();
+        writeln!(drop_in_file2, "ReadWritePaths= /var/lib/g").unwrap();
+        assert_eq!(
+            Service::config_vals(
+                "ReadWritePaths",
+                &[unit_file.path(), drop_in_file1.path(), drop_in_file2.path()]
+            )
+            .unwrap(),
+            vec!["/var/lib/g"]
+        );
+
+        // Reset as the last assignment
+        let mut drop_in_file3 = tempfile::NamedTempFile::new().unwrap();
+        writeln!(drop_in_file3, "[Service]").unwrap();
+        writeln!(drop_in_file3, "ReadWritePaths=").unwrap();
+        assert!(Service::config_vals(
+            "ReadWritePaths",
+            &[unit_file.path(), drop_in_file2.path(), drop_in_file3.path()]
+        )
+        .unwrap()
+        .is_empty());
+
+        // Order of files matters
+        assert_eq!(
+            Service::config_vals(
+                "ReadWritePaths",
+                &[drop_in_file3.path(), drop_in_file1.path(), unit_file.path()]
+            )
+            .unwrap(),
+            vec!["/var/lib/e", "/var/lib/a", "/var/lib/b"]
+        );
+    }
+
+    #[test]
+    fn test_config_val_multiline() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+
+        writeln!(
+            cfg_file,
+            r#"ExecStartPre=/bin/sh -c "[ ! -e /usr/bin/galera_recovery ] && VAR= || \
+ VAR=`cd /usr/bin/..; /usr/bin/galera_recovery`; [ $? -eq 0 ] \
+ && systemctl set-environment _WSREP_START_POSITION=$VAR || exit 1""#
+        )
+        .unwrap();
+
+        assert_eq!(
+            Service::config_vals("ExecStartPre", &[cfg_file.path()]).unwrap(),
+            vec![
+                r#"/bin/sh -c "[ ! -e /usr/bin/galera_recovery ] && VAR= ||  VAR=`cd /usr/bin/..; /usr/bin/galera_recovery`; [ $? -eq 0 ]  && systemctl set-environment _WSREP_START_POSITION=$VAR || exit 1""#
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_val_escaped_backslash() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+
+        writeln!(cfg_file, r"ExecStart=/bin/echo foo\\").unwrap();
+        writeln!(cfg_file, "ExecStart=/bin/echo bar").unwrap();
+        writeln!(cfg_file, r"ExecStart=/bin/echo a\\ \").unwrap();
+        writeln!(cfg_file, r" b\").unwrap();
+        writeln!(cfg_file, r" c\\\").unwrap();
+        writeln!(cfg_file, " d").unwrap();
+        writeln!(cfg_file, "ExecStart=/bin/echo e").unwrap();
+
+        assert_eq!(
+            Service::config_vals("ExecStart", &[cfg_file.path()]).unwrap(),
+            vec![
+                r"/bin/echo foo\\",
+                "/bin/echo bar",
+                r"/bin/echo a\\  b  c\\  d",
+                "/bin/echo e",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ambient_capabilities() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            cfg_file,
+            "[Service]\nAmbientCapabilities=CAP_NET_BIND_SERVICE\nAmbientCapabilities=CAP_NET_ADMIN CAP_NET_BIND_SERVICE\nAmbientCapabilities=~CAP_SYS_ADMIN"
+        )
+        .unwrap();
+        assert_eq!(
+            Service::ambient_capabilities(&[cfg_file.path()]).unwrap(),
+            vec!["CAP_NET_BIND_SERVICE", "CAP_NET_ADMIN"]
+        );
+
+        writeln!(cfg_file, "AmbientCapabilities=").unwrap();
+        assert!(Service::ambient_capabilities(&[cfg_file.path()])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_environment() {
+        let mut env_file = tempfile::NamedTempFile::new().unwrap();
+        env_file
+            .write_all(
+                b"# comment\nBAR=/etc/bar:/etc/baz\nexport QUX=\"/var/lib/qux/some file\"\n\nNAME=foo\n",
+            )
+            .unwrap();
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            cfg_file,
+            "[Service]\nEnvironment=\"RUST_BACKTRACE=full\" FOO=/var/lib/foo/foo.conf\nEnvironmentFile=-{}\nEnvironmentFile=-/nonexistent",
+            env_file.path().display()
+        )
+        .unwrap();
+
+        let env = Service::environment(&[cfg_file.path()]).unwrap();
+        assert_eq!(
+            env,
+            [
+                ("RUST_BACKTRACE", "full"),
+                ("FOO", "/var/lib/foo/foo.conf"),
+                ("BAR", "/etc/bar:/etc/baz"),
+                ("QUX", "/var/lib/qux/some file"),
+                ("NAME", "foo"),
+            ]
+            .map(|(n, v)| (n.to_owned(), v.to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_environment_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf = dir.path().join("foo.conf");
+        let spaced = dir.path().join("some file");
+        fs::write(&conf, "").unwrap();
+        fs::write(&spaced, "").unwrap();
+        let env = [
+            ("HOME", "/root".to_owned()),
+            ("FOO", conf.display().to_string()),
+            (
+                "BAR",
+                format!("{}:{}:/nonexistent", dir.path().display(), spaced.display()),
             ),
-            &format!("zz_{}-{}.conf", env!("CARGO_PKG_NAME"), name),
+            ("NAME", "foo".to_owned()),
         ]
-        .iter()
-        .collect()
+        .map(|(n, v)| (n.to_owned(), v));
+        assert_eq!(Service::environment_paths(&env), vec![conf, spaced]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_profiling_env_vars() {
+        assert_eq!(
+            Service::profiling_env_vars(&[("FOO".to_owned(), "bar".to_owned())]),
+            vec![("RUST_BACKTRACE", "1")]
+        );
+        assert!(
+            Service::profiling_env_vars(&[("RUST_BACKTRACE".to_owned(), "0".to_owned())])
+                .is_empty()
+        );
+    }
 
     #[test]
-    fn test_config_vals() {
+    fn test_writable_paths() {
         let _ = simple_logger::SimpleLogger::new().init();
 
-        let mut cfg_file1 = tempfile::NamedTempFile::new().unwrap();
-        let mut cfg_file2 = tempfile::NamedTempFile::new().unwrap();
-        let mut cfg_file3 = tempfile::NamedTempFile::new().unwrap();
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
 
-        writeln!(cfg_file1, "blah=a").unwrap();
-        writeln!(cfg_file1, "blah=b").unwrap();
-        writeln!(cfg_file2, "blah=").unwrap();
-        writeln!(cfg_file2, "blah=c").unwrap();
-        writeln!(cfg_file2, "blih=e").unwrap();
-        writeln!(cfg_file2, "bloh=f").unwrap();
-        writeln!(cfg_file3, "blah=d").unwrap();
+        writeln!(cfg_file, "StateDirectory=foo bar:baz").unwrap();
+        writeln!(cfg_file, "LogsDirectory=foo").unwrap();
+        writeln!(cfg_file, "RuntimeDirectory=foo").unwrap();
+        writeln!(cfg_file, "ReadWritePaths=-/srv/foo /etc/foo.conf").unwrap();
 
         assert_eq!(
-            Service::config_vals(
-                "blah",
-                &[cfg_file1.path(), cfg_file2.path(), cfg_file3.path()]
+            Service::new("foo", false, 0, FragmentNaming::default())
+                .writable_paths(&[cfg_file.path()])
+                .unwrap(),
+            vec![
+                PathBuf::from("/run/foo"),
+                PathBuf::from("/var/lib/foo"),
+                PathBuf::from("/var/lib/bar"),
+                PathBuf::from("/var/log/foo"),
+                PathBuf::from("/srv/foo"),
+                PathBuf::from("/etc/foo.conf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_writable_paths_dynamic_user() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+
+        writeln!(cfg_file, "DynamicUser=yes").unwrap();
+        writeln!(cfg_file, "StateDirectory=foo").unwrap();
+        writeln!(cfg_file, "RuntimeDirectory=foo").unwrap();
+
+        assert_eq!(
+            Service::new("foo", false, 0, FragmentNaming::default())
+                .writable_paths(&[cfg_file.path()])
+                .unwrap(),
+            vec![
+                PathBuf::from("/run/foo"),
+                PathBuf::from("/var/lib/foo"),
+                PathBuf::from("/var/lib/private/foo"),
+            ]
+        );
+
+        let names = ["PrivateTmp", "ProtectSystem", "PrivateDevices"];
+        let implied: Vec<_> = Service::config_dynamic_user_options(&names, &[cfg_file.path()])
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(implied, vec!["ProtectSystem=strict", "PrivateTmp=true"]);
+
+        let opts = build_options(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions::safe(),
+        );
+        let new_opts: Vec<OptionWithValue> = ["PrivateTmp=true", "PrivateDevices=true"]
+            .iter()
+            .map(|o| o.parse().unwrap())
+            .collect();
+        let implied = Service::config_dynamic_user_options(&names, &[cfg_file.path()]).unwrap();
+        assert_eq!(
+            reconcile_existing(&opts, &implied, new_opts)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["PrivateDevices=true"]
+        );
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(cfg_file, "DynamicUser=no").unwrap();
+        assert!(
+            Service::config_dynamic_user_options(&names, &[cfg_file.path()])
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_standard_output_override_warning() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(cfg_file, "StandardOutput=journal").unwrap();
+        assert!(
+            Service::standard_output_override_warning(&[cfg_file.path()])
+                .unwrap()
+                .is_none()
+        );
+        assert!(Service::standard_output_override_warning(&[])
+            .unwrap()
+            .is_none());
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(cfg_file, "StandardOutput=append:/var/log/foo.log").unwrap();
+        let warning = Service::standard_output_override_warning(&[cfg_file.path()])
+            .unwrap()
+            .unwrap();
+        assert!(warning.contains("standard output and error"));
+        assert!(warning.contains("\"append:/var/log/foo.log\""));
+
+        writeln!(cfg_file, "StandardError=journal").unwrap();
+        let warning = Service::standard_output_override_warning(&[cfg_file.path()])
+            .unwrap()
+            .unwrap();
+        assert!(!warning.contains("and error"));
+
+        writeln!(cfg_file, "StandardOutput=").unwrap();
+        assert!(
+            Service::standard_output_override_warning(&[cfg_file.path()])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "as-root", ignore)]
+    fn test_config_write_error() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let fragment_dir = temp_dir.path().join("foo.service.d");
+        Service::check_dir_writable(&fragment_dir).unwrap();
+
+        fs::set_permissions(temp_dir.path(), fs::Permissions::from_mode(0o555)).unwrap();
+        let err = Service::check_dir_writable(&fragment_dir).unwrap_err();
+        assert!(err.to_string().contains("run as root"), "{err}");
+        let Err(err) = Service::fragment_writer(&fragment_dir.join("shh.conf"), false) else {
+            panic!();
+        };
+        assert!(
+            err.to_string().starts_with(&format!(
+                "Unable to write config in {fragment_dir:?}: missing privileges"
+            )),
+            "{err}"
+        );
+        assert!(!fragment_dir.exists());
+        fs::set_permissions(temp_dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_writable_paths_resolve() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+
+        writeln!(cfg_file, "StateDirectory=foo").unwrap();
+
+        let writable_paths = Service::new("foo", false, 0, FragmentNaming::default())
+            .writable_paths(&[cfg_file.path()])
+            .unwrap();
+        let opts: Vec<_> = build_options(
+            &SystemdVersion::new(254, 0),
+            &KernelVersion::new(6, 4, 0),
+            &HardeningOptions {
+                mode: HardeningMode::Aggressive,
+                writable_paths,
+                ..HardeningOptions::safe()
+            },
+        )
+        .into_iter()
+        .filter(|o| ["ProtectSystem", "ReadWritePaths"].contains(&o.name))
+        .collect();
+        let resolve_writes = |writes: &[&str]| {
+            let actions: Vec<_> = writes
+                .iter()
+                .map(|p| ProgramAction::Write((*p).into()))
+                .collect();
+            resolve(&opts, &actions)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            resolve_writes(&["/var/lib/foo/db"]),
+            vec!["ProtectSystem=strict"]
+        );
+        assert_eq!(
+            resolve_writes(&["/var/lib/foo/db", "/var/lib/bar/db"]),
+            vec!["ProtectSystem=strict", "ReadWritePaths=-/var/lib/bar/db"]
+        );
+    }
+
+    #[test]
+    fn test_parse_listen_binds() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let binds = |listen: &str, proto: SocketProtocol| {
+            Service::parse_listen_binds(listen, &proto)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            binds("8080", SocketProtocol::Tcp),
+            vec!["ipv4:tcp:8080", "ipv6:tcp:8080"]
+        );
+        assert_eq!(
+            binds("127.0.0.1:53", SocketProtocol::Udp),
+            vec!["ipv4:udp:53"]
+        );
+        assert_eq!(
+            binds("[::1]:443", SocketProtocol::Tcp),
+            vec!["ipv6:tcp:443"]
+        );
+        assert!(binds("/run/foo.sock", SocketProtocol::Tcp).is_empty());
+        assert!(binds("@foo", SocketProtocol::Tcp).is_empty());
+        assert!(binds("vsock:2:1234", SocketProtocol::Tcp).is_empty());
+        assert!(binds("[::1]", SocketProtocol::Tcp).is_empty());
+    }
+
+    #[test]
+    fn test_socket_known_binds_resolve() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let known_binds = Service::parse_listen_binds("0.0.0.0:80", &SocketProtocol::Tcp);
+        let resolve_binds = |network_firewalling: bool| {
+            let opts: Vec<_> = build_options(
+                &SystemdVersion::new(254, 0),
+                &KernelVersion::new(6, 4, 0),
+                &HardeningOptions {
+                    network_firewalling,
+                    known_binds: known_binds.clone(),
+                    ..HardeningOptions::safe()
+                },
             )
-            .unwrap(),
-            vec!["c", "d"]
+            .into_iter()
+            .filter(|o| o.name.starts_with("SocketBind"))
+            .collect();
+            resolve(&opts, &[])
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            resolve_binds(true),
+            vec!["SocketBindDeny=any", "SocketBindAllow=ipv4:tcp:80"]
+        );
+        assert_eq!(
+            resolve_binds(false),
+            vec!["SocketBindDeny=ipv4:udp\nSocketBindDeny=ipv6:tcp\nSocketBindDeny=ipv6:udp"]
         );
     }
 
     #[test]
-    fn test_config_val_multiline() {
+    fn test_exec_cmds_none() {
+        let _ = simple_logger::SimpleLogger::new().init();
+
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+
+        writeln!(cfg_file, "[Service]").unwrap();
+        writeln!(cfg_file, "Type=oneshot").unwrap();
+        writeln!(cfg_file, "RemainAfterExit=yes").unwrap();
+
+        let exec_opts = ["ExecStartPre", "ExecStart", "ExecStartPost"];
+        let err = Service::new("foo", false, 0, FragmentNaming::default())
+            .exec_cmds(&exec_opts, &[cfg_file.path()])
+            .unwrap_err();
+        assert!(err.to_string().contains("foo.service"), "{err}");
+
+        writeln!(cfg_file, "ExecStartPost=/bin/true").unwrap();
+        assert_eq!(
+            Service::new("foo", false, 0, FragmentNaming::default())
+                .exec_cmds(&exec_opts, &[cfg_file.path()])
+                .unwrap(),
+            vec![
+                ("ExecStartPre", vec![]),
+                ("ExecStart", vec![]),
+                ("ExecStartPost", vec!["/bin/true".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_retries() {
         let _ = simple_logger::SimpleLogger::new().init();
 
+        let mut calls = 0;
+        let res = Service::with_retries("test", 2, || {
+            calls += 1;
+            if calls < 3 {
+                Err(CommandError::Transient(anyhow::anyhow!("not yet")))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(res.unwrap(), 3);
+
+        let mut calls = 0;
+        let res: anyhow::Result<()> = Service::with_retries("test", 2, || {
+            calls += 1;
+            Err(CommandError::Transient(anyhow::anyhow!("never")))
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let res: anyhow::Result<()> = Service::with_retries("test", 2, || {
+            calls += 1;
+            Err(CommandError::Fatal(anyhow::anyhow!("unit not found")))
+        });
+        assert_eq!(res.unwrap_err().to_string(), "unit not found");
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_systemctl_error() {
+        let status = ExitStatus::from_raw(1 << 8);
+        assert!(matches!(
+            Service::systemctl_error(
+                status,
+                "Failed to connect to bus: No such file or directory"
+            ),
+            CommandError::Transient(_)
+        ));
+        assert!(matches!(
+            Service::systemctl_error(
+                status,
+                "Job for foo.service failed because the control process exited with error code."
+            ),
+            CommandError::Fatal(_)
+        ));
+        assert!(matches!(
+            Service::systemctl_error(status, ""),
+            CommandError::Fatal(_)
+        ));
+    }
+
+    #[test]
+    fn test_profiling_result_error() {
+        assert!(matches!(
+            Service::profiling_result_error(
+                ShhError::ProfilingSnippetMissing {
+                    missing_marker: None
+                }
+                .into()
+            ),
+            CommandError::Transient(_)
+        ));
+        assert!(matches!(
+            Service::profiling_result_error(
+                ShhError::ProfilingSnippetMissing {
+                    missing_marker: Some(SnippetMarker::Start)
+                }
+                .into()
+            ),
+            CommandError::Fatal(_)
+        ));
+        assert!(matches!(
+            Service::profiling_result_error(
+                ShhError::ConfigParse {
+                    line: "Foo".to_owned(),
+                    reason: "invalid".to_owned(),
+                }
+                .into()
+            ),
+            CommandError::Fatal(_)
+        ));
+    }
+
+    #[test]
+    fn test_is_notify_type() {
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(cfg_file, "[Service]").unwrap();
+        writeln!(cfg_file, "ExecStart=/bin/true").unwrap();
+        assert!(!Service::is_notify_type(&[cfg_file.path()]).unwrap());
+
+        writeln!(cfg_file, "Type=notify").unwrap();
+        assert!(Service::is_notify_type(&[cfg_file.path()]).unwrap());
+
+        let mut override_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(override_file, "[Service]").unwrap();
+        writeln!(override_file, "Type=simple").unwrap();
+        assert!(!Service::is_notify_type(&[cfg_file.path(), override_file.path()]).unwrap());
+    }
+
+    #[test]
+    fn test_config_options() {
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(cfg_file, "[Service]").unwrap();
+        writeln!(cfg_file, "ProtectSystem=full").unwrap();
+        writeln!(cfg_file, "PrivateTmp=yes").unwrap();
+        writeln!(cfg_file, "ProtectHome=off").unwrap();
+
+        let mut override_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(override_file, "[Service]").unwrap();
+        writeln!(override_file, "ProtectSystem=strict").unwrap();
+        writeln!(override_file, "ProtectHome=").unwrap();
+
+        assert_eq!(
+            Service::config_options(
+                &[
+                    "ProtectSystem",
+                    "PrivateTmp",
+                    "ProtectHome",
+                    "PrivateDevices"
+                ],
+                &[cfg_file.path(), override_file.path()]
+            )
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+            vec!["ProtectSystem=strict", "PrivateTmp=true"]
+        );
+        assert_eq!(
+            Service::config_options(&["ProtectHome"], &[cfg_file.path()])
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["ProtectHome=false"]
+        );
+    }
+
+    #[test]
+    fn test_parse_verify_issues() {
+        let fragment_path = Path::new("/etc/systemd/system/foo.service.d/zz_shh-hardening.conf");
+        let fragment = "# This file has been autogenerated by shh\n[Service]\n# Reason\nProtectSystem=strict\n# Reason\nProtectFoo=true\n";
+        let output = "/etc/systemd/system/foo.service:8: Unknown key name 'Bar' in section 'Service', ignoring.
+/etc/systemd/system/foo.service.d/zz_shh-hardening.conf:6: Unknown key name 'ProtectFoo' in section 'Service', ignoring.
+foo.service: Command /usr/bin/foo is not executable: No such file or directory
+";
+        assert_eq!(
+            Service::parse_verify_issues(output, fragment_path, fragment),
+            vec!["  line 6 (ProtectFoo=true): Unknown key name 'ProtectFoo' in section 'Service', ignoring."]
+        );
+        assert!(Service::parse_verify_issues(
+            "/etc/systemd/system/foo.service:8: Unknown key name 'Bar' in section 'Service', ignoring.\n",
+            fragment_path,
+            fragment
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_is_forking_type() {
+        let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(cfg_file, "[Service]").unwrap();
+        writeln!(cfg_file, "ExecStart=/usr/sbin/food").unwrap();
+        assert!(!Service::is_forking_type(&[cfg_file.path()]).unwrap());
+
+        writeln!(cfg_file, "Type=forking").unwrap();
+        writeln!(cfg_file, "PIDFile=/run/food.pid").unwrap();
+        assert!(Service::is_forking_type(&[cfg_file.path()]).unwrap());
+
+        let mut override_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(override_file, "[Service]").unwrap();
+        writeln!(override_file, "Type=exec").unwrap();
+        assert!(!Service::is_forking_type(&[cfg_file.path(), override_file.path()]).unwrap());
+
+        writeln!(override_file, "Type=").unwrap();
+        assert!(!Service::is_forking_type(&[cfg_file.path(), override_file.path()]).unwrap());
+    }
+
+    #[test]
+    fn test_is_root_confined() {
         let mut cfg_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(cfg_file, "[Service]").unwrap();
+        writeln!(cfg_file, "ExecStart=/bin/true").unwrap();
+        assert!(!Service::is_root_confined(&[cfg_file.path()]).unwrap());
+
+        writeln!(cfg_file, "RootDirectory=/srv/foo").unwrap();
+        assert!(Service::is_root_confined(&[cfg_file.path()]).unwrap());
 
+        let mut override_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(override_file, "[Service]").unwrap();
+        writeln!(override_file, "RootDirectory=").unwrap();
+        assert!(!Service::is_root_confined(&[cfg_file.path(), override_file.path()]).unwrap());
+
+        writeln!(override_file, "RootImage=/srv/foo.raw").unwrap();
+        assert!(Service::is_root_confined(&[cfg_file.path(), override_file.path()]).unwrap());
+    }
+
+    #[test]
+    fn test_fragment_hardening_level() {
+        let mut fragment_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(fragment_file, "# This file has been autogenerated by shh").unwrap();
+        writeln!(fragment_file, "[Service]").unwrap();
+        writeln!(fragment_file, "NotifyAccess=all").unwrap();
+        assert_eq!(
+            Service::fragment_hardening_level(fragment_file.path()).unwrap(),
+            None
+        );
+
+        let mut fragment_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(fragment_file, "# This file has been autogenerated by shh").unwrap();
+        writeln!(fragment_file, "# Hardening level: paranoid").unwrap();
+        writeln!(fragment_file, "[Service]").unwrap();
+        assert_eq!(
+            Service::fragment_hardening_level(fragment_file.path()).unwrap(),
+            Some("paranoid".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_fragment_hardening_options() {
+        let mut fragment_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(fragment_file, "# This file has been autogenerated by shh").unwrap();
+        writeln!(fragment_file, "[Service]").unwrap();
+        assert!(Service::fragment_hardening_options(fragment_file.path())
+            .unwrap()
+            .is_none());
+
+        let mut hardening_opts = HardeningOptions::safe();
+        hardening_opts.systemd_version = Some(SystemdVersion::new(247, 0));
+        hardening_opts
+            .readable_paths
+            .push(PathBuf::from("/var/lib/qux/some file"));
+        let mut fragment_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(fragment_file, "# This file has been autogenerated by shh").unwrap();
+        writeln!(fragment_file, "# Hardening level: balanced").unwrap();
         writeln!(
-            cfg_file,
-            r#"ExecStartPre=/bin/sh -c "[ ! -e /usr/bin/galera_recovery ] && VAR= || \
- VAR=`cd /usr/bin/..; /usr/bin/galera_recovery`; [ $? -eq 0 ] \
- && systemctl set-environment _WSREP_START_POSITION=$VAR || exit 1""#
+            fragment_file,
+            "# Hardening options: {}",
+            serde_json::to_string(&hardening_opts.to_args()).unwrap()
         )
         .unwrap();
+        writeln!(fragment_file, "[Service]").unwrap();
+        let recorded_opts = Service::fragment_hardening_options(fragment_file.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(recorded_opts.to_args(), hardening_opts.to_args());
+        assert_eq!(
+            recorded_opts.systemd_version,
+            Some(SystemdVersion::new(247, 0))
+        );
+        assert_eq!(recorded_opts.level.as_deref(), Some("balanced"));
+
+        let mut fragment_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(fragment_file, "# Hardening options: [\"--bogus\"]").unwrap();
+        assert!(Service::fragment_hardening_options(fragment_file.path()).is_err());
+    }
+
+    #[test]
+    fn test_fragment_allowances() {
+        let mut fragment_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(fragment_file, "# This file has been autogenerated by shh").unwrap();
+        writeln!(fragment_file, "[Service]").unwrap();
+        assert!(Service::fragment_allowances(fragment_file.path())
+            .unwrap()
+            .is_empty());
 
+        let mut fragment_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(fragment_file, "# This file has been autogenerated by shh").unwrap();
+        writeln!(fragment_file, "# Hardening level: paranoid").unwrap();
+        writeln!(
+            fragment_file,
+            "# Allowed even if unobserved: path /var/lib/foo/cache"
+        )
+        .unwrap();
+        writeln!(fragment_file, "# Allowed even if unobserved: syscall mlock").unwrap();
+        writeln!(fragment_file, "[Service]").unwrap();
         assert_eq!(
-            Service::config_vals("ExecStartPre", &[cfg_file.path()]).unwrap(),
-            vec![
-                r#"/bin/sh -c "[ ! -e /usr/bin/galera_recovery ] && VAR= ||  VAR=`cd /usr/bin/..; /usr/bin/galera_recovery`; [ $? -eq 0 ]  && systemctl set-environment _WSREP_START_POSITION=$VAR || exit 1""#
-            ]
+            Service::fragment_allowances(fragment_file.path()).unwrap(),
+            vec!["path /var/lib/foo/cache", "syscall mlock"]
         );
     }
 }
@@ -1,19 +1,24 @@
 //! Systemd & kernel version
 
-use std::{fmt, io::BufRead, process::Command, str};
+use std::{fmt, io::BufRead, process::Command, str, str::FromStr};
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
-pub(crate) struct SystemdVersion {
+/// Systemd version
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct SystemdVersion {
+    /// Major version
     pub major: u16,
+    /// Minor version, 0 for release candidates or distribution specific suffixes
     pub minor: u16,
 }
 
 impl SystemdVersion {
-    pub(crate) fn new(major: u16, minor: u16) -> Self {
+    /// Build version from its components
+    pub fn new(major: u16, minor: u16) -> Self {
         Self { major, minor }
     }
 
-    pub(crate) fn local_system() -> anyhow::Result<Self> {
+    /// Get version of the systemd running on the local system
+    pub fn local_system() -> anyhow::Result<Self> {
         let output = Command::new("systemctl").arg("--version").output()?;
         if !output.status.success() {
             anyhow::bail!("systemctl invocation failed with code {:?}", output.status);
@@ -55,21 +60,40 @@ impl SystemdVersion {
     }
 }
 
+impl FromStr for SystemdVersion {
+    type Err = anyhow::Error;
+
+    /// Parse version from a "major" or "major.minor" string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (major, minor) = s.split_once('.').unwrap_or((s, "0"));
+        Ok(Self {
+            major: major
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid systemd major version {major:?}: {e}"))?,
+            minor: minor
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid systemd minor version {minor:?}: {e}"))?,
+        })
+    }
+}
+
 impl fmt::Display for SystemdVersion {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}.{}", self.major, self.minor)
     }
 }
 
+/// Linux kernel version
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
-pub(crate) struct KernelVersion {
+pub struct KernelVersion {
     major: u16,
     minor: u16,
     release: u16,
 }
 
 impl KernelVersion {
-    pub(crate) fn new(major: u16, minor: u16, release: u16) -> Self {
+    /// Build version from its components
+    pub fn new(major: u16, minor: u16, release: u16) -> Self {
         Self {
             major,
             minor,
@@ -77,7 +101,8 @@ impl KernelVersion {
         }
     }
 
-    pub(crate) fn local_system() -> anyhow::Result<Self> {
+    /// Get version of the running kernel
+    pub fn local_system() -> anyhow::Result<Self> {
         let output = Command::new("uname").arg("-r").output()?;
         if !output.status.success() {
             anyhow::bail!("uname invocation failed with code {:?}", output.status);
@@ -124,4 +149,18 @@ mod tests {
             SystemdVersion::new(255, 0)
         );
     }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            "240".parse::<SystemdVersion>().unwrap(),
+            SystemdVersion::new(240, 0)
+        );
+        assert_eq!(
+            "254.1".parse::<SystemdVersion>().unwrap(),
+            SystemdVersion::new(254, 1)
+        );
+        assert!("v254".parse::<SystemdVersion>().is_err());
+        assert!("254.".parse::<SystemdVersion>().is_err());
+    }
 }